@@ -0,0 +1,423 @@
+use std::path::PathBuf;
+
+use gameboy_lib::{error::IllegalOpcodePolicy, model::Model, ppu::Palette, test_rom};
+
+pub const USAGE: &str = "\
+Usage:
+  gameboy-bin [options] [rom]
+  gameboy-bin disasm <rom> [output.asm]
+  gameboy-bin test-rom <rom or directory> [--cycles N] [--junit report.xml]
+
+Options:
+  --boot-rom <file>       Run a boot ROM before the game
+  --model <model>         dmg, mgb, sgb or cgb (default dmg)
+  --illegal-opcode <how>  lockup, break or error (default break when
+                          debugging, lockup otherwise)
+  --frames <n>            Stop after n frames
+  --headless              Run without a display
+  --window                Play in a window (needs the `window` feature)
+  --tui                   Play in the terminal
+  --panels                Show registers and disassembly next to the terminal screen
+  --debug                 Start the command line debugger
+  --gdb [port]            Wait for GDB on a port (default 2345)
+  --scale <n>             Integer window scale (default 3)
+  --palette <palette>     grey, green or four comma separated RRGGBB colors
+  --save-dir <dir>        Where save states go (default: next to the ROM)
+  --screenshot <file>     Write the last frame as PNG, or PPM for .ppm files
+  --dump-memory <file>    Write the memory map when the run ends
+  -h, --help              Show this help
+
+Without --frames, headless runs stop after 60 frames when taking a screenshot.";
+
+const DEFAULT_GDB_PORT: u16 = 2345;
+const DEFAULT_SCALE: usize = 3;
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Run(RunOptions),
+    Disasm {
+        rom: PathBuf,
+        output: Option<PathBuf>,
+    },
+    TestRom {
+        path: PathBuf,
+        cycles: u64,
+        junit: Option<PathBuf>,
+    },
+    Help,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Frontend {
+    Headless,
+    Window,
+    Tui,
+    Debugger,
+    Gdb(u16),
+}
+
+impl Frontend {
+    fn flag(&self) -> &'static str {
+        match self {
+            Frontend::Headless => "--headless",
+            Frontend::Window => "--window",
+            Frontend::Tui => "--tui",
+            Frontend::Debugger => "--debug",
+            Frontend::Gdb(_) => "--gdb",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct RunOptions {
+    pub rom: Option<PathBuf>,
+    pub boot_rom: Option<PathBuf>,
+    pub model: Model,
+    pub illegal_opcode: IllegalOpcodePolicy,
+    pub frames: Option<u64>,
+    pub frontend: Frontend,
+    pub save_dir: Option<PathBuf>,
+    pub screenshot: Option<PathBuf>,
+    pub dump_memory: Option<PathBuf>,
+    pub palette: Palette,
+    pub scale: usize,
+    pub panels: bool,
+}
+
+// Walks the arguments, accepting both `--name value` and `--name=value`
+struct Args<'a> {
+    args: &'a [String],
+    index: usize,
+    inline_value: Option<&'a str>,
+}
+
+impl<'a> Args<'a> {
+    fn new(args: &'a [String]) -> Args<'a> {
+        Args {
+            args,
+            index: 0,
+            inline_value: None,
+        }
+    }
+
+    fn next(&mut self) -> Result<Option<&'a str>, String> {
+        if let Some(value) = self.inline_value.take() {
+            return Err(format!("Unexpected value `{}`", value));
+        }
+        let Some(arg) = self.args.get(self.index) else {
+            return Ok(None);
+        };
+        self.index += 1;
+        match arg.split_once('=') {
+            Some((name, value)) if name.starts_with("--") => {
+                self.inline_value = Some(value);
+                Ok(Some(name))
+            }
+            _ => Ok(Some(arg)),
+        }
+    }
+
+    fn value(&mut self, name: &str) -> Result<&'a str, String> {
+        if let Some(value) = self.inline_value.take() {
+            return Ok(value);
+        }
+        let value = self
+            .args
+            .get(self.index)
+            .ok_or_else(|| format!("Missing value for {}", name))?;
+        self.index += 1;
+        Ok(value)
+    }
+
+    // Value that may be left out, like the port of `--gdb`
+    fn optional_value<T: std::str::FromStr>(&mut self) -> Option<T> {
+        if let Some(value) = self.inline_value {
+            let parsed = value.parse().ok();
+            if parsed.is_some() {
+                self.inline_value = None;
+            }
+            return parsed;
+        }
+        let parsed = self.args.get(self.index)?.parse().ok()?;
+        self.index += 1;
+        Some(parsed)
+    }
+
+    fn parsed<T: std::str::FromStr>(&mut self, name: &str) -> Result<T, String> {
+        let value = self.value(name)?;
+        value
+            .parse()
+            .map_err(|_| format!("Invalid value for {}: {}", name, value))
+    }
+}
+
+/// Parses the arguments after the program name.
+pub fn parse(args: &[String]) -> Result<Command, String> {
+    match args.first().map(String::as_str) {
+        Some("disasm") => parse_disasm(&args[1..]),
+        Some("test-rom") => parse_test_rom(&args[1..]),
+        _ => parse_run(args),
+    }
+}
+
+fn positionals(
+    args: &[String],
+    usage: &str,
+    count: std::ops::RangeInclusive<usize>,
+) -> Result<Vec<PathBuf>, String> {
+    if let Some(option) = args.iter().find(|arg| arg.starts_with('-')) {
+        return Err(format!("Unknown option {}", option));
+    }
+    if !count.contains(&args.len()) {
+        return Err(usage.to_string());
+    }
+    Ok(args.iter().map(PathBuf::from).collect())
+}
+
+fn parse_disasm(args: &[String]) -> Result<Command, String> {
+    let mut paths =
+        positionals(args, "Usage: gameboy-bin disasm <rom> [output.asm]", 1..=2)?.into_iter();
+    Ok(Command::Disasm {
+        rom: paths.next().unwrap(),
+        output: paths.next(),
+    })
+}
+
+fn parse_test_rom(args: &[String]) -> Result<Command, String> {
+    let mut cycles = test_rom::DEFAULT_CYCLE_BUDGET;
+    let mut junit = None;
+    let mut rest = Vec::new();
+    let mut args = Args::new(args);
+    while let Some(arg) = args.next()? {
+        match arg {
+            "--cycles" => cycles = args.parsed(arg)?,
+            "--junit" => junit = Some(PathBuf::from(args.value(arg)?)),
+            _ => rest.push(arg.to_string()),
+        }
+    }
+    let usage = "Usage: gameboy-bin test-rom <rom or directory> [--cycles N] [--junit report.xml]";
+    let path = positionals(&rest, usage, 1..=1)?.remove(0);
+    Ok(Command::TestRom {
+        path,
+        cycles,
+        junit,
+    })
+}
+
+fn parse_run(args: &[String]) -> Result<Command, String> {
+    let mut options = RunOptions {
+        rom: None,
+        boot_rom: None,
+        model: Model::default(),
+        illegal_opcode: IllegalOpcodePolicy::default(),
+        frames: None,
+        frontend: default_frontend(),
+        save_dir: None,
+        screenshot: None,
+        dump_memory: None,
+        palette: Palette::default(),
+        scale: DEFAULT_SCALE,
+        panels: false,
+    };
+    let mut illegal_opcode = None;
+    let mut frontend = None;
+    let mut set_frontend = |value: Frontend| match frontend.replace(value) {
+        Some(previous) if previous != value => Err(format!(
+            "{} cannot be combined with {}",
+            value.flag(),
+            previous.flag()
+        )),
+        _ => Ok(()),
+    };
+
+    let mut args = Args::new(args);
+    while let Some(arg) = args.next()? {
+        match arg {
+            "-h" | "--help" => return Ok(Command::Help),
+            "--boot-rom" => options.boot_rom = Some(PathBuf::from(args.value(arg)?)),
+            "--model" => options.model = args.value(arg)?.parse()?,
+            "--illegal-opcode" => illegal_opcode = Some(args.value(arg)?.parse()?),
+            "--frames" => options.frames = Some(args.parsed(arg)?),
+            "--headless" => set_frontend(Frontend::Headless)?,
+            "--window" => set_frontend(Frontend::Window)?,
+            "--tui" => set_frontend(Frontend::Tui)?,
+            "--debug" => set_frontend(Frontend::Debugger)?,
+            "--gdb" => set_frontend(Frontend::Gdb(
+                args.optional_value().unwrap_or(DEFAULT_GDB_PORT),
+            ))?,
+            "--panels" => options.panels = true,
+            "--scale" => {
+                options.scale = args.parsed(arg)?;
+                if options.scale == 0 {
+                    return Err("--scale must be at least 1".to_string());
+                }
+            }
+            "--palette" => options.palette = args.value(arg)?.parse()?,
+            "--save-dir" => options.save_dir = Some(PathBuf::from(args.value(arg)?)),
+            "--screenshot" => options.screenshot = Some(PathBuf::from(args.value(arg)?)),
+            "--dump-memory" => options.dump_memory = Some(PathBuf::from(args.value(arg)?)),
+            option if option.starts_with('-') => return Err(format!("Unknown option {}", option)),
+            rom if options.rom.is_none() => options.rom = Some(PathBuf::from(rom)),
+            extra => return Err(format!("Unexpected argument {}", extra)),
+        }
+    }
+
+    if options.rom.is_none() && options.boot_rom.is_none() {
+        return Err("No ROM given".to_string());
+    }
+    options.frontend = match frontend {
+        Some(frontend) => frontend,
+        None if options.screenshot.is_some() => Frontend::Headless,
+        None => default_frontend(),
+    };
+    options.illegal_opcode = match (illegal_opcode, options.frontend) {
+        (Some(policy), _) => policy,
+        (None, Frontend::Debugger | Frontend::Gdb(_)) => IllegalOpcodePolicy::Break,
+        (None, _) => IllegalOpcodePolicy::Lockup,
+    };
+    if options.frontend == Frontend::Headless && options.screenshot.is_some() {
+        options.frames = options.frames.or(Some(60));
+    }
+    Ok(Command::Run(options))
+}
+
+fn default_frontend() -> Frontend {
+    if cfg!(feature = "window") {
+        Frontend::Window
+    } else {
+        Frontend::Headless
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_args(args: &str) -> Result<Command, String> {
+        let args: Vec<String> = args.split_whitespace().map(String::from).collect();
+        parse(&args)
+    }
+
+    fn run_options(args: &str) -> RunOptions {
+        match parse_args(args) {
+            Ok(Command::Run(options)) => options,
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_run() {
+        let options = run_options(
+            "game.gb --boot-rom=dmg.bin --model mgb --frames 10 --tui --panels --save-dir saves --dump-memory mem.bin",
+        );
+        assert_eq!(options.rom, Some(PathBuf::from("game.gb")));
+        assert_eq!(options.boot_rom, Some(PathBuf::from("dmg.bin")));
+        assert_eq!(options.model, Model::Mgb);
+        assert_eq!(options.frames, Some(10));
+        assert_eq!(options.frontend, Frontend::Tui);
+        assert!(options.panels);
+        assert_eq!(options.save_dir, Some(PathBuf::from("saves")));
+        assert_eq!(options.dump_memory, Some(PathBuf::from("mem.bin")));
+    }
+
+    #[test]
+    fn test_parse_gdb_port() {
+        assert_eq!(run_options("--gdb game.gb").frontend, Frontend::Gdb(2345));
+        assert_eq!(
+            run_options("--gdb 1234 game.gb").frontend,
+            Frontend::Gdb(1234)
+        );
+        assert_eq!(
+            run_options("game.gb --gdb=1234").frontend,
+            Frontend::Gdb(1234)
+        );
+    }
+
+    #[test]
+    fn test_illegal_opcode_defaults() {
+        let lockup = IllegalOpcodePolicy::Lockup;
+        assert_eq!(run_options("game.gb --tui").illegal_opcode, lockup);
+        assert_eq!(
+            run_options("game.gb --debug").illegal_opcode,
+            IllegalOpcodePolicy::Break
+        );
+        assert_eq!(
+            run_options("game.gb --gdb --illegal-opcode=lockup").illegal_opcode,
+            lockup
+        );
+        assert_eq!(
+            run_options("game.gb --illegal-opcode error").illegal_opcode,
+            IllegalOpcodePolicy::Error
+        );
+    }
+
+    #[test]
+    fn test_screenshot_defaults() {
+        let options = run_options("game.gb --screenshot out.png");
+        assert_eq!(options.frontend, Frontend::Headless);
+        assert_eq!(options.frames, Some(60));
+        assert_eq!(
+            run_options("game.gb --screenshot out.png --frames 5").frames,
+            Some(5)
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(parse_args(""), Err("No ROM given".to_string()));
+        assert_eq!(
+            parse_args("game.gb --frames"),
+            Err("Missing value for --frames".to_string())
+        );
+        assert_eq!(
+            parse_args("game.gb --frames lots"),
+            Err("Invalid value for --frames: lots".to_string())
+        );
+        assert_eq!(
+            parse_args("game.gb --turbo"),
+            Err("Unknown option --turbo".to_string())
+        );
+        assert_eq!(
+            parse_args("game.gb other.gb"),
+            Err("Unexpected argument other.gb".to_string())
+        );
+        assert_eq!(
+            parse_args("game.gb --tui --window"),
+            Err("--window cannot be combined with --tui".to_string())
+        );
+        assert_eq!(
+            parse_args("game.gb --model gba"),
+            Err("Unknown model: gba".to_string())
+        );
+        assert_eq!(
+            parse_args("game.gb --illegal-opcode ignore"),
+            Err("Unknown illegal opcode policy: ignore".to_string())
+        );
+        assert_eq!(
+            parse_args("game.gb --headless=yes"),
+            Err("Unexpected value `yes`".to_string())
+        );
+        assert_eq!(parse_args("game.gb --help"), Ok(Command::Help));
+    }
+
+    #[test]
+    fn test_parse_subcommands() {
+        assert_eq!(
+            parse_args("disasm game.gb out.asm"),
+            Ok(Command::Disasm {
+                rom: PathBuf::from("game.gb"),
+                output: Some(PathBuf::from("out.asm"))
+            })
+        );
+        assert_eq!(
+            parse_args("test-rom roms --cycles 100 --junit=report.xml"),
+            Ok(Command::TestRom {
+                path: PathBuf::from("roms"),
+                cycles: 100,
+                junit: Some(PathBuf::from("report.xml"))
+            })
+        );
+        assert!(parse_args("test-rom").is_err());
+        assert!(parse_args("disasm game.gb --verbose").is_err());
+    }
+}
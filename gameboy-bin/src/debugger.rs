@@ -0,0 +1,409 @@
+use std::io::{self, BufRead, Write};
+
+use gameboy_lib::{
+    debugger::{Access, StopReason, WatchKind},
+    disassembler::Disassembly,
+    Gameboy,
+};
+
+const HELP: &str = "\
+Addresses and bytes are hexadecimal, counts are decimal.
+Commands:
+  s, step [n]                 Execute n instructions (default 1)
+  n, next                     Step over CALL and RST
+  c, continue                 Run until a breakpoint or watchpoint
+  r, regs                     Show registers
+  x, mem <addr> [len]         Hexdump memory (default 64 bytes)
+  w, write <addr> <byte>...   Write bytes to memory
+  d, disas [addr] [n]         Disassemble n instructions (default 8 around PC)
+  b, break <addr>             Set a breakpoint
+  delete <addr>               Remove a breakpoint
+  watch <addr> [r|w|rw]       Set a watchpoint (default rw)
+  unwatch <addr>              Remove a watchpoint
+  i, info                     List breakpoints and watchpoints
+  reset                       Reset the machine
+  h, help                     Show this help
+  q, quit                     Exit the debugger";
+
+#[derive(Debug, PartialEq)]
+enum Command {
+    Step(u32),
+    Next,
+    Continue,
+    Registers,
+    Memory(u16, u16),
+    Write(u16, Vec<u8>),
+    Disassemble(Option<u16>, u16),
+    Break(u16),
+    Delete(u16),
+    Watch(u16, WatchKind),
+    Unwatch(u16),
+    Info,
+    Reset,
+    Help,
+    Quit,
+}
+
+fn parse_number(s: &str) -> Result<u16, String> {
+    let s = s.trim_start_matches('$');
+    let parsed = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u16::from_str_radix(hex, 16),
+        None => u16::from_str_radix(s, 16),
+    };
+    parsed.map_err(|_| format!("Invalid number: {}", s))
+}
+
+fn parse_count(s: &str) -> Result<u16, String> {
+    s.parse().map_err(|_| format!("Invalid count: {}", s))
+}
+
+fn parse_byte(s: &str) -> Result<u8, String> {
+    let value = parse_number(s)?;
+    u8::try_from(value).map_err(|_| format!("Value does not fit in a byte: {}", s))
+}
+
+fn parse_command(line: &str) -> Result<Option<Command>, String> {
+    let mut parts = line.split_whitespace();
+    let Some(name) = parts.next() else {
+        return Ok(None);
+    };
+    let args: Vec<&str> = parts.collect();
+    let arg = |index: usize| -> Result<u16, String> {
+        args.get(index)
+            .ok_or_else(|| format!("Missing argument for {}", name))
+            .and_then(|s| parse_number(s))
+    };
+    let count = |index: usize, default: u16| -> Result<u16, String> {
+        args.get(index).map_or(Ok(default), |s| parse_count(s))
+    };
+
+    let command = match name {
+        "s" | "step" => Command::Step(count(0, 1)? as u32),
+        "n" | "next" => Command::Next,
+        "c" | "continue" => Command::Continue,
+        "r" | "regs" => Command::Registers,
+        "x" | "mem" => Command::Memory(arg(0)?, count(1, 64)?),
+        "w" | "write" => {
+            let bytes = args[1.min(args.len())..]
+                .iter()
+                .map(|s| parse_byte(s))
+                .collect::<Result<Vec<u8>, String>>()?;
+            if bytes.is_empty() {
+                return Err(format!("Missing argument for {}", name));
+            }
+            Command::Write(arg(0)?, bytes)
+        }
+        "d" | "disas" => {
+            let address = args.first().map(|s| parse_number(s)).transpose()?;
+            Command::Disassemble(address, count(1, 8)?)
+        }
+        "b" | "break" => Command::Break(arg(0)?),
+        "delete" => Command::Delete(arg(0)?),
+        "watch" => {
+            let kind = match args.get(1).copied() {
+                None | Some("rw") => WatchKind::ReadWrite,
+                Some("r") => WatchKind::Read,
+                Some("w") => WatchKind::Write,
+                Some(kind) => return Err(format!("Invalid watch kind: {}", kind)),
+            };
+            Command::Watch(arg(0)?, kind)
+        }
+        "unwatch" => Command::Unwatch(arg(0)?),
+        "i" | "info" => Command::Info,
+        "reset" => Command::Reset,
+        "h" | "help" => Command::Help,
+        "q" | "quit" => Command::Quit,
+        _ => return Err(format!("Unknown command: {}", name)),
+    };
+    Ok(Some(command))
+}
+
+fn print_registers(gameboy: &Gameboy, output: &mut impl Write) -> io::Result<()> {
+    let registers = gameboy.registers();
+    let f = &registers.f;
+    writeln!(
+        output,
+        "A: {:02X}  F: {:02X}  [{}{}{}{}]",
+        registers.a,
+        f.get(),
+        if f.zero { 'Z' } else { '-' },
+        if f.subtract { 'N' } else { '-' },
+        if f.half_carry { 'H' } else { '-' },
+        if f.carry { 'C' } else { '-' },
+    )?;
+    writeln!(output, "B: {:02X}  C: {:02X}", registers.b, registers.c)?;
+    writeln!(output, "D: {:02X}  E: {:02X}", registers.d, registers.e)?;
+    writeln!(output, "H: {:02X}  L: {:02X}", registers.h, registers.l)?;
+    writeln!(
+        output,
+        "SP: {:04X}  PC: {:04X}",
+        registers.sp.get(),
+        gameboy.pc()
+    )
+}
+
+fn print_memory(
+    gameboy: &Gameboy,
+    address: u16,
+    length: u16,
+    output: &mut impl Write,
+) -> io::Result<()> {
+    for row in (0..length).step_by(16) {
+        let start = address.wrapping_add(row);
+        let bytes: Vec<u8> = (0..16.min(length - row))
+            .map(|i| gameboy.read_memory(start.wrapping_add(i)))
+            .collect();
+        let hex: Vec<String> = bytes.iter().map(|b| format!("{:02X}", b)).collect();
+        let ascii: String = bytes
+            .iter()
+            .map(|&b| if b.is_ascii_graphic() { b as char } else { '.' })
+            .collect();
+        writeln!(output, "{:04X}: {:<47}  {}", start, hex.join(" "), ascii)?;
+    }
+    Ok(())
+}
+
+fn print_disassembly(
+    gameboy: &Gameboy,
+    instructions: &[Disassembly],
+    output: &mut impl Write,
+) -> io::Result<()> {
+    for instruction in instructions {
+        let bytes: Vec<String> = instruction
+            .bytes
+            .iter()
+            .map(|b| format!("{:02X}", b))
+            .collect();
+        let marker = if instruction.address == gameboy.pc() {
+            "=>"
+        } else {
+            "  "
+        };
+        writeln!(
+            output,
+            "{} {:04X}: {:<9} {}",
+            marker,
+            instruction.address,
+            bytes.join(" "),
+            instruction.text
+        )?;
+    }
+    Ok(())
+}
+
+fn print_stop(gameboy: &Gameboy, reason: StopReason, output: &mut impl Write) -> io::Result<()> {
+    match reason {
+        StopReason::Step => {}
+        StopReason::Breakpoint(address) => writeln!(output, "Breakpoint at {:04X}", address)?,
+        StopReason::Watchpoint(hit) => {
+            let access = match hit.access {
+                Access::Read => "Read",
+                Access::Write => "Write",
+            };
+            writeln!(
+                output,
+                "{} of {:02X} at {:04X} by instruction at {:04X}",
+                access, hit.value, hit.address, hit.pc
+            )?;
+        }
+        StopReason::Error(error) => writeln!(output, "{}", error)?,
+        StopReason::Lockup(address) => writeln!(
+            output,
+            "CPU locked up on the illegal opcode at {:04X}",
+            address
+        )?,
+        StopReason::Timeout => writeln!(output, "Call did not return, stopped")?,
+    }
+    print_disassembly(gameboy, &gameboy.disassemble(gameboy.pc(), 1), output)
+}
+
+fn execute(gameboy: &mut Gameboy, command: Command, output: &mut impl Write) -> io::Result<()> {
+    match command {
+        Command::Step(count) => {
+            let mut reason = StopReason::Step;
+            for _ in 0..count {
+                reason = gameboy.step();
+                if reason != StopReason::Step {
+                    break;
+                }
+            }
+            print_stop(gameboy, reason, output)?;
+        }
+        Command::Next => {
+            let reason = gameboy.step_over();
+            print_stop(gameboy, reason, output)?;
+        }
+        Command::Continue => {
+            let reason = gameboy.resume();
+            print_stop(gameboy, reason, output)?;
+        }
+        Command::Registers => print_registers(gameboy, output)?,
+        Command::Memory(address, length) => print_memory(gameboy, address, length, output)?,
+        Command::Write(address, bytes) => {
+            for (i, byte) in bytes.into_iter().enumerate() {
+                gameboy.write_memory(address.wrapping_add(i as u16), byte);
+            }
+        }
+        Command::Disassemble(address, count) => {
+            let count = count as usize;
+            let instructions = match address {
+                Some(address) => gameboy.disassemble(address, count),
+                None => {
+                    let before = count / 2;
+                    gameboy.disassemble_around(
+                        gameboy.pc(),
+                        before,
+                        count.saturating_sub(before + 1),
+                    )
+                }
+            };
+            print_disassembly(gameboy, &instructions, output)?;
+        }
+        Command::Break(address) => {
+            gameboy.add_breakpoint(address);
+            writeln!(output, "Breakpoint set at {:04X}", address)?;
+        }
+        Command::Delete(address) => {
+            if !gameboy.remove_breakpoint(address) {
+                writeln!(output, "No breakpoint at {:04X}", address)?;
+            }
+        }
+        Command::Watch(address, kind) => {
+            gameboy.add_watchpoint(address, kind);
+            writeln!(output, "Watchpoint ({:?}) set at {:04X}", kind, address)?;
+        }
+        Command::Unwatch(address) => {
+            if !gameboy.remove_watchpoint(address) {
+                writeln!(output, "No watchpoint at {:04X}", address)?;
+            }
+        }
+        Command::Info => {
+            for address in gameboy.breakpoints() {
+                writeln!(output, "Breakpoint {:04X}", address)?;
+            }
+            for (address, kind) in gameboy.watchpoints() {
+                writeln!(output, "Watchpoint {:04X} ({:?})", address, kind)?;
+            }
+        }
+        Command::Reset => {
+            gameboy.reset();
+            print_disassembly(gameboy, &gameboy.disassemble(gameboy.pc(), 1), output)?;
+        }
+        Command::Help => writeln!(output, "{}", HELP)?,
+        Command::Quit => {}
+    }
+    Ok(())
+}
+
+pub fn run(gameboy: &mut Gameboy, input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+    gameboy.reset();
+    writeln!(output, "Type 'help' for a list of commands")?;
+    print_disassembly(gameboy, &gameboy.disassemble(gameboy.pc(), 1), &mut output)?;
+
+    let mut lines = input.lines();
+    loop {
+        write!(output, "(gbdb) ")?;
+        output.flush()?;
+        let Some(line) = lines.next() else {
+            break;
+        };
+        match parse_command(&line?) {
+            Ok(Some(Command::Quit)) => break,
+            Ok(Some(command)) => execute(gameboy, command, &mut output)?,
+            Ok(None) => {}
+            Err(message) => writeln!(output, "{}", message)?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gameboy_lib::error::IllegalOpcodePolicy;
+
+    #[test]
+    fn test_parse_number() {
+        assert_eq!(parse_number("0x100"), Ok(0x100));
+        assert_eq!(parse_number("$FF80"), Ok(0xFF80));
+        assert_eq!(parse_number("c000"), Ok(0xC000));
+        assert!(parse_number("zz").is_err());
+    }
+
+    #[test]
+    fn test_parse_command() {
+        assert_eq!(parse_command("s"), Ok(Some(Command::Step(1))));
+        assert_eq!(parse_command("step 10"), Ok(Some(Command::Step(10))));
+        assert_eq!(
+            parse_command("x c000"),
+            Ok(Some(Command::Memory(0xC000, 0x40)))
+        );
+        assert_eq!(
+            parse_command("w c000 01 02"),
+            Ok(Some(Command::Write(0xC000, vec![0x01, 0x02])))
+        );
+        assert_eq!(
+            parse_command("watch ff40 w"),
+            Ok(Some(Command::Watch(0xFF40, WatchKind::Write)))
+        );
+        assert_eq!(parse_command("d"), Ok(Some(Command::Disassemble(None, 8))));
+        assert_eq!(parse_command(""), Ok(None));
+        assert!(parse_command("w c000").is_err());
+        assert!(parse_command("w c000 100").is_err());
+        assert!(parse_command("frobnicate").is_err());
+    }
+
+    #[test]
+    fn test_session() {
+        let mut gameboy = Gameboy::new(vec![0x3E, 0x42, 0x00, 0x00, 0x00], vec![]);
+        let input = "b 3\nc\nr\nx 0 4\nq\n".as_bytes();
+        let mut output = Vec::new();
+        run(&mut gameboy, input, &mut output).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("Breakpoint at 0003"));
+        assert!(output.contains("A: 42"));
+        assert!(output.contains("0000: 3E 42 00 00"));
+        assert!(output.contains("=> 0003: 00        nop"));
+    }
+
+    #[test]
+    fn test_disassemble_around_pc() {
+        let mut gameboy = Gameboy::new(vec![0x00; 16], vec![]);
+        let input = "s 4\nd\nq\n".as_bytes();
+        let mut output = Vec::new();
+        run(&mut gameboy, input, &mut output).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("   0000: 00        nop"));
+        assert!(output.contains("=> 0004: 00        nop"));
+        assert!(output.contains("   0007: 00        nop"));
+        assert!(!output.contains("0008:"));
+    }
+
+    #[test]
+    fn test_session_stops_on_error() {
+        let mut gameboy = Gameboy::new(vec![0x00, 0xDD], vec![]);
+        gameboy.set_illegal_opcode_policy(IllegalOpcodePolicy::Error);
+        let input = "c\nq\n".as_bytes();
+        let mut output = Vec::new();
+        run(&mut gameboy, input, &mut output).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("Illegal opcode $DD at 00:0001"));
+        assert!(output.contains("=> 0001: DD"));
+    }
+
+    #[test]
+    fn test_session_stops_on_lockup() {
+        let mut gameboy = Gameboy::new(vec![0x00, 0xDD], vec![]);
+        gameboy.set_illegal_opcode_policy(IllegalOpcodePolicy::Break);
+        let input = "c\ns\nq\n".as_bytes();
+        let mut output = Vec::new();
+        run(&mut gameboy, input, &mut output).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("CPU locked up on the illegal opcode at 0001"));
+        assert!(output.contains("=> 0001: DD"));
+    }
+}
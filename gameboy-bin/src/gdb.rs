@@ -0,0 +1,557 @@
+use std::{
+    io::{self, BufRead, BufReader, ErrorKind, Read, Write},
+    net::{TcpListener, TcpStream},
+};
+
+use gameboy_lib::{
+    debugger::{Access, StopReason, WatchKind},
+    Gameboy,
+};
+
+// Check for a Ctrl-C from the client every this many instructions while running
+const INTERRUPT_POLL_INTERVAL: u32 = 4096;
+
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.gnu.gdb.sm83.core">
+    <reg name="a" bitsize="8" type="uint8" regnum="0"/>
+    <reg name="f" bitsize="8" type="uint8"/>
+    <reg name="b" bitsize="8" type="uint8"/>
+    <reg name="c" bitsize="8" type="uint8"/>
+    <reg name="d" bitsize="8" type="uint8"/>
+    <reg name="e" bitsize="8" type="uint8"/>
+    <reg name="h" bitsize="8" type="uint8"/>
+    <reg name="l" bitsize="8" type="uint8"/>
+    <reg name="sp" bitsize="16" type="data_ptr"/>
+    <reg name="pc" bitsize="16" type="code_ptr"/>
+  </feature>
+</target>"#;
+
+const SIGILL: &str = "S04";
+const SIGTRAP: &str = "S05";
+
+/// Sizes in bytes of the registers as numbered in `TARGET_XML`.
+const REGISTER_SIZES: [usize; 10] = [1, 1, 1, 1, 1, 1, 1, 1, 2, 2];
+
+enum Action {
+    Reply(String),
+    Continue,
+    Step,
+    Detach,
+}
+
+pub struct Session<'a> {
+    gameboy: &'a mut Gameboy,
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    no_ack: bool,
+}
+
+/// Waits for a single debugger connection on `listener` and serves it until
+/// the client detaches or kills the session.
+pub fn serve(gameboy: &mut Gameboy, listener: TcpListener) -> io::Result<()> {
+    let (stream, address) = listener.accept()?;
+    eprintln!("GDB connected from {}", address);
+    stream.set_nodelay(true)?;
+    gameboy.reset();
+
+    let mut session = Session {
+        gameboy,
+        reader: BufReader::new(stream.try_clone()?),
+        writer: stream,
+        no_ack: false,
+    };
+    session.run()
+}
+
+impl Session<'_> {
+    fn run(&mut self) -> io::Result<()> {
+        while let Some(packet) = self.read_packet()? {
+            match self.handle(&packet) {
+                Action::Reply(reply) => self.send(&reply)?,
+                Action::Continue => {
+                    let reply = self.resume()?;
+                    self.send(&reply)?;
+                }
+                Action::Step => {
+                    let reply = stop_reply(self.gameboy.step());
+                    self.send(&reply)?;
+                }
+                Action::Detach => {
+                    self.send("OK")?;
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn read_byte(&mut self) -> io::Result<Option<u8>> {
+        let mut byte = [0];
+        match self.reader.read(&mut byte)? {
+            0 => Ok(None),
+            _ => Ok(Some(byte[0])),
+        }
+    }
+
+    /// Reads the next `$data#xx` packet, skipping acks and stray interrupts.
+    fn read_packet(&mut self) -> io::Result<Option<String>> {
+        loop {
+            match self.read_byte()? {
+                None => return Ok(None),
+                Some(b'$') => break,
+                Some(_) => continue,
+            }
+        }
+
+        let mut data = Vec::new();
+        loop {
+            match self.read_byte()? {
+                None => return Ok(None),
+                Some(b'#') => break,
+                Some(byte) => data.push(byte),
+            }
+        }
+        let mut checksum = [0; 2];
+        self.reader.read_exact(&mut checksum)?;
+
+        let expected = std::str::from_utf8(&checksum)
+            .ok()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        if !self.no_ack {
+            let valid = expected == Some(checksum_of(&data));
+            self.writer.write_all(if valid { b"+" } else { b"-" })?;
+            if !valid {
+                return self.read_packet();
+            }
+        }
+        Ok(Some(String::from_utf8_lossy(&data).into_owned()))
+    }
+
+    fn send(&mut self, data: &str) -> io::Result<()> {
+        let packet = format!("${}#{:02x}", data, checksum_of(data.as_bytes()));
+        loop {
+            self.writer.write_all(packet.as_bytes())?;
+            self.writer.flush()?;
+            if self.no_ack {
+                return Ok(());
+            }
+            match self.read_byte()? {
+                Some(b'-') => continue,
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn interrupted(&mut self) -> io::Result<bool> {
+        // Bytes that arrived with the last packet come before the socket's
+        if let Some(&byte) = self.reader.buffer().first() {
+            if byte == 0x03 {
+                self.reader.consume(1);
+            }
+            return Ok(byte == 0x03);
+        }
+        let stream = self.reader.get_mut();
+        stream.set_nonblocking(true)?;
+        let mut byte = [0];
+        let result = match stream.peek(&mut byte) {
+            Ok(1) if byte[0] == 0x03 => {
+                stream.read_exact(&mut byte)?;
+                Ok(true)
+            }
+            Ok(_) => Ok(false),
+            Err(error) if error.kind() == ErrorKind::WouldBlock => Ok(false),
+            Err(error) => Err(error),
+        };
+        stream.set_nonblocking(false)?;
+        result
+    }
+
+    fn resume(&mut self) -> io::Result<String> {
+        let mut steps = 0;
+        loop {
+            let reason = self.gameboy.step();
+            if reason != StopReason::Step {
+                return Ok(stop_reply(reason));
+            }
+            let pc = self.gameboy.pc();
+            if self.gameboy.has_breakpoint(pc) {
+                return Ok(stop_reply(StopReason::Breakpoint(pc)));
+            }
+
+            steps += 1;
+            if steps % INTERRUPT_POLL_INTERVAL == 0 && self.interrupted()? {
+                return Ok("S02".to_string());
+            }
+        }
+    }
+
+    fn handle(&mut self, packet: &str) -> Action {
+        let (command, args) = packet.split_at(packet.len().min(1));
+        let reply = match command {
+            "?" => SIGTRAP.to_string(),
+            "g" => self.read_registers(),
+            "G" => self.write_registers(args),
+            "p" => self.read_register(args),
+            "P" => self.write_register(args),
+            "m" => self.read_memory(args),
+            "M" => self.write_memory(args),
+            "c" | "s" => {
+                if let Some(address) = parse_hex(args) {
+                    self.gameboy.set_pc(address);
+                }
+                return if command == "c" {
+                    Action::Continue
+                } else {
+                    Action::Step
+                };
+            }
+            "Z" => self.breakpoint(args, true),
+            "z" => self.breakpoint(args, false),
+            "H" => "OK".to_string(),
+            "D" | "k" => return Action::Detach,
+            "q" | "Q" | "v" => self.query(packet),
+            _ => String::new(),
+        };
+        Action::Reply(reply)
+    }
+
+    fn query(&mut self, packet: &str) -> String {
+        if packet.starts_with("qSupported") {
+            "PacketSize=4000;qXfer:features:read+;QStartNoAckMode+".to_string()
+        } else if packet == "QStartNoAckMode" {
+            self.no_ack = true;
+            "OK".to_string()
+        } else if packet == "qAttached" {
+            "1".to_string()
+        } else if packet == "qC" {
+            "QC1".to_string()
+        } else if packet == "qfThreadInfo" {
+            "m1".to_string()
+        } else if packet == "qsThreadInfo" {
+            "l".to_string()
+        } else if let Some(range) = packet.strip_prefix("qXfer:features:read:target.xml:") {
+            read_transfer(TARGET_XML, range)
+        } else {
+            String::new()
+        }
+    }
+
+    fn register_values(&self) -> [u16; 10] {
+        let registers = self.gameboy.registers();
+        [
+            registers.a as u16,
+            registers.f.get() as u16,
+            registers.b as u16,
+            registers.c as u16,
+            registers.d as u16,
+            registers.e as u16,
+            registers.h as u16,
+            registers.l as u16,
+            registers.sp.get(),
+            self.gameboy.pc(),
+        ]
+    }
+
+    fn set_register_value(&mut self, index: usize, value: u16) {
+        let registers = self.gameboy.registers_mut();
+        match index {
+            0 => registers.a = value as u8,
+            1 => registers.f.set(value as u8),
+            2 => registers.b = value as u8,
+            3 => registers.c = value as u8,
+            4 => registers.d = value as u8,
+            5 => registers.e = value as u8,
+            6 => registers.h = value as u8,
+            7 => registers.l = value as u8,
+            8 => registers.sp.set(value),
+            _ => self.gameboy.set_pc(value),
+        }
+    }
+
+    fn read_registers(&self) -> String {
+        self.register_values()
+            .iter()
+            .zip(REGISTER_SIZES)
+            .map(|(value, size)| encode_le(*value, size))
+            .collect()
+    }
+
+    fn write_registers(&mut self, args: &str) -> String {
+        let Some(bytes) = decode_hex(args) else {
+            return "E01".to_string();
+        };
+        if bytes.len() != REGISTER_SIZES.iter().sum::<usize>() {
+            return "E01".to_string();
+        }
+        let mut offset = 0;
+        for (index, size) in REGISTER_SIZES.iter().enumerate() {
+            self.set_register_value(index, decode_le(&bytes[offset..offset + size]));
+            offset += size;
+        }
+        "OK".to_string()
+    }
+
+    fn read_register(&self, args: &str) -> String {
+        match parse_hex(args).map(usize::from) {
+            Some(index) if index < REGISTER_SIZES.len() => {
+                encode_le(self.register_values()[index], REGISTER_SIZES[index])
+            }
+            _ => "E01".to_string(),
+        }
+    }
+
+    fn write_register(&mut self, args: &str) -> String {
+        let parsed = args.split_once('=').and_then(|(index, value)| {
+            let index = usize::from(parse_hex(index)?);
+            let bytes = decode_hex(value)?;
+            (REGISTER_SIZES.get(index) == Some(&bytes.len())).then_some((index, bytes))
+        });
+        match parsed {
+            Some((index, bytes)) => {
+                self.set_register_value(index, decode_le(&bytes));
+                "OK".to_string()
+            }
+            None => "E01".to_string(),
+        }
+    }
+
+    fn read_memory(&self, args: &str) -> String {
+        match parse_range(args) {
+            Some((address, length)) => (0..length)
+                .map(|i| {
+                    let value = self.gameboy.read_memory(address.wrapping_add(i));
+                    format!("{:02x}", value)
+                })
+                .collect(),
+            None => "E01".to_string(),
+        }
+    }
+
+    fn write_memory(&mut self, args: &str) -> String {
+        let parsed = args.split_once(':').and_then(|(range, data)| {
+            let (address, length) = parse_range(range)?;
+            let bytes = decode_hex(data)?;
+            (bytes.len() == length as usize).then_some((address, bytes))
+        });
+        match parsed {
+            Some((address, bytes)) => {
+                for (i, byte) in bytes.into_iter().enumerate() {
+                    self.gameboy
+                        .write_memory(address.wrapping_add(i as u16), byte);
+                }
+                "OK".to_string()
+            }
+            None => "E01".to_string(),
+        }
+    }
+
+    // Z0/z0 software breakpoints, Z2/Z3/Z4 write/read/access watchpoints
+    fn breakpoint(&mut self, args: &str, insert: bool) -> String {
+        let mut parts = args.split(',');
+        let (Some(kind), Some(address)) = (parts.next(), parts.next().and_then(parse_hex)) else {
+            return "E01".to_string();
+        };
+        let watch = match kind {
+            "0" | "1" => None,
+            "2" => Some(WatchKind::Write),
+            "3" => Some(WatchKind::Read),
+            "4" => Some(WatchKind::ReadWrite),
+            _ => return String::new(),
+        };
+        match (watch, insert) {
+            (None, true) => self.gameboy.add_breakpoint(address),
+            (None, false) => {
+                self.gameboy.remove_breakpoint(address);
+            }
+            (Some(kind), true) => self.gameboy.add_watchpoint(address, kind),
+            (Some(_), false) => {
+                self.gameboy.remove_watchpoint(address);
+            }
+        }
+        "OK".to_string()
+    }
+}
+
+fn stop_reply(reason: StopReason) -> String {
+    match reason {
+        StopReason::Step | StopReason::Breakpoint(_) | StopReason::Timeout => SIGTRAP.to_string(),
+        StopReason::Watchpoint(hit) => {
+            let kind = match hit.access {
+                Access::Read => "rwatch",
+                Access::Write => "watch",
+            };
+            format!("T05{}:{:04x};", kind, hit.address)
+        }
+        StopReason::Error(_) | StopReason::Lockup(_) => SIGILL.to_string(),
+    }
+}
+
+fn read_transfer(document: &str, range: &str) -> String {
+    let Some((offset, length)) = range.split_once(',') else {
+        return "E01".to_string();
+    };
+    let (Ok(offset), Ok(length)) = (
+        usize::from_str_radix(offset, 16),
+        usize::from_str_radix(length, 16),
+    ) else {
+        return "E01".to_string();
+    };
+    let start = offset.min(document.len());
+    let end = (offset + length).min(document.len());
+    let prefix = if end < document.len() { "m" } else { "l" };
+    format!("{}{}", prefix, &document[start..end])
+}
+
+fn checksum_of(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte))
+}
+
+fn parse_hex(s: &str) -> Option<u16> {
+    u16::from_str_radix(s, 16).ok()
+}
+
+fn parse_range(s: &str) -> Option<(u16, u16)> {
+    let (address, length) = s.split_once(',')?;
+    Some((parse_hex(address)?, parse_hex(length)?))
+}
+
+fn decode_hex(s: &str) -> Option<Vec<u8>> {
+    if s.len() % 2 != 0 {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn encode_le(value: u16, size: usize) -> String {
+    value.to_le_bytes()[..size]
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn decode_le(bytes: &[u8]) -> u16 {
+    bytes
+        .iter()
+        .rev()
+        .fold(0, |value, byte| value << 8 | *byte as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    struct Client {
+        reader: BufReader<TcpStream>,
+        writer: TcpStream,
+    }
+
+    impl Client {
+        fn connect(program: Vec<u8>) -> (Client, thread::JoinHandle<()>) {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let address = listener.local_addr().unwrap();
+            let server = thread::spawn(move || {
+                let mut gameboy = Gameboy::new(program, vec![]);
+                serve(&mut gameboy, listener).unwrap();
+            });
+            let stream = TcpStream::connect(address).unwrap();
+            let client = Client {
+                reader: BufReader::new(stream.try_clone().unwrap()),
+                writer: stream,
+            };
+            (client, server)
+        }
+
+        fn request(&mut self, data: &str) -> String {
+            self.request_followed_by(data, &[])
+        }
+
+        // Sends `extra` in the same write as the packet
+        fn request_followed_by(&mut self, data: &str, extra: &[u8]) -> String {
+            let mut packet = format!("${}#{:02x}", data, checksum_of(data.as_bytes())).into_bytes();
+            packet.extend_from_slice(extra);
+            self.writer.write_all(&packet).unwrap();
+
+            let mut ack = [0];
+            self.reader.read_exact(&mut ack).unwrap();
+            assert_eq!(ack[0], b'+');
+
+            let mut reply = Vec::new();
+            self.reader.read_until(b'#', &mut reply).unwrap();
+            let mut checksum = [0; 2];
+            self.reader.read_exact(&mut checksum).unwrap();
+            self.writer.write_all(b"+").unwrap();
+
+            let reply = String::from_utf8(reply).unwrap();
+            let reply = reply.trim_start_matches('$').trim_end_matches('#');
+            assert_eq!(
+                u8::from_str_radix(std::str::from_utf8(&checksum).unwrap(), 16).unwrap(),
+                checksum_of(reply.as_bytes())
+            );
+            reply.to_string()
+        }
+    }
+
+    #[test]
+    fn test_hex_helpers() {
+        assert_eq!(decode_hex("0aff"), Some(vec![0x0A, 0xFF]));
+        assert_eq!(decode_hex("0af"), None);
+        assert_eq!(encode_le(0xFFFE, 2), "feff");
+        assert_eq!(decode_le(&[0xFE, 0xFF]), 0xFFFE);
+        assert_eq!(checksum_of(b"OK"), 0x9A);
+    }
+
+    #[test]
+    fn test_read_transfer() {
+        assert_eq!(read_transfer("abcdef", "0,3"), "mabc");
+        assert_eq!(read_transfer("abcdef", "3,10"), "ldef");
+    }
+
+    #[test]
+    fn test_session_on_loopback() {
+        // LD A, 0x42; NOP; LD (0xC000), A; NOP
+        let (mut client, server) = Client::connect(vec![0x3E, 0x42, 0x00, 0xEA, 0x00, 0xC0, 0x00]);
+
+        assert!(client.request("qSupported:multiprocess+").contains("qXfer"));
+        assert_eq!(client.request("?"), "S05");
+        assert_eq!(client.request("g"), "000000000000000000000000");
+        assert_eq!(client.request("s"), "S05");
+        assert_eq!(client.request("p0"), "42");
+        assert_eq!(client.request("p9"), "0200");
+
+        assert_eq!(client.request("Z0,3,1"), "OK");
+        assert_eq!(client.request("c"), "S05");
+        assert_eq!(client.request("p9"), "0300");
+        assert_eq!(client.request("z0,3,1"), "OK");
+
+        assert_eq!(client.request("Z2,c000,1"), "OK");
+        assert_eq!(client.request("c"), "T05watch:c000;");
+        assert_eq!(client.request("mc000,2"), "4200");
+
+        assert_eq!(client.request("Mc001,2:abcd"), "OK");
+        assert_eq!(client.request("mc001,2"), "abcd");
+        assert_eq!(client.request("P8=feff"), "OK");
+        assert_eq!(client.request("p8"), "feff");
+
+        assert_eq!(client.request("D"), "OK");
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_interrupt_sent_with_continue() {
+        // JR -2
+        let (mut client, server) = Client::connect(vec![0x18, 0xFE]);
+
+        assert_eq!(client.request_followed_by("c", &[0x03]), "S02");
+        assert_eq!(client.request("p9"), "0000");
+
+        assert_eq!(client.request("D"), "OK");
+        server.join().unwrap();
+    }
+}
@@ -1,25 +1,249 @@
 extern crate gameboy_lib;
 
-use std::{fs::{self, File}, io::Write};
+use std::{
+    fs,
+    io::Write,
+    net::TcpListener,
+    path::{Path, PathBuf},
+};
 
-use gameboy_lib::Gameboy;
+use cli::{Command, Frontend, RunOptions};
+use gameboy_lib::{disassembler, image, log, memory, ppu, test_rom, Gameboy};
+use save_slot::SaveSlot;
+
+mod cli;
+mod debugger;
+mod gdb;
+mod pacing;
+mod save_slot;
+mod tui;
+#[cfg(feature = "window")]
+mod window;
 
 fn main() {
-    println!("{}", std::env::current_dir().unwrap().display());
-    let boot_rom = load_boot_rom();
-    let _rom = load_rom("./roms/tetris.gb");
-    let mut gameboy: Gameboy = Gameboy::new(boot_rom, vec![]);
-    gameboy.start();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let command = match cli::parse(&args) {
+        Ok(command) => command,
+        Err(error) => {
+            eprintln!("error: {}\n\n{}", error, cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+    let result = init_logging().and_then(|()| match command {
+        Command::Help => {
+            println!("{}", cli::USAGE);
+            Ok(())
+        }
+        Command::Disasm { rom, output } => disasm(&rom, output.as_deref()),
+        Command::TestRom {
+            path,
+            cycles,
+            junit,
+        } => run_test_roms(&path, cycles, junit.as_deref()).map(|passed| {
+            if !passed {
+                std::process::exit(1);
+            }
+        }),
+        Command::Run(options) => run(&options),
+    });
+    if let Err(error) = result {
+        eprintln!("error: {}", error);
+        std::process::exit(1);
+    }
+}
+
+fn run(options: &RunOptions) -> Result<(), String> {
+    let boot_rom = options.boot_rom.as_deref().map(read_boot_rom).transpose()?;
+    let rom = options.rom.as_deref().map(read_rom).transpose()?;
+    let mut gameboy = Gameboy::new(boot_rom.unwrap_or_default(), rom.unwrap_or_default());
+    gameboy.set_model(options.model);
+    gameboy.set_illegal_opcode_policy(options.illegal_opcode);
+    let save_slot = SaveSlot::new(options.save_dir.as_deref(), options.rom.as_deref());
+
+    match options.frontend {
+        Frontend::Headless => {
+            gameboy.reset();
+            let mut frame = 0;
+            while options.frames.is_none_or(|frames| frame < frames) {
+                gameboy.run_frame().map_err(|error| error.to_string())?;
+                frame += 1;
+            }
+        }
+        Frontend::Window => run_window(&mut gameboy, options, save_slot)?,
+        Frontend::Tui => {
+            let tui_options = tui::Options {
+                palette: options.palette,
+                panels: options.panels,
+                frame_limit: options.frames,
+                save_slot,
+            };
+            tui::run(&mut gameboy, &tui_options).map_err(|error| error.to_string())?;
+        }
+        Frontend::Debugger => {
+            let stdin = std::io::stdin();
+            debugger::run(&mut gameboy, stdin.lock(), std::io::stdout())
+                .map_err(|error| format!("Debugger I/O error: {}", error))?;
+        }
+        Frontend::Gdb(port) => {
+            let listener = TcpListener::bind(("127.0.0.1", port))
+                .map_err(|error| format!("Could not listen on port {}: {}", port, error))?;
+            println!("Waiting for GDB on 127.0.0.1:{}", port);
+            gdb::serve(&mut gameboy, listener)
+                .map_err(|error| format!("GDB connection error: {}", error))?;
+        }
+    }
+
+    if let Some(path) = &options.screenshot {
+        write_screenshot(&gameboy, &options.palette, path)?;
+    }
+    if let Some(path) = &options.dump_memory {
+        write_file(path, &gameboy.dump_memory())?;
+    }
+    Ok(())
+}
+
+// PPM for paths ending in .ppm, PNG otherwise
+fn write_screenshot(gameboy: &Gameboy, palette: &ppu::Palette, path: &Path) -> Result<(), String> {
+    let rgb = gameboy.framebuffer_rgb(palette);
+    let (width, height) = (ppu::SCREEN_WIDTH, ppu::SCREEN_HEIGHT);
+    let data = if path.extension().is_some_and(|extension| extension == "ppm") {
+        image::encode_ppm(width, height, &rgb)
+    } else {
+        image::encode_png(width, height, &rgb)
+    };
+    write_file(path, &data)
+}
+
+#[cfg(feature = "window")]
+fn run_window(
+    gameboy: &mut Gameboy,
+    options: &RunOptions,
+    save_slot: SaveSlot,
+) -> Result<(), String> {
+    let window_options = window::Options {
+        scale: options.scale,
+        palette: options.palette,
+        frame_limit: options.frames,
+        save_slot,
+    };
+    window::run(gameboy, &window_options)
+}
+
+#[cfg(not(feature = "window"))]
+fn run_window(
+    _gameboy: &mut Gameboy,
+    _options: &RunOptions,
+    _save_slot: SaveSlot,
+) -> Result<(), String> {
+    Err("gameboy-bin was built without the `window` feature".to_string())
+}
+
+fn disasm(path: &Path, output: Option<&Path>) -> Result<(), String> {
+    let rom = read_file(path, "ROM")?;
+    let listing = disassembler::format_listing(&disassembler::disassemble(&rom, 0x0000));
+    match output {
+        Some(output) => write_file(output, listing.as_bytes()),
+        None => std::io::stdout()
+            .write_all(listing.as_bytes())
+            .map_err(|error| format!("Could not write disassembly: {}", error)),
+    }
+}
+
+fn run_test_roms(root: &Path, cycles: u64, junit: Option<&Path>) -> Result<bool, String> {
+    let mut roms = Vec::new();
+    if root.is_dir() {
+        find_roms(root, &mut roms)
+            .map_err(|error| format!("Could not read {}: {}", root.display(), error))?;
+        roms.sort();
+    } else {
+        roms.push(root.to_path_buf());
+    }
+
+    let mut results = Vec::new();
+    for rom in &roms {
+        let name = rom
+            .strip_prefix(root)
+            .ok()
+            .filter(|name| !name.as_os_str().is_empty())
+            .unwrap_or(rom);
+        let result =
+            test_rom::run_test_rom(&name.display().to_string(), read_file(rom, "ROM")?, cycles);
+        println!(
+            "{:<8} {} ({} cycles)",
+            outcome_label(&result.outcome),
+            result.name,
+            result.cycles
+        );
+        if let test_rom::TestOutcome::Error(error) = &result.outcome {
+            println!("{:<8} {}", "", error);
+        }
+        results.push(result);
+    }
+    let passed = results.iter().filter(|result| result.passed()).count();
+    println!("{} of {} test ROMs passed", passed, results.len());
+
+    if let Some(report) = junit {
+        let suite = root
+            .file_name()
+            .map_or("test-roms".into(), |name| name.to_string_lossy());
+        write_file(report, test_rom::junit_xml(&suite, &results).as_bytes())?;
+    }
+    Ok(passed == results.len())
+}
+
+fn outcome_label(outcome: &test_rom::TestOutcome) -> &'static str {
+    match outcome {
+        test_rom::TestOutcome::Passed => "PASS",
+        test_rom::TestOutcome::Failed(_) => "FAIL",
+        test_rom::TestOutcome::TimedOut => "TIMEOUT",
+        test_rom::TestOutcome::Error(_) => "ERROR",
+    }
+}
+
+fn find_roms(directory: &Path, roms: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in fs::read_dir(directory)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            find_roms(&path, roms)?;
+        } else if matches!(
+            path.extension().and_then(|extension| extension.to_str()),
+            Some("gb" | "gbc")
+        ) {
+            roms.push(path);
+        }
+    }
+    Ok(())
+}
+
+// Log filter like `debug` or `cpu=trace,mem=off`, only has an effect when
+// built with the `logging` feature
+fn init_logging() -> Result<(), String> {
+    if let Ok(spec) = std::env::var("GB_LOG") {
+        log::configure(&spec).map_err(|error| format!("Invalid GB_LOG filter: {}", error))?;
+        log::set_sink(Box::new(log::WriteSink::new(std::io::stdout())));
+    }
+    Ok(())
+}
+
+fn read_file(path: &Path, what: &str) -> Result<Vec<u8>, String> {
+    fs::read(path).map_err(|error| format!("Could not read {} {}: {}", what, path.display(), error))
+}
 
-    let mem_dump = gameboy.dump_memory();
-    let mut file = File::create("memory.bin").expect("Error while creating memory.bin");
-    file.write_all(&mem_dump.as_slice()).expect("Error while writing memory.bin");
+// Cartridge ROMs have to fit the address space until there is an MBC
+fn read_rom(path: &Path) -> Result<Vec<u8>, String> {
+    let rom = read_file(path, "ROM")?;
+    memory::check_rom_size(&rom)
+        .map_err(|error| format!("Could not load {}: {}", path.display(), error))?;
+    Ok(rom)
 }
 
-fn load_boot_rom() -> Vec<u8> {
-    return fs::read("./boot_roms/dmg_boot.bin").expect("Error while reading boot rom");
+fn read_boot_rom(path: &Path) -> Result<Vec<u8>, String> {
+    let boot_rom = read_file(path, "boot ROM")?;
+    memory::check_boot_rom_size(&boot_rom)
+        .map_err(|error| format!("Could not load {}: {}", path.display(), error))?;
+    Ok(boot_rom)
 }
 
-fn load_rom(file: &str) -> Vec<u8> {
-    return fs::read(file).expect("Error while reading rom");
+fn write_file(path: &Path, data: &[u8]) -> Result<(), String> {
+    fs::write(path, data).map_err(|error| format!("Could not write {}: {}", path.display(), error))
 }
@@ -0,0 +1,80 @@
+use std::time::{Duration, Instant};
+
+use gameboy_lib::CYCLES_PER_FRAME;
+
+const CLOCK_HZ: u64 = 4_194_304;
+/// 70224 cycles at 4.194304 MHz, about 59.7275 frames per second.
+pub const FRAME_DURATION: Duration =
+    Duration::from_nanos(CYCLES_PER_FRAME * 1_000_000_000 / CLOCK_HZ);
+// Resynchronise instead of running frames back to back to catch up
+const MAX_LAG: Duration = Duration::from_millis(100);
+
+/// Works out how long a frontend should sleep between frames to keep a
+/// steady rate even when individual frames take longer than others.
+pub struct FramePacer {
+    period: Duration,
+    next: Instant,
+}
+
+impl FramePacer {
+    pub fn new(period: Duration, now: Instant) -> FramePacer {
+        FramePacer {
+            period,
+            next: now + period,
+        }
+    }
+
+    /// Time left until the current frame is due, advancing to the next one.
+    pub fn advance(&mut self, now: Instant) -> Duration {
+        if now > self.next + MAX_LAG {
+            self.next = now;
+        }
+        let wait = self.next.saturating_duration_since(now);
+        self.next += self.period;
+        wait
+    }
+
+    pub fn resync(&mut self, now: Instant) {
+        self.next = now + self.period;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_duration() {
+        let rate = 1.0 / FRAME_DURATION.as_secs_f64();
+        assert!((rate - 59.7275).abs() < 0.0001, "{}", rate);
+    }
+
+    #[test]
+    fn test_pacer() {
+        let start = Instant::now();
+        let period = Duration::from_millis(10);
+        let mut pacer = FramePacer::new(period, start);
+        assert_eq!(
+            pacer.advance(start + Duration::from_millis(4)),
+            Duration::from_millis(6)
+        );
+        // A slow frame is made up for by the next one
+        assert_eq!(
+            pacer.advance(start + Duration::from_millis(22)),
+            Duration::ZERO
+        );
+        assert_eq!(
+            pacer.advance(start + Duration::from_millis(25)),
+            Duration::from_millis(5)
+        );
+        // Falling far behind starts over instead of racing to catch up
+        assert_eq!(
+            pacer.advance(start + Duration::from_secs(1)),
+            Duration::ZERO
+        );
+        assert_eq!(
+            pacer.advance(start + Duration::from_secs(1)),
+            Duration::from_millis(10)
+        );
+    }
+}
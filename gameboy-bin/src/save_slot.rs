@@ -0,0 +1,81 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use gameboy_lib::Gameboy;
+
+/// Save state file of the running ROM, written and read by frontend hotkeys.
+pub struct SaveSlot {
+    path: PathBuf,
+}
+
+impl SaveSlot {
+    /// `<save_dir>/<rom name>.state`, the save directory defaults to the
+    /// directory of the ROM.
+    pub fn new(save_dir: Option<&Path>, rom: Option<&Path>) -> SaveSlot {
+        let directory = save_dir
+            .or_else(|| rom.and_then(Path::parent))
+            .unwrap_or(Path::new("."));
+        let name = rom
+            .and_then(Path::file_stem)
+            .map_or("boot".into(), |name| name.to_string_lossy());
+        SaveSlot {
+            path: directory.join(format!("{}.state", name)),
+        }
+    }
+
+    pub fn save(&self, gameboy: &Gameboy) -> Result<String, String> {
+        if let Some(directory) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(directory)
+                .map_err(|error| format!("Could not create {}: {}", directory.display(), error))?;
+        }
+        fs::write(&self.path, gameboy.save_state())
+            .map_err(|error| format!("Could not write {}: {}", self.path.display(), error))?;
+        Ok(format!("Saved state to {}", self.path.display()))
+    }
+
+    pub fn load(&self, gameboy: &mut Gameboy) -> Result<String, String> {
+        let data = fs::read(&self.path)
+            .map_err(|error| format!("Could not read {}: {}", self.path.display(), error))?;
+        gameboy
+            .load_state(&data)
+            .map_err(|error| format!("Could not load {}: {}", self.path.display(), error))?;
+        Ok(format!("Loaded state from {}", self.path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_path() {
+        let slot = SaveSlot::new(None, Some(Path::new("roms/tetris.gb")));
+        assert_eq!(slot.path, Path::new("roms/tetris.state"));
+        let slot = SaveSlot::new(Some(Path::new("saves")), Some(Path::new("tetris.gb")));
+        assert_eq!(slot.path, Path::new("saves/tetris.state"));
+        assert_eq!(SaveSlot::new(None, None).path, Path::new("./boot.state"));
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let directory =
+            std::env::temp_dir().join(format!("gameboy-save-slot-{}", std::process::id()));
+        let slot = SaveSlot::new(Some(&directory), Some(Path::new("game.gb")));
+        let mut gameboy = Gameboy::new(vec![], vec![0x00; 0x200]);
+        gameboy.reset();
+        gameboy.run_frame().unwrap();
+        slot.save(&gameboy).unwrap();
+
+        gameboy.run_frame().unwrap();
+        slot.load(&mut gameboy).unwrap();
+        assert_eq!(gameboy.frame(), 1);
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert!(slot
+            .load(&mut gameboy)
+            .unwrap_err()
+            .starts_with("Could not read"));
+    }
+}
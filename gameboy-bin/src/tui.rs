@@ -0,0 +1,449 @@
+use std::{
+    fmt::Write as _,
+    io::{self, Read, Write},
+    process::{Command, Stdio},
+    sync::mpsc::{self, Receiver},
+    thread,
+    time::Instant,
+};
+
+use gameboy_lib::{
+    debugger::StopReason,
+    joypad::{Button, JoypadState, BUTTONS},
+    ppu::{Palette, SCREEN_HEIGHT, SCREEN_WIDTH},
+    Gameboy,
+};
+
+use crate::{
+    pacing::{FramePacer, FRAME_DURATION},
+    save_slot::SaveSlot,
+};
+
+// Every character cell shows two pixels, the top one as the foreground color
+// of an upper half block and the bottom one as the background color
+const HALF_BLOCK: char = '▀';
+const ROWS: usize = SCREEN_HEIGHT / 2;
+const PANEL_COLUMN: usize = SCREEN_WIDTH + 2;
+const PANEL_WIDTH: usize = 36;
+const DISASSEMBLY_BEFORE: usize = 4;
+const DISASSEMBLY_AFTER: usize = 10;
+const FAST_FORWARD_FRAMES: u32 = 4;
+// Terminals only report key presses, so a button stays down for a few frames
+// after its key was last seen and key repeat keeps it topped up
+const HOLD_FRAMES: u8 = 8;
+
+const HELP: [&str; 5] = [
+    "Arrows d-pad  X A  Z B",
+    "Enter Start  Backspace Select",
+    "P pause  R reset  F fast-forward",
+    "W save state  L load state",
+    "Tab panels  S step  N frame  Q quit",
+];
+
+pub struct Options {
+    pub palette: Palette,
+    pub panels: bool,
+    /// Quit after this many frames.
+    pub frame_limit: Option<u64>,
+    pub save_slot: SaveSlot,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Key {
+    Up,
+    Down,
+    Left,
+    Right,
+    Enter,
+    Backspace,
+    Tab,
+    Interrupt, // Ctrl-C, which raw mode delivers as a byte
+    Char(char),
+}
+
+fn parse_keys(bytes: &[u8]) -> Vec<Key> {
+    let mut keys = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let key = match bytes[i] {
+            // Cursor keys are sent as ESC [ A..D or ESC O A..D
+            0x1B if matches!(bytes.get(i + 1), Some(b'[' | b'O')) && i + 2 < bytes.len() => {
+                i += 2;
+                match bytes[i] {
+                    b'A' => Some(Key::Up),
+                    b'B' => Some(Key::Down),
+                    b'C' => Some(Key::Right),
+                    b'D' => Some(Key::Left),
+                    _ => None,
+                }
+            }
+            0x03 => Some(Key::Interrupt),
+            b'\t' => Some(Key::Tab),
+            b'\r' | b'\n' => Some(Key::Enter),
+            0x7F | 0x08 => Some(Key::Backspace),
+            byte if byte.is_ascii_graphic() => Some(Key::Char(byte.to_ascii_lowercase() as char)),
+            _ => None,
+        };
+        keys.extend(key);
+        i += 1;
+    }
+    keys
+}
+
+fn button(key: Key) -> Option<Button> {
+    match key {
+        Key::Up => Some(Button::Up),
+        Key::Down => Some(Button::Down),
+        Key::Left => Some(Button::Left),
+        Key::Right => Some(Button::Right),
+        Key::Char('x') => Some(Button::A),
+        Key::Char('z') => Some(Button::B),
+        Key::Enter => Some(Button::Start),
+        Key::Backspace => Some(Button::Select),
+        _ => None,
+    }
+}
+
+#[derive(Default)]
+struct HeldButtons {
+    frames: [u8; 8], // Indexed like `BUTTONS`
+}
+
+impl HeldButtons {
+    fn press(&mut self, button: Button) {
+        self.frames[button as usize] = HOLD_FRAMES;
+    }
+
+    fn state(&self) -> JoypadState {
+        let mut joypad = JoypadState::default();
+        for (button, frames) in BUTTONS.iter().zip(self.frames) {
+            if frames > 0 {
+                joypad.press(*button);
+            }
+        }
+        joypad
+    }
+
+    fn tick(&mut self) {
+        for frames in &mut self.frames {
+            *frames = frames.saturating_sub(1);
+        }
+    }
+}
+
+/// Draws the framebuffer, only sending the cells that changed since the
+/// previous frame to keep the output small enough for SSH.
+#[derive(Default)]
+struct Screen {
+    previous: Option<Vec<([u8; 3], [u8; 3])>>,
+}
+
+impl Screen {
+    fn invalidate(&mut self) {
+        self.previous = None;
+    }
+
+    fn render(&mut self, rgb: &[u8], output: &mut String) {
+        let pixel = |x: usize, y: usize| -> [u8; 3] {
+            let offset = (y * SCREEN_WIDTH + x) * 3;
+            [rgb[offset], rgb[offset + 1], rgb[offset + 2]]
+        };
+        let cells: Vec<([u8; 3], [u8; 3])> = (0..ROWS)
+            .flat_map(|row| (0..SCREEN_WIDTH).map(move |x| (x, row)))
+            .map(|(x, row)| (pixel(x, row * 2), pixel(x, row * 2 + 1)))
+            .collect();
+
+        let (mut foreground, mut background) = (None, None);
+        let mut cursor = None;
+        for (i, cell) in cells.iter().enumerate() {
+            if self
+                .previous
+                .as_ref()
+                .is_some_and(|previous| previous[i] == *cell)
+            {
+                continue;
+            }
+            let (row, column) = (i / SCREEN_WIDTH, i % SCREEN_WIDTH);
+            if cursor != Some(i) {
+                let _ = write!(output, "\x1b[{};{}H", row + 1, column + 1);
+            }
+            if foreground != Some(cell.0) {
+                let [r, g, b] = cell.0;
+                let _ = write!(output, "\x1b[38;2;{};{};{}m", r, g, b);
+                foreground = Some(cell.0);
+            }
+            if background != Some(cell.1) {
+                let [r, g, b] = cell.1;
+                let _ = write!(output, "\x1b[48;2;{};{};{}m", r, g, b);
+                background = Some(cell.1);
+            }
+            output.push(HALF_BLOCK);
+            // The cursor does not wrap to the next row by itself
+            cursor = Some(i + 1).filter(|next| next % SCREEN_WIDTH != 0);
+        }
+        output.push_str("\x1b[0m");
+        self.previous = Some(cells);
+    }
+}
+
+fn panel_lines(gameboy: &Gameboy, status: &[&str]) -> Vec<String> {
+    let registers = gameboy.registers();
+    let f = &registers.f;
+    let mut lines = vec![
+        format!(
+            "A: {:02X}  F: {:02X}  [{}{}{}{}]",
+            registers.a,
+            f.get(),
+            if f.zero { 'Z' } else { '-' },
+            if f.subtract { 'N' } else { '-' },
+            if f.half_carry { 'H' } else { '-' },
+            if f.carry { 'C' } else { '-' },
+        ),
+        format!("B: {:02X}  C: {:02X}", registers.b, registers.c),
+        format!("D: {:02X}  E: {:02X}", registers.d, registers.e),
+        format!("H: {:02X}  L: {:02X}", registers.h, registers.l),
+        format!("SP: {:04X}  PC: {:04X}", registers.sp.get(), gameboy.pc()),
+        format!("Frame {}  Cycles {}", gameboy.frame(), gameboy.cycles()),
+        String::new(),
+    ];
+    for instruction in
+        gameboy.disassemble_around(gameboy.pc(), DISASSEMBLY_BEFORE, DISASSEMBLY_AFTER)
+    {
+        let marker = if instruction.address == gameboy.pc() {
+            '>'
+        } else {
+            ' '
+        };
+        lines.push(format!(
+            "{} {:04X}  {}",
+            marker, instruction.address, instruction.text
+        ));
+    }
+    lines.push(String::new());
+    lines.extend(status.iter().map(|line| line.to_string()));
+    lines.extend(HELP.iter().map(|line| line.to_string()));
+    lines
+}
+
+fn render_panels(lines: &[String], output: &mut String) {
+    for (row, line) in lines.iter().enumerate() {
+        let text: String = line.chars().take(PANEL_WIDTH).collect();
+        let _ = write!(output, "\x1b[{};{}H{}\x1b[K", row + 1, PANEL_COLUMN, text);
+    }
+}
+
+// Puts the terminal into raw mode on the alternate screen and restores it
+// when dropped, including when unwinding from a panic
+struct RawTerminal {
+    saved: String,
+}
+
+impl RawTerminal {
+    fn enter() -> io::Result<RawTerminal> {
+        let saved = stty(&["-g"])?;
+        stty(&["raw", "-echo"])?;
+        let mut stdout = io::stdout();
+        stdout.write_all(b"\x1b[?1049h\x1b[?25l\x1b[2J")?;
+        stdout.flush()?;
+        Ok(RawTerminal { saved })
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        let mut stdout = io::stdout();
+        let _ = stdout.write_all(b"\x1b[0m\x1b[?25h\x1b[?1049l");
+        let _ = stdout.flush();
+        let _ = stty(&[&self.saved]);
+    }
+}
+
+fn stty(args: &[&str]) -> io::Result<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::other("--tui needs an interactive terminal"));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+// Reads stdin on a separate thread so the emulation never blocks on input
+fn spawn_input() -> Receiver<Vec<u8>> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut stdin = io::stdin();
+        let mut buffer = [0u8; 64];
+        while let Ok(length @ 1..) = stdin.read(&mut buffer) {
+            if sender.send(buffer[..length].to_vec()).is_err() {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+/// Plays in the terminal until Q or Ctrl-C is pressed. The game is reset first.
+pub fn run(gameboy: &mut Gameboy, options: &Options) -> io::Result<()> {
+    let _terminal = RawTerminal::enter()?;
+    let input = spawn_input();
+    let mut stdout = io::stdout();
+
+    gameboy.reset();
+    let mut screen = Screen::default();
+    let mut held = HeldButtons::default();
+    let mut panels = options.panels;
+    let (mut paused, mut fast_forward) = (false, false);
+    let mut message = String::new();
+    let mut frames = 0;
+    let mut pacer = FramePacer::new(FRAME_DURATION, Instant::now());
+    loop {
+        if options.frame_limit.is_some_and(|limit| frames >= limit) {
+            return Ok(());
+        }
+        let bytes: Vec<u8> = input.try_iter().flatten().collect();
+        let mut step = false;
+        for key in parse_keys(&bytes) {
+            match key {
+                Key::Interrupt | Key::Char('q') => return Ok(()),
+                Key::Char('p') => paused = !paused,
+                Key::Char('r') => gameboy.reset(),
+                Key::Char('f') => fast_forward = !fast_forward,
+                Key::Char('w') => {
+                    message = options
+                        .save_slot
+                        .save(gameboy)
+                        .unwrap_or_else(|error| error)
+                }
+                Key::Char('l') => {
+                    message = options
+                        .save_slot
+                        .load(gameboy)
+                        .unwrap_or_else(|error| error)
+                }
+                Key::Tab => {
+                    panels = !panels;
+                    stdout.write_all(b"\x1b[2J")?;
+                    screen.invalidate();
+                }
+                Key::Char('s') if paused => {
+                    if let StopReason::Error(error) = gameboy.step() {
+                        message = error.to_string();
+                    }
+                }
+                Key::Char('n') if paused => step = true,
+                key => {
+                    if let Some(button) = button(key) {
+                        held.press(button);
+                    }
+                }
+            }
+        }
+
+        gameboy.set_joypad(held.state());
+        if !paused || step {
+            let count = if fast_forward && !paused {
+                FAST_FORWARD_FRAMES
+            } else {
+                1
+            };
+            for _ in 0..count {
+                if let Err(error) = gameboy.run_frame() {
+                    message = error.to_string();
+                    paused = true;
+                    break;
+                }
+            }
+            held.tick();
+            frames += 1;
+        }
+
+        let mut output = String::new();
+        screen.render(&gameboy.framebuffer_rgb(&options.palette), &mut output);
+        if panels {
+            let status = match (paused, fast_forward) {
+                _ if gameboy.is_locked_up() => "Locked up",
+                (true, _) => "Paused",
+                (false, true) => "Fast-forward",
+                (false, false) => "Running",
+            };
+            render_panels(&panel_lines(gameboy, &[status, &message]), &mut output);
+        }
+        stdout.write_all(output.as_bytes())?;
+        stdout.flush()?;
+
+        let now = Instant::now();
+        if fast_forward && !paused {
+            pacer.resync(now);
+        } else {
+            thread::sleep(pacer.advance(now));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_keys() {
+        assert_eq!(
+            parse_keys(b"\x1b[A\x1bOCxZ\r\x7f\t\x03"),
+            vec![
+                Key::Up,
+                Key::Right,
+                Key::Char('x'),
+                Key::Char('z'),
+                Key::Enter,
+                Key::Backspace,
+                Key::Tab,
+                Key::Interrupt,
+            ]
+        );
+        assert_eq!(parse_keys(b"\x1b"), vec![]);
+    }
+
+    #[test]
+    fn test_held_buttons() {
+        let mut held = HeldButtons::default();
+        held.press(Button::Start);
+        assert!(held.state().is_pressed(Button::Start));
+        for _ in 0..HOLD_FRAMES {
+            held.tick();
+        }
+        assert_eq!(held.state(), JoypadState::default());
+    }
+
+    #[test]
+    fn test_screen_sends_changed_cells() {
+        let mut rgb = vec![0xFF; SCREEN_WIDTH * SCREEN_HEIGHT * 3];
+        let mut screen = Screen::default();
+        let mut output = String::new();
+        screen.render(&rgb, &mut output);
+        assert_eq!(output.matches(HALF_BLOCK).count(), SCREEN_WIDTH * ROWS);
+        // Colors are only sent once while they stay the same
+        assert_eq!(output.matches("\x1b[38;2;255;255;255m").count(), 1);
+        assert_eq!(output.matches("H").count(), ROWS);
+
+        // Bottom pixel of the cell in row 1, column 2
+        let offset = (3 * SCREEN_WIDTH + 2) * 3;
+        rgb[offset..offset + 3].copy_from_slice(&[0, 0, 0]);
+        let mut output = String::new();
+        screen.render(&rgb, &mut output);
+        assert_eq!(
+            output,
+            "\x1b[2;3H\x1b[38;2;255;255;255m\x1b[48;2;0;0;0m▀\x1b[0m"
+        );
+    }
+
+    #[test]
+    fn test_panel_lines() {
+        let mut gameboy = Gameboy::new(vec![], vec![0x00; 0x200]);
+        gameboy.reset();
+        let lines = panel_lines(&gameboy, &["Paused"]);
+        assert_eq!(lines[0], "A: 01  F: B0  [Z-HC]");
+        assert!(lines.contains(&"> 0100  nop".to_string()));
+        assert!(lines.contains(&"Paused".to_string()));
+    }
+}
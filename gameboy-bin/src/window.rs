@@ -0,0 +1,167 @@
+use std::{thread, time::Instant};
+
+use gameboy_lib::{
+    joypad::{Button, JoypadState},
+    ppu::{Palette, SCREEN_HEIGHT, SCREEN_WIDTH},
+    Gameboy,
+};
+use minifb::{Key, KeyRepeat, Window, WindowOptions};
+
+use crate::{
+    pacing::{FramePacer, FRAME_DURATION},
+    save_slot::SaveSlot,
+};
+
+// Frames emulated per displayed frame while fast-forwarding
+const FAST_FORWARD_FRAMES: u32 = 4;
+const KEYMAP: [(Key, Button); 8] = [
+    (Key::Right, Button::Right),
+    (Key::Left, Button::Left),
+    (Key::Up, Button::Up),
+    (Key::Down, Button::Down),
+    (Key::X, Button::A),
+    (Key::Z, Button::B),
+    (Key::Backspace, Button::Select),
+    (Key::Enter, Button::Start),
+];
+const PAUSE_KEY: Key = Key::P;
+const RESET_KEY: Key = Key::R;
+const FAST_FORWARD_KEY: Key = Key::Space;
+const QUIT_KEY: Key = Key::Escape;
+const SAVE_STATE_KEY: Key = Key::F5;
+const LOAD_STATE_KEY: Key = Key::F8;
+
+pub struct Options {
+    pub scale: usize,
+    pub palette: Palette,
+    /// Close the window after this many frames, used for smoke tests.
+    pub frame_limit: Option<u64>,
+    pub save_slot: SaveSlot,
+}
+
+fn joypad_state(keys: &[Key]) -> JoypadState {
+    let mut joypad = JoypadState::default();
+    for (key, button) in KEYMAP {
+        if keys.contains(&key) {
+            joypad.press(button);
+        }
+    }
+    joypad
+}
+
+// Packs RGB into minifb's 0RGB pixels, repeating every pixel `scale` times in
+// both directions
+fn scale_frame(rgb: &[u8], scale: usize) -> Vec<u32> {
+    let mut buffer = Vec::with_capacity(SCREEN_WIDTH * SCREEN_HEIGHT * scale * scale);
+    for row in rgb.chunks(SCREEN_WIDTH * 3) {
+        let line: Vec<u32> = row
+            .chunks(3)
+            .flat_map(|pixel| {
+                let color = (pixel[0] as u32) << 16 | (pixel[1] as u32) << 8 | pixel[2] as u32;
+                std::iter::repeat_n(color, scale)
+            })
+            .collect();
+        for _ in 0..scale {
+            buffer.extend_from_slice(&line);
+        }
+    }
+    buffer
+}
+
+fn report(result: Result<String, String>) {
+    match result {
+        Ok(message) => println!("{}", message),
+        Err(error) => eprintln!("{}", error),
+    }
+}
+
+/// Opens a window and plays until it is closed. The game is reset first.
+pub fn run(gameboy: &mut Gameboy, options: &Options) -> Result<(), String> {
+    let (width, height) = (SCREEN_WIDTH * options.scale, SCREEN_HEIGHT * options.scale);
+    let mut window = Window::new("Game Boy", width, height, WindowOptions::default())
+        .map_err(|error| format!("Unable to open window: {}", error))?;
+    // Pacing is done by `FramePacer`
+    window.set_target_fps(0);
+
+    gameboy.reset();
+    let mut paused = false;
+    let mut frames = 0;
+    let mut pacer = FramePacer::new(FRAME_DURATION, Instant::now());
+    while window.is_open() && !window.is_key_down(QUIT_KEY) {
+        if options.frame_limit.is_some_and(|limit| frames >= limit) {
+            break;
+        }
+        if window.is_key_pressed(PAUSE_KEY, KeyRepeat::No) {
+            paused = !paused;
+        }
+        if window.is_key_pressed(RESET_KEY, KeyRepeat::No) {
+            gameboy.reset();
+        }
+        if window.is_key_pressed(SAVE_STATE_KEY, KeyRepeat::No) {
+            report(options.save_slot.save(gameboy));
+        }
+        if window.is_key_pressed(LOAD_STATE_KEY, KeyRepeat::No) {
+            report(options.save_slot.load(gameboy));
+        }
+        let fast_forward = window.is_key_down(FAST_FORWARD_KEY);
+
+        if !paused {
+            gameboy.set_joypad(joypad_state(&window.get_keys()));
+            let count = if fast_forward { FAST_FORWARD_FRAMES } else { 1 };
+            for _ in 0..count {
+                // Pause at the failing instruction so the last frame stays visible
+                if let Err(error) = gameboy.run_frame() {
+                    eprintln!("{}", error);
+                    paused = true;
+                    break;
+                }
+            }
+            frames += 1;
+        }
+
+        let title = match (paused, fast_forward) {
+            _ if gameboy.is_locked_up() => "Game Boy [locked up]",
+            (true, _) => "Game Boy [paused]",
+            (false, true) => "Game Boy [fast-forward]",
+            (false, false) => "Game Boy",
+        };
+        window.set_title(title);
+        let buffer = scale_frame(&gameboy.framebuffer_rgb(&options.palette), options.scale);
+        window
+            .update_with_buffer(&buffer, width, height)
+            .map_err(|error| format!("Unable to update window: {}", error))?;
+
+        let now = Instant::now();
+        if fast_forward && !paused {
+            pacer.resync(now);
+        } else {
+            thread::sleep(pacer.advance(now));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_joypad_state() {
+        let joypad = joypad_state(&[Key::Enter, Key::Left, Key::Q]);
+        assert!(joypad.is_pressed(Button::Start));
+        assert!(joypad.is_pressed(Button::Left));
+        assert_eq!(joypad.bits().count_ones(), 2);
+    }
+
+    #[test]
+    fn test_scale_frame() {
+        let mut rgb = vec![0u8; SCREEN_WIDTH * SCREEN_HEIGHT * 3];
+        rgb[..3].copy_from_slice(&[0x12, 0x34, 0x56]);
+        let buffer = scale_frame(&rgb, 2);
+        assert_eq!(buffer.len(), SCREEN_WIDTH * SCREEN_HEIGHT * 4);
+        let width = SCREEN_WIDTH * 2;
+        assert_eq!(&buffer[..3], &[0x123456, 0x123456, 0]);
+        assert_eq!(&buffer[width..width + 3], &[0x123456, 0x123456, 0]);
+        assert_eq!(buffer[2 * width], 0);
+    }
+}
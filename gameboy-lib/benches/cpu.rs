@@ -0,0 +1,77 @@
+//! CPU throughput on a synthetic ALU, load and branch heavy loop, run for a
+//! fixed number of cycles with no rendering. Run with `cargo bench --bench cpu`.
+
+use std::time::{Duration, Instant};
+
+use gameboy_lib::{Gameboy, CYCLES_PER_FRAME};
+
+const CLOCK_HZ: f64 = 4_194_304.0;
+const FRAMES: u64 = 600; // Ten seconds of emulated time
+const RUNS: usize = 5;
+
+// Mixes loads, 8-bit ALU ops, CB-prefixed ops, relative and absolute jumps
+// and a CALL/RET pair, touching WRAM on every iteration
+fn cpu_rom() -> Vec<u8> {
+    #[rustfmt::skip]
+    let program = [
+        0x21, 0x00, 0xC0, // 0x0100 LD HL, 0xC000
+        0x01, 0x00, 0x00, // 0x0103 LD BC, 0x0000
+        0x7E,             // 0x0106 loop: LD A, (HL)
+        0x80,             // 0x0107 ADD A, B
+        0xA9,             // 0x0108 XOR C
+        0x22,             // 0x0109 LD (HL+), A
+        0x04,             // 0x010A INC B
+        0x07,             // 0x010B RLCA
+        0xCB, 0x37,       // 0x010C SWAP A
+        0xCB, 0x5F,       // 0x010E BIT 3, A
+        0xE6, 0x3F,       // 0x0110 AND 0x3F
+        0x26, 0xC0,       // 0x0112 LD H, 0xC0
+        0xFE, 0x10,       // 0x0114 CP 0x10
+        0x20, 0xEE,       // 0x0116 JR NZ, loop
+        0x0C,             // 0x0118 INC C
+        0xCD, 0x1F, 0x01, // 0x0119 CALL sub
+        0xC3, 0x06, 0x01, // 0x011C JP loop
+        0xC5,             // 0x011F sub: PUSH BC
+        0xD1,             // 0x0120 POP DE
+        0xC9,             // 0x0121 RET
+    ];
+    let mut rom = vec![0x00; 0x8000];
+    rom[0x100..0x100 + program.len()].copy_from_slice(&program);
+    rom
+}
+
+fn main() {
+    let rom = cpu_rom();
+    let end = FRAMES * CYCLES_PER_FRAME;
+    let mut best = Duration::MAX;
+    let mut instructions = 0u64;
+    for _ in 0..RUNS {
+        let mut gameboy = Gameboy::new(vec![], rom.clone());
+        gameboy.reset();
+        instructions = 0;
+        let started = Instant::now();
+        gameboy
+            .run_until(|gameboy| {
+                instructions += 1;
+                gameboy.cycles() >= end
+            })
+            .expect("benchmark ROM failed");
+        assert!(!gameboy.is_locked_up());
+        best = best.min(started.elapsed());
+    }
+
+    let seconds = best.as_secs_f64();
+    println!(
+        "cpu: {} frames, {} instructions in {:.1} ms (best of {})",
+        FRAMES,
+        instructions,
+        seconds * 1000.0,
+        RUNS
+    );
+    println!(
+        "     {:.1} M instructions/s, {:.1} MHz, {:.1}x real time",
+        instructions as f64 / seconds / 1e6,
+        end as f64 / seconds / 1e6,
+        end as f64 / CLOCK_HZ / seconds
+    );
+}
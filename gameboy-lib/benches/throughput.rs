@@ -0,0 +1,293 @@
+//! Throughput of the opcode tables, the memory map, the CPU on synthetic
+//! instruction streams and whole-system emulation of a small homebrew ROM.
+//! Run with `cargo bench --bench throughput`, optionally followed by the
+//! names of the groups to run: decode, memory, cpu, frame. The CPU-heavy ROM
+//! run for a fixed number of cycles is `cargo bench --bench cpu`.
+//!
+//! The homebrew ROM is assembled in `demo_rom` rather than vendored as a
+//! binary, so the benchmark needs no ROM files and every instruction it runs
+//! can be read here.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use gameboy_lib::cpu::{command, Cpu};
+use gameboy_lib::memory::Memory;
+use gameboy_lib::model::Model;
+use gameboy_lib::{Gameboy, CYCLES_PER_FRAME};
+
+const CLOCK_HZ: f64 = 4_194_304.0;
+const FRAMES_PER_SECOND: f64 = CLOCK_HZ / CYCLES_PER_FRAME as f64;
+const RUNS: usize = 5;
+
+const ROM_START: u16 = 0x100;
+
+// Best wall time over `RUNS` runs of `run`
+fn best_of(mut run: impl FnMut()) -> Duration {
+    let mut best = Duration::MAX;
+    for _ in 0..RUNS {
+        let started = Instant::now();
+        run();
+        best = best.min(started.elapsed());
+    }
+    best
+}
+
+fn per_second(count: u64, time: Duration) -> f64 {
+    count as f64 / time.as_secs_f64()
+}
+
+// Opcode table lookups, as done by `Cpu::step`
+fn decode() {
+    const PASSES: u64 = 20_000;
+    let time = best_of(|| {
+        for _ in 0..PASSES {
+            for byte in 0..=255u8 {
+                black_box(command::lookup::<Memory>(black_box(byte), false));
+                black_box(command::lookup::<Memory>(black_box(byte), true));
+            }
+        }
+    });
+    let lookups = PASSES * 512;
+    println!(
+        "decode   {:>10.1} M lookups/s ({:.2} ns each)",
+        per_second(lookups, time) / 1e6,
+        time.as_nanos() as f64 / lookups as f64
+    );
+}
+
+// Name, first address and size of the area exercised in each region. The I/O
+// and interrupt enable areas stick to registers without side effects.
+const REGIONS: [(&str, u16, u16); 10] = [
+    ("rom0", 0x0000, 0x4000),
+    ("romx", 0x4000, 0x4000),
+    ("vram", 0x8000, 0x2000),
+    ("eram", 0xA000, 0x2000),
+    ("wram", 0xC000, 0x2000),
+    ("echo", 0xE000, 0x1E00),
+    ("oam", 0xFE00, 0x00A0),
+    ("io", 0xFF42, 0x0002), // SCY and SCX
+    ("hram", 0xFF80, 0x007F),
+    ("ie", 0xFFFF, 0x0001),
+];
+
+fn memory() {
+    const ACCESSES: u64 = 4_000_000;
+    let mut memory = Memory::new();
+    for (name, begin, size) in REGIONS {
+        let address = |i: u64| begin + (i % size as u64) as u16;
+        let read = best_of(|| {
+            for i in 0..ACCESSES {
+                black_box(memory.read(address(i)));
+            }
+        });
+        let write = best_of(|| {
+            for i in 0..ACCESSES {
+                memory.write(address(i), black_box(i as u8));
+            }
+        });
+        println!(
+            "memory   {:<5} {:>8.1} M reads/s {:>8.1} M writes/s",
+            name,
+            per_second(ACCESSES, read) / 1e6,
+            per_second(ACCESSES, write) / 1e6
+        );
+    }
+}
+
+// JR from the end of `program` back to `target`
+fn jr(program: &mut Vec<u8>, opcode: u8, target: u16) {
+    let next = ROM_START + program.len() as u16 + 2;
+    program.extend_from_slice(&[opcode, target.wrapping_sub(next) as u8]);
+}
+
+fn rom(program: &[u8]) -> Vec<u8> {
+    let mut rom = vec![0x00; 0x8000];
+    rom[ROM_START as usize..ROM_START as usize + program.len()].copy_from_slice(program);
+    rom
+}
+
+// 8 and 16-bit ALU operations, rotates and CB-prefixed bit operations
+fn alu_stream() -> Vec<u8> {
+    #[rustfmt::skip]
+    let mut program = vec![
+        0x80,       // loop: ADD A, B
+        0x89,       // ADC A, C
+        0x92,       // SUB D
+        0xA3,       // AND E
+        0xB4,       // OR H
+        0xAD,       // XOR L
+        0xB8,       // CP B
+        0x3C,       // INC A
+        0x05,       // DEC B
+        0x09,       // ADD HL, BC
+        0x13,       // INC DE
+        0x07,       // RLCA
+        0xC6, 0x11, // ADD A, 0x11
+        0xEE, 0x5A, // XOR 0x5A
+        0xCB, 0x37, // SWAP A
+        0xCB, 0x00, // RLC B
+        0xCB, 0x39, // SRL C
+        0xCB, 0x7A, // BIT 7, D
+    ];
+    jr(&mut program, 0x18, ROM_START); // JR loop
+    rom(&program)
+}
+
+// Register, immediate, indirect and stack loads, all within WRAM and HRAM
+fn load_stream() -> Vec<u8> {
+    let mut program = vec![0x21, 0x00, 0xC0]; // LD HL, 0xC000
+    let start = ROM_START + program.len() as u16;
+    #[rustfmt::skip]
+    program.extend_from_slice(&[
+        0x7E,             // loop: LD A, (HL)
+        0x47,             // LD B, A
+        0x48,             // LD C, B
+        0x51,             // LD D, C
+        0x22,             // LD (HL+), A
+        0x56,             // LD D, (HL)
+        0x3E, 0x42,       // LD A, 0x42
+        0xE0, 0x80,       // LDH (0x80), A
+        0xF0, 0x81,       // LDH A, (0x81)
+        0xEA, 0x00, 0xC1, // LD (0xC100), A
+        0xFA, 0x01, 0xC1, // LD A, (0xC101)
+        0x11, 0x34, 0x12, // LD DE, 0x1234
+        0xC5,             // PUSH BC
+        0xD1,             // POP DE
+        0x26, 0xC0,       // LD H, 0xC0
+    ]);
+    jr(&mut program, 0x18, start); // JR loop
+    rom(&program)
+}
+
+// Relative and absolute jumps, taken and not taken, and CALL/RET pairs
+fn branch_stream() -> Vec<u8> {
+    let mut program = vec![0x06, 0x08]; // LD B, 8
+    let start = ROM_START + program.len() as u16;
+    let subroutine = start + 16;
+    #[rustfmt::skip]
+    program.extend_from_slice(&[
+        0x18, 0x00,                                          // loop: JR +0
+        0xC3, (start + 5) as u8, ((start + 5) >> 8) as u8,   // JP next
+        0xCD, subroutine as u8, (subroutine >> 8) as u8,     // next: CALL subroutine
+        0x28, 0x00,                                          // JR Z, +0
+        0x05,                                                // DEC B
+    ]);
+    jr(&mut program, 0x20, start); // JR NZ, loop
+    program.extend_from_slice(&[0xC3, ROM_START as u8, (ROM_START >> 8) as u8]); // JP 0x100
+    assert_eq!(ROM_START + program.len() as u16, subroutine);
+    program.push(0xC9); // subroutine: RET
+    rom(&program)
+}
+
+fn cpu() {
+    const INSTRUCTIONS: u64 = 2_000_000;
+    let streams = [
+        ("alu", alu_stream()),
+        ("load", load_stream()),
+        ("branch", branch_stream()),
+    ];
+    for (name, rom) in streams {
+        let mut cycles = 0;
+        let time = best_of(|| {
+            let mut cpu = Cpu::new();
+            cpu.boot(vec![], rom.clone());
+            cpu.reset_post_boot(Model::default());
+            for _ in 0..INSTRUCTIONS {
+                cpu.step().expect("benchmark stream failed");
+            }
+            assert!(!cpu.locked_up, "{} stream locked up", name);
+            cycles = cpu.cycles;
+        });
+        println!(
+            "cpu      {:<6} {:>6.1} M instructions/s {:>7.1} MHz {:>6.1}x real time",
+            name,
+            per_second(INSTRUCTIONS, time) / 1e6,
+            per_second(cycles, time) / 1e6,
+            cycles as f64 / CLOCK_HZ / time.as_secs_f64()
+        );
+    }
+}
+
+// Homebrew demo: fills VRAM with a pattern, turns the LCD on and scrolls the
+// background diagonally once per frame, polling LY for VBlank
+fn demo_rom() -> Vec<u8> {
+    let mut program = vec![0x21, 0x00, 0x80]; // LD HL, 0x8000
+    let fill = ROM_START + program.len() as u16;
+    #[rustfmt::skip]
+    program.extend_from_slice(&[
+        0x7D,       // fill: LD A, L
+        0xAC,       // XOR H
+        0x22,       // LD (HL+), A
+        0x7C,       // LD A, H
+        0xFE, 0xA0, // CP 0xA0
+    ]);
+    jr(&mut program, 0x20, fill); // JR NZ, fill
+    #[rustfmt::skip]
+    program.extend_from_slice(&[
+        0x3E, 0xE4, // LD A, 0xE4
+        0xE0, 0x47, // LDH (BGP), A
+        0x3E, 0x91, // LD A, 0x91
+        0xE0, 0x40, // LDH (LCDC), A
+    ]);
+    let frame = ROM_START + program.len() as u16;
+    program.extend_from_slice(&[0xF0, 0x44, 0xFE, 0x90]); // frame: LDH A, (LY); CP 144
+    jr(&mut program, 0x20, frame); // JR NZ, frame
+    #[rustfmt::skip]
+    program.extend_from_slice(&[
+        0xF0, 0x43, // LDH A, (SCX)
+        0x3C,       // INC A
+        0xE0, 0x43, // LDH (SCX), A
+        0xE0, 0x42, // LDH (SCY), A
+    ]);
+    let vblank = ROM_START + program.len() as u16;
+    program.extend_from_slice(&[0xF0, 0x44, 0xFE, 0x90]); // vblank: LDH A, (LY); CP 144
+    jr(&mut program, 0x28, vblank); // JR Z, vblank
+    jr(&mut program, 0x18, frame); // JR frame
+    rom(&program)
+}
+
+fn frame() {
+    const FRAMES: u64 = 600; // Ten seconds of emulated time
+    let rom = demo_rom();
+    let mut cycles = 0;
+    let time = best_of(|| {
+        let mut gameboy = Gameboy::new(vec![], rom.clone());
+        gameboy.reset();
+        // The first frames are spent filling VRAM with the LCD off
+        let mut frames = 0;
+        while frames < FRAMES {
+            if gameboy.run_frame().expect("demo ROM failed").frame_ready {
+                frames += 1;
+            }
+        }
+        assert!(!gameboy.is_locked_up());
+        cycles = gameboy.cycles();
+    });
+    let fps = per_second(FRAMES, time);
+    println!(
+        "frame    demo   {:>7.1} fps {:>7.1} MHz {:>6.1}x real time",
+        fps,
+        per_second(cycles, time) / 1e6,
+        fps / FRAMES_PER_SECOND
+    );
+}
+
+fn main() {
+    // `cargo bench` passes --bench, everything else selects groups
+    let selected: Vec<String> = std::env::args()
+        .skip(1)
+        .filter(|arg| !arg.starts_with("--"))
+        .collect();
+    let groups: [(&str, fn()); 4] = [
+        ("decode", decode),
+        ("memory", memory),
+        ("cpu", cpu),
+        ("frame", frame),
+    ];
+    for (name, run) in groups {
+        if selected.is_empty() || selected.iter().any(|group| group == name) {
+            run();
+        }
+    }
+}
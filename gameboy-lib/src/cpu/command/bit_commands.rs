@@ -1,54 +1,48 @@
-use crate::cpu::{instructions::BitInstruction, registers::Register, Cpu};
+use crate::cpu::{instructions::BitInstruction, registers::Register, Cpu, ExecutionError};
 
-use super::Command;
+use super::{Handler, Operands};
+use crate::bus::Bus;
 
-pub struct BitCommand<'a> {
-    instruction: &'a BitInstruction,
-    cpu: &'a mut Cpu,
-}
-
-impl<'a> BitCommand<'a> {
-    pub fn new(instruction: &'a BitInstruction, cpu: &'a mut Cpu) -> BitCommand<'a> {
-        BitCommand { instruction, cpu }
+pub(super) const fn decode<B: Bus>(instruction: BitInstruction) -> (Handler<B>, Operands) {
+    match instruction {
+        BitInstruction::Bit(bit, from) => (bit_test, Operands::bit(bit, from)),
+        BitInstruction::Res(bit, from) => (res, Operands::bit(bit, from)),
+        BitInstruction::Set(bit, from) => (set, Operands::bit(bit, from)),
     }
+}
 
-    fn bit(&mut self, bit: &u8, from: &Register) -> u16 {
-        let (value, pc) = self.cpu.extract_operand(from);
-
-        self.cpu.registers.f.zero = (value >> bit) & 0xF == 0;
-        self.cpu.registers.f.subtract = false;
-        self.cpu.registers.f.half_carry = true;
-
-        println!("[CPU] Bit {} from 0x{:02X} is {}", bit, value, self.cpu.registers.f.zero);
+fn bit_test<B: Bus>(cpu: &mut Cpu<B>, operands: Operands) -> Result<u16, ExecutionError> {
+    let bit = operands.value;
+    let (value, pc) = cpu.extract_operand(&operands.to)?;
 
-        pc
-    }
+    cpu.registers.f.zero = value & (1 << bit) == 0;
+    cpu.registers.f.subtract = false;
+    cpu.registers.f.half_carry = true;
 
-    fn res(&mut self, bit: &u8, from: &Register) -> u16 {
-        let value = self.cpu.registers.get(from);
-        let result = value & !(1 << bit);
+    log!(Cpu, Trace, "Bit {} from 0x{:02X} is {}", bit, value, cpu.registers.f.zero);
 
-        self.cpu.registers.set(from, result);
+    Ok(pc)
+}
 
-        self.cpu.pc.wrapping_add(2)
-    }
+fn res<B: Bus>(cpu: &mut Cpu<B>, operands: Operands) -> Result<u16, ExecutionError> {
+    let (value, pc) = cpu.extract_operand(&operands.to)?;
+    write_back(cpu, &operands.to, value & !(1 << operands.value))?;
 
-    fn set(&mut self, bit: &u8, from: &Register) -> u16 {
-        let value = self.cpu.registers.get(from);
-        let result = value | (1 << bit);
+    Ok(pc)
+}
 
-        self.cpu.registers.set(from, result);
+fn set<B: Bus>(cpu: &mut Cpu<B>, operands: Operands) -> Result<u16, ExecutionError> {
+    let (value, pc) = cpu.extract_operand(&operands.to)?;
+    write_back(cpu, &operands.to, value | (1 << operands.value))?;
 
-        self.cpu.pc.wrapping_add(2)
-    }
+    Ok(pc)
 }
 
-impl Command for BitCommand<'_> {
-    fn execute(&mut self) -> u16 {
-        match &self.instruction {
-            BitInstruction::Bit(bit, from) => self.bit(bit, from),
-            BitInstruction::Res(bit, from) => self.res(bit, from),
-            BitInstruction::Set(bit, from) => self.set(bit, from),
-        }
+fn write_back<B: Bus>(cpu: &mut Cpu<B>, to: &Register, value: u8) -> Result<(), ExecutionError> {
+    if let Register::HL = to {
+        cpu.write_cycle(cpu.registers.get_16(&Register::HL)?, value);
+        Ok(())
+    } else {
+        cpu.registers.set(to, value)
     }
 }
@@ -1,49 +1,34 @@
-use crate::cpu::{instructions::{CallInstruction, FlagCondition}, Cpu};
+use crate::cpu::{instructions::CallInstruction, Cpu, ExecutionError};
 
-use super::Command;
+use super::{Handler, Operands};
+use crate::bus::Bus;
 
-
-pub struct CallCommand<'a> {
-    instruction: &'a CallInstruction,
-    cpu: &'a mut Cpu,
-}
-
-impl<'a> CallCommand<'a> {
-    pub fn new(instruction: &'a CallInstruction, cpu: &'a mut Cpu) -> CallCommand<'a> {
-        CallCommand { instruction, cpu }
+pub(super) const fn decode<B: Bus>(instruction: CallInstruction) -> (Handler<B>, Operands) {
+    match instruction {
+        CallInstruction::Call => (call, Operands::NONE),
+        CallInstruction::CallCond(condition) => (call_cc, Operands::condition(condition)),
     }
+}
 
-    fn call(&mut self) -> u16 {
-        let address = self.cpu.memory.read_16(self.cpu.pc + 1);
-        let next_pc = self.cpu.pc.wrapping_add(3);
-        let next_sp = self.cpu.registers.sp.get().wrapping_sub(2);
-        self.cpu.registers.sp.set(next_sp);
-        self.cpu.memory.write_16(self.cpu.registers.sp.get(), next_pc);
+fn call<B: Bus>(cpu: &mut Cpu<B>, _: Operands) -> Result<u16, ExecutionError> {
+    let address = cpu.read_cycle_16(cpu.pc.wrapping_add(1));
+    let next_pc = cpu.pc.wrapping_add(3);
+    cpu.idle_cycle();
+    cpu.push_cycle_16(next_pc);
 
-        address
-    }
-
-    fn call_conditional(&mut self, condition: &FlagCondition) -> u16 {
-        let address = self.cpu.memory.read_16(self.cpu.pc + 1);
-        let next_pc = self.cpu.pc.wrapping_add(3);
+    Ok(address)
+}
 
-        if self.cpu.resolve_flag_condition(&condition) {
-            let next_sp = self.cpu.registers.sp.get().wrapping_sub(2);
-            self.cpu.registers.sp.set(next_sp);
-            self.cpu.memory.write_16(self.cpu.registers.sp.get(), next_pc);
+fn call_cc<B: Bus>(cpu: &mut Cpu<B>, operands: Operands) -> Result<u16, ExecutionError> {
+    let address = cpu.read_cycle_16(cpu.pc.wrapping_add(1));
+    let next_pc = cpu.pc.wrapping_add(3);
 
-            self.cpu.pc = address;
-        }
+    if cpu.resolve_flag_condition(&operands.condition) {
+        cpu.idle_cycle();
+        cpu.push_cycle_16(next_pc);
 
-        next_pc
+        return Ok(address);
     }
-}
 
-impl Command for CallCommand<'_> {
-    fn execute(&mut self) -> u16 {
-        match &self.instruction {
-            CallInstruction::Call => self.call(),
-            CallInstruction::CallCond(condition) => self.call_conditional(condition),
-        }
-    }
+    Ok(next_pc)
 }
@@ -1,66 +1,50 @@
-use crate::cpu::{
-    instructions::{FlagCondition, JumpInstruction},
-    registers::Register,
-    Cpu,
-};
-
-use super::Command;
-
-pub struct JumpCommand<'a> {
-    instruction: &'a JumpInstruction,
-    cpu: &'a mut Cpu,
-}
-
-impl<'a> JumpCommand<'a> {
-    pub fn new(instruction: &'a JumpInstruction, cpu: &'a mut Cpu) -> JumpCommand<'a> {
-        JumpCommand { instruction, cpu }
-    }
-
-    fn jp(&mut self) -> u16 {
-        self.cpu.memory.read_16(self.cpu.pc + 1)
+use crate::cpu::{instructions::JumpInstruction, registers::Register, Cpu, ExecutionError};
+
+use super::{Handler, Operands};
+use crate::bus::Bus;
+
+pub(super) const fn decode<B: Bus>(instruction: JumpInstruction) -> (Handler<B>, Operands) {
+    match instruction {
+        JumpInstruction::Jp => (jp, Operands::NONE),
+        JumpInstruction::JpCond(condition) => (jp_cc, Operands::condition(condition)),
+        JumpInstruction::JpHL => (jp_hl, Operands::NONE),
+        JumpInstruction::Jr => (jr, Operands::NONE),
+        JumpInstruction::JrCond(condition) => (jr_cc, Operands::condition(condition)),
     }
+}
 
-    fn jp_cc(&mut self, condition: &FlagCondition) -> u16 {
-        if self.cpu.resolve_flag_condition(condition) {
-            self.jp()
-        } else {
-            self.cpu.pc.wrapping_add(3)
-        }
-    }
+fn jp<B: Bus>(cpu: &mut Cpu<B>, _: Operands) -> Result<u16, ExecutionError> {
+    Ok(cpu.read_cycle_16(cpu.pc.wrapping_add(1)))
+}
 
-    fn jp_hl(&mut self) -> u16 {
-        self.cpu.registers.get_16(&Register::HL)
+// The address is read whether or not the jump is taken
+fn jp_cc<B: Bus>(cpu: &mut Cpu<B>, operands: Operands) -> Result<u16, ExecutionError> {
+    let address = cpu.read_cycle_16(cpu.pc.wrapping_add(1));
+    if cpu.resolve_flag_condition(&operands.condition) {
+        Ok(address)
+    } else {
+        Ok(cpu.pc.wrapping_add(3))
     }
+}
 
-    fn jr(&mut self) -> u16 {
-        let offset = self.cpu.memory.read(self.cpu.pc + 1) as i8;
-        let new_pc = self
-            .cpu
-            .pc
-            .wrapping_add(2)
-            .wrapping_add(offset as i16 as u16);
-        println!("[CPU] Jump to address 0x{:x}", new_pc);
-        new_pc
-    }
+fn jp_hl<B: Bus>(cpu: &mut Cpu<B>, _: Operands) -> Result<u16, ExecutionError> {
+    cpu.registers.get_16(&Register::HL)
+}
 
-    fn jr_cc(&mut self, condition: &FlagCondition) -> u16 {
-        let next_step = self.cpu.pc.wrapping_add(2);
-        if self.cpu.resolve_flag_condition(condition) {
-            self.jr()
-        } else {
-            return next_step;
-        }
-    }
+fn jr<B: Bus>(cpu: &mut Cpu<B>, _: Operands) -> Result<u16, ExecutionError> {
+    let offset = cpu.read_cycle(cpu.pc.wrapping_add(1)) as i8;
+    let new_pc = cpu.pc.wrapping_add(2).wrapping_add(offset as i16 as u16);
+    log!(Cpu, Trace, "Jump to address 0x{:x}", new_pc);
+    Ok(new_pc)
 }
 
-impl Command for JumpCommand<'_> {
-    fn execute(&mut self) -> u16 {
-        match &self.instruction {
-            JumpInstruction::Jp => self.jp(),
-            JumpInstruction::JpCond(condition) => self.jp_cc(condition),
-            JumpInstruction::JpHL => self.jp_hl(),
-            JumpInstruction::Jr => self.jr(),
-            JumpInstruction::JrCond(condition) => self.jr_cc(condition),
-        }
+// Like `jp_cc`, the offset is read either way
+fn jr_cc<B: Bus>(cpu: &mut Cpu<B>, operands: Operands) -> Result<u16, ExecutionError> {
+    let next_step = cpu.pc.wrapping_add(2);
+    let new_pc = jr(cpu, operands)?;
+    if cpu.resolve_flag_condition(&operands.condition) {
+        Ok(new_pc)
+    } else {
+        Ok(next_step)
     }
 }
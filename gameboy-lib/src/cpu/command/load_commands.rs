@@ -1,214 +1,240 @@
 use crate::cpu::instructions::LoadInstruction;
 use crate::cpu::registers::Register;
-use crate::cpu::Cpu;
-
-use super::Command;
+use crate::cpu::{Cpu, ExecutionError};
+
+use super::{Handler, Operands};
+use crate::bus::Bus;
+
+pub(super) const fn decode<B: Bus>(instruction: LoadInstruction) -> (Handler<B>, Operands) {
+    match instruction {
+        LoadInstruction::Push(register) => (push, Operands::register(register)),
+        LoadInstruction::Pop(register) => (pop, Operands::register(register)),
+        LoadInstruction::Ld8(to, from) => (load_8(to, from), Operands::load(to, from)),
+        LoadInstruction::Ld16(to, from) => (load_16(to, from), Operands::load(to, from)),
+        LoadInstruction::LdCa => (ld_c_a, Operands::NONE),
+        LoadInstruction::LdAc => (ld_a_c, Operands::NONE),
+        LoadInstruction::LdNa => (ld_n_a, Operands::NONE),
+        LoadInstruction::LdAn => (ld_a_n, Operands::NONE),
+        LoadInstruction::LdHi => (ld_hli_a, Operands::NONE),
+        LoadInstruction::LdHd => (ld_hld_a, Operands::NONE),
+        LoadInstruction::LdAHi => (ld_a_hli, Operands::NONE),
+        LoadInstruction::LdAHd => (ld_a_hld, Operands::NONE),
+    }
+}
 
-pub struct LoadCommand<'a> {
-    instruction: &'a LoadInstruction,
-    cpu: &'a mut Cpu,
+// Addressing mode of an 8-bit load, 16-bit registers stand for the memory
+// they point at
+const fn load_8<B: Bus>(to: Register, from: Register) -> Handler<B> {
+    match (to, from) {
+        (Register::BC | Register::DE | Register::HL | Register::AF, Register::D8) => {
+            ld_indirect_immediate
+        }
+        (Register::BC | Register::DE | Register::HL | Register::AF, _) => ld_indirect_register,
+        (Register::D16, _) => ld_absolute_register,
+        (_, Register::HL | Register::BC | Register::DE | Register::AF) => ld_register_indirect,
+        (_, Register::D8) => ld_register_immediate,
+        (_, Register::D16) => ld_register_absolute,
+        _ => ld_register_register,
+    }
 }
 
-impl<'a> LoadCommand<'a> {
-    pub fn new(instruction: &'a LoadInstruction, cpu: &'a mut Cpu) -> LoadCommand<'a> {
-        LoadCommand { instruction, cpu }
+const fn load_16<B: Bus>(to: Register, from: Register) -> Handler<B> {
+    match (to, from) {
+        (Register::SP, Register::HL) => ld_sp_hl,
+        (Register::SP, Register::D8) => ld_hl_sp_offset,
+        (Register::D16, Register::SP) => ld_absolute_sp,
+        (_, Register::D16) => ld_16_immediate,
+        _ => ld_16_register,
     }
+}
 
-    fn push(&mut self, register: &Register) -> u16 {
-        let value = self.cpu.registers.get_16(&register);
-        let next_sp = self.cpu.registers.sp.get().wrapping_sub(2);
-        self.cpu.registers.sp.set(next_sp);
-        self.cpu.memory.write_16(self.cpu.registers.sp.get(), value);
+fn push<B: Bus>(cpu: &mut Cpu<B>, operands: Operands) -> Result<u16, ExecutionError> {
+    let value = cpu.registers.get_16(&operands.to)?;
+    cpu.idle_cycle();
+    cpu.push_cycle_16(value);
 
-        self.cpu.pc.wrapping_add(1)
-    }
+    Ok(cpu.pc.wrapping_add(1))
+}
 
-    fn pop(&mut self, register: &Register) -> u16 {
-        let value = self.cpu.memory.read_16(self.cpu.registers.sp.get());
-        self.cpu.registers.set_16(&register, value);
-        self.cpu
-            .registers
-            .sp
-            .set(self.cpu.registers.sp.get().wrapping_add(2));
+fn pop<B: Bus>(cpu: &mut Cpu<B>, operands: Operands) -> Result<u16, ExecutionError> {
+    let value = cpu.pop_cycle_16();
+    cpu.registers.set_16(&operands.to, value)?;
 
-        self.cpu.pc.wrapping_add(1)
-    }
+    Ok(cpu.pc.wrapping_add(1))
+}
 
-    fn load_8(&mut self, instruction: &LoadInstruction) -> u16 {
-        match instruction {
-            LoadInstruction::Ld8(to, from) => match (&to, &from) {
-                (Register::BC | Register::DE | Register::HL | Register::AF, Register::D8) => {
-                    let value = self.cpu.memory.read(self.cpu.pc + 1);
-                    let address = self.cpu.registers.get_16(&to);
-                    self.cpu.memory.write(address, value);
-
-                    self.cpu.pc.wrapping_add(2)
-                }
-                (Register::BC | Register::DE | Register::HL | Register::AF, from) => {
-                    let value = self.cpu.registers.get(from);
-                    let address = self.cpu.registers.get_16(&to);
-                    self.cpu.memory.write(address, value);
-
-                    self.cpu.pc.wrapping_add(1)
-                }
-                (Register::D16, from) => {
-                    let value = self.cpu.registers.get(from);
-                    let address = self.cpu.memory.read_16(self.cpu.pc + 1);
-                    self.cpu.memory.write(address, value);
-
-                    self.cpu.pc.wrapping_add(3)
-                }
-                (to, Register::HL | Register::BC | Register::DE | Register::AF) => {
-                    let address = self.cpu.registers.get_16(&from);
-                    let value = self.cpu.memory.read(address);
-                    self.cpu.registers.set(to, value);
-
-                    self.cpu.pc.wrapping_add(1)
-                }
-                (to, Register::D8) => {
-                    let value = self.cpu.memory.read(self.cpu.pc + 1);
-                    self.cpu.registers.set(to, value);
-
-                    self.cpu.pc.wrapping_add(2)
-                }
-                (to, Register::D16) => {
-                    let address = self.cpu.memory.read_16(self.cpu.pc + 1);
-                    let value = self.cpu.memory.read(address);
-                    self.cpu.registers.set(to, value);
-
-                    self.cpu.pc.wrapping_add(3)
-                }
-                (to, from) => {
-                    let value = self.cpu.registers.get(from);
-                    self.cpu.registers.set(to, value);
-
-                    self.cpu.pc.wrapping_add(1)
-                }
-            },
-            _ => panic!("[CPU] Invalid instruction {:?}", instruction),
-        }
-    }
+fn ld_indirect_immediate<B: Bus>(
+    cpu: &mut Cpu<B>,
+    operands: Operands,
+) -> Result<u16, ExecutionError> {
+    let value = cpu.read_cycle(cpu.pc.wrapping_add(1));
+    let address = cpu.registers.get_16(&operands.to)?;
+    cpu.write_cycle(address, value);
 
-    fn load_16(&mut self, instruction: &LoadInstruction) -> u16 {
-        match instruction {
-            LoadInstruction::Ld16(to, from) => match (&to, &from) {
-                (Register::SP, Register::HL) => {
-                    let value = self.cpu.registers.get_16(&Register::HL);
-                    self.cpu.registers.sp.set(value);
-
-                    self.cpu.pc.wrapping_add(1)
-                }
-                (Register::SP, Register::D8) => {
-                    let n = self.cpu.memory.read(self.cpu.pc + 1) as u16;
-                    let (address, did_overflow) = self.cpu.registers.sp.get().overflowing_add(n);
-                    println!(
-                        "[CPU] SP: 0x{:x} + 0x{:x} = 0x{:x}",
-                        self.cpu.registers.sp.get(),
-                        n,
-                        address
-                    );
-                    self.cpu.registers.set_16(&Register::HL, address);
-
-                    self.cpu.registers.f.zero = false;
-                    self.cpu.registers.f.subtract = false;
-                    self.cpu.registers.f.half_carry =
-                        (((self.cpu.registers.sp.get() & 0xFFF) + (n & 0xFFF)) & 0x1000) == 0x1000;
-                    self.cpu.registers.f.carry = did_overflow;
-
-                    self.cpu.pc.wrapping_add(3)
-                }
-                (Register::D16, Register::SP) => {
-                    let address = self.cpu.memory.read_16(self.cpu.pc + 1);
-                    self.cpu.registers.sp.set(address);
-
-                    self.cpu.pc.wrapping_add(3)
-                }
-                (Register::BC | Register::DE | Register::HL | Register::SP, Register::D16) => {
-                    let value = self.cpu.memory.read_16(self.cpu.pc + 1);
-                    self.cpu.registers.set_16(&to, value);
-
-                    self.cpu.pc.wrapping_add(3)
-                }
-                (to, from) => {
-                    let value = self.cpu.registers.get_16(from);
-                    self.cpu.registers.set_16(to, value);
-
-                    self.cpu.pc.wrapping_add(1)
-                }
-            },
-            _ => panic!("[CPU] Invalid instruction {:?}", instruction),
-        }
-    }
+    Ok(cpu.pc.wrapping_add(2))
+}
 
-    fn load_special(&mut self, instruction: &LoadInstruction) -> u16 {
-        match instruction {
-            LoadInstruction::LdCa => {
-                let address = 0xFF00 + self.cpu.registers.get(&Register::C) as u16;
-                let value = self.cpu.registers.get(&Register::A);
-                self.cpu.memory.write(address, value);
-
-                self.cpu.pc.wrapping_add(1)
-            }
-            LoadInstruction::LdAc => {
-                let address = 0xFF00 + self.cpu.registers.get(&Register::C) as u16;
-                let value = self.cpu.memory.read(address);
-                self.cpu.registers.set(&Register::A, value);
-
-                self.cpu.pc.wrapping_add(1)
-            }
-            LoadInstruction::LdNa => {
-                let address = 0xFF00 + self.cpu.memory.read(self.cpu.pc + 1) as u16;
-                let value = self.cpu.registers.get(&Register::A);
-                self.cpu.memory.write(address, value);
-
-                self.cpu.pc.wrapping_add(2)
-            }
-            LoadInstruction::LdAn => {
-                let address = 0xFF00 + self.cpu.memory.read(self.cpu.pc + 1) as u16;
-                let value = self.cpu.memory.read(address);
-                self.cpu.registers.set(&Register::A, value);
-
-                self.cpu.pc.wrapping_add(2)
-            }
-            LoadInstruction::LdHi => {
-                let address = self.cpu.registers.get_16(&Register::HL);
-                let value = self.cpu.registers.get(&Register::A);
-                self.cpu.memory.write(address, value);
-
-                let value = self.cpu.registers.get_16(&Register::HL).wrapping_add(1);
-                self.cpu.registers.set_16(&Register::HL, value);
-
-                self.cpu.pc.wrapping_add(1)
-            }
-            LoadInstruction::LdHd => {
-                let address = self.cpu.registers.get_16(&Register::HL);
-                let value = self.cpu.registers.get(&Register::A);
-                self.cpu.memory.write(address, value);
-                
-                let value = self.cpu.registers.get_16(&Register::HL).wrapping_sub(1);
-                self.cpu.registers.set_16(&Register::HL, value);
-
-                self.cpu.pc.wrapping_add(1)
-            }
-            _ => panic!("[CPU] Invalid instruction {:?}", instruction),
-        }
-    }
+fn ld_indirect_register<B: Bus>(
+    cpu: &mut Cpu<B>,
+    operands: Operands,
+) -> Result<u16, ExecutionError> {
+    let value = cpu.registers.get(&operands.from)?;
+    let address = cpu.registers.get_16(&operands.to)?;
+    cpu.write_cycle(address, value);
+
+    Ok(cpu.pc.wrapping_add(1))
 }
 
-impl Command for LoadCommand<'_> {
-    fn execute(&mut self) -> u16 {
-        let instruction = &self.instruction;
-        match instruction {
-            LoadInstruction::Push(register) => self.push(register),
-            LoadInstruction::Pop(ref register) => self.pop(register),
-            LoadInstruction::Ld8(_, _) => self.load_8(&self.instruction),
-            LoadInstruction::Ld16(_, _) => self.load_16(&self.instruction),
-            LoadInstruction::LdCa
-            | LoadInstruction::LdAc
-            | LoadInstruction::LdNa
-            | LoadInstruction::LdAn
-            | LoadInstruction::LdHi
-            | LoadInstruction::LdHd => self.load_special(&self.instruction),
-        }
-    }
+fn ld_absolute_register<B: Bus>(
+    cpu: &mut Cpu<B>,
+    operands: Operands,
+) -> Result<u16, ExecutionError> {
+    let value = cpu.registers.get(&operands.from)?;
+    let address = cpu.read_cycle_16(cpu.pc.wrapping_add(1));
+    cpu.write_cycle(address, value);
+
+    Ok(cpu.pc.wrapping_add(3))
+}
+
+fn ld_register_indirect<B: Bus>(
+    cpu: &mut Cpu<B>,
+    operands: Operands,
+) -> Result<u16, ExecutionError> {
+    let address = cpu.registers.get_16(&operands.from)?;
+    let value = cpu.read_cycle(address);
+    cpu.registers.set(&operands.to, value)?;
+
+    Ok(cpu.pc.wrapping_add(1))
+}
+
+fn ld_register_immediate<B: Bus>(
+    cpu: &mut Cpu<B>,
+    operands: Operands,
+) -> Result<u16, ExecutionError> {
+    let value = cpu.read_cycle(cpu.pc.wrapping_add(1));
+    cpu.registers.set(&operands.to, value)?;
+
+    Ok(cpu.pc.wrapping_add(2))
+}
+
+fn ld_register_absolute<B: Bus>(
+    cpu: &mut Cpu<B>,
+    operands: Operands,
+) -> Result<u16, ExecutionError> {
+    let address = cpu.read_cycle_16(cpu.pc.wrapping_add(1));
+    let value = cpu.read_cycle(address);
+    cpu.registers.set(&operands.to, value)?;
+
+    Ok(cpu.pc.wrapping_add(3))
+}
+
+fn ld_register_register<B: Bus>(
+    cpu: &mut Cpu<B>,
+    operands: Operands,
+) -> Result<u16, ExecutionError> {
+    let value = cpu.registers.get(&operands.from)?;
+    cpu.registers.set(&operands.to, value)?;
+
+    Ok(cpu.pc.wrapping_add(1))
+}
+
+fn ld_sp_hl<B: Bus>(cpu: &mut Cpu<B>, _: Operands) -> Result<u16, ExecutionError> {
+    let value = cpu.registers.get_16(&Register::HL)?;
+    cpu.registers.sp.set(value);
+
+    Ok(cpu.pc.wrapping_add(1))
+}
+
+fn ld_hl_sp_offset<B: Bus>(cpu: &mut Cpu<B>, _: Operands) -> Result<u16, ExecutionError> {
+    let address = cpu.sp_plus_offset();
+    cpu.registers.set_16(&Register::HL, address)?;
+
+    Ok(cpu.pc.wrapping_add(2))
+}
+
+fn ld_absolute_sp<B: Bus>(cpu: &mut Cpu<B>, _: Operands) -> Result<u16, ExecutionError> {
+    let address = cpu.read_cycle_16(cpu.pc.wrapping_add(1));
+    let sp = cpu.registers.sp.get();
+    cpu.write_cycle(address, sp as u8);
+    cpu.write_cycle(address.wrapping_add(1), (sp >> 8) as u8);
+
+    Ok(cpu.pc.wrapping_add(3))
+}
+
+fn ld_16_immediate<B: Bus>(cpu: &mut Cpu<B>, operands: Operands) -> Result<u16, ExecutionError> {
+    let value = cpu.read_cycle_16(cpu.pc.wrapping_add(1));
+    cpu.registers.set_16(&operands.to, value)?;
+
+    Ok(cpu.pc.wrapping_add(3))
+}
+
+fn ld_16_register<B: Bus>(cpu: &mut Cpu<B>, operands: Operands) -> Result<u16, ExecutionError> {
+    let value = cpu.registers.get_16(&operands.from)?;
+    cpu.registers.set_16(&operands.to, value)?;
+
+    Ok(cpu.pc.wrapping_add(1))
+}
+
+fn ld_c_a<B: Bus>(cpu: &mut Cpu<B>, _: Operands) -> Result<u16, ExecutionError> {
+    let address = 0xFF00 + cpu.registers.c as u16;
+    cpu.write_cycle(address, cpu.registers.a);
+
+    Ok(cpu.pc.wrapping_add(1))
+}
+
+fn ld_a_c<B: Bus>(cpu: &mut Cpu<B>, _: Operands) -> Result<u16, ExecutionError> {
+    let address = 0xFF00 + cpu.registers.c as u16;
+    cpu.registers.a = cpu.read_cycle(address);
+
+    Ok(cpu.pc.wrapping_add(1))
+}
+
+fn ld_n_a<B: Bus>(cpu: &mut Cpu<B>, _: Operands) -> Result<u16, ExecutionError> {
+    let address = 0xFF00 + cpu.read_cycle(cpu.pc.wrapping_add(1)) as u16;
+    cpu.write_cycle(address, cpu.registers.a);
+
+    Ok(cpu.pc.wrapping_add(2))
+}
+
+fn ld_a_n<B: Bus>(cpu: &mut Cpu<B>, _: Operands) -> Result<u16, ExecutionError> {
+    let address = 0xFF00 + cpu.read_cycle(cpu.pc.wrapping_add(1)) as u16;
+    cpu.registers.a = cpu.read_cycle(address);
+
+    Ok(cpu.pc.wrapping_add(2))
+}
+
+fn ld_hli_a<B: Bus>(cpu: &mut Cpu<B>, _: Operands) -> Result<u16, ExecutionError> {
+    let address = cpu.registers.get_16(&Register::HL)?;
+    cpu.write_cycle(address, cpu.registers.a);
+    cpu.registers
+        .set_16(&Register::HL, address.wrapping_add(1))?;
+
+    Ok(cpu.pc.wrapping_add(1))
+}
+
+fn ld_hld_a<B: Bus>(cpu: &mut Cpu<B>, _: Operands) -> Result<u16, ExecutionError> {
+    let address = cpu.registers.get_16(&Register::HL)?;
+    cpu.write_cycle(address, cpu.registers.a);
+    cpu.registers
+        .set_16(&Register::HL, address.wrapping_sub(1))?;
+
+    Ok(cpu.pc.wrapping_add(1))
+}
+
+fn ld_a_hli<B: Bus>(cpu: &mut Cpu<B>, _: Operands) -> Result<u16, ExecutionError> {
+    let address = cpu.registers.get_16(&Register::HL)?;
+    cpu.registers.a = cpu.read_cycle(address);
+    cpu.registers
+        .set_16(&Register::HL, address.wrapping_add(1))?;
+
+    Ok(cpu.pc.wrapping_add(1))
+}
+
+fn ld_a_hld<B: Bus>(cpu: &mut Cpu<B>, _: Operands) -> Result<u16, ExecutionError> {
+    let address = cpu.registers.get_16(&Register::HL)?;
+    cpu.registers.a = cpu.read_cycle(address);
+    cpu.registers
+        .set_16(&Register::HL, address.wrapping_sub(1))?;
+
+    Ok(cpu.pc.wrapping_add(1))
 }
@@ -1,80 +1,70 @@
-use crate::cpu::{instructions::MiscInstruction, registers::Register, Cpu};
-
-use super::Command;
-
-
-pub struct MiscCommand<'a> {
-    instruction: &'a MiscInstruction,
-    cpu: &'a mut Cpu,
+use crate::cpu::{instructions::MiscInstruction, registers::Register, Cpu, ExecutionError};
+
+use super::{unimplemented, Handler, Operands};
+use crate::bus::Bus;
+
+pub(super) const fn decode<B: Bus>(instruction: MiscInstruction) -> (Handler<B>, Operands) {
+    match instruction {
+        MiscInstruction::Nop => (nop, Operands::NONE),
+        MiscInstruction::Swap(from) => (swap, Operands::register(from)),
+        MiscInstruction::CCF => (ccf, Operands::NONE),
+        MiscInstruction::SCF => (scf, Operands::NONE),
+        MiscInstruction::EI => (ei, Operands::NONE),
+        MiscInstruction::DI => (di, Operands::NONE),
+        MiscInstruction::DAA
+        | MiscInstruction::CPL
+        | MiscInstruction::HALT
+        | MiscInstruction::STOP => (unimplemented, Operands::NONE),
+    }
 }
 
-impl<'a> MiscCommand<'a> {
-    pub fn new(instruction: &'a MiscInstruction, cpu: &'a mut Cpu) -> MiscCommand<'a> {
-        MiscCommand { instruction, cpu }
-    }
+fn nop<B: Bus>(cpu: &mut Cpu<B>, _: Operands) -> Result<u16, ExecutionError> {
+    Ok(cpu.pc.wrapping_add(1))
+}
 
-    fn nop(&mut self) -> u16 {
-        self.cpu.pc.wrapping_add(1)
+fn swap<B: Bus>(cpu: &mut Cpu<B>, operands: Operands) -> Result<u16, ExecutionError> {
+    let from = &operands.to;
+    let (value, pc) = cpu.extract_operand(from)?;
+    let upper = value >> 4;
+    let lower = value << 4;
+    let result = upper | lower;
+
+    if let Register::HL = from {
+        cpu.write_cycle(cpu.registers.get_16(&Register::HL)?, result);
+    } else {
+        cpu.registers.set(from, result)?;
     }
 
-    fn swap(&mut self, from: &Register) -> u16 {
-        let (value, pc) = self.cpu.extract_operand(from);
-        let upper = value >> 4;
-        let lower = value << 4;
-        let result = upper | lower;
-
-        if let Register::HL = from {
-            self.cpu.memory
-                .write(self.cpu.registers.get_16(&Register::HL), result);
-        } else {
-            self.cpu.registers.set(from, result);
-        }
-
-        self.cpu.registers.f.zero = result == 0;
-        self.cpu.registers.f.subtract = false;
-        self.cpu.registers.f.half_carry = false;
-        self.cpu.registers.f.carry = false;
-
-        return pc;
-    }
+    cpu.registers.f.zero = result == 0;
+    cpu.registers.f.subtract = false;
+    cpu.registers.f.half_carry = false;
+    cpu.registers.f.carry = false;
 
-    fn ccf(&mut self) -> u16 {
-        self.cpu.registers.f.subtract = false;
-        self.cpu.registers.f.half_carry = false;
-        self.cpu.registers.f.carry = !self.cpu.registers.f.carry;
+    Ok(pc)
+}
 
-        self.cpu.pc.wrapping_add(1)
-    }
+fn ccf<B: Bus>(cpu: &mut Cpu<B>, _: Operands) -> Result<u16, ExecutionError> {
+    cpu.registers.f.subtract = false;
+    cpu.registers.f.half_carry = false;
+    cpu.registers.f.carry = !cpu.registers.f.carry;
 
-    fn scf(&mut self) -> u16 {
-        self.cpu.registers.f.subtract = false;
-        self.cpu.registers.f.half_carry = false;
-        self.cpu.registers.f.carry = true;
+    Ok(cpu.pc.wrapping_add(1))
+}
 
-        self.cpu.pc.wrapping_add(1)
-    }
+fn scf<B: Bus>(cpu: &mut Cpu<B>, _: Operands) -> Result<u16, ExecutionError> {
+    cpu.registers.f.subtract = false;
+    cpu.registers.f.half_carry = false;
+    cpu.registers.f.carry = true;
 
-    fn ei(&mut self) -> u16 {
-        self.cpu.interrupts_enabled = true;
-        self.cpu.pc.wrapping_add(1)
-    }
+    Ok(cpu.pc.wrapping_add(1))
+}
 
-    fn di(&mut self) -> u16 {
-        self.cpu.interrupts_enabled = false;
-        self.cpu.pc.wrapping_add(1)
-    }
+fn ei<B: Bus>(cpu: &mut Cpu<B>, _: Operands) -> Result<u16, ExecutionError> {
+    cpu.interrupts_enabled = true;
+    Ok(cpu.pc.wrapping_add(1))
 }
 
-impl Command for MiscCommand<'_> {
-    fn execute(&mut self) -> u16 {
-        match &self.instruction {
-            MiscInstruction::Nop => self.nop(),
-            MiscInstruction::Swap(from) => self.swap(from),
-            MiscInstruction::CCF => self.ccf(),
-            MiscInstruction::SCF => self.scf(),
-            MiscInstruction::EI => self.ei(),
-            MiscInstruction::DI => self.di(),
-            _ => unimplemented!(),
-        }
-    }
+fn di<B: Bus>(cpu: &mut Cpu<B>, _: Operands) -> Result<u16, ExecutionError> {
+    cpu.interrupts_enabled = false;
+    Ok(cpu.pc.wrapping_add(1))
 }
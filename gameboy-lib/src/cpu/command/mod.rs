@@ -1,6 +1,11 @@
-use self::{alu_commands::ArithmeticCommand, load_commands::LoadCommand};
+use std::marker::PhantomData;
 
-use super::{instructions::Instruction, Cpu};
+use super::{
+    instructions::{FlagCondition, Instruction},
+    registers::Register,
+    Cpu, ExecutionError,
+};
+use crate::bus::Bus;
 
 pub mod alu_commands;
 pub mod load_commands;
@@ -11,47 +16,130 @@ pub mod jump_commands;
 pub mod call_commands;
 pub mod return_commands;
 
-pub trait Command {
-    fn execute(&mut self) -> u16;
+/// Operands decoded from the opcode. A handler reads the ones its
+/// instruction has, the others keep the values of `Operands::NONE`.
+#[derive(Debug, Clone, Copy)]
+pub struct Operands {
+    pub to: Register,   // Destination, or the only register operand
+    pub from: Register, // Source of a load
+    pub value: u8,      // Bit of BIT, RES and SET, target of RST
+    pub condition: FlagCondition,
 }
 
-pub struct CommandFactory<'a> {
-    cpu: &'a mut Cpu,
+impl Operands {
+    pub const NONE: Operands = Operands {
+        to: Register::A,
+        from: Register::A,
+        value: 0,
+        condition: FlagCondition::NZ,
+    };
+
+    const fn register(register: Register) -> Operands {
+        Operands {
+            to: register,
+            ..Operands::NONE
+        }
+    }
+
+    const fn load(to: Register, from: Register) -> Operands {
+        Operands {
+            to,
+            from,
+            ..Operands::NONE
+        }
+    }
+
+    const fn bit(bit: u8, register: Register) -> Operands {
+        Operands {
+            to: register,
+            value: bit,
+            ..Operands::NONE
+        }
+    }
+
+    const fn condition(condition: FlagCondition) -> Operands {
+        Operands {
+            condition,
+            ..Operands::NONE
+        }
+    }
 }
 
-impl<'a> CommandFactory<'a> {
-    pub fn new(cpu: &'a mut Cpu) -> CommandFactory<'a> {
-        CommandFactory { cpu }
+/// Executes one instruction with its decoded operands and returns the next
+/// PC.
+pub type Handler<B> = fn(&mut Cpu<B>, Operands) -> Result<u16, ExecutionError>;
+
+/// Table entry, the handler of an opcode and the operands it runs with.
+#[derive(Debug)]
+pub struct Opcode<B: Bus> {
+    pub instruction: Instruction, // For logging and the debugger
+    pub handler: Handler<B>,
+    pub operands: Operands,
+}
+
+// Not derived, that would require `B: Copy`
+impl<B: Bus> Clone for Opcode<B> {
+    fn clone(&self) -> Self {
+        *self
     }
+}
+
+impl<B: Bus> Copy for Opcode<B> {}
+
+/// Decode tables for a CPU on bus `B`, built at compile time.
+pub struct Opcodes<B>(PhantomData<B>);
+
+impl<B: Bus> Opcodes<B> {
+    /// Every unprefixed opcode. Illegal opcodes are `None`.
+    pub const UNPREFIXED: [Option<Opcode<B>>; 256] = opcode_table(false);
+
+    /// Every opcode following a 0xCB prefix.
+    pub const PREFIXED: [Option<Opcode<B>>; 256] = opcode_table(true);
+}
 
-    pub fn create_command(&'a mut self, instruction: &'a Instruction) -> Box<dyn Command + 'a> {
-        match instruction {
-            Instruction::Load(load_command) => Box::new(LoadCommand::new(load_command, self.cpu)),
-            Instruction::Arithmetic(alu_command) => Box::new(ArithmeticCommand::new(alu_command, self.cpu)),
-            Instruction::Misc(misc_command) => Box::new(misc_commands::MiscCommand::new(&misc_command, self.cpu)),
-            Instruction::Rotate(rotate_command) => Box::new(rotate_commands::RotateCommand::new(rotate_command, self.cpu)),
-            Instruction::Bit(bit_command) => Box::new(bit_commands::BitCommand::new(bit_command, self.cpu)),
-            Instruction::Jump(jump_command) => Box::new(jump_commands::JumpCommand::new(jump_command, self.cpu)),
-            Instruction::Call(call_command) => Box::new(call_commands::CallCommand::new(call_command, self.cpu)),
-            Instruction::Return(return_command) => Box::new(return_commands::ReturnCommand::new(return_command, self.cpu)),
+/// Looks up `opcode` in the decode tables, no allocation or decoding happens.
+pub fn lookup<B: Bus>(opcode: u8, prefixed: bool) -> Option<Opcode<B>> {
+    // Borrowed, so the table is promoted to a static instead of copied
+    let table: &[Option<Opcode<B>>; 256] = if prefixed {
+        &Opcodes::PREFIXED
+    } else {
+        &Opcodes::UNPREFIXED
+    };
+    table[opcode as usize]
+}
+
+const fn opcode_table<B: Bus>(prefixed: bool) -> [Option<Opcode<B>>; 256] {
+    let mut table = [None; 256];
+    let mut opcode = 0;
+    while opcode < 256 {
+        if let Some(instruction) = Instruction::from_byte(opcode as u8, prefixed) {
+            let (handler, operands) = decode(instruction);
+            table[opcode] = Some(Opcode {
+                instruction,
+                handler,
+                operands,
+            });
         }
+        opcode += 1;
+    }
+    table
+}
+
+/// Picks the handler for `instruction` and pulls out its operands.
+const fn decode<B: Bus>(instruction: Instruction) -> (Handler<B>, Operands) {
+    match instruction {
+        Instruction::Load(instruction) => load_commands::decode(instruction),
+        Instruction::Arithmetic(instruction) => alu_commands::decode(instruction),
+        Instruction::Misc(instruction) => misc_commands::decode(instruction),
+        Instruction::Rotate(instruction) => rotate_commands::decode(instruction),
+        Instruction::Bit(instruction) => bit_commands::decode(instruction),
+        Instruction::Jump(instruction) => jump_commands::decode(instruction),
+        Instruction::Call(instruction) => call_commands::decode(instruction),
+        Instruction::Return(instruction) => return_commands::decode(instruction),
     }
+}
 
-    // fn command(&'a mut self, instruction: &'a Instruction) -> Box<dyn Command + 'a> {
-    //     match instruction {
-    //         Instruction::Load(load_command) => Box::new(LoadCommand::new(load_command, self.cpu)),
-    //         Instruction::Arithmetic(alu_command) => Box::new(ArithmeticCommand::new(alu_command, self.cpu)),
-    //         Instruction::Misc(misc_command) => Box::new(misc_commands::MiscCommand::new(&misc_command, self.cpu)),
-    //         Instruction::Rotate(rotate_command) => Box::new(rotate_commands::RotateCommand::new(rotate_command, self.cpu)),
-    //         _ => unimplemented!(),
-    //     }
-    // }
-
-    // fn prefix_command(&'a mut self, instruction: &'a Instruction) -> Box<dyn Command + 'a> {
-    //     match instruction {
-    //         Instruction::Misc(misc_command) => Box::new(misc_commands::MiscCommand::new(&misc_command, self.cpu)),
-    //         Instruction::Rotate(rotate_command) => Box::new(rotate_commands::RotateCommand::new(rotate_command, self.cpu)),
-    //         _ => unimplemented!(),
-    //     }
-    // }
+/// Handler of the instructions that decode but aren't emulated yet.
+fn unimplemented<B: Bus>(_: &mut Cpu<B>, _: Operands) -> Result<u16, ExecutionError> {
+    Err(ExecutionError::Unimplemented)
 }
@@ -1,62 +1,41 @@
-use crate::cpu::{
-    instructions::{FlagCondition, ReturnInstruction},
-    Cpu,
-};
-
-use super::Command;
-
-pub struct ReturnCommand<'a> {
-    instruction: &'a ReturnInstruction,
-    cpu: &'a mut Cpu,
-}
-
-impl<'a> ReturnCommand<'a> {
-    pub fn new(instruction: &'a ReturnInstruction, cpu: &'a mut Cpu) -> ReturnCommand<'a> {
-        ReturnCommand { instruction, cpu }
-    }
-
-    fn rst(&mut self, address: &u8) -> u16 {
-        let current_address = self.cpu.memory.read_16(self.cpu.pc);
-        let next_sp = self.cpu.registers.sp.get().wrapping_sub(2);
-        self.cpu.registers.sp.set(next_sp);
-        self.cpu
-            .memory
-            .write_16(self.cpu.registers.sp.get(), current_address);
-
-        0x0000 + (*address as u16)
+use crate::cpu::{instructions::ReturnInstruction, Cpu, ExecutionError};
+
+use super::{unimplemented, Handler, Operands};
+use crate::bus::Bus;
+
+pub(super) const fn decode<B: Bus>(instruction: ReturnInstruction) -> (Handler<B>, Operands) {
+    match instruction {
+        ReturnInstruction::Rst(address) => (
+            rst,
+            Operands {
+                value: address,
+                ..Operands::NONE
+            },
+        ),
+        ReturnInstruction::Ret => (ret, Operands::NONE),
+        ReturnInstruction::RetCond(condition) => (ret_cc, Operands::condition(condition)),
+        ReturnInstruction::Reti => (unimplemented, Operands::NONE),
     }
+}
 
-    fn ret(&mut self) -> u16 {
-        let address = self.cpu.memory.read_16(self.cpu.registers.sp.get());
-        self.cpu
-            .registers
-            .sp
-            .set(self.cpu.registers.sp.get().wrapping_add(2));
+fn rst<B: Bus>(cpu: &mut Cpu<B>, operands: Operands) -> Result<u16, ExecutionError> {
+    let return_address = cpu.pc.wrapping_add(1);
+    cpu.idle_cycle();
+    cpu.push_cycle_16(return_address);
 
-        address
-    }
+    Ok(operands.value as u16)
+}
 
-    fn ret_conditional(&mut self, condition: &FlagCondition) -> u16 {
-        if self.cpu.resolve_flag_condition(&condition) {
-            let address = self.cpu.memory.read_16(self.cpu.registers.sp.get());
-            self.cpu
-                .registers
-                .sp
-                .set(self.cpu.registers.sp.get().wrapping_add(2));
-            address
-        } else {
-            self.cpu.pc.wrapping_add(1)
-        }
-    }
+fn ret<B: Bus>(cpu: &mut Cpu<B>, _: Operands) -> Result<u16, ExecutionError> {
+    Ok(cpu.pop_cycle_16())
 }
 
-impl Command for ReturnCommand<'_> {
-    fn execute(&mut self) -> u16 {
-        match &self.instruction {
-            ReturnInstruction::Rst(address) => self.rst(&address),
-            ReturnInstruction::Ret => self.ret(),
-            ReturnInstruction::RetCond(condition) => self.ret_conditional(condition),
-            _ => unimplemented!(),
-        }
+fn ret_cc<B: Bus>(cpu: &mut Cpu<B>, operands: Operands) -> Result<u16, ExecutionError> {
+    // The condition is checked on a cycle of its own
+    cpu.idle_cycle();
+    if cpu.resolve_flag_condition(&operands.condition) {
+        Ok(cpu.pop_cycle_16())
+    } else {
+        Ok(cpu.pc.wrapping_add(1))
     }
 }
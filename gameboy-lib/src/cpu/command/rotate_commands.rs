@@ -1,229 +1,216 @@
-use crate::cpu::{instructions::RotateInstruction, registers::Register, Cpu};
+use crate::cpu::{instructions::RotateInstruction, registers::Register, Cpu, ExecutionError};
+
+use super::{Handler, Operands};
+use crate::bus::Bus;
+
+pub(super) const fn decode<B: Bus>(instruction: RotateInstruction) -> (Handler<B>, Operands) {
+    match instruction {
+        RotateInstruction::RLCA => (rlca, Operands::NONE),
+        RotateInstruction::RLA => (rla, Operands::NONE),
+        RotateInstruction::RRCA => (rrca, Operands::NONE),
+        RotateInstruction::RRA => (rra, Operands::NONE),
+        RotateInstruction::RLC(register) => (rlc, Operands::register(register)),
+        RotateInstruction::RL(register) => (rl, Operands::register(register)),
+        RotateInstruction::RRC(register) => (rrc, Operands::register(register)),
+        RotateInstruction::RR(register) => (rr, Operands::register(register)),
+        RotateInstruction::SLA(register) => (sla, Operands::register(register)),
+        RotateInstruction::SRA(register) => (sra, Operands::register(register)),
+        RotateInstruction::SRL(register) => (srl, Operands::register(register)),
+    }
+}
 
-use super::Command;
+fn rlca<B: Bus>(cpu: &mut Cpu<B>, _: Operands) -> Result<u16, ExecutionError> {
+    let value = cpu.registers.a;
+    let carry = value >> 7;
+    let result = (value << 1) | carry;
 
+    cpu.registers.a = result;
+    cpu.registers.f.zero = false;
+    cpu.registers.f.subtract = false;
+    cpu.registers.f.half_carry = false;
+    cpu.registers.f.carry = carry == 1;
 
-pub struct RotateCommand<'a> {
-    instruction: &'a RotateInstruction,
-    cpu: &'a mut Cpu,
+    Ok(cpu.pc.wrapping_add(1))
 }
 
-impl<'a> RotateCommand<'a> {
-    pub fn new(instruction: &'a RotateInstruction, cpu: &'a mut Cpu) -> RotateCommand<'a> {
-        RotateCommand { instruction, cpu }
-    }
-
-    fn rlca(&mut self) -> u16 {
-        let value = self.cpu.registers.a;
-        let carry = value >> 7;
-        let result = (value << 1) | carry;
-
-        self.cpu.registers.a = result;
-        self.cpu.registers.f.zero = result == 0;
-        self.cpu.registers.f.subtract = false;
-        self.cpu.registers.f.half_carry = false;
-        self.cpu.registers.f.carry = carry == 1;
+fn rla<B: Bus>(cpu: &mut Cpu<B>, _: Operands) -> Result<u16, ExecutionError> {
+    let value = cpu.registers.a;
+    let carry = value >> 7;
+    let result = (value << 1) | cpu.registers.f.carry as u8;
 
-        self.cpu.pc.wrapping_add(1)
-    }
+    cpu.registers.a = result;
+    cpu.registers.f.zero = false;
+    cpu.registers.f.subtract = false;
+    cpu.registers.f.half_carry = false;
+    cpu.registers.f.carry = carry == 1;
 
-    fn rla(&mut self) -> u16 {
-        let value = self.cpu.registers.a;
-        let carry = value >> 7;
-        let result = (value << 1) | self.cpu.registers.f.carry as u8;
+    Ok(cpu.pc.wrapping_add(1))
+}
 
-        self.cpu.registers.a = result;
-        self.cpu.registers.f.zero = result == 0;
-        self.cpu.registers.f.subtract = false;
-        self.cpu.registers.f.half_carry = false;
-        self.cpu.registers.f.carry = carry == 1;
+fn rrca<B: Bus>(cpu: &mut Cpu<B>, _: Operands) -> Result<u16, ExecutionError> {
+    let value = cpu.registers.a;
+    let carry = value & 1;
+    let result = (value >> 1) | (carry << 7);
 
-        self.cpu.pc.wrapping_add(1)
-    }
+    cpu.registers.a = result;
+    cpu.registers.f.zero = false;
+    cpu.registers.f.subtract = false;
+    cpu.registers.f.half_carry = false;
+    cpu.registers.f.carry = carry == 1;
 
-    fn rrca(&mut self) -> u16 {
-        let value = self.cpu.registers.a;
-        let carry = value & 1;
-        let result = (value >> 1) | (carry << 7);
+    Ok(cpu.pc.wrapping_add(1))
+}
 
-        self.cpu.registers.a = result;
-        self.cpu.registers.f.zero = result == 0;
-        self.cpu.registers.f.subtract = false;
-        self.cpu.registers.f.half_carry = false;
-        self.cpu.registers.f.carry = carry == 1;
+fn rra<B: Bus>(cpu: &mut Cpu<B>, _: Operands) -> Result<u16, ExecutionError> {
+    let value = cpu.registers.a;
+    let carry = value & 1;
+    let result = (value >> 1) | ((cpu.registers.f.carry as u8) << 7);
 
-        self.cpu.pc.wrapping_add(1)
-    }
+    cpu.registers.a = result;
+    cpu.registers.f.zero = false;
+    cpu.registers.f.subtract = false;
+    cpu.registers.f.half_carry = false;
+    cpu.registers.f.carry = carry == 1;
 
-    fn rra(&mut self) -> u16 {
-        let value = self.cpu.registers.a;
-        let carry = value & 1;
-        let result = (value >> 1) | ((self.cpu.registers.f.carry as u8) << 7);
+    Ok(cpu.pc.wrapping_add(1))
+}
 
-        self.cpu.registers.a = result;
-        self.cpu.registers.f.zero = result == 0;
-        self.cpu.registers.f.subtract = false;
-        self.cpu.registers.f.half_carry = false;
-        self.cpu.registers.f.carry = carry == 1;
+fn rlc<B: Bus>(cpu: &mut Cpu<B>, operands: Operands) -> Result<u16, ExecutionError> {
+    let register = &operands.to;
+    let (value, pc) = cpu.extract_operand(register)?;
+    let carry = value >> 7;
+    let result = (value << 1) | carry;
 
-        self.cpu.pc.wrapping_add(1)
+    if let Register::HL = register {
+        cpu.write_cycle(cpu.registers.get_16(&Register::HL)?, result);
+    } else {
+        cpu.registers.set(register, result)?;
     }
 
-    fn rlc(&mut self, register: &Register) -> u16 {
-        let (value, pc) = self.cpu.extract_operand(register);
-        let carry = value >> 7;
-        let result = (value << 1) | carry;
+    cpu.registers.f.zero = result == 0;
+    cpu.registers.f.subtract = false;
+    cpu.registers.f.half_carry = false;
+    cpu.registers.f.carry = carry == 1;
 
-        if let Register::HL = register {
-            self.cpu.memory
-                .write(self.cpu.registers.get_16(&Register::HL), result);
-        } else {
-            self.cpu.registers.set(register, result);
-        }
+    Ok(pc)
+}
 
-        self.cpu.registers.f.zero = result == 0;
-        self.cpu.registers.f.subtract = false;
-        self.cpu.registers.f.half_carry = false;
-        self.cpu.registers.f.carry = carry == 1;
+fn rl<B: Bus>(cpu: &mut Cpu<B>, operands: Operands) -> Result<u16, ExecutionError> {
+    let register = &operands.to;
+    let (value, pc) = cpu.extract_operand(register)?;
+    let carry = value >> 7;
+    let result = (value << 1) | cpu.registers.f.carry as u8;
 
-        return pc;
+    if let Register::HL = register {
+        cpu.write_cycle(cpu.registers.get_16(&Register::HL)?, result);
+    } else {
+        cpu.registers.set(register, result)?;
     }
 
-    fn rl(&mut self, register: &Register) -> u16 {
-        let (value, pc) = self.cpu.extract_operand(register);
-        let carry = value >> 7;
-        let result = (value << 1) | self.cpu.registers.f.carry as u8;
+    cpu.registers.f.zero = result == 0;
+    cpu.registers.f.subtract = false;
+    cpu.registers.f.half_carry = false;
+    cpu.registers.f.carry = carry == 1;
 
-        if let Register::HL = register {
-            self.cpu.memory
-                .write(self.cpu.registers.get_16(&Register::HL), result);
-        } else {
-            self.cpu.registers.set(register, result);
-        }
+    Ok(pc)
+}
 
-        self.cpu.registers.f.zero = result == 0;
-        self.cpu.registers.f.subtract = false;
-        self.cpu.registers.f.half_carry = false;
-        self.cpu.registers.f.carry = carry == 1;
+fn rrc<B: Bus>(cpu: &mut Cpu<B>, operands: Operands) -> Result<u16, ExecutionError> {
+    let register = &operands.to;
+    let (value, pc) = cpu.extract_operand(register)?;
+    let carry = value & 1;
+    let result = (value >> 1) | (carry << 7);
 
-        return pc;
+    if let Register::HL = register {
+        cpu.write_cycle(cpu.registers.get_16(&Register::HL)?, result);
+    } else {
+        cpu.registers.set(register, result)?;
     }
 
-    fn rrc(&mut self, register: &Register) -> u16 {
-        let (value, pc) = self.cpu.extract_operand(register);
-        let carry = value & 1;
-        let result = (value >> 1) | (carry << 7);
+    cpu.registers.f.zero = result == 0;
+    cpu.registers.f.subtract = false;
+    cpu.registers.f.half_carry = false;
+    cpu.registers.f.carry = carry == 1;
 
-        if let Register::HL = register {
-            self.cpu.memory
-                .write(self.cpu.registers.get_16(&Register::HL), result);
-        } else {
-            self.cpu.registers.set(register, result);
-        }
+    Ok(pc)
+}
 
-        self.cpu.registers.f.zero = result == 0;
-        self.cpu.registers.f.subtract = false;
-        self.cpu.registers.f.half_carry = false;
-        self.cpu.registers.f.carry = carry == 1;
+fn rr<B: Bus>(cpu: &mut Cpu<B>, operands: Operands) -> Result<u16, ExecutionError> {
+    let register = &operands.to;
+    let (value, pc) = cpu.extract_operand(register)?;
+    let carry = value & 1;
+    let result = (value >> 1) | ((cpu.registers.f.carry as u8) << 7);
 
-        return pc;
+    if let Register::HL = register {
+        cpu.write_cycle(cpu.registers.get_16(&Register::HL)?, result);
+    } else {
+        cpu.registers.set(register, result)?;
     }
 
-    fn rr(&mut self, register: &Register) -> u16 {
-        let (value, pc) = self.cpu.extract_operand(register);
-        let carry = value & 1;
-        let result = (value >> 1) | ((self.cpu.registers.f.carry as u8) << 7);
+    cpu.registers.f.zero = result == 0;
+    cpu.registers.f.subtract = false;
+    cpu.registers.f.half_carry = false;
+    cpu.registers.f.carry = carry == 1;
 
-        if let Register::HL = register {
-            self.cpu.memory
-                .write(self.cpu.registers.get_16(&Register::HL), result);
-        } else {
-            self.cpu.registers.set(register, result);
-        }
+    Ok(pc)
+}
 
-        self.cpu.registers.f.zero = result == 0;
-        self.cpu.registers.f.subtract = false;
-        self.cpu.registers.f.half_carry = false;
-        self.cpu.registers.f.carry = carry == 1;
+fn sla<B: Bus>(cpu: &mut Cpu<B>, operands: Operands) -> Result<u16, ExecutionError> {
+    let register = &operands.to;
+    let (value, pc) = cpu.extract_operand(register)?;
+    let carry = value >> 7;
+    let result = value << 1;
 
-        return pc;
+    if let Register::HL = register {
+        cpu.write_cycle(cpu.registers.get_16(&Register::HL)?, result);
+    } else {
+        cpu.registers.set(register, result)?;
     }
 
-    fn sla(&mut self, register: &Register) -> u16 {
-        let (value, pc) = self.cpu.extract_operand(register);
-        let carry = value >> 7;
-        let result = value << 1;
+    cpu.registers.f.zero = result == 0;
+    cpu.registers.f.subtract = false;
+    cpu.registers.f.half_carry = false;
+    cpu.registers.f.carry = carry == 1;
 
-        if let Register::HL = register {
-            self.cpu.memory
-                .write(self.cpu.registers.get_16(&Register::HL), result);
-        } else {
-            self.cpu.registers.set(register, result);
-        }
+    Ok(pc)
+}
 
-        self.cpu.registers.f.zero = result == 0;
-        self.cpu.registers.f.subtract = false;
-        self.cpu.registers.f.half_carry = false;
-        self.cpu.registers.f.carry = carry == 1;
+fn sra<B: Bus>(cpu: &mut Cpu<B>, operands: Operands) -> Result<u16, ExecutionError> {
+    let register = &operands.to;
+    let (value, pc) = cpu.extract_operand(register)?;
+    let carry = value & 1;
+    let result = (value >> 1) | (value & 0x80);
 
-        return pc;
+    if let Register::HL = register {
+        cpu.write_cycle(cpu.registers.get_16(&Register::HL)?, result);
+    } else {
+        cpu.registers.set(register, result)?;
     }
 
-    fn sra(&mut self, register: &Register) -> u16 {
-        let (value, pc) = self.cpu.extract_operand(register);
-        let carry = value & 1;
-        let result = (value >> 1) | (value & 0x80);
+    cpu.registers.f.zero = result == 0;
+    cpu.registers.f.subtract = false;
+    cpu.registers.f.half_carry = false;
+    cpu.registers.f.carry = carry == 1;
 
-        if let Register::HL = register {
-            self.cpu.memory
-                .write(self.cpu.registers.get_16(&Register::HL), result);
-        } else {
-            self.cpu.registers.set(register, result);
-        }
+    Ok(pc)
+}
 
-        self.cpu.registers.f.zero = result == 0;
-        self.cpu.registers.f.subtract = false;
-        self.cpu.registers.f.half_carry = false;
-        self.cpu.registers.f.carry = carry == 1;
+fn srl<B: Bus>(cpu: &mut Cpu<B>, operands: Operands) -> Result<u16, ExecutionError> {
+    let register = &operands.to;
+    let (value, pc) = cpu.extract_operand(register)?;
+    let carry = value & 1;
+    let result = value >> 1;
 
-        return pc;
+    if let Register::HL = register {
+        cpu.write_cycle(cpu.registers.get_16(&Register::HL)?, result);
+    } else {
+        cpu.registers.set(register, result)?;
     }
 
-    fn srl(&mut self, register: &Register) -> u16 {
-        let (value, pc) = self.cpu.extract_operand(register);
-        let carry = value & 1;
-        let result = value >> 1;
-
-        if let Register::HL = register {
-            self.cpu.memory
-                .write(self.cpu.registers.get_16(&Register::HL), result);
-        } else {
-            self.cpu.registers.set(register, result);
-        }
+    cpu.registers.f.zero = result == 0;
+    cpu.registers.f.subtract = false;
+    cpu.registers.f.half_carry = false;
+    cpu.registers.f.carry = carry == 1;
 
-        self.cpu.registers.f.zero = result == 0;
-        self.cpu.registers.f.subtract = false;
-        self.cpu.registers.f.half_carry = false;
-        self.cpu.registers.f.carry = carry == 1;
-
-        return pc;
-    }
-}
-
-impl Command for RotateCommand<'_> {
-    fn execute(&mut self) -> u16 {
-        match &self.instruction {
-            RotateInstruction::RLCA => self.rlca(),
-            RotateInstruction::RLA => self.rla(),
-            RotateInstruction::RRCA => self.rrca(),
-            RotateInstruction::RRA => self.rra(),
-            RotateInstruction::RLC(register) => self.rlc(register),
-            RotateInstruction::RL(register) => self.rl(register),
-            RotateInstruction::RRC(register) => self.rrc(register),
-            RotateInstruction::RR(register) => self.rr(register),
-            RotateInstruction::SLA(register) => self.sla(register),
-            RotateInstruction::SRA(register) => self.sra(register),
-            RotateInstruction::SRL(register) => self.srl(register),
-        }
-    }
+    Ok(pc)
 }
@@ -1,6 +1,6 @@
 use super::registers::Register;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum FlagCondition {
     NZ, // Not zero
     Z,  // Zero
@@ -8,7 +8,7 @@ pub enum FlagCondition {
     C,  // Carry
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum LoadInstruction {
     Ld8(Register, Register),  // Load 8-bit value into register
     Ld16(Register, Register), // Load 16-bit value into register
@@ -18,15 +18,17 @@ pub enum LoadInstruction {
     LdAn,                     // Load $FF00 + n into A
     LdHi,                     // Load A into HL + 1
     LdHd,                     // Load A into HL - 1
+    LdAHi,                    // Load HL + 1 into A
+    LdAHd,                    // Load HL - 1 into A
     Push(Register),           // Push register onto stack
     Pop(Register),            // Pop register from stack
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum ArithmeticInstruction {
     Add(Register),   // Add register to A
     Add16(Register), // Add register to HL
-    Add16SP,         // Add SP to HL
+    Add16SP,         // Add signed 8-bit value to SP
     Adc(Register),   // Add register to A with carry
     Sub(Register),   // Subtract register from A
     Sbc(Register),   // Subtract register from A with carry
@@ -40,7 +42,7 @@ pub enum ArithmeticInstruction {
     Dec16(Register), // Decrement register
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum MiscInstruction {
     Nop,            // No operation
     Swap(Register), // Swap upper and lower nibbles of register
@@ -54,7 +56,7 @@ pub enum MiscInstruction {
     EI,             // Enable interrupts
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum RotateInstruction {
     RLCA,          // Rotate A left
     RLA,           // Rotate A left through carry
@@ -69,7 +71,7 @@ pub enum RotateInstruction {
     SRL(Register), // Shift register right
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum JumpInstruction {
     Jp,                    // Jump to address
     JpCond(FlagCondition), // Conditional jump to address
@@ -78,20 +80,20 @@ pub enum JumpInstruction {
     JrCond(FlagCondition), // Conditional jump relative
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum BitInstruction {
     Bit(u8, Register), // Test bit in register
     Set(u8, Register), // Set bit in register
     Res(u8, Register), // Reset bit in register
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum CallInstruction {
     Call,                    // Call address
     CallCond(FlagCondition), // Conditional call address
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum ReturnInstruction {
     Rst(u8),                // Restart to address
     Ret,                    // Return from subroutine
@@ -99,7 +101,7 @@ pub enum ReturnInstruction {
     Reti,                   // Return from interrupt
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum Instruction {
     Load(LoadInstruction),             
     Arithmetic(ArithmeticInstruction), 
@@ -112,7 +114,7 @@ pub enum Instruction {
 }
 
 impl Instruction {
-    pub fn from_byte(byte: u8, prefix: bool) -> Option<Instruction> {
+    pub const fn from_byte(byte: u8, prefix: bool) -> Option<Instruction> {
         return if prefix {
             Instruction::from_byte_with_prefix(byte)
         } else {
@@ -120,7 +122,7 @@ impl Instruction {
         };
     }
 
-    fn from_byte_with_prefix(byte: u8) -> Option<Instruction> {
+    const fn from_byte_with_prefix(byte: u8) -> Option<Instruction> {
         match byte {
             0x37 => Some(Instruction::Misc(MiscInstruction::Swap(Register::A))),
             0x30 => Some(Instruction::Misc(MiscInstruction::Swap(Register::B))),
@@ -202,14 +204,16 @@ impl Instruction {
             0x45 => Some(Instruction::Bit(BitInstruction::Bit(0, Register::L))),
             0x46 => Some(Instruction::Bit(BitInstruction::Bit(0, Register::HL))),
 
-            0x47 => Some(Instruction::Bit(BitInstruction::Bit(1, Register::B))),
-            0x48 => Some(Instruction::Bit(BitInstruction::Bit(1, Register::C))),
-            0x49 => Some(Instruction::Bit(BitInstruction::Bit(1, Register::D))),
-            0x4A => Some(Instruction::Bit(BitInstruction::Bit(1, Register::E))),
-            0x4B => Some(Instruction::Bit(BitInstruction::Bit(1, Register::H))),
-            0x4C => Some(Instruction::Bit(BitInstruction::Bit(1, Register::L))),
-            0x4D => Some(Instruction::Bit(BitInstruction::Bit(1, Register::HL))),
-            0x4E => Some(Instruction::Bit(BitInstruction::Bit(1, Register::A))),
+            0x47 => Some(Instruction::Bit(BitInstruction::Bit(0, Register::A))),
+
+            0x48 => Some(Instruction::Bit(BitInstruction::Bit(1, Register::B))),
+            0x49 => Some(Instruction::Bit(BitInstruction::Bit(1, Register::C))),
+            0x4A => Some(Instruction::Bit(BitInstruction::Bit(1, Register::D))),
+            0x4B => Some(Instruction::Bit(BitInstruction::Bit(1, Register::E))),
+            0x4C => Some(Instruction::Bit(BitInstruction::Bit(1, Register::H))),
+            0x4D => Some(Instruction::Bit(BitInstruction::Bit(1, Register::L))),
+            0x4E => Some(Instruction::Bit(BitInstruction::Bit(1, Register::HL))),
+            0x4F => Some(Instruction::Bit(BitInstruction::Bit(1, Register::A))),
 
             0x50 => Some(Instruction::Bit(BitInstruction::Bit(2, Register::B))),
             0x51 => Some(Instruction::Bit(BitInstruction::Bit(2, Register::C))),
@@ -408,12 +412,10 @@ impl Instruction {
             0xBD => Some(Instruction::Bit(BitInstruction::Res(7, Register::L))),
             0xBE => Some(Instruction::Bit(BitInstruction::Res(7, Register::HL))),
             0xBF => Some(Instruction::Bit(BitInstruction::Res(7, Register::A))),
-
-            _ => None,
         }
     }
 
-    fn from_byte_without_prefix(byte: u8) -> Option<Instruction> {
+    const fn from_byte_without_prefix(byte: u8) -> Option<Instruction> {
         match byte {
             0x00 => Some(Instruction::Misc(MiscInstruction::Nop)),
             0x06 => Some(Instruction::Load(LoadInstruction::Ld8(Register::B, Register::D8))),
@@ -506,10 +508,10 @@ impl Instruction {
             0xF2 => Some(Instruction::Load(LoadInstruction::LdAc)),
             0xE2 => Some(Instruction::Load(LoadInstruction::LdCa)),
 
-            0x3A => Some(Instruction::Load(LoadInstruction::LdHd)),
+            0x3A => Some(Instruction::Load(LoadInstruction::LdAHd)),
             0x32 => Some(Instruction::Load(LoadInstruction::LdHd)),
 
-            0x2A => Some(Instruction::Load(LoadInstruction::LdHi)),
+            0x2A => Some(Instruction::Load(LoadInstruction::LdAHi)),
             0x22 => Some(Instruction::Load(LoadInstruction::LdHi)),
 
             0xE0 => Some(Instruction::Load(LoadInstruction::LdNa)),
@@ -706,3 +708,74 @@ impl Instruction {
         }
     }
 }
+
+/// Number of bytes an instruction occupies, including its opcode and operands.
+pub fn instruction_length(opcode: u8) -> u16 {
+    match opcode {
+        0xCB | 0x10 => 2,
+        0x06 | 0x0E | 0x16 | 0x1E | 0x26 | 0x2E | 0x36 | 0x3E => 2,
+        0x18 | 0x20 | 0x28 | 0x30 | 0x38 => 2,
+        0xC6 | 0xCE | 0xD6 | 0xDE | 0xE6 | 0xEE | 0xF6 | 0xFE => 2,
+        0xE0 | 0xF0 | 0xE8 | 0xF8 => 2,
+        0x01 | 0x11 | 0x21 | 0x31 | 0x08 => 3,
+        0xC2 | 0xC3 | 0xCA | 0xD2 | 0xDA => 3,
+        0xC4 | 0xCC | 0xCD | 0xD4 | 0xDC => 3,
+        0xEA | 0xFA => 3,
+        _ => 1,
+    }
+}
+
+// T-cycles of every unprefixed opcode, conditional jumps, calls and returns
+// are listed with the branch not taken. 0xCB is accounted for separately and
+// illegal opcodes are 0.
+#[rustfmt::skip]
+const CYCLES: [u8; 256] = [
+//  x0  x1  x2  x3  x4  x5  x6  x7  x8  x9  xA  xB  xC  xD  xE  xF
+     4, 12,  8,  8,  4,  4,  8,  4, 20,  8,  8,  8,  4,  4,  8,  4, // 0x
+     4, 12,  8,  8,  4,  4,  8,  4, 12,  8,  8,  8,  4,  4,  8,  4, // 1x
+     8, 12,  8,  8,  4,  4,  8,  4,  8,  8,  8,  8,  4,  4,  8,  4, // 2x
+     8, 12,  8,  8, 12, 12, 12,  4,  8,  8,  8,  8,  4,  4,  8,  4, // 3x
+     4,  4,  4,  4,  4,  4,  8,  4,  4,  4,  4,  4,  4,  4,  8,  4, // 4x
+     4,  4,  4,  4,  4,  4,  8,  4,  4,  4,  4,  4,  4,  4,  8,  4, // 5x
+     4,  4,  4,  4,  4,  4,  8,  4,  4,  4,  4,  4,  4,  4,  8,  4, // 6x
+     8,  8,  8,  8,  8,  8,  4,  8,  4,  4,  4,  4,  4,  4,  8,  4, // 7x
+     4,  4,  4,  4,  4,  4,  8,  4,  4,  4,  4,  4,  4,  4,  8,  4, // 8x
+     4,  4,  4,  4,  4,  4,  8,  4,  4,  4,  4,  4,  4,  4,  8,  4, // 9x
+     4,  4,  4,  4,  4,  4,  8,  4,  4,  4,  4,  4,  4,  4,  8,  4, // Ax
+     4,  4,  4,  4,  4,  4,  8,  4,  4,  4,  4,  4,  4,  4,  8,  4, // Bx
+     8, 12, 12, 16, 12, 16,  8, 16,  8, 16, 12,  0, 12, 24,  8, 16, // Cx
+     8, 12, 12,  0, 12, 16,  8, 16,  8, 16, 12,  0, 12,  0,  8, 16, // Dx
+    12, 12,  8,  0,  0, 16,  8, 16, 16,  4, 16,  0,  0,  0,  8, 16, // Ex
+    12, 12,  8,  4,  0, 16,  8, 16, 12,  8, 16,  4,  0,  0,  8, 16, // Fx
+];
+
+/// Returns the condition of a conditional JR, JP, CALL or RET.
+pub fn branch_condition(opcode: u8) -> Option<FlagCondition> {
+    match opcode {
+        0x20 | 0xC0 | 0xC2 | 0xC4 => Some(FlagCondition::NZ),
+        0x28 | 0xC8 | 0xCA | 0xCC => Some(FlagCondition::Z),
+        0x30 | 0xD0 | 0xD2 | 0xD4 => Some(FlagCondition::NC),
+        0x38 | 0xD8 | 0xDA | 0xDC => Some(FlagCondition::C),
+        _ => None,
+    }
+}
+
+/// Number of T-cycles an unprefixed instruction takes.
+pub fn instruction_cycles(opcode: u8, branch_taken: bool) -> u32 {
+    let extra = match (opcode, branch_taken) {
+        (_, false) => 0,
+        (0x20 | 0x28 | 0x30 | 0x38 | 0xC2 | 0xCA | 0xD2 | 0xDA, true) => 4,
+        (0xC0 | 0xC8 | 0xD0 | 0xD8 | 0xC4 | 0xCC | 0xD4 | 0xDC, true) => 12,
+        (_, true) => 0,
+    };
+    CYCLES[opcode as usize] as u32 + extra
+}
+
+/// Number of T-cycles of a 0xCB prefixed instruction, including the prefix.
+pub fn prefixed_instruction_cycles(opcode: u8) -> u32 {
+    match (opcode & 0x07, opcode & 0xC0) {
+        (0x06, 0x40) => 12, // BIT n, (HL)
+        (0x06, _) => 16,
+        _ => 8,
+    }
+}
@@ -1,10 +1,30 @@
-use crate::{cpu::instructions::Instruction, memory::Memory};
-
-use self::{command::CommandFactory, instructions::FlagCondition, registers::Register};
+use crate::{
+    bus::Bus,
+    cpu::instructions::{
+        branch_condition, instruction_cycles, prefixed_instruction_cycles,
+    },
+    disassembler,
+    error::{GameboyError, IllegalOpcodePolicy},
+    memory::Memory,
+    model::Model,
+};
+
+use self::{
+    command::Opcode, instructions::FlagCondition, registers::Register, tracer::Tracer,
+};
 
 pub mod command;
 pub mod instructions;
 pub mod registers;
+pub mod tracer;
+
+/// Why a decoded instruction could not be executed. `Cpu::step` turns it
+/// into a `GameboyError` carrying the address and disassembly.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExecutionError {
+    Unimplemented,
+    InvalidOperand,
+}
 
 enum FlagUpdate {
     Zero(bool),
@@ -13,76 +33,209 @@ enum FlagUpdate {
     Carry(bool),
 }
 
+/// The SM83 core, generic over what it is wired to. The emulator runs it on
+/// `Memory`, tests can use `bus::FlatRam`.
 #[derive(Debug)]
-pub struct Cpu {
+pub struct Cpu<B: Bus = Memory> {
     pub interrupts_enabled: bool,
     pub registers: registers::Registers,
     pub pc: u16,
-    pub memory: Memory,
+    pub bus: B,
+    pub tracer: Option<Tracer>,
+    pub cycles: u64, // T-cycles executed since power on
+    pub illegal_opcode_policy: IllegalOpcodePolicy,
+    pub locked_up: bool, // Hit an illegal opcode, cleared by a reset
+    ticked: u32,         // T-cycles of the current instruction already ticked
 }
 
 impl Cpu {
     pub fn new() -> Cpu {
+        Cpu::with_bus(Memory::new())
+    }
+
+    pub fn boot(&mut self, boot_rom: Vec<u8>, game_rom: Vec<u8>) {
+        log!(Cpu, Info, "Copy Game ROM to memory");
+        self.bus.write_vec(0x0, game_rom);
+        log!(Cpu, Info, "Map Boot ROM over the Game ROM");
+        self.bus.map_boot_rom(boot_rom);
+    }
+}
+
+impl<B: Bus> Cpu<B> {
+    pub fn with_bus(bus: B) -> Cpu<B> {
         Cpu {
             interrupts_enabled: false,
             registers: registers::Registers::new(),
             pc: 0,
-            memory: Memory::new(),
+            bus,
+            tracer: None,
+            cycles: 0,
+            illegal_opcode_policy: IllegalOpcodePolicy::default(),
+            locked_up: false,
+            ticked: 0,
         }
     }
 
-    pub fn boot(&mut self, boot_rom: Vec<u8>, game_rom: Vec<u8>) {
-        println!("[CPU] Copy Game ROM to memory");
-        self.memory.write_vec(0x0, game_rom);
-        println!("[CPU] Copy Boot ROM to memory");
-        self.memory.write_vec(0x0, boot_rom);
+    /// Puts the CPU into the state the boot ROM of `model` leaves behind.
+    pub fn reset_post_boot(&mut self, model: Model) {
+        let [af, bc, de, hl] = model.post_boot_registers();
+        let registers = [Register::AF, Register::BC, Register::DE, Register::HL, Register::SP];
+        for (register, value) in registers.iter().zip([af, bc, de, hl, 0xFFFE]) {
+            self.registers
+                .set_16(register, value)
+                .expect("post-boot registers are 16-bit");
+        }
+        self.pc = 0x100;
     }
 
-    pub fn run(&mut self) {
-        // Boot Sequence
-        self.pc = 0x0;
-        for _ in 0..0xFF {
-            self.step();
+    /// Executes one instruction. Every bus access takes its own M-cycle and
+    /// ticks the rest of the system first; internal cycles not spent on the
+    /// bus are ticked at the end. On error PC still points at the offending
+    /// instruction, its opcode fetch has already taken its M-cycle.
+    pub fn step(&mut self) -> Result<(), GameboyError> {
+        if self.locked_up {
+            // Nothing is fetched anymore, but the rest of the machine keeps going
+            self.cycles += 4;
+            self.bus.tick(4);
+            return Ok(());
         }
 
-        // Program Counter default value
-        self.pc = 0x100;
-
-        // TODO: Add timing
-        loop {
-            self.step();
+        if let Some(tracer) = self.tracer.as_mut() {
+            tracer.trace(&self.registers, self.pc, &self.bus);
         }
-    }
 
-    pub fn step(&mut self) {
-        let opcode = self.memory.read(self.pc);
+        // Peek first, an illegal opcode under the error policy ticks nothing
+        let opcode = self.bus.peek(self.pc);
         let prefixed = opcode == 0xCB;
         let instruction = if prefixed {
-            self.memory.read(self.pc + 1)
+            self.bus.peek(self.pc.wrapping_add(1))
         } else {
             opcode
         };
 
         if !prefixed {
-            println!("[CPU] PC: 0x{:x} Opcode: 0x{:x}", self.pc, opcode);
+            log!(Cpu, Debug, "PC: 0x{:x} Opcode: 0x{:x}", self.pc, opcode);
         } else {
-            println!("[CPU] PC: 0x{:x} Prefixed: 0x{:x}", self.pc, instruction);
+            log!(Cpu, Debug, "PC: 0x{:x} Prefixed: 0x{:x}", self.pc, instruction);
         }
 
-        let next_pc = match Instruction::from_byte(instruction, prefixed) {
-            Some(instruction) => self.execute(instruction, prefixed),
-            None => panic!("[CPU] Invalid instruction 0x{:x}", instruction),
+        let cycles = if prefixed {
+            prefixed_instruction_cycles(instruction)
+        } else {
+            let taken = branch_condition(opcode)
+                .is_some_and(|condition| self.resolve_flag_condition(&condition));
+            instruction_cycles(opcode, taken)
         };
 
-        if next_pc == self.pc {
-            panic!("[CPU] Instruction did not increment PC");
-        }
+        let pc = self.pc;
+        let bank = self.bus.rom_bank(pc);
+        let next_pc = match command::lookup(instruction, prefixed) {
+            Some(opcode) => self.fetch_and_execute(&opcode, prefixed).map_err(|error| {
+                let instruction = self.disassemble_at(pc);
+                match error {
+                    ExecutionError::Unimplemented => GameboyError::Unimplemented {
+                        instruction,
+                        pc,
+                        bank,
+                    },
+                    ExecutionError::InvalidOperand => GameboyError::InvalidOperand {
+                        instruction,
+                        pc,
+                        bank,
+                    },
+                }
+            })?,
+            None => {
+                let error = GameboyError::IllegalOpcode { opcode, pc, bank };
+                if self.illegal_opcode_policy == IllegalOpcodePolicy::Error {
+                    return Err(error);
+                }
+                log!(Cpu, Warn, "{}, locking up", error);
+                self.locked_up = true;
+                self.cycles += 4;
+                self.bus.tick(4);
+                return Ok(());
+            }
+        };
 
         if prefixed {
-            self.pc = next_pc + 1;
+            self.pc = next_pc.wrapping_add(1);
         } else {
             self.pc = next_pc;
         }
+        debug_assert!(
+            self.ticked <= cycles,
+            "`{}` ran over its cycle count",
+            self.disassemble_at(pc)
+        );
+        let remaining = cycles.saturating_sub(self.ticked);
+        self.cycles += remaining as u64;
+        self.bus.tick(remaining);
+        Ok(())
+    }
+
+    fn fetch_and_execute(
+        &mut self,
+        opcode: &Opcode<B>,
+        prefixed: bool,
+    ) -> Result<u16, ExecutionError> {
+        self.ticked = 0;
+        self.read_cycle(self.pc);
+        if prefixed {
+            self.read_cycle(self.pc.wrapping_add(1));
+        }
+        self.execute(opcode)
+    }
+
+    /// One M-cycle reading `address`.
+    pub(crate) fn read_cycle(&mut self, address: u16) -> u8 {
+        self.idle_cycle();
+        self.bus.read(address)
+    }
+
+    /// Two M-cycles reading a little-endian word, low byte first.
+    pub(crate) fn read_cycle_16(&mut self, address: u16) -> u16 {
+        let low = self.read_cycle(address) as u16;
+        let high = self.read_cycle(address.wrapping_add(1)) as u16;
+        (high << 8) | low
+    }
+
+    /// One M-cycle writing `value` to `address`.
+    pub(crate) fn write_cycle(&mut self, address: u16, value: u8) {
+        self.idle_cycle();
+        self.bus.write(address, value);
+    }
+
+    /// One M-cycle without a bus access.
+    pub(crate) fn idle_cycle(&mut self) {
+        self.ticked += 4;
+        self.cycles += 4;
+        self.bus.tick(4);
+    }
+
+    /// Two M-cycles pushing `value`, high byte first like the hardware.
+    pub(crate) fn push_cycle_16(&mut self, value: u16) {
+        let sp = self.registers.sp.get().wrapping_sub(1);
+        self.write_cycle(sp, (value >> 8) as u8);
+        let sp = sp.wrapping_sub(1);
+        self.write_cycle(sp, value as u8);
+        self.registers.sp.set(sp);
+    }
+
+    /// Two M-cycles popping a word.
+    pub(crate) fn pop_cycle_16(&mut self) -> u16 {
+        let value = self.read_cycle_16(self.registers.sp.get());
+        self.registers
+            .sp
+            .set(self.registers.sp.get().wrapping_add(2));
+        value
+    }
+
+    fn disassemble_at(&self, pc: u16) -> String {
+        let bytes: Vec<u8> = (0..3)
+            .map(|offset| self.bus.peek(pc.wrapping_add(offset)))
+            .collect();
+        disassembler::disassemble_one(&bytes, pc).text
     }
 
     fn resolve_flag_condition(&mut self, condition: &FlagCondition) -> bool {
@@ -94,6 +247,18 @@ impl Cpu {
         }
     }
 
+    /// SP plus the signed immediate byte, for ADD SP, e8 and LD HL, SP+e8.
+    /// H and C come from adding the unsigned byte to the low byte of SP.
+    pub(crate) fn sp_plus_offset(&mut self) -> u16 {
+        let offset = self.read_cycle(self.pc.wrapping_add(1)) as u16;
+        let sp = self.registers.sp.get();
+        self.registers.f.zero = false;
+        self.registers.f.subtract = false;
+        self.registers.f.half_carry = (sp & 0xF) + (offset & 0xF) > 0xF;
+        self.registers.f.carry = (sp & 0xFF) + offset > 0xFF;
+        sp.wrapping_add(offset as u8 as i8 as u16)
+    }
+
     fn update_flag(&mut self, flag: FlagUpdate) {
         match flag {
             FlagUpdate::Zero(value) => self.registers.f.zero = value,
@@ -103,59 +268,234 @@ impl Cpu {
         }
     }
 
-    fn execute(&mut self, instruction: Instruction, _prefixed: bool) -> u16 {
-        println!();
-        println!("[CPU] Executing {:?}", instruction);
-        let mut factory = CommandFactory::new(self);
-        let mut command = factory.create_command(&instruction);
-        command.execute()
+    fn execute(&mut self, opcode: &Opcode<B>) -> Result<u16, ExecutionError> {
+        log!(Cpu, Debug, "Executing {:?}", opcode.instruction);
+        (opcode.handler)(self, opcode.operands)
     }
 
-    fn extract_operand(&mut self, from: &Register) -> (u8, u16) {
-        match from {
-            Register::D8 => (self.memory.read(self.pc + 1), self.pc.wrapping_add(2)),
+    fn extract_operand(&mut self, from: &Register) -> Result<(u8, u16), ExecutionError> {
+        Ok(match from {
+            Register::D8 => (
+                self.read_cycle(self.pc.wrapping_add(1)),
+                self.pc.wrapping_add(2),
+            ),
             Register::HL => {
-                let value = self.memory.read(self.registers.get_16(&Register::HL));
+                let value = self.read_cycle(self.registers.get_16(&Register::HL)?);
                 (value, self.pc.wrapping_add(1))
             }
-            _ => (self.registers.get(from), self.pc.wrapping_add(1)),
-        }
+            _ => (self.registers.get(from)?, self.pc.wrapping_add(1)),
+        })
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::debugger::WatchKind;
 
     #[test]
     fn boot() {
         let mut cpu = Cpu::new();
         cpu.boot(vec![0x00, 0x01, 0x02, 0x03], vec![]);
-        assert_eq!(cpu.memory.read(0x0), 0x00);
-        assert_eq!(cpu.memory.read(0x1), 0x01);
-        assert_eq!(cpu.memory.read(0x2), 0x02);
-        assert_eq!(cpu.memory.read(0x3), 0x03);
+        assert_eq!(cpu.bus.read(0x0), 0x00);
+        assert_eq!(cpu.bus.read(0x1), 0x01);
+        assert_eq!(cpu.bus.read(0x2), 0x02);
+        assert_eq!(cpu.bus.read(0x3), 0x03);
     }
 
     #[test]
     fn step() {
         let mut cpu = Cpu::new();
         cpu.boot(vec![0x00, 0x00, 0x00, 0x00], vec![]);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x1);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x2);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x3);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x4);
     }
 
+    #[test]
+    fn step_counts_cycles() {
+        let mut cpu = Cpu::new();
+        // NOP; LD A, 0x01; JR NZ, +0; JR Z, +0; SWAP A
+        cpu.boot(vec![0x00, 0x3E, 0x01, 0x20, 0x00, 0x28, 0x00, 0xCB, 0x37], vec![]);
+        cpu.step().unwrap();
+        assert_eq!(cpu.cycles, 4);
+        cpu.step().unwrap();
+        assert_eq!(cpu.cycles, 12);
+        cpu.step().unwrap(); // Taken
+        assert_eq!(cpu.cycles, 24);
+        cpu.step().unwrap(); // Not taken
+        assert_eq!(cpu.cycles, 32);
+        cpu.step().unwrap();
+        assert_eq!(cpu.cycles, 40);
+    }
+
+    #[test]
+    fn lookup_all_legal_opcodes() {
+        for opcode in 0..=0xFF {
+            let decoded = command::lookup::<Memory>(opcode, false);
+            if crate::error::ILLEGAL_OPCODES.contains(&opcode) {
+                assert!(decoded.is_none(), "0x{:02X} decoded", opcode);
+            } else if opcode != 0xCB {
+                assert!(decoded.is_some(), "0x{:02X} did not decode", opcode);
+            }
+            assert!(command::lookup::<Memory>(opcode, true).is_some());
+        }
+    }
+
+    #[test]
+    fn lookup_decodes_operands() {
+        // LD B, C
+        let operands = command::lookup::<Memory>(0x41, false).unwrap().operands;
+        assert!(matches!((operands.to, operands.from), (Register::B, Register::C)));
+        // BIT 3, (HL)
+        let operands = command::lookup::<Memory>(0x5E, true).unwrap().operands;
+        assert!(matches!((operands.value, operands.to), (3, Register::HL)));
+        // JP NC, a16
+        let operands = command::lookup::<Memory>(0xD2, false).unwrap().operands;
+        assert!(matches!(operands.condition, FlagCondition::NC));
+        // RST 0x38
+        assert_eq!(command::lookup::<Memory>(0xFF, false).unwrap().operands.value, 0x38);
+    }
+
+    #[test]
+    fn step_illegal_opcode() {
+        let mut cpu = Cpu::new();
+        cpu.illegal_opcode_policy = IllegalOpcodePolicy::Error;
+        cpu.boot(vec![0x00, 0xDD], vec![]);
+        cpu.step().unwrap();
+        assert_eq!(
+            cpu.step(),
+            Err(GameboyError::IllegalOpcode {
+                opcode: 0xDD,
+                pc: 0x0001,
+                bank: 0,
+            })
+        );
+        assert_eq!(cpu.pc, 0x0001);
+        assert_eq!(cpu.cycles, 4);
+    }
+
+    #[test]
+    fn step_illegal_opcode_locks_up() {
+        let mut cpu = Cpu::new();
+        cpu.boot(vec![0xD3, 0x00], vec![]);
+        cpu.bus.write(0xFF40, 0x80); // LCD on
+        cpu.step().unwrap();
+        assert!(cpu.locked_up);
+        let line = cpu.bus.read(0xFF44);
+        for _ in 0..1000 {
+            cpu.step().unwrap();
+        }
+        assert_eq!(cpu.pc, 0x0000);
+        assert_eq!(cpu.cycles, 4 * 1001);
+        assert_ne!(cpu.bus.read(0xFF44), line, "PPU stopped with the CPU");
+    }
+
+    #[test]
+    fn step_unimplemented() {
+        let mut cpu = Cpu::new();
+        cpu.boot(vec![], vec![0x00; 0x8000]);
+        cpu.bus.write(0x4000, 0x76); // HALT
+        cpu.pc = 0x4000;
+        let error = cpu.step().unwrap_err();
+        assert_eq!(error.to_string(), "Unimplemented instruction `halt` at 01:4000");
+        assert_eq!(cpu.pc, 0x4000);
+    }
+
+    #[test]
+    fn step_ticks_between_bus_accesses() {
+        let mut cpu = Cpu::new();
+        // 111 NOPs, then LD A, (LY) reads LY on its fourth M-cycle
+        let mut program = vec![0x00; 111];
+        program.extend_from_slice(&[0xFA, 0x44, 0xFF]);
+        cpu.boot(program, vec![]);
+        cpu.bus.write(0xFF40, 0x80); // LCD on
+        for _ in 0..111 {
+            cpu.step().unwrap();
+        }
+        assert_eq!(cpu.cycles, 444);
+        assert_eq!(cpu.bus.read(0xFF44), 0);
+        cpu.step().unwrap();
+        // Line 0 ends at 456, before the read at 460
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 1);
+        assert_eq!(cpu.cycles, 460);
+    }
+
+    #[test]
+    fn step_spends_table_cycles() {
+        for prefixed in [false, true] {
+            for opcode in 0..=0xFF {
+                let Some(decoded) = command::lookup::<Memory>(opcode, prefixed) else {
+                    continue;
+                };
+                let mut cpu = Cpu::new();
+                cpu.registers.sp.set(0xD000);
+                cpu.registers.set_16(&Register::HL, 0xC000).unwrap();
+                let bytes = if prefixed {
+                    [0xCB, opcode, 0x00]
+                } else {
+                    [opcode, 0x00, 0xC0]
+                };
+                cpu.pc = 0xC100;
+                cpu.bus.write_vec(cpu.pc, bytes.to_vec());
+                let expected = if prefixed {
+                    prefixed_instruction_cycles(opcode)
+                } else {
+                    let taken = branch_condition(opcode)
+                        .is_some_and(|condition| cpu.resolve_flag_condition(&condition));
+                    instruction_cycles(opcode, taken)
+                };
+                // Skips HALT, STOP and friends, and RES/SET on (HL)
+                if cpu.step().is_ok() {
+                    assert_eq!(
+                        cpu.cycles, expected as u64,
+                        "{:?} took {} cycles",
+                        decoded.instruction, cpu.cycles
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn step_push_writes_high_byte_first() {
+        let mut cpu = Cpu::new();
+        cpu.boot(vec![0xC5, 0xFF], vec![]); // PUSH BC; RST 0x38
+        cpu.registers.sp.set(0xD000);
+        cpu.registers.set_16(&Register::BC, 0x1234).unwrap();
+        for address in [0xCFFE, 0xCFFF] {
+            cpu.bus.watchpoints.insert(address, WatchKind::Write);
+        }
+        cpu.step().unwrap();
+        let hit = cpu.bus.watchpoints.take_hit(0).unwrap();
+        assert_eq!((hit.address, hit.value), (0xCFFF, 0x12));
+        assert_eq!(cpu.bus.read_16(0xCFFE), 0x1234);
+        assert_eq!(cpu.cycles, 16);
+        cpu.step().unwrap();
+        assert_eq!(cpu.pc, 0x0038);
+        assert_eq!(cpu.bus.read_16(0xCFFC), 0x0002); // Returns after the RST
+    }
+
+    #[test]
+    fn step_jump_to_self() {
+        let mut cpu = Cpu::new();
+        cpu.boot(vec![0x18, 0xFE], vec![]); // JR -2
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.pc, 0x0000);
+        assert_eq!(cpu.cycles, 24);
+    }
+
     #[test]
     fn execute_nop() {
         let mut cpu = Cpu::new();
         let pc = cpu.pc;
-        let next_pc = cpu.execute(Instruction::Misc(instructions::MiscInstruction::Nop), false);
+        let next_pc = cpu.execute(&command::lookup(0x00, false).unwrap()).unwrap();
         assert_eq!(next_pc, pc + 1);
     }
 
@@ -168,117 +508,117 @@ mod tests {
             ],
             vec![],
         );
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::A), 0x42);
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::B), 0x69);
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::C), 0x42);
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::D), 0x69);
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::E), 0x42);
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::H), 0x69);
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::L), 0x42);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0x42);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::B).unwrap(), 0x69);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::C).unwrap(), 0x42);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::D).unwrap(), 0x69);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::E).unwrap(), 0x42);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::H).unwrap(), 0x69);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::L).unwrap(), 0x42);
     }
 
     #[test]
     fn execute_ld8_to_hl_from_reg() {
         let mut cpu = Cpu::new();
-        cpu.registers.set_16(&Register::HL, 0x5123);
+        cpu.registers.set_16(&Register::HL, 0x5123).unwrap();
 
-        cpu.registers.set(&Register::B, 0x43);
-        cpu.registers.set(&Register::C, 0x44);
-        cpu.registers.set(&Register::D, 0x45);
-        cpu.registers.set(&Register::E, 0x46);
+        cpu.registers.set(&Register::B, 0x43).unwrap();
+        cpu.registers.set(&Register::C, 0x44).unwrap();
+        cpu.registers.set(&Register::D, 0x45).unwrap();
+        cpu.registers.set(&Register::E, 0x46).unwrap();
 
         cpu.boot(vec![0x36, 0x42, 0x70, 0x71, 0x72, 0x73, 0x74, 0x75], vec![]);
-        cpu.step();
+        cpu.step().unwrap();
 
         // Load 0x42 into memory at 0x5123
-        assert_eq!(cpu.memory.read(0x5123), 0x42);
-        cpu.step();
+        assert_eq!(cpu.bus.read(0x5123), 0x42);
+        cpu.step().unwrap();
 
         // Load B 0x43 into memory at 0x5123
-        assert_eq!(cpu.memory.read(0x5123), 0x43);
-        cpu.step();
+        assert_eq!(cpu.bus.read(0x5123), 0x43);
+        cpu.step().unwrap();
 
         // Load C 0x44 into memory at 0x5123
-        assert_eq!(cpu.memory.read(0x5123), 0x44);
-        cpu.step();
+        assert_eq!(cpu.bus.read(0x5123), 0x44);
+        cpu.step().unwrap();
 
         // Load D 0x45 into memory at 0x5123
-        assert_eq!(cpu.memory.read(0x5123), 0x45);
-        cpu.step();
+        assert_eq!(cpu.bus.read(0x5123), 0x45);
+        cpu.step().unwrap();
 
         // Load E 0x46 into memory at 0x5123
-        assert_eq!(cpu.memory.read(0x5123), 0x46);
-        cpu.step();
+        assert_eq!(cpu.bus.read(0x5123), 0x46);
+        cpu.step().unwrap();
 
         // Load H 0x51 into memory at 0x5123
-        assert_eq!(cpu.memory.read(0x5123), 0x51);
-        cpu.step();
+        assert_eq!(cpu.bus.read(0x5123), 0x51);
+        cpu.step().unwrap();
 
         // Load L 0x23 into memory at 0x5123
-        assert_eq!(cpu.memory.read(0x5123), 0x23);
+        assert_eq!(cpu.bus.read(0x5123), 0x23);
     }
 
     #[test]
     fn execute_ld8_to_reg_from_hl() {
         let mut cpu = Cpu::new();
-        cpu.registers.set_16(&Register::HL, 0x5123);
+        cpu.registers.set_16(&Register::HL, 0x5123).unwrap();
 
-        cpu.memory.write(0x5123, 0x42);
-        cpu.memory.write(0x4223, 0x42);
-        cpu.memory.write(0x4242, 0x42);
+        cpu.bus.write(0x5123, 0x42);
+        cpu.bus.write(0x4223, 0x42);
+        cpu.bus.write(0x4242, 0x42);
 
         cpu.boot(vec![0x46, 0x4E, 0x56, 0x5E, 0x66, 0x6E, 0x7E], vec![]);
-        assert_eq!(cpu.registers.get(&Register::B), 0x00);
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::B), 0x42);
+        assert_eq!(cpu.registers.get(&Register::B).unwrap(), 0x00);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::B).unwrap(), 0x42);
 
-        assert_eq!(cpu.registers.get(&Register::C), 0x00);
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::C), 0x42);
+        assert_eq!(cpu.registers.get(&Register::C).unwrap(), 0x00);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::C).unwrap(), 0x42);
 
-        assert_eq!(cpu.registers.get(&Register::D), 0x00);
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::D), 0x42);
+        assert_eq!(cpu.registers.get(&Register::D).unwrap(), 0x00);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::D).unwrap(), 0x42);
 
-        assert_eq!(cpu.registers.get(&Register::E), 0x00);
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::E), 0x42);
+        assert_eq!(cpu.registers.get(&Register::E).unwrap(), 0x00);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::E).unwrap(), 0x42);
 
-        assert_eq!(cpu.registers.get(&Register::H), 0x51);
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::H), 0x42);
+        assert_eq!(cpu.registers.get(&Register::H).unwrap(), 0x51);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::H).unwrap(), 0x42);
 
-        assert_eq!(cpu.registers.get(&Register::L), 0x23);
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::L), 0x42);
+        assert_eq!(cpu.registers.get(&Register::L).unwrap(), 0x23);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::L).unwrap(), 0x42);
 
-        assert_eq!(cpu.registers.get(&Register::A), 0x00);
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::A), 0x42);
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0x00);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0x42);
     }
 
     #[test]
     fn execute_ld8_to_a_from_reg() {
         let mut cpu = Cpu::new();
-        cpu.registers.set(&Register::A, 0x00);
-        cpu.registers.set(&Register::B, 0xAA);
-        cpu.registers.set(&Register::C, 0xBB);
-        cpu.registers.set(&Register::D, 0x03);
-        cpu.registers.set(&Register::E, 0x04);
-        cpu.registers.set(&Register::H, 0x05);
-        cpu.registers.set(&Register::L, 0x06);
-
-        cpu.memory.write(0x0506, 0x69); // [HL]
-        cpu.memory.write(0xAABB, 0x69); // [BC]
-        cpu.memory.write(0x0304, 0x69); // [DE]
-        cpu.memory.write(0xABCD, 0x69); // [nn]
+        cpu.registers.set(&Register::A, 0x00).unwrap();
+        cpu.registers.set(&Register::B, 0xAA).unwrap();
+        cpu.registers.set(&Register::C, 0xBB).unwrap();
+        cpu.registers.set(&Register::D, 0x03).unwrap();
+        cpu.registers.set(&Register::E, 0x04).unwrap();
+        cpu.registers.set(&Register::H, 0x05).unwrap();
+        cpu.registers.set(&Register::L, 0x06).unwrap();
+
+        cpu.bus.write(0x0506, 0x69); // [HL]
+        cpu.bus.write(0xAABB, 0x69); // [BC]
+        cpu.bus.write(0x0304, 0x69); // [DE]
+        cpu.bus.write(0xABCD, 0x69); // [nn]
 
         cpu.boot(
             vec![
@@ -287,66 +627,66 @@ mod tests {
             ],
             vec![],
         );
-        cpu.step();
+        cpu.step().unwrap();
 
         // Load 0x00 into A LD A, A
-        assert_eq!(cpu.registers.get(&Register::A), 0x00);
-        cpu.step();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0x00);
+        cpu.step().unwrap();
 
         // Load 0xAA into A LD A, B
-        assert_eq!(cpu.registers.get(&Register::A), 0xAA);
-        cpu.step();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0xAA);
+        cpu.step().unwrap();
 
         // Load 0xBB into A LD A, C
-        assert_eq!(cpu.registers.get(&Register::A), 0xBB);
-        cpu.step();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0xBB);
+        cpu.step().unwrap();
 
         // Load 0x03 into A LD A, D
-        assert_eq!(cpu.registers.get(&Register::A), 0x03);
-        cpu.step();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0x03);
+        cpu.step().unwrap();
 
         // Load 0x04 into A LD A, E
-        assert_eq!(cpu.registers.get(&Register::A), 0x04);
-        cpu.step();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0x04);
+        cpu.step().unwrap();
 
         // Load 0x05 into A LD A, H
-        assert_eq!(cpu.registers.get(&Register::A), 0x05);
-        cpu.step();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0x05);
+        cpu.step().unwrap();
 
         // Load 0x06 into A LD A, L
-        assert_eq!(cpu.registers.get(&Register::A), 0x06);
-        cpu.step();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0x06);
+        cpu.step().unwrap();
 
         // Load 0x69 into A LD A, (HL)
-        assert_eq!(cpu.registers.get(&Register::A), 0x69);
-        cpu.step();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0x69);
+        cpu.step().unwrap();
 
         // Load 0x69 into A LD A, (BC)
-        assert_eq!(cpu.registers.get(&Register::A), 0x69);
-        cpu.step();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0x69);
+        cpu.step().unwrap();
 
         // Load 0x69 into A LD A, (DE)
-        assert_eq!(cpu.registers.get(&Register::A), 0x69);
-        cpu.step();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0x69);
+        cpu.step().unwrap();
 
         // Load 0x69 into A LD A, (nn)
-        assert_eq!(cpu.registers.get(&Register::A), 0x69);
-        cpu.step();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0x69);
+        cpu.step().unwrap();
 
         // Load 0x42 into A LD A, 0x42
-        assert_eq!(cpu.registers.get(&Register::A), 0x42);
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0x42);
     }
 
     #[test]
     fn execute_ld8_to_reg_from_a() {
         let mut cpu = Cpu::new();
-        cpu.registers.set(&Register::A, 0x42);
-        cpu.registers.set(&Register::B, 0x00);
-        cpu.registers.set(&Register::C, 0x00);
-        cpu.registers.set(&Register::D, 0x00);
-        cpu.registers.set(&Register::E, 0x00);
-        cpu.registers.set(&Register::H, 0x00);
-        cpu.registers.set(&Register::L, 0x00);
+        cpu.registers.set(&Register::A, 0x42).unwrap();
+        cpu.registers.set(&Register::B, 0x00).unwrap();
+        cpu.registers.set(&Register::C, 0x00).unwrap();
+        cpu.registers.set(&Register::D, 0x00).unwrap();
+        cpu.registers.set(&Register::E, 0x00).unwrap();
+        cpu.registers.set(&Register::H, 0x00).unwrap();
+        cpu.registers.set(&Register::L, 0x00).unwrap();
 
         cpu.boot(
             vec![
@@ -356,138 +696,138 @@ mod tests {
         );
 
         // Load A 0x42 into B LD B, A
-        cpu.registers.set(&Register::A, 0x42);
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::B), 0x42);
+        cpu.registers.set(&Register::A, 0x42).unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::B).unwrap(), 0x42);
 
         // Load A 0x43 into C LD C, A
-        cpu.registers.set(&Register::A, 0x43);
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::C), 0x43);
+        cpu.registers.set(&Register::A, 0x43).unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::C).unwrap(), 0x43);
 
         // Load A 0x44 into D LD D, A
-        cpu.registers.set(&Register::A, 0x44);
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::D), 0x44);
+        cpu.registers.set(&Register::A, 0x44).unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::D).unwrap(), 0x44);
 
         // Load A 0x45 into E LD E, A
-        cpu.registers.set(&Register::A, 0x45);
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::E), 0x45);
+        cpu.registers.set(&Register::A, 0x45).unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::E).unwrap(), 0x45);
 
         // Load A 0x46 into H LD H, A
-        cpu.registers.set(&Register::A, 0x46);
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::H), 0x46);
+        cpu.registers.set(&Register::A, 0x46).unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::H).unwrap(), 0x46);
 
         // Load A 0x47 into L LD L, A
-        cpu.registers.set(&Register::A, 0x47);
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::L), 0x47);
+        cpu.registers.set(&Register::A, 0x47).unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::L).unwrap(), 0x47);
 
         // Load A 0x48 into A LD A, A
-        cpu.registers.set(&Register::A, 0x48);
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::A), 0x48);
+        cpu.registers.set(&Register::A, 0x48).unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0x48);
 
         // Load A 0x49 into (BC) LD (BC), A
-        cpu.registers.set(&Register::A, 0x49);
-        cpu.step();
-        assert_eq!(cpu.memory.read(0x4243), 0x49);
+        cpu.registers.set(&Register::A, 0x49).unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.bus.read(0x4243), 0x49);
 
         // Load A 0x4A into (DE) LD (DE), A
-        cpu.registers.set(&Register::A, 0x4A);
-        cpu.step();
-        assert_eq!(cpu.memory.read(0x4445), 0x4A);
+        cpu.registers.set(&Register::A, 0x4A).unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.bus.read(0x4445), 0x4A);
 
         // Load A 0x48 into (HL) LD (HL), A
-        cpu.registers.set(&Register::A, 0x48);
-        cpu.step();
-        assert_eq!(cpu.memory.read(0x4647), 0x48);
+        cpu.registers.set(&Register::A, 0x48).unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.bus.read(0x4647), 0x48);
 
         // Load A 0x4B into (nn) LD (nn), A
-        cpu.registers.set(&Register::A, 0x4B);
-        cpu.step();
-        assert_eq!(cpu.memory.read(0xABCD), 0x4B);
+        cpu.registers.set(&Register::A, 0x4B).unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.bus.read(0xABCD), 0x4B);
     }
 
     #[test]
     fn execute_ldac() {
         let mut cpu = Cpu::new();
-        cpu.registers.set(&Register::A, 0x00);
-        cpu.registers.set(&Register::C, 0x42);
-        cpu.memory.write(0xFF42, 0x69);
+        cpu.registers.set(&Register::A, 0x00).unwrap();
+        cpu.registers.set(&Register::C, 0x42).unwrap();
+        cpu.bus.write(0xFF42, 0x69);
 
         cpu.boot(vec![0xF2], vec![]);
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::A), 0x69);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0x69);
     }
 
     #[test]
     fn execute_ldca() {
         let mut cpu = Cpu::new();
-        cpu.registers.set(&Register::A, 0x69);
-        cpu.registers.set(&Register::C, 0x42);
+        cpu.registers.set(&Register::A, 0x69).unwrap();
+        cpu.registers.set(&Register::C, 0x42).unwrap();
 
         cpu.boot(vec![0xE2], vec![]);
-        cpu.step();
-        assert_eq!(cpu.memory.read(0xFF42), 0x69);
+        cpu.step().unwrap();
+        assert_eq!(cpu.bus.read(0xFF42), 0x69);
     }
 
     #[test]
     fn execute_ldna() {
         let mut cpu = Cpu::new();
-        cpu.registers.set(&Register::A, 0x69);
-        cpu.memory.write(0xFF42, 0x00);
+        cpu.registers.set(&Register::A, 0x69).unwrap();
+        cpu.bus.write(0xFF42, 0x00);
 
         cpu.boot(vec![0xE0, 0x42], vec![]);
-        cpu.step();
-        assert_eq!(cpu.memory.read(0xFF42), 0x69);
+        cpu.step().unwrap();
+        assert_eq!(cpu.bus.read(0xFF42), 0x69);
     }
 
     #[test]
     fn execute_ldan() {
         let mut cpu = Cpu::new();
-        cpu.registers.set(&Register::A, 0x00);
-        cpu.memory.write(0xFF42, 0x69);
+        cpu.registers.set(&Register::A, 0x00).unwrap();
+        cpu.bus.write(0xFF42, 0x69);
 
         cpu.boot(vec![0xF0, 0x42], vec![]);
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::A), 0x69);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0x69);
     }
 
     #[test]
     fn execute_ldhi() {
         let mut cpu = Cpu::new();
-        cpu.registers.set(&Register::A, 0x69);
-        cpu.registers.set_16(&Register::HL, 0x1234);
-        cpu.memory.write(0x1235, 0x00);
+        cpu.registers.set(&Register::A, 0x69).unwrap();
+        cpu.registers.set_16(&Register::HL, 0x1234).unwrap();
+        cpu.bus.write(0x1235, 0x00);
 
         cpu.boot(vec![0x22], vec![]);
-        cpu.step();
-        assert_eq!(cpu.memory.read(0x1234), 0x69);
-        assert_eq!(cpu.registers.get_16(&Register::HL), 0x1235);
+        cpu.step().unwrap();
+        assert_eq!(cpu.bus.read(0x1234), 0x69);
+        assert_eq!(cpu.registers.get_16(&Register::HL).unwrap(), 0x1235);
     }
 
     #[test]
     fn execute_ldhd() {
         let mut cpu = Cpu::new();
-        cpu.registers.set(&Register::A, 0x69);
-        cpu.registers.set_16(&Register::HL, 0x1234);
-        cpu.memory.write(0x1233, 0x00);
+        cpu.registers.set(&Register::A, 0x69).unwrap();
+        cpu.registers.set_16(&Register::HL, 0x1234).unwrap();
+        cpu.bus.write(0x1233, 0x00);
 
         cpu.boot(vec![0x32], vec![]);
-        cpu.step();
-        assert_eq!(cpu.memory.read(0x1234), 0x69);
-        assert_eq!(cpu.registers.get_16(&Register::HL), 0x1233);
+        cpu.step().unwrap();
+        assert_eq!(cpu.bus.read(0x1234), 0x69);
+        assert_eq!(cpu.registers.get_16(&Register::HL).unwrap(), 0x1233);
     }
 
     #[test]
     fn execute_ld16() {
         let mut cpu = Cpu::new();
-        cpu.registers.set_16(&Register::BC, 0x0000);
-        cpu.registers.set_16(&Register::DE, 0x0000);
-        cpu.registers.set_16(&Register::HL, 0x0000);
+        cpu.registers.set_16(&Register::BC, 0x0000).unwrap();
+        cpu.registers.set_16(&Register::DE, 0x0000).unwrap();
+        cpu.registers.set_16(&Register::HL, 0x0000).unwrap();
         cpu.registers.sp.set(0x0000);
 
         cpu.boot(
@@ -496,40 +836,40 @@ mod tests {
             ],
             vec![],
         );
-        cpu.step();
-        assert_eq!(cpu.registers.get_16(&Register::BC), 0x1234);
-        cpu.step();
-        assert_eq!(cpu.registers.get_16(&Register::DE), 0x3456);
-        cpu.step();
-        assert_eq!(cpu.registers.get_16(&Register::HL), 0x5678);
-        cpu.step();
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get_16(&Register::BC).unwrap(), 0x1234);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get_16(&Register::DE).unwrap(), 0x3456);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get_16(&Register::HL).unwrap(), 0x5678);
+        cpu.step().unwrap();
         assert_eq!(cpu.registers.sp.get(), 0xABCD);
     }
 
     #[test]
     fn execute_ld16_sp() {
         let mut cpu = Cpu::new();
-        cpu.registers.set_16(&Register::HL, 0xFF69);
+        cpu.registers.set_16(&Register::HL, 0xFF69).unwrap();
         cpu.registers.sp.set(0x0000);
 
         cpu.boot(vec![0xF9, 0xF8, 0xFF], vec![]);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.registers.sp.get(), 0xFF69);
-        cpu.step();
+        cpu.step().unwrap();
 
-        // test half carry true and carry true
-        assert_eq!(cpu.registers.get_16(&Register::HL), 0x68);
+        // test half carry true and carry true, the offset is -1
+        assert_eq!(cpu.registers.get_16(&Register::HL).unwrap(), 0xFF68);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.subtract, false);
         assert_eq!(cpu.registers.f.half_carry, true);
         assert_eq!(cpu.registers.f.carry, true);
 
-        // test half carry true and carry false
-        cpu.registers.sp.set(0x0FFF);
+        // test half carry true and carry false, flags come from the low byte
+        cpu.registers.sp.set(0x0F0F);
         cpu.pc = 0x00;
         cpu.boot(vec![0xF8, 0x01], vec![]);
-        cpu.step();
-        assert_eq!(cpu.registers.get_16(&Register::HL), 0x1000);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get_16(&Register::HL).unwrap(), 0x0F10);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.subtract, false);
         assert_eq!(cpu.registers.f.half_carry, true);
@@ -539,155 +879,155 @@ mod tests {
     #[test]
     fn execute_push_pop() {
         let mut cpu = Cpu::new();
-        cpu.registers.set_16(&Register::BC, 0x1234);
-        cpu.registers.set_16(&Register::DE, 0x5678);
-        cpu.registers.set_16(&Register::HL, 0x9ABC);
-        cpu.registers.set_16(&Register::AF, 0xAA55);
+        cpu.registers.set_16(&Register::BC, 0x1234).unwrap();
+        cpu.registers.set_16(&Register::DE, 0x5678).unwrap();
+        cpu.registers.set_16(&Register::HL, 0x9ABC).unwrap();
+        cpu.registers.set_16(&Register::AF, 0xAA50).unwrap();
         cpu.registers.sp.set(0xFFFE);
 
         cpu.boot(vec![0xC5, 0xD5, 0xE5, 0xF5, 0xF1, 0xC1, 0xD1, 0xE1], vec![]);
-        cpu.step();
-        assert_eq!(cpu.memory.read_16(0xFFFC), 0x1234);
-        cpu.step();
-        assert_eq!(cpu.memory.read_16(0xFFFA), 0x5678);
-        cpu.step();
-        assert_eq!(cpu.memory.read_16(0xFFF8), 0x9ABC);
-        cpu.step();
-        assert_eq!(cpu.memory.read_16(0xFFF6), 0xAA55);
-
-        cpu.registers.set_16(&Register::BC, 0x0000);
-        cpu.registers.set_16(&Register::DE, 0x0000);
-        cpu.registers.set_16(&Register::HL, 0x0000);
-        cpu.registers.set_16(&Register::AF, 0x0000);
-
-        cpu.step();
-        assert_eq!(cpu.registers.get_16(&Register::AF), 0xAA55);
-        cpu.step();
-        assert_eq!(cpu.registers.get_16(&Register::BC), 0x9ABC);
-        cpu.step();
-        assert_eq!(cpu.registers.get_16(&Register::DE), 0x5678);
-        cpu.step();
-        assert_eq!(cpu.registers.get_16(&Register::HL), 0x1234);
+        cpu.step().unwrap();
+        assert_eq!(cpu.bus.read_16(0xFFFC), 0x1234);
+        cpu.step().unwrap();
+        assert_eq!(cpu.bus.read_16(0xFFFA), 0x5678);
+        cpu.step().unwrap();
+        assert_eq!(cpu.bus.read_16(0xFFF8), 0x9ABC);
+        cpu.step().unwrap();
+        assert_eq!(cpu.bus.read_16(0xFFF6), 0xAA50);
+
+        cpu.registers.set_16(&Register::BC, 0x0000).unwrap();
+        cpu.registers.set_16(&Register::DE, 0x0000).unwrap();
+        cpu.registers.set_16(&Register::HL, 0x0000).unwrap();
+        cpu.registers.set_16(&Register::AF, 0x0000).unwrap();
+
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get_16(&Register::AF).unwrap(), 0xAA50);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get_16(&Register::BC).unwrap(), 0x9ABC);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get_16(&Register::DE).unwrap(), 0x5678);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get_16(&Register::HL).unwrap(), 0x1234);
     }
 
     #[test]
     fn execute_add() {
         let mut cpu = Cpu::new();
-        cpu.registers.set(&Register::A, 0x00);
-        cpu.registers.set(&Register::B, 0x01);
-        cpu.registers.set(&Register::C, 0x02);
-        cpu.registers.set(&Register::D, 0x03);
-        cpu.registers.set(&Register::E, 0x04);
-        cpu.registers.set(&Register::H, 0x05);
-        cpu.registers.set(&Register::L, 0x06);
-        cpu.memory.write(0x0506, 0x07);
+        cpu.registers.set(&Register::A, 0x00).unwrap();
+        cpu.registers.set(&Register::B, 0x01).unwrap();
+        cpu.registers.set(&Register::C, 0x02).unwrap();
+        cpu.registers.set(&Register::D, 0x03).unwrap();
+        cpu.registers.set(&Register::E, 0x04).unwrap();
+        cpu.registers.set(&Register::H, 0x05).unwrap();
+        cpu.registers.set(&Register::L, 0x06).unwrap();
+        cpu.bus.write(0x0506, 0x07);
 
         cpu.boot(
             vec![0x87, 0x80, 0x81, 0x82, 0x83, 0x84, 0x85, 0x86, 0xC6, 0x42],
             vec![],
         );
-        cpu.step();
+        cpu.step().unwrap();
 
         // Add A 0x00
-        assert_eq!(cpu.registers.get(&Register::A), 0x00);
-        cpu.step();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0x00);
+        cpu.step().unwrap();
 
         // Add A 0x01
-        assert_eq!(cpu.registers.get(&Register::A), 0x01);
-        cpu.step();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0x01);
+        cpu.step().unwrap();
 
         // Add A 0x02
-        assert_eq!(cpu.registers.get(&Register::A), 0x03);
-        cpu.step();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0x03);
+        cpu.step().unwrap();
 
         // Add A 0x03
-        assert_eq!(cpu.registers.get(&Register::A), 0x06);
-        cpu.step();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0x06);
+        cpu.step().unwrap();
 
         // Add A 0x04
-        assert_eq!(cpu.registers.get(&Register::A), 0x0A);
-        cpu.step();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0x0A);
+        cpu.step().unwrap();
 
         // Add A 0x05
-        assert_eq!(cpu.registers.get(&Register::A), 0x0F);
-        cpu.step();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0x0F);
+        cpu.step().unwrap();
 
         // Add A 0x06
-        assert_eq!(cpu.registers.get(&Register::A), 0x15);
-        cpu.step();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0x15);
+        cpu.step().unwrap();
 
         // Add A (HL)
-        assert_eq!(cpu.registers.get(&Register::A), 0x1C);
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0x1C);
     }
 
     #[test]
     fn execute_add_carry() {
         let mut cpu = Cpu::new();
-        cpu.registers.set(&Register::A, 0x00);
-        cpu.registers.set(&Register::B, 0xFF);
-        cpu.registers.set(&Register::C, 0x01);
-        cpu.registers.set(&Register::D, 0x0F);
-        cpu.registers.set(&Register::E, 0x10);
-        cpu.registers.set(&Register::H, 0x0F);
-        cpu.registers.set(&Register::L, 0x10);
-        cpu.memory.write(0x0F10, 0x01);
+        cpu.registers.set(&Register::A, 0x00).unwrap();
+        cpu.registers.set(&Register::B, 0xFF).unwrap();
+        cpu.registers.set(&Register::C, 0x01).unwrap();
+        cpu.registers.set(&Register::D, 0x0F).unwrap();
+        cpu.registers.set(&Register::E, 0x10).unwrap();
+        cpu.registers.set(&Register::H, 0x0F).unwrap();
+        cpu.registers.set(&Register::L, 0x10).unwrap();
+        cpu.bus.write(0x0F10, 0x01);
 
         cpu.boot(
             vec![0x80, 0x81, 0x82, 0x83, 0x84, 0x85, 0x86, 0xC6, 0x42],
             vec![],
         );
-        cpu.step();
+        cpu.step().unwrap();
 
         // Add A 0xFF from B
-        assert_eq!(cpu.registers.get(&Register::A), 0xFF);
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0xFF);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.subtract, false);
         assert_eq!(cpu.registers.f.half_carry, false);
         assert_eq!(cpu.registers.f.carry, false);
-        cpu.step();
+        cpu.step().unwrap();
 
         // Add A 0x01 from C
-        assert_eq!(cpu.registers.get(&Register::A), 0x00);
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0x00);
         assert_eq!(cpu.registers.f.zero, true);
         assert_eq!(cpu.registers.f.subtract, false);
         assert_eq!(cpu.registers.f.half_carry, true);
         assert_eq!(cpu.registers.f.carry, true);
-        cpu.step();
+        cpu.step().unwrap();
 
         // Add A 0x0F from D
-        assert_eq!(cpu.registers.get(&Register::A), 0x0F);
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0x0F);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.subtract, false);
         assert_eq!(cpu.registers.f.half_carry, false);
         assert_eq!(cpu.registers.f.carry, false);
-        cpu.step();
+        cpu.step().unwrap();
 
         // Add A 0x10 from E
-        assert_eq!(cpu.registers.get(&Register::A), 0x1F);
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0x1F);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.subtract, false);
         assert_eq!(cpu.registers.f.half_carry, false);
         assert_eq!(cpu.registers.f.carry, false);
-        cpu.step();
+        cpu.step().unwrap();
 
         // Add A 0x0F from H
-        assert_eq!(cpu.registers.get(&Register::A), 0x2E);
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0x2E);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.subtract, false);
         assert_eq!(cpu.registers.f.half_carry, true);
         assert_eq!(cpu.registers.f.carry, false);
-        cpu.step();
+        cpu.step().unwrap();
 
         // Add A 0x10 from L
-        assert_eq!(cpu.registers.get(&Register::A), 0x3E);
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0x3E);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.subtract, false);
         assert_eq!(cpu.registers.f.half_carry, false);
         assert_eq!(cpu.registers.f.carry, false);
-        cpu.step();
+        cpu.step().unwrap();
 
         // Add A (HL)
-        assert_eq!(cpu.registers.get(&Register::A), 0x3F);
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0x3F);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.subtract, false);
         assert_eq!(cpu.registers.f.half_carry, false);
@@ -698,167 +1038,167 @@ mod tests {
     fn execute_adc() {
         let mut cpu = Cpu::new();
 
-        cpu.registers.set(&Register::A, 0x00);
-        cpu.registers.set(&Register::B, 0xFF);
-        cpu.registers.set(&Register::C, 0x01);
-        cpu.registers.set(&Register::D, 0x0F);
-        cpu.registers.set(&Register::E, 0x10);
-        cpu.registers.set(&Register::H, 0x0F);
-        cpu.registers.set(&Register::L, 0x10);
-        cpu.memory.write(0x0F10, 0x01);
+        cpu.registers.set(&Register::A, 0x00).unwrap();
+        cpu.registers.set(&Register::B, 0xFF).unwrap();
+        cpu.registers.set(&Register::C, 0x01).unwrap();
+        cpu.registers.set(&Register::D, 0x0F).unwrap();
+        cpu.registers.set(&Register::E, 0x10).unwrap();
+        cpu.registers.set(&Register::H, 0x0F).unwrap();
+        cpu.registers.set(&Register::L, 0x10).unwrap();
+        cpu.bus.write(0x0F10, 0x01);
 
         cpu.boot(
             vec![0x8F, 0x88, 0x89, 0x8A, 0x8B, 0x8C, 0x8D, 0x8E, 0xCE, 0x42],
             vec![],
         );
 
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::A), 0x00); // Add A 0x00 from A
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::A), 0xFF); // Add A 0xFF from B
-        cpu.step();
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0x00); // Add A 0x00 from A
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0xFF); // Add A 0xFF from B
+        cpu.step().unwrap();
         println!("{:?}", cpu.registers);
-        assert_eq!(cpu.registers.get(&Register::A), 0x00); // Add A 0x01 from C
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::A), 0x10); // Add A 0x0F from D carry 1
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::A), 0x20); // Add A 0x10 from E
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::A), 0x2F); // Add A 0x0F from H
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::A), 0x3F); // Add A 0x10 from L
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::A), 0x40); // Add A (HL)
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::A), 0x82); // Add A 0x42
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0x00); // Add A 0x01 from C
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0x10); // Add A 0x0F from D carry 1
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0x20); // Add A 0x10 from E
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0x2F); // Add A 0x0F from H
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0x3F); // Add A 0x10 from L
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0x40); // Add A (HL)
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0x82); // Add A 0x42
     }
 
     #[test]
     fn execute_sub() {
         let mut cpu = Cpu::new();
-        cpu.registers.set(&Register::A, 0x00);
-        cpu.registers.set(&Register::B, 0x01);
-        cpu.registers.set(&Register::C, 0x02);
-        cpu.registers.set(&Register::D, 0x03);
-        cpu.registers.set(&Register::E, 0x04);
-        cpu.registers.set(&Register::H, 0x05);
-        cpu.registers.set(&Register::L, 0x06);
-        cpu.memory.write(0x0506, 0x07);
+        cpu.registers.set(&Register::A, 0x00).unwrap();
+        cpu.registers.set(&Register::B, 0x01).unwrap();
+        cpu.registers.set(&Register::C, 0x02).unwrap();
+        cpu.registers.set(&Register::D, 0x03).unwrap();
+        cpu.registers.set(&Register::E, 0x04).unwrap();
+        cpu.registers.set(&Register::H, 0x05).unwrap();
+        cpu.registers.set(&Register::L, 0x06).unwrap();
+        cpu.bus.write(0x0506, 0x07);
 
         cpu.boot(
             vec![0x97, 0x90, 0x91, 0x92, 0x93, 0x94, 0x95, 0x96, 0xD6, 0x42],
             vec![],
         );
-        cpu.step();
+        cpu.step().unwrap();
 
         // Sub A 0x00
-        assert_eq!(cpu.registers.get(&Register::A), 0x00);
-        cpu.step();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0x00);
+        cpu.step().unwrap();
 
         // Sub A 0x01
-        assert_eq!(cpu.registers.get(&Register::A), 0xFF);
-        cpu.step();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0xFF);
+        cpu.step().unwrap();
 
         // Sub A 0x02
-        assert_eq!(cpu.registers.get(&Register::A), 0xFD);
-        cpu.step();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0xFD);
+        cpu.step().unwrap();
 
         // Sub A 0x03
-        assert_eq!(cpu.registers.get(&Register::A), 0xFA);
-        cpu.step();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0xFA);
+        cpu.step().unwrap();
 
         // Sub A 0x04
-        assert_eq!(cpu.registers.get(&Register::A), 0xF6);
-        cpu.step();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0xF6);
+        cpu.step().unwrap();
 
         // Sub A 0x05
-        assert_eq!(cpu.registers.get(&Register::A), 0xF1);
-        cpu.step();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0xF1);
+        cpu.step().unwrap();
 
         // Sub A 0x06
-        assert_eq!(cpu.registers.get(&Register::A), 0xEB);
-        cpu.step();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0xEB);
+        cpu.step().unwrap();
 
         // Sub A (HL)
-        assert_eq!(cpu.registers.get(&Register::A), 0xE4);
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0xE4);
     }
 
     #[test]
     fn execute_sub_carry() {
         let mut cpu = Cpu::new();
-        cpu.registers.set(&Register::A, 0x00);
-        cpu.registers.set(&Register::B, 0xFF);
-        cpu.registers.set(&Register::C, 0x01);
-        cpu.registers.set(&Register::D, 0x0F);
-        cpu.registers.set(&Register::E, 0x10);
-        cpu.registers.set(&Register::H, 0x0F);
-        cpu.registers.set(&Register::L, 0x10);
-        cpu.memory.write(0x0F10, 0x01);
+        cpu.registers.set(&Register::A, 0x00).unwrap();
+        cpu.registers.set(&Register::B, 0xFF).unwrap();
+        cpu.registers.set(&Register::C, 0x01).unwrap();
+        cpu.registers.set(&Register::D, 0x0F).unwrap();
+        cpu.registers.set(&Register::E, 0x10).unwrap();
+        cpu.registers.set(&Register::H, 0x0F).unwrap();
+        cpu.registers.set(&Register::L, 0x10).unwrap();
+        cpu.bus.write(0x0F10, 0x01);
 
         cpu.boot(
             vec![0x90, 0x91, 0x92, 0x93, 0x94, 0x95, 0x96, 0xD6, 0x42],
             vec![],
         );
-        cpu.step();
+        cpu.step().unwrap();
 
         // Sub A 0xFF from B
-        assert_eq!(cpu.registers.get(&Register::A), 0x01);
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0x01);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.subtract, true);
         assert_eq!(cpu.registers.f.half_carry, true);
         assert_eq!(cpu.registers.f.carry, true);
-        cpu.step();
+        cpu.step().unwrap();
 
         // Sub A 0x01 from C
-        assert_eq!(cpu.registers.get(&Register::A), 0x00);
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0x00);
         assert_eq!(cpu.registers.f.zero, true);
         assert_eq!(cpu.registers.f.subtract, true);
         assert_eq!(cpu.registers.f.half_carry, false);
         assert_eq!(cpu.registers.f.carry, false);
-        cpu.step();
+        cpu.step().unwrap();
 
         // Sub A 0x0F from D
-        assert_eq!(cpu.registers.get(&Register::A), 0xF1);
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0xF1);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.subtract, true);
         assert_eq!(cpu.registers.f.half_carry, true);
         assert_eq!(cpu.registers.f.carry, true);
-        cpu.step();
+        cpu.step().unwrap();
 
         // Sub A 0x10 from E
-        assert_eq!(cpu.registers.get(&Register::A), 0xE1);
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0xE1);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.subtract, true);
         assert_eq!(cpu.registers.f.half_carry, false);
         assert_eq!(cpu.registers.f.carry, false);
-        cpu.step();
+        cpu.step().unwrap();
 
         // Sub A 0x0F from H
-        assert_eq!(cpu.registers.get(&Register::A), 0xD2);
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0xD2);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.subtract, true);
         assert_eq!(cpu.registers.f.half_carry, true);
         assert_eq!(cpu.registers.f.carry, false);
-        cpu.step();
+        cpu.step().unwrap();
 
         // Sub A 0x10 from L
-        assert_eq!(cpu.registers.get(&Register::A), 0xC2);
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0xC2);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.subtract, true);
         assert_eq!(cpu.registers.f.half_carry, false);
         assert_eq!(cpu.registers.f.carry, false);
-        cpu.step();
+        cpu.step().unwrap();
 
         // Sub A (HL)
-        assert_eq!(cpu.registers.get(&Register::A), 0xC1);
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0xC1);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.subtract, true);
         assert_eq!(cpu.registers.f.half_carry, false);
         assert_eq!(cpu.registers.f.carry, false);
-        cpu.step();
+        cpu.step().unwrap();
 
         // Sub A 0x42
-        assert_eq!(cpu.registers.get(&Register::A), 0x7F);
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0x7F);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.subtract, true);
         assert_eq!(cpu.registers.f.half_carry, true);
@@ -869,51 +1209,51 @@ mod tests {
     fn execute_sbc() {
         let mut cpu = Cpu::new();
 
-        cpu.registers.set(&Register::A, 0x00);
-        cpu.registers.set(&Register::B, 0xFF);
-        cpu.registers.set(&Register::C, 0x01);
-        cpu.registers.set(&Register::D, 0x0F);
-        cpu.registers.set(&Register::E, 0x10);
-        cpu.registers.set(&Register::H, 0x0F);
-        cpu.registers.set(&Register::L, 0x10);
-        cpu.memory.write(0x0F10, 0x01);
+        cpu.registers.set(&Register::A, 0x00).unwrap();
+        cpu.registers.set(&Register::B, 0xFF).unwrap();
+        cpu.registers.set(&Register::C, 0x01).unwrap();
+        cpu.registers.set(&Register::D, 0x0F).unwrap();
+        cpu.registers.set(&Register::E, 0x10).unwrap();
+        cpu.registers.set(&Register::H, 0x0F).unwrap();
+        cpu.registers.set(&Register::L, 0x10).unwrap();
+        cpu.bus.write(0x0F10, 0x01);
 
         cpu.boot(
             vec![0x9F, 0x98, 0x99, 0x9A, 0x9B, 0x9C, 0x9D, 0x9E, 0xDE, 0x42],
             vec![],
         );
 
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::A), 0x00); // Sub A 0x00 from A
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::A), 0x01); // Sub A 0xFF from B
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::A), 0xFF); // Sub A 0x01 from C
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::A), 0xEF); // Sub A 0x0F from D carry 1
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::A), 0xDF); // Sub A 0x10 from E
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::A), 0xD0); // Sub A 0x0F from H
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::A), 0xC0); // Sub A 0x10 from L
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::A), 0xBF); // Sub A (HL)
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::A), 0x7D); // Sub A 0x42
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0x00); // Sub A 0x00 from A
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0x01); // Sub A 0xFF from B
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0xFF); // Sub A 0x01 from C
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0xEF); // Sub A 0x0F from D carry 1
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0xDF); // Sub A 0x10 from E
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0xD0); // Sub A 0x0F from H
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0xC0); // Sub A 0x10 from L
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0xBF); // Sub A (HL)
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0x7D); // Sub A 0x42
     }
 
     #[test]
     fn execute_and() {
         let mut cpu = Cpu::new();
-        cpu.registers.set(&Register::A, 0b10101010);
-        cpu.registers.set(&Register::B, 0b11001100);
-        cpu.registers.set(&Register::C, 0b11110000);
-        cpu.registers.set(&Register::D, 0b00001111);
-        cpu.registers.set(&Register::E, 0b11111111);
-        cpu.registers.set(&Register::H, 0b00000000);
-        cpu.registers.set(&Register::L, 0b11111111);
-        cpu.memory.write(0x00FF, 0b10101010);
+        cpu.registers.set(&Register::A, 0b10101010).unwrap();
+        cpu.registers.set(&Register::B, 0b11001100).unwrap();
+        cpu.registers.set(&Register::C, 0b11110000).unwrap();
+        cpu.registers.set(&Register::D, 0b00001111).unwrap();
+        cpu.registers.set(&Register::E, 0b11111111).unwrap();
+        cpu.registers.set(&Register::H, 0b00000000).unwrap();
+        cpu.registers.set(&Register::L, 0b11111111).unwrap();
+        cpu.bus.write(0x00FF, 0b10101010);
 
         cpu.boot(
             vec![
@@ -923,8 +1263,8 @@ mod tests {
         );
 
         // And A from A
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::A), 0b10101010);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0b10101010);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.subtract, false);
         assert_eq!(cpu.registers.f.half_carry, true);
@@ -932,8 +1272,8 @@ mod tests {
 
         // And A from B
         // 10101010 & 11001100 = 10001000
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::A), 0b10001000);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0b10001000);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.subtract, false);
         assert_eq!(cpu.registers.f.half_carry, true);
@@ -941,8 +1281,8 @@ mod tests {
 
         // And A from C
         // 10001000 & 11110000 = 10000000
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::A), 0b10000000);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0b10000000);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.subtract, false);
         assert_eq!(cpu.registers.f.half_carry, true);
@@ -950,9 +1290,9 @@ mod tests {
 
         // And A from D
         // 10101010 & 00001111 = 00001010
-        cpu.registers.set(&Register::A, 0b10101010);
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::A), 0b00001010);
+        cpu.registers.set(&Register::A, 0b10101010).unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0b00001010);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.subtract, false);
         assert_eq!(cpu.registers.f.half_carry, true);
@@ -960,9 +1300,9 @@ mod tests {
 
         // And A from E
         // 00001010 & 11111111 = b00001010
-        cpu.registers.set(&Register::A, 0b00001010);
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::A), 0b00001010);
+        cpu.registers.set(&Register::A, 0b00001010).unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0b00001010);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.subtract, false);
         assert_eq!(cpu.registers.f.half_carry, true);
@@ -970,8 +1310,8 @@ mod tests {
 
         // And A from H
         // 00001010 & 10101010 = 00000000
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::A), 0b00000000);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0b00000000);
         assert_eq!(cpu.registers.f.zero, true);
         assert_eq!(cpu.registers.f.subtract, false);
         assert_eq!(cpu.registers.f.half_carry, true);
@@ -979,9 +1319,9 @@ mod tests {
 
         // And A from L
         // 10110100 & 11111111 = 10110100
-        cpu.registers.set(&Register::A, 0b10110100);
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::A), 0b10110100);
+        cpu.registers.set(&Register::A, 0b10110100).unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0b10110100);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.subtract, false);
         assert_eq!(cpu.registers.f.half_carry, true);
@@ -989,8 +1329,8 @@ mod tests {
 
         // And A (HL)
         // 10110100 & 10101010 = 10100000
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::A), 0b10100000);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0b10100000);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.subtract, false);
         assert_eq!(cpu.registers.f.half_carry, true);
@@ -998,9 +1338,9 @@ mod tests {
 
         // And A from D8
         // 10011111 & 11001100 = 10001100
-        cpu.registers.set(&Register::A, 0b10011111);
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::A), 0b10001100);
+        cpu.registers.set(&Register::A, 0b10011111).unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0b10001100);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.subtract, false);
         assert_eq!(cpu.registers.f.half_carry, true);
@@ -1010,14 +1350,14 @@ mod tests {
     #[test]
     fn execute_or() {
         let mut cpu = Cpu::new();
-        cpu.registers.set(&Register::A, 0b10101010);
-        cpu.registers.set(&Register::B, 0b11001100);
-        cpu.registers.set(&Register::C, 0b11110000);
-        cpu.registers.set(&Register::D, 0b00001111);
-        cpu.registers.set(&Register::E, 0b11111111);
-        cpu.registers.set(&Register::H, 0b00000000);
-        cpu.registers.set(&Register::L, 0b11111111);
-        cpu.memory.write(0x00FF, 0b10101010);
+        cpu.registers.set(&Register::A, 0b10101010).unwrap();
+        cpu.registers.set(&Register::B, 0b11001100).unwrap();
+        cpu.registers.set(&Register::C, 0b11110000).unwrap();
+        cpu.registers.set(&Register::D, 0b00001111).unwrap();
+        cpu.registers.set(&Register::E, 0b11111111).unwrap();
+        cpu.registers.set(&Register::H, 0b00000000).unwrap();
+        cpu.registers.set(&Register::L, 0b11111111).unwrap();
+        cpu.bus.write(0x00FF, 0b10101010);
 
         cpu.boot(
             vec![
@@ -1027,8 +1367,8 @@ mod tests {
         );
 
         // Or A from A
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::A), 0b10101010);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0b10101010);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.subtract, false);
         assert_eq!(cpu.registers.f.half_carry, false);
@@ -1036,8 +1376,8 @@ mod tests {
 
         // Or A from B
         // 10101010 | 11001100 = 11101110
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::A), 0b11101110);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0b11101110);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.subtract, false);
         assert_eq!(cpu.registers.f.half_carry, false);
@@ -1045,8 +1385,8 @@ mod tests {
 
         // Or A from C
         // 11101110 | 11110000 = 11111110
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::A), 0b11111110);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0b11111110);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.subtract, false);
         assert_eq!(cpu.registers.f.half_carry, false);
@@ -1054,9 +1394,9 @@ mod tests {
 
         // Or A from D
         // 11111110 | 00001111 = 11111111
-        cpu.registers.set(&Register::A, 0b11111110);
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::A), 0b11111111);
+        cpu.registers.set(&Register::A, 0b11111110).unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0b11111111);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.subtract, false);
         assert_eq!(cpu.registers.f.half_carry, false);
@@ -1064,8 +1404,8 @@ mod tests {
 
         // Or A from E
         // 11111111 | 11111111 = 11111111
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::A), 0b11111111);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0b11111111);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.subtract, false);
         assert_eq!(cpu.registers.f.half_carry, false);
@@ -1073,8 +1413,8 @@ mod tests {
 
         // Or A from H
         // 11111111 | 00000000 = 11111111
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::A), 0b11111111);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0b11111111);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.subtract, false);
         assert_eq!(cpu.registers.f.half_carry, false);
@@ -1084,14 +1424,14 @@ mod tests {
     #[test]
     fn execute_xor() {
         let mut cpu = Cpu::new();
-        cpu.registers.set(&Register::A, 0b10101010);
-        cpu.registers.set(&Register::B, 0b11001100);
-        cpu.registers.set(&Register::C, 0b11110000);
-        cpu.registers.set(&Register::D, 0b00001111);
-        cpu.registers.set(&Register::E, 0b11111111);
-        cpu.registers.set(&Register::H, 0b00000000);
-        cpu.registers.set(&Register::L, 0b11111111);
-        cpu.memory.write(0x00FF, 0b10101010);
+        cpu.registers.set(&Register::A, 0b10101010).unwrap();
+        cpu.registers.set(&Register::B, 0b11001100).unwrap();
+        cpu.registers.set(&Register::C, 0b11110000).unwrap();
+        cpu.registers.set(&Register::D, 0b00001111).unwrap();
+        cpu.registers.set(&Register::E, 0b11111111).unwrap();
+        cpu.registers.set(&Register::H, 0b00000000).unwrap();
+        cpu.registers.set(&Register::L, 0b11111111).unwrap();
+        cpu.bus.write(0x00FF, 0b10101010);
 
         cpu.boot(
             vec![
@@ -1101,8 +1441,8 @@ mod tests {
         );
 
         // Xor A from A
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::A), 0b00000000);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0b00000000);
         assert_eq!(cpu.registers.f.zero, true);
         assert_eq!(cpu.registers.f.subtract, false);
         assert_eq!(cpu.registers.f.half_carry, false);
@@ -1110,8 +1450,8 @@ mod tests {
 
         // Xor A from B
         // 00000000 ^ 11001100 = 11001100
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::A), 0b11001100);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0b11001100);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.subtract, false);
         assert_eq!(cpu.registers.f.half_carry, false);
@@ -1119,8 +1459,8 @@ mod tests {
 
         // Xor A from C
         // 11001100 ^ 11110000 = 00111100
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::A), 0b00111100);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0b00111100);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.subtract, false);
         assert_eq!(cpu.registers.f.half_carry, false);
@@ -1128,8 +1468,8 @@ mod tests {
 
         // Xor A from D
         // 00111100 ^ 00001111 = 00110011
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::A), 0b00110011);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0b00110011);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.subtract, false);
         assert_eq!(cpu.registers.f.half_carry, false);
@@ -1137,8 +1477,8 @@ mod tests {
 
         // Xor A from E
         // 00110011 ^ 11111111 = 11001100
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::A), 0b11001100);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0b11001100);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.subtract, false);
         assert_eq!(cpu.registers.f.half_carry, false);
@@ -1146,8 +1486,8 @@ mod tests {
 
         // Xor A from H
         // 11001100 ^ 00000000 = 11001100
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::A), 0b11001100);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0b11001100);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.subtract, false);
         assert_eq!(cpu.registers.f.half_carry, false);
@@ -1155,8 +1495,8 @@ mod tests {
 
         // Xor A from L
         // 11001100 ^ 11111111 = 00110011
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::A), 0b00110011);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0b00110011);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.subtract, false);
         assert_eq!(cpu.registers.f.half_carry, false);
@@ -1164,8 +1504,8 @@ mod tests {
 
         // Xor A from (HL)
         // 00110011 ^ 10101010 = 10011001
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::A), 0b10011001);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0b10011001);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.subtract, false);
         assert_eq!(cpu.registers.f.half_carry, false);
@@ -1173,9 +1513,9 @@ mod tests {
 
         // Xor A from D8
         // 10011001 ^ 11001100 = 11111111
-        cpu.registers.set(&Register::A, 0b00110011);
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::A), 0b11111111);
+        cpu.registers.set(&Register::A, 0b00110011).unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0b11111111);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.subtract, false);
         assert_eq!(cpu.registers.f.half_carry, false);
@@ -1185,14 +1525,14 @@ mod tests {
     #[test]
     fn execute_cp() {
         let mut cpu = Cpu::new();
-        cpu.registers.set(&Register::A, 0b10101010);
-        cpu.registers.set(&Register::B, 0b11001100);
-        cpu.registers.set(&Register::C, 0b11110000);
-        cpu.registers.set(&Register::D, 0b00001111);
-        cpu.registers.set(&Register::E, 0b11111111);
-        cpu.registers.set(&Register::H, 0b00000000);
-        cpu.registers.set(&Register::L, 0b11111111);
-        cpu.memory.write(0x00FF, 0b10101010);
+        cpu.registers.set(&Register::A, 0b10101010).unwrap();
+        cpu.registers.set(&Register::B, 0b11001100).unwrap();
+        cpu.registers.set(&Register::C, 0b11110000).unwrap();
+        cpu.registers.set(&Register::D, 0b00001111).unwrap();
+        cpu.registers.set(&Register::E, 0b11111111).unwrap();
+        cpu.registers.set(&Register::H, 0b00000000).unwrap();
+        cpu.registers.set(&Register::L, 0b11111111).unwrap();
+        cpu.bus.write(0x00FF, 0b10101010);
 
         cpu.boot(
             vec![
@@ -1203,8 +1543,8 @@ mod tests {
 
         // Cp A from A
         // 10101010 - 10101010 = 00000000
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::A), 0b10101010);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0b10101010);
         assert_eq!(cpu.registers.f.zero, true);
         assert_eq!(cpu.registers.f.subtract, true);
         assert_eq!(cpu.registers.f.half_carry, false);
@@ -1212,8 +1552,8 @@ mod tests {
 
         // Cp A from B
         // 10101010 - 11001100 = 11111110
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::A), 0b10101010);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0b10101010);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.subtract, true);
         assert_eq!(cpu.registers.f.half_carry, true);
@@ -1221,8 +1561,8 @@ mod tests {
 
         // Cp A from C
         // 10101010 - 11110000 = 10011010
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::A), 0b10101010);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0b10101010);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.subtract, true);
         assert_eq!(cpu.registers.f.half_carry, false);
@@ -1232,70 +1572,70 @@ mod tests {
     #[test]
     fn execute_inc() {
         let mut cpu = Cpu::new();
-        cpu.registers.set(&Register::A, 0b10101010);
-        cpu.registers.set(&Register::B, 0b11001100);
-        cpu.registers.set(&Register::C, 0b11110000);
-        cpu.registers.set(&Register::D, 0b00001111);
-        cpu.registers.set(&Register::E, 0b11111111);
-        cpu.registers.set(&Register::H, 0b11111111);
-        cpu.registers.set(&Register::L, 0b11111110);
-        cpu.memory.write(0x00FF, 0b10101010);
+        cpu.registers.set(&Register::A, 0b10101010).unwrap();
+        cpu.registers.set(&Register::B, 0b11001100).unwrap();
+        cpu.registers.set(&Register::C, 0b11110000).unwrap();
+        cpu.registers.set(&Register::D, 0b00001111).unwrap();
+        cpu.registers.set(&Register::E, 0b11111111).unwrap();
+        cpu.registers.set(&Register::H, 0b11111111).unwrap();
+        cpu.registers.set(&Register::L, 0b11111110).unwrap();
+        cpu.bus.write(0x00FF, 0b10101010);
 
         cpu.boot(vec![0x3C, 0x04, 0x0C, 0x14, 0x1C, 0x24, 0x2C, 0x34], vec![]);
 
         // Inc A
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::A), 0b10101011);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0b10101011);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.subtract, false);
         assert_eq!(cpu.registers.f.half_carry, false);
 
         // Inc B
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::B), 0b11001101);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::B).unwrap(), 0b11001101);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.subtract, false);
         assert_eq!(cpu.registers.f.half_carry, false);
 
         // Inc C
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::C), 0b11110001);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::C).unwrap(), 0b11110001);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.subtract, false);
         assert_eq!(cpu.registers.f.half_carry, false);
 
         // Inc D
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::D), 0b00010000);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::D).unwrap(), 0b00010000);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.subtract, false);
         assert_eq!(cpu.registers.f.half_carry, true);
 
         // Inc E
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::E), 0b00000000);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::E).unwrap(), 0b00000000);
         assert_eq!(cpu.registers.f.zero, true);
         assert_eq!(cpu.registers.f.subtract, false);
         assert_eq!(cpu.registers.f.half_carry, true);
 
         // Inc H
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::H), 0b00000000);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::H).unwrap(), 0b00000000);
         assert_eq!(cpu.registers.f.zero, true);
         assert_eq!(cpu.registers.f.subtract, false);
         assert_eq!(cpu.registers.f.half_carry, true);
 
         // Inc L
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::L), 0b11111111);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::L).unwrap(), 0b11111111);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.subtract, false);
         assert_eq!(cpu.registers.f.half_carry, false);
 
         // Inc (HL)
-        cpu.registers.set_16(&Register::HL, 0x00FF);
-        cpu.step();
-        assert_eq!(cpu.memory.read(0x00FF), 0b10101011);
+        cpu.registers.set_16(&Register::HL, 0x00FF).unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.bus.read(0x00FF), 0b10101011);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.subtract, false);
         assert_eq!(cpu.registers.f.half_carry, false);
@@ -1304,62 +1644,62 @@ mod tests {
     #[test]
     fn execute_dec() {
         let mut cpu = Cpu::new();
-        cpu.registers.set(&Register::A, 0b10101010);
-        cpu.registers.set(&Register::B, 0b11001100);
-        cpu.registers.set(&Register::C, 0b11110000);
-        cpu.registers.set(&Register::D, 0b00001111);
-        cpu.registers.set(&Register::E, 0b11111111);
-        cpu.registers.set(&Register::H, 0b11111111);
-        cpu.registers.set(&Register::L, 0b11111110);
-        cpu.memory.write(0x00FF, 0b10101010);
+        cpu.registers.set(&Register::A, 0b10101010).unwrap();
+        cpu.registers.set(&Register::B, 0b11001100).unwrap();
+        cpu.registers.set(&Register::C, 0b11110000).unwrap();
+        cpu.registers.set(&Register::D, 0b00001111).unwrap();
+        cpu.registers.set(&Register::E, 0b11111111).unwrap();
+        cpu.registers.set(&Register::H, 0b11111111).unwrap();
+        cpu.registers.set(&Register::L, 0b11111110).unwrap();
+        cpu.bus.write(0x00FF, 0b10101010);
 
         cpu.boot(vec![0x3D, 0x05, 0x0D, 0x15, 0x1D, 0x25, 0x2D, 0x35], vec![]);
 
         // Dec A
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::A), 0b10101001);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0b10101001);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.subtract, true);
         assert_eq!(cpu.registers.f.half_carry, false);
 
         // Dec B
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::B), 0b11001011);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::B).unwrap(), 0b11001011);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.subtract, true);
         assert_eq!(cpu.registers.f.half_carry, false);
 
         // Dec C
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::C), 0b11101111);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::C).unwrap(), 0b11101111);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.subtract, true);
         assert_eq!(cpu.registers.f.half_carry, true);
 
         // Dec D
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::D), 0b00001110);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::D).unwrap(), 0b00001110);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.subtract, true);
         assert_eq!(cpu.registers.f.half_carry, false);
 
         // Dec E
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::E), 0b11111110);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::E).unwrap(), 0b11111110);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.subtract, true);
         assert_eq!(cpu.registers.f.half_carry, false);
 
         // Dec H
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::H), 0b11111110);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::H).unwrap(), 0b11111110);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.subtract, true);
         assert_eq!(cpu.registers.f.half_carry, false);
 
         // Dec L
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::L), 0b11111101);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::L).unwrap(), 0b11111101);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.subtract, true);
         assert_eq!(cpu.registers.f.half_carry, false);
@@ -1368,34 +1708,34 @@ mod tests {
     #[test]
     fn execute_add_16() {
         let mut cpu = Cpu::new();
-        cpu.registers.set_16(&Register::BC, 0x1234);
-        cpu.registers.set_16(&Register::DE, 0x5678);
-        cpu.registers.set_16(&Register::HL, 0x9ABC);
-        cpu.registers.set_16(&Register::SP, 0x0000);
+        cpu.registers.set_16(&Register::BC, 0x1234).unwrap();
+        cpu.registers.set_16(&Register::DE, 0x5678).unwrap();
+        cpu.registers.set_16(&Register::HL, 0x9ABC).unwrap();
+        cpu.registers.set_16(&Register::SP, 0x0000).unwrap();
 
         cpu.boot(vec![0x09, 0x19, 0x29, 0x39], vec![]);
 
         // Add HL, BC
-        cpu.registers.set_16(&Register::HL, 0x9ABC);
-        cpu.step();
-        assert_eq!(cpu.registers.get_16(&Register::HL), 0x1234 + 0x9ABC);
+        cpu.registers.set_16(&Register::HL, 0x9ABC).unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get_16(&Register::HL).unwrap(), 0x1234 + 0x9ABC);
         assert_eq!(cpu.registers.f.subtract, false);
         assert_eq!(cpu.registers.f.half_carry, false);
         assert_eq!(cpu.registers.f.carry, false);
 
         // Add HL, DE
-        cpu.registers.set_16(&Register::HL, 0x9ABC);
-        cpu.step();
-        assert_eq!(cpu.registers.get_16(&Register::HL), 0x9ABC + 0x5678);
+        cpu.registers.set_16(&Register::HL, 0x9ABC).unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get_16(&Register::HL).unwrap(), 0x9ABC + 0x5678);
         assert_eq!(cpu.registers.f.subtract, false);
         assert_eq!(cpu.registers.f.half_carry, true);
         assert_eq!(cpu.registers.f.carry, false);
 
         // Add HL, HL
-        cpu.registers.set_16(&Register::HL, 0x9ABC);
-        cpu.step();
+        cpu.registers.set_16(&Register::HL, 0x9ABC).unwrap();
+        cpu.step().unwrap();
         assert_eq!(
-            cpu.registers.get_16(&Register::HL),
+            cpu.registers.get_16(&Register::HL).unwrap(),
             0x09ABC_u16.wrapping_add(0x9ABC)
         );
         assert_eq!(cpu.registers.f.subtract, false);
@@ -1403,9 +1743,9 @@ mod tests {
         assert_eq!(cpu.registers.f.carry, true);
 
         // Add HL, SP
-        cpu.registers.set_16(&Register::HL, 0x9ABC);
-        cpu.step();
-        assert_eq!(cpu.registers.get_16(&Register::HL), 0x9ABC + 0x0000);
+        cpu.registers.set_16(&Register::HL, 0x9ABC).unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get_16(&Register::HL).unwrap(), 0x9ABC + 0x0000);
         assert_eq!(cpu.registers.f.subtract, false);
         assert_eq!(cpu.registers.f.half_carry, false);
         assert_eq!(cpu.registers.f.carry, false);
@@ -1419,10 +1759,10 @@ mod tests {
         cpu.boot(vec![0xE8, 0x00, 0xE8, 0x01, 0xE8, 0x02, 0xE8, 0xFF], vec![]);
 
         // Add SP, 0x00
-        cpu.registers.set_16(&Register::SP, default_sp.clone());
-        cpu.step();
+        cpu.registers.set_16(&Register::SP, default_sp.clone()).unwrap();
+        cpu.step().unwrap();
         assert_eq!(
-            cpu.registers.get_16(&Register::SP),
+            cpu.registers.get_16(&Register::SP).unwrap(),
             default_sp.wrapping_add(0x00)
         );
         assert_eq!(cpu.registers.f.zero, false);
@@ -1431,10 +1771,10 @@ mod tests {
         assert_eq!(cpu.registers.f.carry, false);
 
         // Add SP, 0x01
-        cpu.registers.set_16(&Register::SP, default_sp.clone());
-        cpu.step();
+        cpu.registers.set_16(&Register::SP, default_sp.clone()).unwrap();
+        cpu.step().unwrap();
         assert_eq!(
-            cpu.registers.get_16(&Register::SP),
+            cpu.registers.get_16(&Register::SP).unwrap(),
             default_sp.wrapping_add(0x01)
         );
         assert_eq!(cpu.registers.f.zero, false);
@@ -1443,10 +1783,10 @@ mod tests {
         assert_eq!(cpu.registers.f.carry, false);
 
         // Add SP, 0x02
-        cpu.registers.set_16(&Register::SP, default_sp.clone());
-        cpu.step();
+        cpu.registers.set_16(&Register::SP, default_sp.clone()).unwrap();
+        cpu.step().unwrap();
         assert_eq!(
-            cpu.registers.get_16(&Register::SP),
+            cpu.registers.get_16(&Register::SP).unwrap(),
             default_sp.wrapping_add(0x02)
         );
         assert_eq!(cpu.registers.f.zero, false);
@@ -1454,72 +1794,72 @@ mod tests {
         assert_eq!(cpu.registers.f.half_carry, false);
         assert_eq!(cpu.registers.f.carry, false);
 
-        // Add SP, 0xFF
-        cpu.registers.set_16(&Register::SP, default_sp.clone());
-        cpu.step();
+        // Add SP, -1, flags come from 0x34 + 0xFF
+        cpu.registers.set_16(&Register::SP, default_sp.clone()).unwrap();
+        cpu.step().unwrap();
         assert_eq!(
-            cpu.registers.get_16(&Register::SP),
-            default_sp.wrapping_add(0xFF)
+            cpu.registers.get_16(&Register::SP).unwrap(),
+            default_sp.wrapping_sub(0x01)
         );
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.subtract, false);
-        assert_eq!(cpu.registers.f.half_carry, false);
-        assert_eq!(cpu.registers.f.carry, false);
+        assert_eq!(cpu.registers.f.half_carry, true);
+        assert_eq!(cpu.registers.f.carry, true);
     }
 
     #[test]
     fn execute_inc16() {
         let mut cpu = Cpu::new();
-        cpu.registers.set_16(&Register::BC, 0x1234);
-        cpu.registers.set_16(&Register::DE, 0x5678);
-        cpu.registers.set_16(&Register::HL, 0x9ABC);
-        cpu.registers.set_16(&Register::SP, 0x0000);
+        cpu.registers.set_16(&Register::BC, 0x1234).unwrap();
+        cpu.registers.set_16(&Register::DE, 0x5678).unwrap();
+        cpu.registers.set_16(&Register::HL, 0x9ABC).unwrap();
+        cpu.registers.set_16(&Register::SP, 0x0000).unwrap();
 
         cpu.boot(vec![0x03, 0x13, 0x23, 0x33], vec![]);
 
         // Inc BC
-        cpu.step();
-        assert_eq!(cpu.registers.get_16(&Register::BC), 0x1234 + 1);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get_16(&Register::BC).unwrap(), 0x1234 + 1);
 
         // Inc DE
-        cpu.step();
-        assert_eq!(cpu.registers.get_16(&Register::DE), 0x5678 + 1);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get_16(&Register::DE).unwrap(), 0x5678 + 1);
 
         // Inc HL
-        cpu.step();
-        assert_eq!(cpu.registers.get_16(&Register::HL), 0x9ABC + 1);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get_16(&Register::HL).unwrap(), 0x9ABC + 1);
 
         // Inc SP
-        cpu.step();
-        assert_eq!(cpu.registers.get_16(&Register::SP), 0x0000 + 1);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get_16(&Register::SP).unwrap(), 0x0000 + 1);
     }
 
     #[test]
     fn execute_dec16() {
         let mut cpu = Cpu::new();
-        cpu.registers.set_16(&Register::BC, 0x1234);
-        cpu.registers.set_16(&Register::DE, 0x5678);
-        cpu.registers.set_16(&Register::HL, 0x9ABC);
-        cpu.registers.set_16(&Register::SP, 0x0000);
+        cpu.registers.set_16(&Register::BC, 0x1234).unwrap();
+        cpu.registers.set_16(&Register::DE, 0x5678).unwrap();
+        cpu.registers.set_16(&Register::HL, 0x9ABC).unwrap();
+        cpu.registers.set_16(&Register::SP, 0x0000).unwrap();
 
         cpu.boot(vec![0x0B, 0x1B, 0x2B, 0x3B], vec![]);
 
         // Dec BC
-        cpu.step();
-        assert_eq!(cpu.registers.get_16(&Register::BC), 0x1234 - 1);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get_16(&Register::BC).unwrap(), 0x1234 - 1);
 
         // Dec DE
-        cpu.step();
-        assert_eq!(cpu.registers.get_16(&Register::DE), 0x5678 - 1);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get_16(&Register::DE).unwrap(), 0x5678 - 1);
 
         // Dec HL
-        cpu.step();
-        assert_eq!(cpu.registers.get_16(&Register::HL), 0x9ABC - 1);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get_16(&Register::HL).unwrap(), 0x9ABC - 1);
 
         // Dec SP
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(
-            cpu.registers.get_16(&Register::SP),
+            cpu.registers.get_16(&Register::SP).unwrap(),
             0x0000_u16.wrapping_sub(1)
         );
     }
@@ -1527,14 +1867,14 @@ mod tests {
     #[test]
     fn execute_swap() {
         let mut cpu = Cpu::new();
-        cpu.registers.set(&Register::A, 0b10101010);
-        cpu.registers.set(&Register::B, 0b11001100);
-        cpu.registers.set(&Register::C, 0b11110000);
-        cpu.registers.set(&Register::D, 0b00001111);
-        cpu.registers.set(&Register::E, 0b01100010);
-        cpu.registers.set(&Register::H, 0b00000000);
-        cpu.registers.set(&Register::L, 0b11111111);
-        cpu.memory.write(0x00FF, 0b01001101);
+        cpu.registers.set(&Register::A, 0b10101010).unwrap();
+        cpu.registers.set(&Register::B, 0b11001100).unwrap();
+        cpu.registers.set(&Register::C, 0b11110000).unwrap();
+        cpu.registers.set(&Register::D, 0b00001111).unwrap();
+        cpu.registers.set(&Register::E, 0b01100010).unwrap();
+        cpu.registers.set(&Register::H, 0b00000000).unwrap();
+        cpu.registers.set(&Register::L, 0b11111111).unwrap();
+        cpu.bus.write(0x00FF, 0b01001101);
 
         cpu.boot(
             vec![
@@ -1545,65 +1885,65 @@ mod tests {
         );
 
         // Swap A
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::A), 0b10101010);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0b10101010);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.subtract, false);
         assert_eq!(cpu.registers.f.half_carry, false);
         assert_eq!(cpu.registers.f.carry, false);
 
         // Swap B
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::B), 0b11001100);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::B).unwrap(), 0b11001100);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.subtract, false);
         assert_eq!(cpu.registers.f.half_carry, false);
         assert_eq!(cpu.registers.f.carry, false);
 
         // Swap C
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::C), 0b00001111);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::C).unwrap(), 0b00001111);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.subtract, false);
         assert_eq!(cpu.registers.f.half_carry, false);
         assert_eq!(cpu.registers.f.carry, false);
 
         // Swap D
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::D), 0b11110000);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::D).unwrap(), 0b11110000);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.subtract, false);
         assert_eq!(cpu.registers.f.half_carry, false);
         assert_eq!(cpu.registers.f.carry, false);
 
         // Swap E
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::E), 0b00100110);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::E).unwrap(), 0b00100110);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.subtract, false);
         assert_eq!(cpu.registers.f.half_carry, false);
         assert_eq!(cpu.registers.f.carry, false);
 
         // Swap H
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::H), 0b00000000);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::H).unwrap(), 0b00000000);
         assert_eq!(cpu.registers.f.zero, true);
         assert_eq!(cpu.registers.f.subtract, false);
         assert_eq!(cpu.registers.f.half_carry, false);
         assert_eq!(cpu.registers.f.carry, false);
 
         // Swap L
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::L), 0b11111111);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::L).unwrap(), 0b11111111);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.subtract, false);
         assert_eq!(cpu.registers.f.half_carry, false);
         assert_eq!(cpu.registers.f.carry, false);
 
         // Swap (HL)
-        cpu.registers.set_16(&Register::HL, 0x00FF);
-        cpu.step();
-        assert_eq!(cpu.memory.read(0x00FF), 0b11010100);
+        cpu.registers.set_16(&Register::HL, 0x00FF).unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.bus.read(0x00FF), 0b11010100);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.subtract, false);
         assert_eq!(cpu.registers.f.half_carry, false);
@@ -1619,7 +1959,7 @@ mod tests {
         cpu.registers.f.subtract = true;
         cpu.registers.f.half_carry = true;
         cpu.registers.f.carry = true;
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.registers.f.subtract, false);
         assert_eq!(cpu.registers.f.half_carry, false);
         assert_eq!(cpu.registers.f.carry, false);
@@ -1627,7 +1967,7 @@ mod tests {
         cpu.registers.f.subtract = false;
         cpu.registers.f.half_carry = true;
         cpu.registers.f.carry = false;
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.registers.f.subtract, false);
         assert_eq!(cpu.registers.f.half_carry, false);
         assert_eq!(cpu.registers.f.carry, true);
@@ -1642,7 +1982,7 @@ mod tests {
         cpu.registers.f.subtract = true;
         cpu.registers.f.half_carry = true;
         cpu.registers.f.carry = true;
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.registers.f.subtract, false);
         assert_eq!(cpu.registers.f.half_carry, false);
         assert_eq!(cpu.registers.f.carry, true);
@@ -1650,7 +1990,7 @@ mod tests {
         cpu.registers.f.subtract = false;
         cpu.registers.f.half_carry = true;
         cpu.registers.f.carry = false;
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.registers.f.subtract, false);
         assert_eq!(cpu.registers.f.half_carry, false);
         assert_eq!(cpu.registers.f.carry, true);
@@ -1665,23 +2005,23 @@ mod tests {
         cpu.registers.f.subtract = true;
         cpu.registers.f.half_carry = true;
         cpu.registers.f.carry = true;
-        cpu.registers.set(&Register::A, 0b10000000);
-        cpu.step();
+        cpu.registers.set(&Register::A, 0b10000000).unwrap();
+        cpu.step().unwrap();
 
-        assert_eq!(cpu.registers.get(&Register::A), 0b00000001);
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0b00000001);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.subtract, false);
         assert_eq!(cpu.registers.f.half_carry, false);
         assert_eq!(cpu.registers.f.carry, true);
 
-        cpu.registers.set(&Register::A, 0b00000001);
+        cpu.registers.set(&Register::A, 0b00000001).unwrap();
         cpu.registers.f.zero = true;
         cpu.registers.f.subtract = true;
         cpu.registers.f.half_carry = true;
         cpu.registers.f.carry = true;
-        cpu.step();
+        cpu.step().unwrap();
 
-        assert_eq!(cpu.registers.get(&Register::A), 0b00000010);
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0b00000010);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.subtract, false);
         assert_eq!(cpu.registers.f.half_carry, false);
@@ -1694,15 +2034,16 @@ mod tests {
 
         cpu.boot(vec![0x17, 0x17], vec![]);
 
-        cpu.registers.set(&Register::A, 0b10000000);
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::A), 0b00000000);
-        assert_eq!(cpu.registers.f.zero, true);
+        cpu.registers.set(&Register::A, 0b10000000).unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0b00000000);
+        // Unlike the CB-prefixed rotates, Z is always cleared
+        assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.carry, true);
 
-        cpu.registers.set(&Register::A, 0b00000001);
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::A), 0b00000011);
+        cpu.registers.set(&Register::A, 0b00000001).unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0b00000011);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.carry, false);
     }
@@ -1713,15 +2054,15 @@ mod tests {
 
         cpu.boot(vec![0x0F, 0x0F], vec![]);
 
-        cpu.registers.set(&Register::A, 0b00000001);
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::A), 0b10000000);
+        cpu.registers.set(&Register::A, 0b00000001).unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0b10000000);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.carry, true);
 
-        cpu.registers.set(&Register::A, 0b10000000);
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::A), 0b01000000);
+        cpu.registers.set(&Register::A, 0b10000000).unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0b01000000);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.carry, false);
     }
@@ -1733,16 +2074,17 @@ mod tests {
         cpu.boot(vec![0x1F, 0x1F], vec![]);
 
         assert_eq!(cpu.registers.f.carry, false);
-        cpu.registers.set(&Register::A, 0b00000001);
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::A), 0b00000000);
-        assert_eq!(cpu.registers.f.zero, true);
+        cpu.registers.set(&Register::A, 0b00000001).unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0b00000000);
+        // Unlike the CB-prefixed rotates, Z is always cleared
+        assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.carry, true);
 
         assert_eq!(cpu.registers.f.carry, true);
-        cpu.registers.set(&Register::A, 0b00000010);
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::A), 0b10000001);
+        cpu.registers.set(&Register::A, 0b00000010).unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0b10000001);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.carry, false);
     }
@@ -1759,59 +2101,59 @@ mod tests {
         );
 
         // RLC B
-        cpu.registers.set(&Register::B, 0b10000000);
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::B), 0b00000001);
+        cpu.registers.set(&Register::B, 0b10000000).unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::B).unwrap(), 0b00000001);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.carry, true);
 
         // RLC C
-        cpu.registers.set(&Register::C, 0b00000001);
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::C), 0b00000010);
+        cpu.registers.set(&Register::C, 0b00000001).unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::C).unwrap(), 0b00000010);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.carry, false);
 
         // RLC D
-        cpu.registers.set(&Register::D, 0b00000000);
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::D), 0b00000000);
+        cpu.registers.set(&Register::D, 0b00000000).unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::D).unwrap(), 0b00000000);
         assert_eq!(cpu.registers.f.zero, true);
         assert_eq!(cpu.registers.f.carry, false);
 
         // RLC E
-        cpu.registers.set(&Register::E, 0b00000001);
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::E), 0b00000010);
+        cpu.registers.set(&Register::E, 0b00000001).unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::E).unwrap(), 0b00000010);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.carry, false);
 
         // RLC H
-        cpu.registers.set(&Register::H, 0b10000000);
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::H), 0b00000001);
+        cpu.registers.set(&Register::H, 0b10000000).unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::H).unwrap(), 0b00000001);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.carry, true);
 
         // RLC L
-        cpu.registers.set(&Register::L, 0b00000001);
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::L), 0b00000010);
+        cpu.registers.set(&Register::L, 0b00000001).unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::L).unwrap(), 0b00000010);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.carry, false);
 
         // RLC (HL)
-        cpu.memory.write(0x00FF, 0b10000000);
-        cpu.registers.set_16(&Register::HL, 0x00FF);
-        cpu.step();
-        assert_eq!(cpu.memory.read(0x00FF), 0b00000001);
+        cpu.bus.write(0x00FF, 0b10000000);
+        cpu.registers.set_16(&Register::HL, 0x00FF).unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.bus.read(0x00FF), 0b00000001);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.carry, true);
 
         // RLC A
-        cpu.registers.set(&Register::A, 0b10000000);
-        cpu.step();
-        assert_eq!(cpu.registers.get(&Register::A), 0b00000001);
+        cpu.registers.set(&Register::A, 0b10000000).unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0b00000001);
         assert_eq!(cpu.registers.f.zero, false);
         assert_eq!(cpu.registers.f.carry, true);
     }
@@ -1823,11 +2165,209 @@ mod tests {
 
         // PC startet bei 0x0000
         // JR 0x02: PC + 2 (Opcode und Offset) + 2 = 0x0004
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x0002 + 2, "JR 0x02 failed");
 
         // JR 0x02: PC + 2 (Opcode und Offset) + 2 = 0x0008
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x0004 + 2 + 2, "JR 0x02 failed");
     }
+
+    #[test]
+    fn execute_bit() {
+        let mut cpu = Cpu::new();
+        cpu.registers.set(&Register::A, 0b0000_0010).unwrap();
+        cpu.registers.f.carry = true;
+
+        // BIT 0, A; BIT 1, A; BIT 7, A
+        cpu.boot(vec![0xCB, 0x47, 0xCB, 0x4F, 0xCB, 0x7F], vec![]);
+        cpu.step().unwrap();
+        assert!(cpu.registers.f.zero, "only bit 0 is tested");
+        assert!(!cpu.registers.f.subtract);
+        assert!(cpu.registers.f.half_carry);
+        assert!(cpu.registers.f.carry);
+        cpu.step().unwrap();
+        assert!(!cpu.registers.f.zero);
+        cpu.step().unwrap();
+        assert!(cpu.registers.f.zero);
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0b0000_0010);
+    }
+
+    #[test]
+    fn execute_res_set() {
+        let mut cpu = Cpu::new();
+        cpu.registers.set(&Register::B, 0b0000_0001).unwrap();
+        cpu.registers.set_16(&Register::HL, 0xC000).unwrap();
+        cpu.bus.write(0xC000, 0b1000_0000);
+
+        // SET 7, B; RES 0, B; SET 0, (HL); RES 7, (HL)
+        cpu.boot(vec![0xCB, 0xF8, 0xCB, 0x80, 0xCB, 0xC6, 0xCB, 0xBE], vec![]);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::B).unwrap(), 0b1000_0001);
+        assert_eq!(cpu.pc, 0x0002);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::B).unwrap(), 0b1000_0000);
+        assert_eq!(cpu.pc, 0x0004);
+        cpu.step().unwrap();
+        assert_eq!(cpu.bus.read(0xC000), 0b1000_0001);
+        cpu.step().unwrap();
+        assert_eq!(cpu.bus.read(0xC000), 0b0000_0001);
+        assert_eq!(cpu.pc, 0x0008);
+        assert_eq!(cpu.cycles, 8 + 8 + 16 + 16);
+    }
+
+    #[test]
+    fn execute_adc_sbc_carry_in() {
+        let mut cpu = Cpu::new();
+
+        // ADC A, B; SBC A, B
+        cpu.boot(vec![0x88, 0x98], vec![]);
+
+        // 0x00 + 0xFF + 1 carries out, folding the carry into B would not
+        cpu.registers.set(&Register::A, 0x00).unwrap();
+        cpu.registers.set(&Register::B, 0xFF).unwrap();
+        cpu.registers.f.carry = true;
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0x00);
+        assert!(cpu.registers.f.zero);
+        assert!(cpu.registers.f.half_carry);
+        assert!(cpu.registers.f.carry);
+
+        // 0x00 - 0xFF - 1 borrows
+        cpu.registers.set(&Register::A, 0x00).unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0x00);
+        assert!(cpu.registers.f.zero);
+        assert!(cpu.registers.f.subtract);
+        assert!(cpu.registers.f.half_carry);
+        assert!(cpu.registers.f.carry);
+    }
+
+    #[test]
+    fn execute_add_16_keeps_zero() {
+        let mut cpu = Cpu::new();
+        cpu.registers.set_16(&Register::HL, 0x0FFF).unwrap();
+        cpu.registers.set_16(&Register::BC, 0x0001).unwrap();
+        cpu.registers.f.zero = true;
+        cpu.registers.f.subtract = true;
+
+        cpu.boot(vec![0x09], vec![]); // ADD HL, BC
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get_16(&Register::HL).unwrap(), 0x1000);
+        assert!(cpu.registers.f.zero);
+        assert!(!cpu.registers.f.subtract);
+        assert!(cpu.registers.f.half_carry);
+        assert!(!cpu.registers.f.carry);
+    }
+
+    #[test]
+    fn execute_sp_plus_offset_length() {
+        let mut cpu = Cpu::new();
+        cpu.registers.sp.set(0xFFF8);
+
+        // LD HL, SP-8; ADD SP, 8; NOP
+        cpu.boot(vec![0xF8, 0xF8, 0xE8, 0x08, 0x00], vec![]);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get_16(&Register::HL).unwrap(), 0xFFF0);
+        assert_eq!(cpu.pc, 0x0002);
+        assert_eq!(cpu.cycles, 12);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.sp.get(), 0x0000);
+        assert!(cpu.registers.f.carry);
+        assert_eq!(cpu.pc, 0x0004);
+        assert_eq!(cpu.cycles, 12 + 16);
+    }
+
+    #[test]
+    fn execute_ld_a16_sp() {
+        let mut cpu = Cpu::new();
+        cpu.registers.sp.set(0xBEEF);
+
+        // LD (0xC100), SP
+        cpu.boot(vec![0x08, 0x00, 0xC1], vec![]);
+        cpu.step().unwrap();
+        assert_eq!(cpu.bus.read(0xC100), 0xEF);
+        assert_eq!(cpu.bus.read(0xC101), 0xBE);
+        assert_eq!(cpu.registers.sp.get(), 0xBEEF);
+        assert_eq!(cpu.pc, 0x0003);
+        assert_eq!(cpu.cycles, 20);
+    }
+
+    #[test]
+    fn execute_ld_a_hl_inc_dec() {
+        let mut cpu = Cpu::new();
+        cpu.bus.write(0xC100, 0x12);
+        cpu.bus.write(0xC101, 0x34);
+        cpu.registers.set_16(&Register::HL, 0xC100).unwrap();
+
+        // LD A, (HL+); LD A, (HL-)
+        cpu.boot(vec![0x2A, 0x3A], vec![]);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0x12);
+        assert_eq!(cpu.registers.get_16(&Register::HL).unwrap(), 0xC101);
+        assert_eq!(cpu.bus.read(0xC100), 0x12);
+
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0x34);
+        assert_eq!(cpu.registers.get_16(&Register::HL).unwrap(), 0xC100);
+        assert_eq!(cpu.bus.read(0xC101), 0x34);
+        assert_eq!(cpu.cycles, 16);
+    }
+
+    #[test]
+    fn execute_call_conditional_jumps() {
+        let mut cpu = Cpu::new();
+        cpu.registers.sp.set(0xFFFE);
+        cpu.registers.f.zero = true;
+
+        // CALL NZ, 0x0010; CALL Z, 0x0020
+        cpu.boot(vec![0xC4, 0x10, 0x00, 0xCC, 0x20, 0x00], vec![]);
+        cpu.step().unwrap();
+        assert_eq!(cpu.pc, 0x0003);
+        assert_eq!(cpu.registers.sp.get(), 0xFFFE);
+        assert_eq!(cpu.cycles, 12);
+
+        cpu.step().unwrap();
+        assert_eq!(cpu.pc, 0x0020);
+        assert_eq!(cpu.registers.sp.get(), 0xFFFC);
+        assert_eq!(cpu.bus.read(0xFFFC), 0x06);
+        assert_eq!(cpu.bus.read(0xFFFD), 0x00);
+        assert_eq!(cpu.cycles, 12 + 24);
+    }
+
+    #[test]
+    fn execute_rotate_a_clears_zero() {
+        let mut cpu = Cpu::new();
+
+        // RLCA; RRCA with A = 0 and Z set beforehand
+        cpu.boot(vec![0x07, 0x0F], vec![]);
+        cpu.registers.f.zero = true;
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0x00);
+        assert!(!cpu.registers.f.zero);
+
+        cpu.registers.f.zero = true;
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0x00);
+        assert!(!cpu.registers.f.zero);
+    }
+
+    #[test]
+    fn execute_wraps_at_end_of_memory() {
+        let mut ram = crate::bus::FlatRam::new();
+        // LD A, 0x42 with its operand at 0x0000
+        ram.load(0xFFFF, &[0x3E, 0x42]);
+        let mut cpu = Cpu::with_bus(ram);
+        cpu.pc = 0xFFFF;
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::A).unwrap(), 0x42);
+        assert_eq!(cpu.pc, 0x0001);
+
+        // SET 0, B ending at 0xFFFF
+        cpu.pc = 0xFFFE;
+        cpu.bus.load(0xFFFE, &[0xCB, 0xC0]);
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(&Register::B).unwrap(), 0x01);
+        assert_eq!(cpu.pc, 0x0000);
+    }
 }
@@ -4,7 +4,6 @@ pub struct FlagRegister {
     pub subtract: bool,
     pub half_carry: bool,
     pub carry: bool,
-    pub rest: u8, // The rest of the flag register 4 bits
 }
 
 impl FlagRegister {
@@ -14,7 +13,6 @@ impl FlagRegister {
             subtract: false,
             half_carry: false,
             carry: false,
-            rest: 0,
         }
     }
 
@@ -23,7 +21,6 @@ impl FlagRegister {
             | (if self.subtract { 1 } else { 0 }) << 6
             | (if self.half_carry { 1 } else { 0 }) << 5
             | (if self.carry { 1 } else { 0 } << 4)
-            | self.rest
     }
 
     pub fn set(&mut self, value: u8) {
@@ -31,7 +28,7 @@ impl FlagRegister {
         self.subtract = ((value >> 6) & 0b1) != 0;
         self.half_carry = ((value >> 5) & 0b1) != 0;
         self.carry = ((value >> 4) & 0b1) != 0;
-        self.rest = value & 0b0000_1111;
+        // The low 4 bits do not exist and always read as 0
     }
 }
 
@@ -77,4 +74,11 @@ mod tests {
         assert_eq!(flag_register.half_carry, true);
         assert_eq!(flag_register.carry, true);
     }
+
+    #[test]
+    fn test_set_ignores_low_bits() {
+        let mut flag_register = FlagRegister::new();
+        flag_register.set(0b0001_1111);
+        assert_eq!(flag_register.get(), 0b0001_0000);
+    }
 }
@@ -1,9 +1,10 @@
 use self::{flag_register::FlagRegister, stack_pointer::StackPointer};
+use super::ExecutionError;
 
 pub(crate) mod flag_register;
 pub(crate) mod stack_pointer;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum Register {
     A,
     B,
@@ -50,8 +51,8 @@ impl Registers {
         }
     }
 
-    pub fn get(&self, register: &Register) -> u8 {
-        match register {
+    pub fn get(&self, register: &Register) -> Result<u8, ExecutionError> {
+        Ok(match register {
             Register::A => self.a,
             Register::B => self.b,
             Register::C => self.c,
@@ -60,23 +61,23 @@ impl Registers {
             Register::F => self.f.get(),
             Register::H => self.h,
             Register::L => self.l,
-            _ => panic!("Invalid register"),
-        }
+            _ => return Err(ExecutionError::InvalidOperand),
+        })
     }
 
-    pub fn get_16(&self, register: &Register) -> u16 {
-        match register {
+    pub fn get_16(&self, register: &Register) -> Result<u16, ExecutionError> {
+        Ok(match register {
             Register::AF => self.get_af(),
             Register::BC => self.get_bc(),
             Register::DE => self.get_de(),
             Register::HL => self.get_hl(),
             Register::SP => self.sp.get(),
-            _ => panic!("Invalid register"),
-        }
+            _ => return Err(ExecutionError::InvalidOperand),
+        })
     }
 
-    pub fn set(&mut self, register: &Register, value: u8) {
-        println!("[REG] Setting 8-bit register {:?} to 0x{:X}", register, value);
+    pub fn set(&mut self, register: &Register, value: u8) -> Result<(), ExecutionError> {
+        log!(Reg, Trace, "Setting 8-bit register {:?} to 0x{:X}", register, value);
         match register {
             Register::A => self.a = value,
             Register::B => self.b = value,
@@ -89,20 +90,22 @@ impl Registers {
             Register::BC => self.set_bc((self.b as u16) << 8 | value as u16),
             Register::DE => self.set_de((self.d as u16) << 8 | value as u16),
             Register::HL => self.set_hl((self.h as u16) << 8 | value as u16),
-            _ => panic!("Invalid register"),
+            _ => return Err(ExecutionError::InvalidOperand),
         }
+        Ok(())
     }
 
-    pub fn set_16(&mut self, register: &Register, value: u16) {
-        println!("[REG] Setting 16-bit register {:?} to 0x{:X}", register, value);
+    pub fn set_16(&mut self, register: &Register, value: u16) -> Result<(), ExecutionError> {
+        log!(Reg, Trace, "Setting 16-bit register {:?} to 0x{:X}", register, value);
         match register {
             Register::AF => self.set_af(value),
             Register::BC => self.set_bc(value),
             Register::DE => self.set_de(value),
             Register::HL => self.set_hl(value),
             Register::SP => self.sp.set(value),
-            _ => panic!("Invalid register"),
+            _ => return Err(ExecutionError::InvalidOperand),
         }
+        Ok(())
     }
 
     fn get_af(&self) -> u16 {
@@ -154,54 +157,54 @@ mod tests {
         registers.c = 3;
         registers.d = 4;
         registers.e = 5;
-        registers.f.set(6);
+        registers.f.set(0x60);
         registers.h = 7;
         registers.l = 8;
 
-        assert_eq!(registers.get(&Register::A), 1);
-        assert_eq!(registers.get(&Register::B), 2);
-        assert_eq!(registers.get(&Register::C), 3);
-        assert_eq!(registers.get(&Register::D), 4);
-        assert_eq!(registers.get(&Register::E), 5);
-        assert_eq!(registers.get(&Register::F), 6);
-        assert_eq!(registers.get(&Register::H), 7);
-        assert_eq!(registers.get(&Register::L), 8);
+        assert_eq!(registers.get(&Register::A).unwrap(), 1);
+        assert_eq!(registers.get(&Register::B).unwrap(), 2);
+        assert_eq!(registers.get(&Register::C).unwrap(), 3);
+        assert_eq!(registers.get(&Register::D).unwrap(), 4);
+        assert_eq!(registers.get(&Register::E).unwrap(), 5);
+        assert_eq!(registers.get(&Register::F).unwrap(), 0x60);
+        assert_eq!(registers.get(&Register::H).unwrap(), 7);
+        assert_eq!(registers.get(&Register::L).unwrap(), 8);
     }
 
     #[test]
     fn get_16() {
         let mut registers = Registers::new();
-        registers.set_af(0x0102);
+        registers.set_af(0x0120);
         registers.set_bc(0x0304);
         registers.set_de(0x0506);
         registers.set_hl(0x0708);
         registers.sp.set(0x090A);
 
-        assert_eq!(registers.get_16(&Register::AF), 0x0102);
-        assert_eq!(registers.get_16(&Register::BC), 0x0304);
-        assert_eq!(registers.get_16(&Register::DE), 0x0506);
-        assert_eq!(registers.get_16(&Register::HL), 0x0708);
-        assert_eq!(registers.get_16(&Register::SP), 0x090A);
+        assert_eq!(registers.get_16(&Register::AF).unwrap(), 0x0120);
+        assert_eq!(registers.get_16(&Register::BC).unwrap(), 0x0304);
+        assert_eq!(registers.get_16(&Register::DE).unwrap(), 0x0506);
+        assert_eq!(registers.get_16(&Register::HL).unwrap(), 0x0708);
+        assert_eq!(registers.get_16(&Register::SP).unwrap(), 0x090A);
     }
 
     #[test]
     fn set() {
         let mut registers = Registers::new();
-        registers.set(&Register::A, 1);
-        registers.set(&Register::B, 2);
-        registers.set(&Register::C, 3);
-        registers.set(&Register::D, 4);
-        registers.set(&Register::E, 5);
-        registers.set(&Register::F, 6);
-        registers.set(&Register::H, 7);
-        registers.set(&Register::L, 8);
+        registers.set(&Register::A, 1).unwrap();
+        registers.set(&Register::B, 2).unwrap();
+        registers.set(&Register::C, 3).unwrap();
+        registers.set(&Register::D, 4).unwrap();
+        registers.set(&Register::E, 5).unwrap();
+        registers.set(&Register::F, 0x60).unwrap();
+        registers.set(&Register::H, 7).unwrap();
+        registers.set(&Register::L, 8).unwrap();
 
         assert_eq!(registers.a, 1);
         assert_eq!(registers.b, 2);
         assert_eq!(registers.c, 3);
         assert_eq!(registers.d, 4);
         assert_eq!(registers.e, 5);
-        assert_eq!(registers.f.get(), 6);
+        assert_eq!(registers.f.get(), 0x60);
         assert_eq!(registers.h, 7);
         assert_eq!(registers.l, 8);
     }
@@ -209,13 +212,13 @@ mod tests {
     #[test]
     fn set_16() {
         let mut registers = Registers::new();
-        registers.set_16(&Register::AF, 0x0102);
-        registers.set_16(&Register::BC, 0x0304);
-        registers.set_16(&Register::DE, 0x0506);
-        registers.set_16(&Register::HL, 0x0708);
-        registers.set_16(&Register::SP, 0x090A);
+        registers.set_16(&Register::AF, 0x0120).unwrap();
+        registers.set_16(&Register::BC, 0x0304).unwrap();
+        registers.set_16(&Register::DE, 0x0506).unwrap();
+        registers.set_16(&Register::HL, 0x0708).unwrap();
+        registers.set_16(&Register::SP, 0x090A).unwrap();
 
-        assert_eq!(registers.get_af(), 0x0102);
+        assert_eq!(registers.get_af(), 0x0120);
         assert_eq!(registers.get_bc(), 0x0304);
         assert_eq!(registers.get_de(), 0x0506);
         assert_eq!(registers.get_hl(), 0x0708);
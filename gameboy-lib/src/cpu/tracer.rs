@@ -0,0 +1,191 @@
+use std::{fmt, io::Write};
+
+use crate::bus::Bus;
+
+use super::registers::Registers;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TraceStart {
+    Immediately,
+    AtPc(u16),              // Start once PC reaches the address
+    AfterInstructions(u64), // Start after skipping a number of instructions
+}
+
+/// Writes one Gameboy-Doctor line per executed instruction, e.g.
+/// `A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,C3,13,02`
+pub struct Tracer {
+    writer: Box<dyn Write + Send>,
+    start: TraceStart,
+    started: bool,
+    instructions: u64,
+}
+
+impl Tracer {
+    pub fn new(writer: Box<dyn Write + Send>, start: TraceStart) -> Tracer {
+        Tracer {
+            writer,
+            start,
+            started: start == TraceStart::Immediately,
+            instructions: 0,
+        }
+    }
+
+    pub fn trace(&mut self, registers: &Registers, pc: u16, bus: &impl Bus) {
+        if !self.started {
+            self.started = match self.start {
+                TraceStart::Immediately => true,
+                TraceStart::AtPc(address) => address == pc,
+                TraceStart::AfterInstructions(count) => self.instructions >= count,
+            };
+        }
+        self.instructions += 1;
+
+        if self.started {
+            let line = format_state(registers, pc, bus);
+            if writeln!(self.writer, "{}", line).is_err() {
+                log!(Cpu, Error, "Failed to write trace line");
+            }
+        }
+    }
+
+    pub fn flush(&mut self) {
+        let _ = self.writer.flush();
+    }
+}
+
+impl fmt::Debug for Tracer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Tracer")
+            .field("start", &self.start)
+            .field("started", &self.started)
+            .field("instructions", &self.instructions)
+            .finish()
+    }
+}
+
+impl Drop for Tracer {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+pub fn format_state(registers: &Registers, pc: u16, bus: &impl Bus) -> String {
+    format!(
+        "A:{:02X} F:{:02X} B:{:02X} C:{:02X} D:{:02X} E:{:02X} H:{:02X} L:{:02X} SP:{:04X} PC:{:04X} PCMEM:{:02X},{:02X},{:02X},{:02X}",
+        registers.a,
+        registers.f.get(),
+        registers.b,
+        registers.c,
+        registers.d,
+        registers.e,
+        registers.h,
+        registers.l,
+        registers.sp.get(),
+        pc,
+        bus.peek(pc),
+        bus.peek(pc.wrapping_add(1)),
+        bus.peek(pc.wrapping_add(2)),
+        bus.peek(pc.wrapping_add(3)),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::{cpu::Cpu, model::Model};
+
+    #[derive(Clone)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl SharedBuffer {
+        fn lines(&self) -> Vec<String> {
+            let buffer = self.0.lock().unwrap();
+            String::from_utf8(buffer.clone())
+                .unwrap()
+                .lines()
+                .map(String::from)
+                .collect()
+        }
+    }
+
+    #[test]
+    fn test_format_state() {
+        let mut cpu = Cpu::new();
+        cpu.reset_post_boot(Model::Dmg);
+        cpu.bus.write_vec(0x100, vec![0x00, 0xC3, 0x13, 0x02]);
+
+        assert_eq!(
+            format_state(&cpu.registers, cpu.pc, &cpu.bus),
+            "A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,C3,13,02"
+        );
+    }
+
+    #[test]
+    fn test_trace_every_step() {
+        let buffer = SharedBuffer(Arc::new(Mutex::new(Vec::new())));
+        let mut cpu = Cpu::new();
+        cpu.boot(vec![0x3E, 0x42, 0x00], vec![]);
+        cpu.tracer = Some(Tracer::new(
+            Box::new(buffer.clone()),
+            TraceStart::Immediately,
+        ));
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+
+        assert_eq!(
+            buffer.lines(),
+            vec![
+                "A:00 F:00 B:00 C:00 D:00 E:00 H:00 L:00 SP:0000 PC:0000 PCMEM:3E,42,00,00",
+                "A:42 F:00 B:00 C:00 D:00 E:00 H:00 L:00 SP:0000 PC:0002 PCMEM:00,00,00,00",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_trace_start_at_pc() {
+        let buffer = SharedBuffer(Arc::new(Mutex::new(Vec::new())));
+        let mut cpu = Cpu::new();
+        cpu.boot(vec![0x00, 0x00, 0x00, 0x00], vec![]);
+        cpu.tracer = Some(Tracer::new(
+            Box::new(buffer.clone()),
+            TraceStart::AtPc(0x02),
+        ));
+        for _ in 0..4 {
+            cpu.step().unwrap();
+        }
+
+        let lines = buffer.lines();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].contains("PC:0002"));
+    }
+
+    #[test]
+    fn test_trace_start_after_instructions() {
+        let buffer = SharedBuffer(Arc::new(Mutex::new(Vec::new())));
+        let mut cpu = Cpu::new();
+        cpu.boot(vec![0x00, 0x00, 0x00, 0x00], vec![]);
+        cpu.tracer = Some(Tracer::new(
+            Box::new(buffer.clone()),
+            TraceStart::AfterInstructions(3),
+        ));
+        for _ in 0..4 {
+            cpu.step().unwrap();
+        }
+
+        let lines = buffer.lines();
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].contains("PC:0003"));
+    }
+}
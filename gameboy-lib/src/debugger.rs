@@ -0,0 +1,431 @@
+use std::{cell::Cell, collections::BTreeMap, collections::BTreeSet};
+
+use crate::{
+    cpu::{instructions::instruction_length, registers::Registers, Cpu},
+    disassembler::{disassemble_around, disassemble_memory, Disassembly},
+    error::{GameboyError, IllegalOpcodePolicy},
+    Gameboy, CYCLES_PER_FRAME,
+};
+
+/// T-cycles `step_over` waits for a call to return, ten seconds of emulated
+/// time.
+pub const STEP_OVER_BUDGET: u64 = 600 * CYCLES_PER_FRAME;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WatchKind {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+impl WatchKind {
+    fn matches(&self, access: Access) -> bool {
+        match self {
+            WatchKind::Read => access == Access::Read,
+            WatchKind::Write => access == Access::Write,
+            WatchKind::ReadWrite => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WatchHit {
+    pub address: u16,
+    pub access: Access,
+    pub value: u8,
+    pub pc: u16, // Address of the instruction that caused the access
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StopReason {
+    Step,                 // The requested number of instructions were executed
+    Breakpoint(u16),      // PC reached a breakpoint
+    Watchpoint(WatchHit), // A watched address was accessed
+    Error(GameboyError),  // The instruction at PC could not be executed
+    Lockup(u16),          // The CPU hung on the illegal opcode at this address
+    Timeout,              // A stepped over call did not return within the budget
+}
+
+/// Watched addresses, checked by `Memory` on every CPU access.
+#[derive(Debug, Default)]
+pub struct Watchpoints {
+    entries: BTreeMap<u16, WatchKind>,
+    hit: Cell<Option<(u16, Access, u8)>>,
+}
+
+impl Watchpoints {
+    pub fn insert(&mut self, address: u16, kind: WatchKind) {
+        self.entries.insert(address, kind);
+    }
+
+    pub fn remove(&mut self, address: u16) -> bool {
+        self.entries.remove(&address).is_some()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (u16, WatchKind)> + '_ {
+        self.entries.iter().map(|(address, kind)| (*address, *kind))
+    }
+
+    pub fn check(&self, address: u16, access: Access, value: u8) {
+        if self.entries.is_empty() || self.hit.get().is_some() {
+            return;
+        }
+        if let Some(kind) = self.entries.get(&address) {
+            if kind.matches(access) {
+                self.hit.set(Some((address, access, value)));
+            }
+        }
+    }
+
+    pub fn take_hit(&self, pc: u16) -> Option<WatchHit> {
+        self.hit.take().map(|(address, access, value)| WatchHit {
+            address,
+            access,
+            value,
+            pc,
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Breakpoints {
+    addresses: BTreeSet<u16>,
+}
+
+impl Breakpoints {
+    pub fn insert(&mut self, address: u16) {
+        self.addresses.insert(address);
+    }
+
+    pub fn remove(&mut self, address: u16) -> bool {
+        self.addresses.remove(&address)
+    }
+
+    pub fn contains(&self, address: u16) -> bool {
+        self.addresses.contains(&address)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = u16> + '_ {
+        self.addresses.iter().copied()
+    }
+}
+
+/// Debugging API used by interactive frontends.
+impl Gameboy {
+    /// Loads the ROMs into a fresh machine and stops before the first instruction.
+    pub fn reset(&mut self) {
+        let tracer = self.cpu.tracer.take();
+        let watchpoints = std::mem::take(&mut self.cpu.bus.watchpoints);
+        let illegal_opcode_policy = self.cpu.illegal_opcode_policy;
+
+        self.cpu = Cpu::new();
+        self.cpu.tracer = tracer;
+        self.cpu.illegal_opcode_policy = illegal_opcode_policy;
+        self.cpu.bus.watchpoints = watchpoints;
+        self.cpu.boot(self.boot_rom.clone(), self.game_rom.clone());
+        if self.boot_rom.is_empty() {
+            self.cpu.reset_post_boot(self.model);
+        }
+    }
+
+    /// Executes a single instruction.
+    pub fn step(&mut self) -> StopReason {
+        let pc = self.cpu.pc;
+        let locked_up = self.cpu.locked_up;
+        if let Err(error) = self.cpu.step() {
+            return StopReason::Error(error);
+        }
+        if !locked_up
+            && self.cpu.locked_up
+            && self.cpu.illegal_opcode_policy == IllegalOpcodePolicy::Break
+        {
+            return StopReason::Lockup(pc);
+        }
+        match self.cpu.bus.watchpoints.take_hit(pc) {
+            Some(hit) => StopReason::Watchpoint(hit),
+            None => StopReason::Step,
+        }
+    }
+
+    /// Like `step`, but runs a CALL or RST until it returns to the next
+    /// instruction, or stops with `StopReason::Timeout` after
+    /// `STEP_OVER_BUDGET` T-cycles.
+    pub fn step_over(&mut self) -> StopReason {
+        let pc = self.cpu.pc;
+        let opcode = self.cpu.bus.peek(pc);
+        // CALL, CALL cc or RST n (0b11xx_x111)
+        let is_call = matches!(opcode, 0xC4 | 0xCC | 0xCD | 0xD4 | 0xDC) || opcode & 0xC7 == 0xC7;
+        if !is_call {
+            return self.step();
+        }
+
+        let return_address = pc.wrapping_add(instruction_length(opcode));
+        let end = self.cpu.cycles + STEP_OVER_BUDGET;
+        let returned = |gameboy: &Gameboy| gameboy.pc() == return_address;
+        let reason = self.run_until_stop(|gameboy| returned(gameboy) || gameboy.cycles() >= end);
+        match reason {
+            StopReason::Step if self.cpu.pc != return_address => StopReason::Timeout,
+            reason => reason,
+        }
+    }
+
+    /// Runs until a breakpoint or watchpoint is hit. The instruction at the
+    /// current PC is always executed, so resuming from a breakpoint works.
+    pub fn resume(&mut self) -> StopReason {
+        self.run_until_stop(|_| false)
+    }
+
+    fn run_until_stop<F: FnMut(&Gameboy) -> bool>(&mut self, done: F) -> StopReason {
+        match self.run_loop(false, done) {
+            Ok(result) => result.stop.unwrap_or(StopReason::Step),
+            Err(error) => StopReason::Error(error),
+        }
+    }
+
+    pub fn registers(&self) -> &Registers {
+        &self.cpu.registers
+    }
+
+    pub fn registers_mut(&mut self) -> &mut Registers {
+        &mut self.cpu.registers
+    }
+
+    pub fn pc(&self) -> u16 {
+        self.cpu.pc
+    }
+
+    /// True once the CPU has hung on an illegal opcode, until the next reset.
+    pub fn is_locked_up(&self) -> bool {
+        self.cpu.locked_up
+    }
+
+    pub fn illegal_opcode_policy(&self) -> IllegalOpcodePolicy {
+        self.cpu.illegal_opcode_policy
+    }
+
+    pub fn set_illegal_opcode_policy(&mut self, policy: IllegalOpcodePolicy) {
+        self.cpu.illegal_opcode_policy = policy;
+    }
+
+    pub fn set_pc(&mut self, pc: u16) {
+        self.cpu.pc = pc;
+    }
+
+    pub fn disassemble(&self, address: u16, count: usize) -> Vec<Disassembly> {
+        disassemble_memory(&self.cpu.bus, address, count)
+    }
+
+    /// The `before` instructions leading up to `address`, the one at
+    /// `address` and `after` more.
+    pub fn disassemble_around(
+        &self,
+        address: u16,
+        before: usize,
+        after: usize,
+    ) -> Vec<Disassembly> {
+        disassemble_around(&self.cpu.bus, address, before, after)
+    }
+
+    pub fn read_memory(&self, address: u16) -> u8 {
+        self.cpu.bus.peek(address)
+    }
+
+    pub fn write_memory(&mut self, address: u16, value: u8) {
+        self.cpu.bus.poke(address, value);
+    }
+
+    pub fn add_breakpoint(&mut self, address: u16) {
+        self.breakpoints.insert(address);
+    }
+
+    pub fn remove_breakpoint(&mut self, address: u16) -> bool {
+        self.breakpoints.remove(address)
+    }
+
+    pub fn has_breakpoint(&self, address: u16) -> bool {
+        self.breakpoints.contains(address)
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = u16> + '_ {
+        self.breakpoints.iter()
+    }
+
+    pub fn add_watchpoint(&mut self, address: u16, kind: WatchKind) {
+        self.cpu.bus.watchpoints.insert(address, kind);
+    }
+
+    pub fn remove_watchpoint(&mut self, address: u16) -> bool {
+        self.cpu.bus.watchpoints.remove(address)
+    }
+
+    pub fn watchpoints(&self) -> impl Iterator<Item = (u16, WatchKind)> + '_ {
+        self.cpu.bus.watchpoints.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gameboy(program: Vec<u8>) -> Gameboy {
+        let mut gameboy = Gameboy::new(program, vec![]);
+        gameboy.reset();
+        gameboy.set_pc(0);
+        gameboy
+    }
+
+    #[test]
+    fn test_watch_kind() {
+        let mut watchpoints = Watchpoints::default();
+        watchpoints.insert(0xC000, WatchKind::Write);
+        watchpoints.check(0xC000, Access::Read, 0x01);
+        assert_eq!(watchpoints.take_hit(0x100), None);
+
+        watchpoints.check(0xC000, Access::Write, 0x42);
+        assert_eq!(
+            watchpoints.take_hit(0x100),
+            Some(WatchHit {
+                address: 0xC000,
+                access: Access::Write,
+                value: 0x42,
+                pc: 0x100,
+            })
+        );
+        assert_eq!(watchpoints.take_hit(0x100), None);
+    }
+
+    #[test]
+    fn test_watch_keeps_first_hit() {
+        let mut watchpoints = Watchpoints::default();
+        watchpoints.insert(0xC000, WatchKind::ReadWrite);
+        watchpoints.insert(0xC001, WatchKind::ReadWrite);
+        watchpoints.check(0xC000, Access::Read, 0x01);
+        watchpoints.check(0xC001, Access::Read, 0x02);
+        assert_eq!(watchpoints.take_hit(0).unwrap().address, 0xC000);
+    }
+
+    #[test]
+    fn test_resume_stops_at_breakpoint() {
+        let mut gameboy = gameboy(vec![0x00, 0x00, 0x00, 0x00]);
+        gameboy.add_breakpoint(0x0003);
+        assert_eq!(gameboy.resume(), StopReason::Breakpoint(0x0003));
+        assert_eq!(gameboy.pc(), 0x0003);
+    }
+
+    #[test]
+    fn test_resume_stops_at_watchpoint() {
+        // LD A, 0x42; LD (0xC000), A; NOP
+        let mut gameboy = gameboy(vec![0x3E, 0x42, 0xEA, 0x00, 0xC0, 0x00]);
+        gameboy.add_watchpoint(0xC000, WatchKind::Write);
+        assert_eq!(
+            gameboy.resume(),
+            StopReason::Watchpoint(WatchHit {
+                address: 0xC000,
+                access: Access::Write,
+                value: 0x42,
+                pc: 0x0002,
+            })
+        );
+        assert_eq!(gameboy.pc(), 0x0005);
+    }
+
+    #[test]
+    fn test_step_over_call() {
+        // CALL 0x0010; NOP ... 0x0010: LD A, 0x42; RET
+        let mut program = vec![0x00; 0x13];
+        program[0x00..0x03].copy_from_slice(&[0xCD, 0x10, 0x00]);
+        program[0x10..0x13].copy_from_slice(&[0x3E, 0x42, 0xC9]);
+        let mut gameboy = gameboy(program);
+        gameboy.registers_mut().sp.set(0xFFFE);
+
+        assert_eq!(gameboy.step_over(), StopReason::Step);
+        assert_eq!(gameboy.pc(), 0x0003);
+        assert_eq!(gameboy.registers().a, 0x42);
+    }
+
+    #[test]
+    fn test_step_over_call_that_never_returns() {
+        // CALL 0x0010; NOP ... 0x0010: JR -2
+        let mut program = vec![0x00; 0x12];
+        program[0x00..0x03].copy_from_slice(&[0xCD, 0x10, 0x00]);
+        program[0x10..0x12].copy_from_slice(&[0x18, 0xFE]);
+        let mut gameboy = gameboy(program);
+        gameboy.registers_mut().sp.set(0xFFFE);
+
+        assert_eq!(gameboy.step_over(), StopReason::Timeout);
+        assert_eq!(gameboy.pc(), 0x0010);
+        assert!(gameboy.cycles() >= STEP_OVER_BUDGET);
+    }
+
+    #[test]
+    fn test_read_write_memory_does_not_trigger_watchpoints() {
+        let mut gameboy = gameboy(vec![0x00]);
+        gameboy.add_watchpoint(0xC000, WatchKind::ReadWrite);
+        gameboy.write_memory(0xC000, 0x42);
+        assert_eq!(gameboy.read_memory(0xC000), 0x42);
+        assert_eq!(gameboy.step(), StopReason::Step);
+    }
+
+    #[test]
+    fn test_resume_stops_at_error() {
+        // NOP; illegal opcode
+        let mut gameboy = gameboy(vec![0x00, 0xDD]);
+        gameboy.set_illegal_opcode_policy(IllegalOpcodePolicy::Error);
+        let reason = gameboy.resume();
+        assert_eq!(
+            reason,
+            StopReason::Error(GameboyError::IllegalOpcode {
+                opcode: 0xDD,
+                pc: 0x0001,
+                bank: 0,
+            })
+        );
+        assert_eq!(gameboy.pc(), 0x0001);
+        assert_eq!(gameboy.step(), reason);
+    }
+
+    #[test]
+    fn test_resume_stops_at_lockup() {
+        let mut gameboy = gameboy(vec![0x00, 0xDD]);
+        gameboy.set_illegal_opcode_policy(IllegalOpcodePolicy::Break);
+        assert_eq!(gameboy.resume(), StopReason::Lockup(0x0001));
+        assert!(gameboy.is_locked_up());
+
+        // Stays hung, and does not stop again
+        gameboy.add_breakpoint(0x0002);
+        assert_eq!(gameboy.step(), StopReason::Step);
+        assert_eq!(gameboy.pc(), 0x0001);
+
+        gameboy.reset();
+        assert!(!gameboy.is_locked_up());
+        assert_eq!(gameboy.illegal_opcode_policy(), IllegalOpcodePolicy::Break);
+    }
+
+    #[test]
+    fn test_lockup_ignores_breakpoint_at_pc() {
+        let mut gameboy = gameboy(vec![0x00, 0xDD]);
+        gameboy.set_illegal_opcode_policy(IllegalOpcodePolicy::Break);
+        assert_eq!(gameboy.resume(), StopReason::Lockup(0x0001));
+
+        gameboy.add_breakpoint(0x0001);
+        let result = gameboy.run_frame().unwrap();
+        assert_eq!(result.stop, None);
+        assert_eq!(gameboy.pc(), 0x0001);
+    }
+
+    #[test]
+    fn test_breakpoints() {
+        let mut breakpoints = Breakpoints::default();
+        breakpoints.insert(0x100);
+        assert!(breakpoints.contains(0x100));
+        assert!(breakpoints.remove(0x100));
+        assert!(!breakpoints.remove(0x100));
+        assert!(!breakpoints.contains(0x100));
+    }
+}
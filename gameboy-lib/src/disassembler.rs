@@ -0,0 +1,479 @@
+use std::collections::BTreeSet;
+
+use crate::{
+    bus::Bus,
+    cpu::{
+        instructions::{
+            instruction_length, ArithmeticInstruction, BitInstruction, CallInstruction,
+            FlagCondition, Instruction, JumpInstruction, LoadInstruction, MiscInstruction,
+            ReturnInstruction, RotateInstruction,
+        },
+        registers::Register,
+    },
+};
+
+/// A single decoded instruction in RGBDS syntax.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Disassembly {
+    pub address: u16,
+    pub bytes: Vec<u8>,
+    pub text: String,
+    pub target: Option<u16>, // Resolved address of a JP, JR or CALL
+}
+
+impl Disassembly {
+    pub fn length(&self) -> u16 {
+        self.bytes.len() as u16
+    }
+
+    fn render(&self, labels: &BTreeSet<u16>) -> String {
+        match self.target {
+            Some(target) if labels.contains(&target) => self
+                .text
+                .replace(&format!("${:04x}", target), &label(target)),
+            _ => self.text.clone(),
+        }
+    }
+
+    fn ends_block(&self) -> bool {
+        matches!(self.bytes[0], 0xC3 | 0x18 | 0xE9 | 0xC9 | 0xD9)
+    }
+}
+
+fn label(address: u16) -> String {
+    format!("Addr_{:04X}", address)
+}
+
+fn register(register: &Register) -> &'static str {
+    match register {
+        Register::A => "a",
+        Register::B => "b",
+        Register::C => "c",
+        Register::D => "d",
+        Register::E => "e",
+        Register::F => "f",
+        Register::H => "h",
+        Register::L => "l",
+        Register::AF => "af",
+        Register::BC => "bc",
+        Register::DE => "de",
+        Register::HL => "hl",
+        Register::SP => "sp",
+        Register::D8 => "n8",
+        Register::D16 => "n16",
+    }
+}
+
+fn condition(condition: &FlagCondition) -> &'static str {
+    match condition {
+        FlagCondition::NZ => "nz",
+        FlagCondition::Z => "z",
+        FlagCondition::NC => "nc",
+        FlagCondition::C => "c",
+    }
+}
+
+// Signed 8-bit offset like `+$05` or `-$03`
+fn offset(value: u8) -> String {
+    let value = value as i8;
+    if value < 0 {
+        format!("-${:02x}", value.unsigned_abs())
+    } else {
+        format!("+${:02x}", value)
+    }
+}
+
+struct Operands<'a> {
+    address: u16,
+    bytes: &'a [u8],
+}
+
+impl Operands<'_> {
+    fn n8(&self) -> u8 {
+        self.bytes[1]
+    }
+
+    fn n16(&self) -> u16 {
+        (self.bytes[2] as u16) << 8 | self.bytes[1] as u16
+    }
+
+    fn relative(&self) -> u16 {
+        self.address
+            .wrapping_add(2)
+            .wrapping_add(self.n8() as i8 as i16 as u16)
+    }
+
+    // 8-bit operand, where 16-bit registers stand for the byte they point to
+    fn r8(&self, from: &Register) -> String {
+        match from {
+            Register::BC | Register::DE | Register::HL => format!("[{}]", register(from)),
+            Register::D8 => format!("${:02x}", self.n8()),
+            Register::D16 => format!("[${:04x}]", self.n16()),
+            _ => register(from).to_string(),
+        }
+    }
+}
+
+fn render(instruction: &Instruction, operands: &Operands) -> (String, Option<u16>) {
+    let text = match instruction {
+        Instruction::Load(load) => match load {
+            LoadInstruction::Ld8(to, from) => {
+                format!("ld {}, {}", operands.r8(to), operands.r8(from))
+            }
+            LoadInstruction::Ld16(Register::SP, Register::D8) => {
+                format!("ld hl, sp{}", offset(operands.n8()))
+            }
+            LoadInstruction::Ld16(Register::D16, Register::SP) => {
+                format!("ld [${:04x}], sp", operands.n16())
+            }
+            LoadInstruction::Ld16(to, Register::D16) => {
+                format!("ld {}, ${:04x}", register(to), operands.n16())
+            }
+            LoadInstruction::Ld16(to, from) => format!("ld {}, {}", register(to), register(from)),
+            LoadInstruction::LdCa => "ldh [c], a".to_string(),
+            LoadInstruction::LdAc => "ldh a, [c]".to_string(),
+            LoadInstruction::LdNa => format!("ldh [$ff{:02x}], a", operands.n8()),
+            LoadInstruction::LdAn => format!("ldh a, [$ff{:02x}]", operands.n8()),
+            LoadInstruction::LdHi => "ld [hl+], a".to_string(),
+            LoadInstruction::LdHd => "ld [hl-], a".to_string(),
+            LoadInstruction::LdAHi => "ld a, [hl+]".to_string(),
+            LoadInstruction::LdAHd => "ld a, [hl-]".to_string(),
+            LoadInstruction::Push(from) => format!("push {}", register(from)),
+            LoadInstruction::Pop(to) => format!("pop {}", register(to)),
+        },
+        Instruction::Arithmetic(arithmetic) => match arithmetic {
+            ArithmeticInstruction::Add(from) => format!("add a, {}", operands.r8(from)),
+            ArithmeticInstruction::Adc(from) => format!("adc a, {}", operands.r8(from)),
+            ArithmeticInstruction::Sub(from) => format!("sub {}", operands.r8(from)),
+            ArithmeticInstruction::Sbc(from) => format!("sbc a, {}", operands.r8(from)),
+            ArithmeticInstruction::And(from) => format!("and {}", operands.r8(from)),
+            ArithmeticInstruction::Or(from) => format!("or {}", operands.r8(from)),
+            ArithmeticInstruction::Xor(from) => format!("xor {}", operands.r8(from)),
+            ArithmeticInstruction::Cp(from) => format!("cp {}", operands.r8(from)),
+            ArithmeticInstruction::Inc(to) => format!("inc {}", operands.r8(to)),
+            ArithmeticInstruction::Dec(to) => format!("dec {}", operands.r8(to)),
+            ArithmeticInstruction::Add16(from) => format!("add hl, {}", register(from)),
+            ArithmeticInstruction::Add16SP => {
+                format!("add sp, {}", offset(operands.n8()).trim_start_matches('+'))
+            }
+            ArithmeticInstruction::Inc16(to) => format!("inc {}", register(to)),
+            ArithmeticInstruction::Dec16(to) => format!("dec {}", register(to)),
+        },
+        Instruction::Misc(misc) => match misc {
+            MiscInstruction::Nop => "nop".to_string(),
+            MiscInstruction::Swap(to) => format!("swap {}", operands.r8(to)),
+            MiscInstruction::DAA => "daa".to_string(),
+            MiscInstruction::CPL => "cpl".to_string(),
+            MiscInstruction::CCF => "ccf".to_string(),
+            MiscInstruction::SCF => "scf".to_string(),
+            MiscInstruction::HALT => "halt".to_string(),
+            MiscInstruction::STOP => "stop".to_string(),
+            MiscInstruction::DI => "di".to_string(),
+            MiscInstruction::EI => "ei".to_string(),
+        },
+        Instruction::Rotate(rotate) => match rotate {
+            RotateInstruction::RLCA => "rlca".to_string(),
+            RotateInstruction::RLA => "rla".to_string(),
+            RotateInstruction::RRCA => "rrca".to_string(),
+            RotateInstruction::RRA => "rra".to_string(),
+            RotateInstruction::RLC(to) => format!("rlc {}", operands.r8(to)),
+            RotateInstruction::RL(to) => format!("rl {}", operands.r8(to)),
+            RotateInstruction::RRC(to) => format!("rrc {}", operands.r8(to)),
+            RotateInstruction::RR(to) => format!("rr {}", operands.r8(to)),
+            RotateInstruction::SLA(to) => format!("sla {}", operands.r8(to)),
+            RotateInstruction::SRA(to) => format!("sra {}", operands.r8(to)),
+            RotateInstruction::SRL(to) => format!("srl {}", operands.r8(to)),
+        },
+        Instruction::Bit(bit) => match bit {
+            BitInstruction::Bit(n, to) => format!("bit {}, {}", n, operands.r8(to)),
+            BitInstruction::Set(n, to) => format!("set {}, {}", n, operands.r8(to)),
+            BitInstruction::Res(n, to) => format!("res {}, {}", n, operands.r8(to)),
+        },
+        Instruction::Jump(jump) => {
+            return match jump {
+                JumpInstruction::Jp => {
+                    let target = operands.n16();
+                    (format!("jp ${:04x}", target), Some(target))
+                }
+                JumpInstruction::JpCond(flag) => {
+                    let target = operands.n16();
+                    (
+                        format!("jp {}, ${:04x}", condition(flag), target),
+                        Some(target),
+                    )
+                }
+                JumpInstruction::JpHL => ("jp hl".to_string(), None),
+                JumpInstruction::Jr => {
+                    let target = operands.relative();
+                    (format!("jr ${:04x}", target), Some(target))
+                }
+                JumpInstruction::JrCond(flag) => {
+                    let target = operands.relative();
+                    (
+                        format!("jr {}, ${:04x}", condition(flag), target),
+                        Some(target),
+                    )
+                }
+            }
+        }
+        Instruction::Call(call) => {
+            let target = operands.n16();
+            return match call {
+                CallInstruction::Call => (format!("call ${:04x}", target), Some(target)),
+                CallInstruction::CallCond(flag) => (
+                    format!("call {}, ${:04x}", condition(flag), target),
+                    Some(target),
+                ),
+            };
+        }
+        Instruction::Return(ret) => match ret {
+            ReturnInstruction::Rst(vector) => format!("rst ${:02x}", vector),
+            ReturnInstruction::Ret => "ret".to_string(),
+            ReturnInstruction::RetCond(flag) => format!("ret {}", condition(flag)),
+            ReturnInstruction::Reti => "reti".to_string(),
+        },
+    };
+    (text, None)
+}
+
+/// Decodes the instruction at the start of `bytes`, which is located at `address`.
+/// Illegal opcodes and instructions cut off by the end of the slice become `db`.
+pub fn disassemble_one(bytes: &[u8], address: u16) -> Disassembly {
+    let opcode = bytes[0];
+    let length = instruction_length(opcode) as usize;
+    let data_byte = || Disassembly {
+        address,
+        bytes: vec![opcode],
+        text: format!("db ${:02x}", opcode),
+        target: None,
+    };
+    if bytes.len() < length {
+        return data_byte();
+    }
+
+    let instruction = if opcode == 0xCB {
+        Instruction::from_byte(bytes[1], true)
+    } else {
+        Instruction::from_byte(opcode, false)
+    };
+    let Some(instruction) = instruction else {
+        return data_byte();
+    };
+
+    let operands = Operands {
+        address,
+        bytes: &bytes[..length],
+    };
+    let (text, target) = render(&instruction, &operands);
+    Disassembly {
+        address,
+        bytes: bytes[..length].to_vec(),
+        text,
+        target,
+    }
+}
+
+/// Linearly disassembles `bytes`, which are loaded at `origin`.
+pub fn disassemble(bytes: &[u8], origin: u16) -> Vec<Disassembly> {
+    let mut result = Vec::new();
+    let mut offset = 0;
+    while offset < bytes.len() {
+        let instruction = disassemble_one(&bytes[offset..], origin.wrapping_add(offset as u16));
+        offset += instruction.bytes.len();
+        result.push(instruction);
+    }
+    result
+}
+
+/// Disassembles `count` instructions starting at `address`, reading with
+/// `Bus::peek` so nothing is ticked or triggered.
+pub fn disassemble_memory(bus: &impl Bus, address: u16, count: usize) -> Vec<Disassembly> {
+    let mut result = Vec::with_capacity(count);
+    let mut address = address;
+    for _ in 0..count {
+        let bytes: Vec<u8> = (0..3).map(|i| bus.peek(address.wrapping_add(i))).collect();
+        let instruction = disassemble_one(&bytes, address);
+        address = address.wrapping_add(instruction.length());
+        result.push(instruction);
+    }
+    result
+}
+
+/// Disassembles the `before` instructions leading up to `address`, the one at
+/// `address` and `after` more. Instruction lengths vary, so decoding starts
+/// at the farthest offset that lines up with `address`, without one the
+/// listing starts at `address`.
+pub fn disassemble_around(
+    bus: &impl Bus,
+    address: u16,
+    before: usize,
+    after: usize,
+) -> Vec<Disassembly> {
+    let count = before * 3 + after + 1;
+    for offset in (1..=before as u16 * 3).rev() {
+        let instructions = disassemble_memory(bus, address.wrapping_sub(offset), count);
+        let Some(index) = instructions.iter().position(|i| i.address == address) else {
+            continue;
+        };
+        if index >= before {
+            return instructions[index - before..index + after + 1].to_vec();
+        }
+    }
+    disassemble_memory(bus, address, after + 1)
+}
+
+/// Formats instructions like `DMG_ROM.asm`: jump targets get `Addr_XXXX` labels
+/// and every line is annotated with its address.
+pub fn format_listing(instructions: &[Disassembly]) -> String {
+    let addresses: BTreeSet<u16> = instructions.iter().map(|i| i.address).collect();
+    let labels: BTreeSet<u16> = instructions
+        .iter()
+        .filter_map(|i| i.target)
+        .filter(|target| addresses.contains(target))
+        .collect();
+
+    let mut listing = String::new();
+    for instruction in instructions {
+        if labels.contains(&instruction.address) {
+            listing.push_str(&format!("{}:\n", label(instruction.address)));
+        }
+        let text = instruction.render(&labels);
+        // Align the address comments on column 32 with a tab width of 8
+        let tabs = 32usize.saturating_sub(8 + text.len()).div_ceil(8).max(1);
+        listing.push_str(&format!(
+            "\t{}{}; ${:04x}\n",
+            text,
+            "\t".repeat(tabs),
+            instruction.address
+        ));
+        if instruction.ends_block() {
+            listing.push('\n');
+        }
+    }
+    listing
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bus::FlatRam, memory::Memory};
+
+    fn text(bytes: &[u8]) -> String {
+        disassemble_one(bytes, 0x0000).text
+    }
+
+    #[test]
+    fn test_loads() {
+        assert_eq!(text(&[0x31, 0xFE, 0xFF]), "ld sp, $fffe");
+        assert_eq!(text(&[0x32]), "ld [hl-], a");
+        assert_eq!(text(&[0x3A]), "ld a, [hl-]");
+        assert_eq!(text(&[0x22]), "ld [hl+], a");
+        assert_eq!(text(&[0x2A]), "ld a, [hl+]");
+        assert_eq!(text(&[0x0E, 0x11]), "ld c, $11");
+        assert_eq!(text(&[0xE2]), "ldh [c], a");
+        assert_eq!(text(&[0xF2]), "ldh a, [c]");
+        assert_eq!(text(&[0xE0, 0x47]), "ldh [$ff47], a");
+        assert_eq!(text(&[0xF0, 0x44]), "ldh a, [$ff44]");
+        assert_eq!(text(&[0x1A]), "ld a, [de]");
+        assert_eq!(text(&[0xEA, 0x10, 0x99]), "ld [$9910], a");
+        assert_eq!(text(&[0x36, 0x42]), "ld [hl], $42");
+        assert_eq!(text(&[0x08, 0x00, 0xC0]), "ld [$c000], sp");
+        assert_eq!(text(&[0xF8, 0x05]), "ld hl, sp+$05");
+        assert_eq!(text(&[0xF8, 0xFD]), "ld hl, sp-$03");
+        assert_eq!(text(&[0xF5]), "push af");
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        assert_eq!(text(&[0xAF]), "xor a");
+        assert_eq!(text(&[0xFE, 0x34]), "cp $34");
+        assert_eq!(text(&[0x86]), "add a, [hl]");
+        assert_eq!(text(&[0x34]), "inc [hl]");
+        assert_eq!(text(&[0x13]), "inc de");
+        assert_eq!(text(&[0x29]), "add hl, hl");
+        assert_eq!(text(&[0xE8, 0xFE]), "add sp, -$02");
+    }
+
+    #[test]
+    fn test_prefixed() {
+        assert_eq!(text(&[0xCB, 0x7C]), "bit 7, h");
+        assert_eq!(text(&[0xCB, 0x47]), "bit 0, a");
+        assert_eq!(text(&[0xCB, 0x4F]), "bit 1, a");
+        assert_eq!(text(&[0xCB, 0x11]), "rl c");
+        assert_eq!(text(&[0xCB, 0x86]), "res 0, [hl]");
+        assert_eq!(text(&[0xCB, 0x37]), "swap a");
+    }
+
+    #[test]
+    fn test_jumps() {
+        let jr = disassemble_one(&[0x20, 0xFB], 0x000A);
+        assert_eq!(jr.text, "jr nz, $0007");
+        assert_eq!(jr.target, Some(0x0007));
+        assert_eq!(jr.length(), 2);
+
+        assert_eq!(text(&[0xCD, 0x95, 0x00]), "call $0095");
+        assert_eq!(text(&[0xC3, 0x50, 0x01]), "jp $0150");
+        assert_eq!(text(&[0xE9]), "jp hl");
+        assert_eq!(text(&[0xFF]), "rst $38");
+        assert_eq!(text(&[0xD8]), "ret c");
+    }
+
+    #[test]
+    fn test_data_bytes() {
+        assert_eq!(text(&[0xD3]), "db $d3");
+        let truncated = disassemble_one(&[0xC3, 0x50], 0x0000);
+        assert_eq!(truncated.text, "db $c3");
+        assert_eq!(truncated.length(), 1);
+    }
+
+    #[test]
+    fn test_disassemble_memory() {
+        let mut memory = Memory::new();
+        memory.write_vec(0x0100, vec![0x00, 0xC3, 0x50, 0x01]);
+        let instructions = disassemble_memory(&memory, 0x0100, 2);
+        assert_eq!(instructions[0].text, "nop");
+        assert_eq!(instructions[1].address, 0x0101);
+        assert_eq!(instructions[1].text, "jp $0150");
+
+        let mut ram = FlatRam::new();
+        ram.load(0xFFFF, &[0x3E, 0x42]);
+        let instructions = disassemble_memory(&ram, 0xFFFF, 1);
+        assert_eq!(instructions[0].text, "ld a, $42");
+    }
+
+    #[test]
+    fn test_disassemble_around() {
+        let mut ram = FlatRam::new();
+        // LD A, 1; LD BC, 0x1234; NOP; LD A, 2 at 0x100
+        ram.load(0x100, &[0x3E, 0x01, 0x01, 0x34, 0x12, 0x00, 0x3E, 0x02]);
+        let instructions = disassemble_around(&ram, 0x106, 2, 1);
+        let addresses: Vec<u16> = instructions.iter().map(|i| i.address).collect();
+        assert_eq!(addresses, [0x102, 0x105, 0x106, 0x108]);
+
+        // Nothing to go back to, the listing starts at the address
+        let addresses: Vec<u16> = disassemble_around(&ram, 0x0000, 0, 1)
+            .iter()
+            .map(|i| i.address)
+            .collect();
+        assert_eq!(addresses, [0x0000, 0x0001]);
+    }
+
+    #[test]
+    fn test_format_listing() {
+        // Start of the DMG boot ROM
+        let rom = [
+            0x31, 0xFE, 0xFF, 0xAF, 0x21, 0xFF, 0x9F, 0x32, 0xCB, 0x7C, 0x20, 0xFB,
+        ];
+        let listing = format_listing(&disassemble(&rom, 0x0000));
+        assert_eq!(
+            listing,
+            "\tld sp, $fffe\t\t; $0000\n\
+             \txor a\t\t\t; $0003\n\
+             \tld hl, $9fff\t\t; $0004\n\
+             Addr_0007:\n\
+             \tld [hl-], a\t\t; $0007\n\
+             \tbit 7, h\t\t; $0008\n\
+             \tjr nz, Addr_0007\t; $000a\n"
+        );
+    }
+}
@@ -0,0 +1,140 @@
+use std::{error::Error, fmt, str::FromStr};
+
+/// Opcodes that do not decode to an instruction. Executing one locks up real
+/// hardware.
+pub const ILLEGAL_OPCODES: [u8; 11] = [
+    0xD3, 0xDB, 0xDD, 0xE3, 0xE4, 0xEB, 0xEC, 0xED, 0xF4, 0xFC, 0xFD,
+];
+
+/// What the CPU does when it fetches one of the `ILLEGAL_OPCODES`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum IllegalOpcodePolicy {
+    /// Hang like hardware: the CPU stops fetching until the next reset while
+    /// the PPU and timers keep running.
+    #[default]
+    Lockup,
+    /// Lock up, and have the debugging API stop with `StopReason::Lockup`.
+    Break,
+    /// Return `GameboyError::IllegalOpcode` without executing anything.
+    Error,
+}
+
+pub const ILLEGAL_OPCODE_POLICIES: [IllegalOpcodePolicy; 3] = [
+    IllegalOpcodePolicy::Lockup,
+    IllegalOpcodePolicy::Break,
+    IllegalOpcodePolicy::Error,
+];
+
+impl IllegalOpcodePolicy {
+    pub fn name(&self) -> &'static str {
+        match self {
+            IllegalOpcodePolicy::Lockup => "lockup",
+            IllegalOpcodePolicy::Break => "break",
+            IllegalOpcodePolicy::Error => "error",
+        }
+    }
+}
+
+impl FromStr for IllegalOpcodePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<IllegalOpcodePolicy, String> {
+        ILLEGAL_OPCODE_POLICIES
+            .iter()
+            .find(|policy| policy.name().eq_ignore_ascii_case(s))
+            .copied()
+            .ok_or_else(|| format!("Unknown illegal opcode policy: {}", s))
+    }
+}
+
+/// Why the emulator had to stop. `pc` is the address of the instruction that
+/// failed and `bank` the ROM bank mapped there, in the `bank:pc` form used by
+/// symbol files.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GameboyError {
+    IllegalOpcode {
+        opcode: u8,
+        pc: u16,
+        bank: u16,
+    },
+    /// An instruction the emulator does not support yet.
+    Unimplemented {
+        instruction: String,
+        pc: u16,
+        bank: u16,
+    },
+    /// An instruction was decoded with operands its implementation does not
+    /// handle, which is an emulator bug.
+    InvalidOperand {
+        instruction: String,
+        pc: u16,
+        bank: u16,
+    },
+}
+
+impl GameboyError {
+    pub fn pc(&self) -> u16 {
+        match self {
+            GameboyError::IllegalOpcode { pc, .. }
+            | GameboyError::Unimplemented { pc, .. }
+            | GameboyError::InvalidOperand { pc, .. } => *pc,
+        }
+    }
+
+    pub fn bank(&self) -> u16 {
+        match self {
+            GameboyError::IllegalOpcode { bank, .. }
+            | GameboyError::Unimplemented { bank, .. }
+            | GameboyError::InvalidOperand { bank, .. } => *bank,
+        }
+    }
+}
+
+impl fmt::Display for GameboyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GameboyError::IllegalOpcode { opcode, .. } => {
+                write!(f, "Illegal opcode ${:02X}", opcode)?
+            }
+            GameboyError::Unimplemented { instruction, .. } => {
+                write!(f, "Unimplemented instruction `{}`", instruction)?
+            }
+            GameboyError::InvalidOperand { instruction, .. } => {
+                write!(f, "Invalid operand in `{}`", instruction)?
+            }
+        }
+        write!(f, " at {:02X}:{:04X}", self.bank(), self.pc())
+    }
+}
+
+impl Error for GameboyError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_policy() {
+        assert_eq!("Break".parse(), Ok(IllegalOpcodePolicy::Break));
+        assert!("ignore".parse::<IllegalOpcodePolicy>().is_err());
+    }
+
+    #[test]
+    fn test_display() {
+        let error = GameboyError::IllegalOpcode {
+            opcode: 0xDD,
+            pc: 0x4A2F,
+            bank: 1,
+        };
+        assert_eq!(error.to_string(), "Illegal opcode $DD at 01:4A2F");
+        let error = GameboyError::Unimplemented {
+            instruction: "halt".to_string(),
+            pc: 0x150,
+            bank: 0,
+        };
+        assert_eq!(
+            error.to_string(),
+            "Unimplemented instruction `halt` at 00:0150"
+        );
+    }
+}
@@ -0,0 +1,131 @@
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Button {
+    Right,
+    Left,
+    Up,
+    Down,
+    A,
+    B,
+    Select,
+    Start,
+}
+
+pub const BUTTONS: [Button; 8] = [
+    Button::Right,
+    Button::Left,
+    Button::Up,
+    Button::Down,
+    Button::A,
+    Button::B,
+    Button::Select,
+    Button::Start,
+];
+
+impl Button {
+    // Bit in `JoypadState`, the low nibble is the d-pad and the high nibble the
+    // action buttons, both in the order the JOYP register reports them
+    fn mask(&self) -> u8 {
+        1 << *self as u8
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Button::Right => "right",
+            Button::Left => "left",
+            Button::Up => "up",
+            Button::Down => "down",
+            Button::A => "a",
+            Button::B => "b",
+            Button::Select => "select",
+            Button::Start => "start",
+        }
+    }
+}
+
+impl FromStr for Button {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Button, String> {
+        BUTTONS
+            .iter()
+            .find(|button| button.name().eq_ignore_ascii_case(s))
+            .copied()
+            .ok_or_else(|| format!("Unknown button: {}", s))
+    }
+}
+
+/// The set of buttons held down, one bit per button.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct JoypadState {
+    pressed: u8,
+}
+
+impl JoypadState {
+    pub fn from_bits(bits: u8) -> JoypadState {
+        JoypadState { pressed: bits }
+    }
+
+    pub fn bits(&self) -> u8 {
+        self.pressed
+    }
+
+    pub fn press(&mut self, button: Button) {
+        self.pressed |= button.mask();
+    }
+
+    pub fn release(&mut self, button: Button) {
+        self.pressed &= !button.mask();
+    }
+
+    pub fn is_pressed(&self, button: Button) -> bool {
+        self.pressed & button.mask() != 0
+    }
+
+    /// Low nibble of JOYP for the given select bits. Bit 4 low selects the
+    /// d-pad, bit 5 low the action buttons, pressed buttons read as 0.
+    pub fn read(&self, select: u8) -> u8 {
+        let mut lines = 0;
+        if select & 0x10 == 0 {
+            lines |= self.pressed & 0x0F;
+        }
+        if select & 0x20 == 0 {
+            lines |= self.pressed >> 4;
+        }
+        !lines & 0x0F
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_press_release() {
+        let mut joypad = JoypadState::default();
+        joypad.press(Button::Start);
+        joypad.press(Button::Left);
+        assert!(joypad.is_pressed(Button::Start));
+        assert_eq!(joypad.bits(), 0b1000_0010);
+        joypad.release(Button::Start);
+        assert!(!joypad.is_pressed(Button::Start));
+    }
+
+    #[test]
+    fn test_read() {
+        let mut joypad = JoypadState::default();
+        joypad.press(Button::Down);
+        joypad.press(Button::A);
+        assert_eq!(joypad.read(0x30), 0x0F);
+        assert_eq!(joypad.read(0x20), 0b0111);
+        assert_eq!(joypad.read(0x10), 0b1110);
+        assert_eq!(joypad.read(0x00), 0b0110);
+    }
+
+    #[test]
+    fn test_parse_button() {
+        assert_eq!("Start".parse(), Ok(Button::Start));
+        assert!("turbo".parse::<Button>().is_err());
+    }
+}
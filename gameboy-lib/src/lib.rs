@@ -1,10 +1,47 @@
+#[macro_use]
+pub mod log;
+
+pub mod bus;
 pub mod cpu;
+pub mod debugger;
+pub mod disassembler;
+pub mod error;
+pub mod image;
+pub mod joypad;
 pub mod memory;
+pub mod model;
+pub mod movie;
+pub mod ppu;
+pub mod rewind;
+pub mod save_state;
+pub mod test_rom;
+
+use debugger::StopReason;
+use error::GameboyError;
+use joypad::JoypadState;
+use model::Model;
+use ppu::Palette;
+
+/// T-cycles per frame, the LCD refreshes at 4194304 / 70224 = 59.7275 Hz.
+pub const CYCLES_PER_FRAME: u64 = 70224;
+
+/// What happened during a `run_frame`, `run_cycles` or `run_until` call.
+/// There is no APU yet, so a full audio buffer never stops a run, that
+/// event gets a field here once sound is emulated.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RunResult {
+    pub cycles: u64,              // T-cycles executed
+    pub frame_ready: bool,        // VBlank started, the framebuffer holds a new frame
+    pub stop: Option<StopReason>, // Stopped early at a breakpoint, watchpoint or lockup
+}
 
 pub struct Gameboy {
     cpu: cpu::Cpu,
     boot_rom: Vec<u8>,
     game_rom: Vec<u8>,
+    breakpoints: debugger::Breakpoints,
+    rewind: Option<rewind::Rewind>,
+    model: Model,
 }
 
 impl Gameboy {
@@ -13,17 +50,114 @@ impl Gameboy {
             cpu: cpu::Cpu::new(),
             boot_rom,
             game_rom,
+            breakpoints: debugger::Breakpoints::default(),
+            rewind: None,
+            model: Model::default(),
         }
     }
 
-    pub fn start(&mut self) {
-        println!("Starting Gameboy");
-        self.cpu.boot(self.boot_rom.clone(), self.game_rom.clone());
-        self.cpu.run();
+    pub fn model(&self) -> Model {
+        self.model
+    }
+
+    /// Takes effect on the next `reset` without a boot ROM.
+    pub fn set_model(&mut self, model: Model) {
+        self.model = model;
+    }
+
+    /// Runs until the next VBlank, when the framebuffer holds a complete
+    /// frame. While the LCD is off there is no VBlank, so at most one
+    /// frame's worth of cycles is run. On error the frame is left
+    /// unfinished at the offending instruction.
+    pub fn run_frame(&mut self) -> Result<RunResult, GameboyError> {
+        let end = self.cpu.cycles + CYCLES_PER_FRAME;
+        let result = self.run_loop(true, |gameboy| gameboy.cycles() >= end)?;
+        if result.stop.is_none() {
+            self.capture_rewind_frame();
+        }
+        Ok(result)
+    }
+
+    /// Runs at least `cycles` T-cycles. The last instruction may overshoot,
+    /// `RunResult::cycles` has the exact count.
+    pub fn run_cycles(&mut self, cycles: u64) -> Result<RunResult, GameboyError> {
+        let end = self.cpu.cycles + cycles;
+        self.run_loop(false, |gameboy| gameboy.cycles() >= end)
+    }
+
+    /// Runs until `done` returns true, checked after every instruction.
+    pub fn run_until<F: FnMut(&Gameboy) -> bool>(
+        &mut self,
+        done: F,
+    ) -> Result<RunResult, GameboyError> {
+        self.run_loop(false, done)
+    }
+
+    // Shared by the `run_*` methods and the debugger. Always executes at
+    // least one instruction, so resuming from a breakpoint works.
+    pub(crate) fn run_loop<F: FnMut(&Gameboy) -> bool>(
+        &mut self,
+        until_frame: bool,
+        mut done: F,
+    ) -> Result<RunResult, GameboyError> {
+        let start = self.cpu.cycles;
+        let mut result = RunResult::default();
+        loop {
+            let reason = self.step();
+            result.cycles = self.cpu.cycles - start;
+            result.frame_ready |= self.cpu.bus.ppu.take_frame_ready();
+            match reason {
+                StopReason::Step => {}
+                StopReason::Error(error) => return Err(error),
+                reason => {
+                    result.stop = Some(reason);
+                    return Ok(result);
+                }
+            }
+            // A locked up CPU never leaves PC, so a breakpoint there would
+            // stop every step
+            if !self.cpu.locked_up && self.breakpoints.contains(self.cpu.pc) {
+                result.stop = Some(StopReason::Breakpoint(self.cpu.pc));
+                return Ok(result);
+            }
+            if (until_frame && result.frame_ready) || done(self) {
+                return Ok(result);
+            }
+        }
+    }
+
+    /// Frames worth of cycles run since power on.
+    pub fn frame(&self) -> u64 {
+        self.cpu.cycles / CYCLES_PER_FRAME
+    }
+
+    pub fn cycles(&self) -> u64 {
+        self.cpu.cycles
+    }
+
+    pub fn joypad(&self) -> JoypadState {
+        self.cpu.bus.joypad
+    }
+
+    pub fn set_joypad(&mut self, state: JoypadState) {
+        self.cpu.bus.joypad = state;
+    }
+
+    /// Shades 0-3 of the last rendered frame, `ppu::SCREEN_WIDTH` pixels per row.
+    pub fn framebuffer(&self) -> &[u8] {
+        self.cpu.bus.ppu.framebuffer()
+    }
+
+    pub fn framebuffer_rgb(&self, palette: &Palette) -> Vec<u8> {
+        ppu::to_rgb(self.framebuffer(), palette)
+    }
+
+    pub fn set_tracer(&mut self, tracer: cpu::tracer::Tracer) {
+        self.cpu.tracer = Some(tracer);
     }
 
     pub fn dump_memory(&self) -> Vec<u8> {
-        self.cpu.memory.dump()
+        self.cpu.bus.dump()
         // let _ = File::create("memory.bin").unwrap();
         // fs::write("memory.bin", &buffer).unwrap();
     }
@@ -31,5 +165,63 @@ impl Gameboy {
 
 #[cfg(test)]
 mod tests {
+    use super::*;
 
+    // Turns the LCD on with `lcdc` and counts in B forever
+    fn gameboy(lcdc: u8) -> Gameboy {
+        let mut rom = vec![0x00; 0x200];
+        rom[0x100..0x107].copy_from_slice(&[
+            0x3E, lcdc, // LD A, lcdc
+            0xE0, 0x40, // LDH (LCDC), A
+            0x04, // INC B
+            0x18, 0xFD, // JR -3
+        ]);
+        let mut gameboy = Gameboy::new(vec![], rom);
+        gameboy.reset();
+        gameboy
+    }
+
+    #[test]
+    fn test_run_frame_stops_at_vblank() {
+        let mut gameboy = gameboy(0x80);
+        let first = gameboy.run_frame().unwrap();
+        assert!(first.frame_ready);
+        assert_eq!(first.stop, None);
+        assert_eq!(gameboy.read_memory(0xFF44), 144);
+
+        let second = gameboy.run_frame().unwrap();
+        assert!(second.frame_ready);
+        assert!(second.cycles.abs_diff(CYCLES_PER_FRAME) < 16);
+    }
+
+    #[test]
+    fn test_run_frame_with_lcd_off() {
+        let mut gameboy = gameboy(0x00);
+        let result = gameboy.run_frame().unwrap();
+        assert!(!result.frame_ready);
+        assert!(result.cycles >= CYCLES_PER_FRAME);
+    }
+
+    #[test]
+    fn test_run_cycles_and_until() {
+        let mut gameboy = gameboy(0x00);
+        let result = gameboy.run_cycles(1000).unwrap();
+        assert!((1000..1016).contains(&result.cycles));
+        assert_eq!(gameboy.cycles(), result.cycles);
+
+        let result = gameboy
+            .run_until(|gameboy| gameboy.registers().b == 200)
+            .unwrap();
+        assert_eq!(gameboy.registers().b, 200);
+        assert_eq!(result.stop, None);
+    }
+
+    #[test]
+    fn test_run_stops_at_breakpoint() {
+        let mut gameboy = gameboy(0x80);
+        gameboy.add_breakpoint(0x0104);
+        let result = gameboy.run_frame().unwrap();
+        assert_eq!(result.stop, Some(StopReason::Breakpoint(0x0104)));
+        assert_eq!(result.cycles, 20);
+    }
 }
@@ -0,0 +1,272 @@
+use std::{
+    fmt,
+    io::Write,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        Mutex,
+    },
+};
+
+/// Whether the `log!` macro emits anything at all. Without the `logging`
+/// feature every call site is compiled out.
+pub const ENABLED: bool = cfg!(feature = "logging");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Error = 1,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    Cpu,
+    Mem,
+    Reg,
+    Ppu,
+    Apu,
+    Timer,
+    Io,
+    System,
+}
+
+pub const TARGETS: [Target; 8] = [
+    Target::Cpu,
+    Target::Mem,
+    Target::Reg,
+    Target::Ppu,
+    Target::Apu,
+    Target::Timer,
+    Target::Io,
+    Target::System,
+];
+
+impl Target {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Target::Cpu => "CPU",
+            Target::Mem => "MEM",
+            Target::Reg => "REG",
+            Target::Ppu => "PPU",
+            Target::Apu => "APU",
+            Target::Timer => "TIMER",
+            Target::Io => "IO",
+            Target::System => "SYS",
+        }
+    }
+}
+
+impl FromStr for Target {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        TARGETS
+            .iter()
+            .find(|target| target.name().eq_ignore_ascii_case(s))
+            .copied()
+            .ok_or_else(|| format!("Unknown log target: {}", s))
+    }
+}
+
+impl FromStr for Level {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "error" => Ok(Level::Error),
+            "warn" => Ok(Level::Warn),
+            "info" => Ok(Level::Info),
+            "debug" => Ok(Level::Debug),
+            "trace" => Ok(Level::Trace),
+            _ => Err(format!("Unknown log level: {}", s)),
+        }
+    }
+}
+
+pub struct Record<'a> {
+    pub target: Target,
+    pub level: Level,
+    pub args: fmt::Arguments<'a>,
+}
+
+/// Receives every record that passes the level filter of its target.
+pub trait Sink: Send {
+    fn log(&mut self, record: &Record);
+}
+
+impl<F: FnMut(&Record) + Send> Sink for F {
+    fn log(&mut self, record: &Record) {
+        self(record)
+    }
+}
+
+/// Writes records as `[CPU] message` lines.
+pub struct WriteSink<W: Write + Send> {
+    writer: W,
+}
+
+impl<W: Write + Send> WriteSink<W> {
+    pub fn new(writer: W) -> WriteSink<W> {
+        WriteSink { writer }
+    }
+}
+
+impl<W: Write + Send> Sink for WriteSink<W> {
+    fn log(&mut self, record: &Record) {
+        let _ = writeln!(self.writer, "[{}] {}", record.target.name(), record.args);
+    }
+}
+
+const OFF: u8 = 0;
+static LEVELS: [AtomicU8; TARGETS.len()] = [const { AtomicU8::new(OFF) }; TARGETS.len()];
+static SINK: Mutex<Option<Box<dyn Sink>>> = Mutex::new(None);
+
+pub fn set_sink(sink: Box<dyn Sink>) {
+    *SINK.lock().unwrap() = Some(sink);
+}
+
+pub fn clear_sink() {
+    *SINK.lock().unwrap() = None;
+}
+
+/// Sets the most verbose level emitted for a target, `None` turns it off.
+pub fn set_level(target: Target, level: Option<Level>) {
+    let value = level.map_or(OFF, |level| level as u8);
+    LEVELS[target as usize].store(value, Ordering::Relaxed);
+}
+
+pub fn set_all_levels(level: Option<Level>) {
+    for target in TARGETS {
+        set_level(target, level);
+    }
+}
+
+/// Applies a filter like `debug` or `cpu=trace,mem=off` to the target levels.
+pub fn configure(spec: &str) -> Result<(), String> {
+    for directive in spec.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+        match directive.split_once('=') {
+            Some((target, level)) => set_level(target.parse()?, parse_filter(level)?),
+            None => set_all_levels(parse_filter(directive)?),
+        }
+    }
+    Ok(())
+}
+
+fn parse_filter(s: &str) -> Result<Option<Level>, String> {
+    if s.eq_ignore_ascii_case("off") {
+        Ok(None)
+    } else {
+        s.parse().map(Some)
+    }
+}
+
+pub fn enabled(target: Target, level: Level) -> bool {
+    level as u8 <= LEVELS[target as usize].load(Ordering::Relaxed)
+}
+
+pub fn log(target: Target, level: Level, args: fmt::Arguments) {
+    if let Some(sink) = SINK.lock().unwrap().as_mut() {
+        sink.log(&Record {
+            target,
+            level,
+            args,
+        });
+    }
+}
+
+/// Logs to a subsystem target, e.g. `log!(Cpu, Debug, "PC: 0x{:x}", pc)`.
+macro_rules! log {
+    ($target:ident, $level:ident, $($arg:tt)+) => {
+        if $crate::log::ENABLED
+            && $crate::log::enabled($crate::log::Target::$target, $crate::log::Level::$level)
+        {
+            $crate::log::log(
+                $crate::log::Target::$target,
+                $crate::log::Level::$level,
+                format_args!($($arg)+),
+            );
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    /// Puts the global levels and sink back when dropped, so a test that
+    /// configures logging doesn't leak its setup into tests running in
+    /// parallel, even if it panics.
+    struct Restore {
+        levels: Vec<u8>,
+        sink: Option<Box<dyn Sink>>,
+    }
+
+    impl Restore {
+        fn save() -> Restore {
+            Restore {
+                levels: LEVELS
+                    .iter()
+                    .map(|level| level.load(Ordering::Relaxed))
+                    .collect(),
+                sink: SINK.lock().unwrap().take(),
+            }
+        }
+    }
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            for (level, value) in LEVELS.iter().zip(&self.levels) {
+                level.store(*value, Ordering::Relaxed);
+            }
+            *SINK.lock().unwrap() = self.sink.take();
+        }
+    }
+
+    #[test]
+    fn test_parse_target() {
+        assert_eq!("cpu".parse::<Target>(), Ok(Target::Cpu));
+        assert_eq!("MEM".parse::<Target>(), Ok(Target::Mem));
+        assert!("gpu".parse::<Target>().is_err());
+    }
+
+    #[test]
+    fn test_parse_level() {
+        assert_eq!("trace".parse::<Level>(), Ok(Level::Trace));
+        assert_eq!("Warn".parse::<Level>(), Ok(Level::Warn));
+        assert!("verbose".parse::<Level>().is_err());
+    }
+
+    #[test]
+    fn test_configure_and_route_to_sink() {
+        let _restore = Restore::save();
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let captured = lines.clone();
+        set_sink(Box::new(move |record: &Record| {
+            if record.target != Target::Ppu {
+                return;
+            }
+            captured.lock().unwrap().push(format!(
+                "{} {:?} {}",
+                record.target.name(),
+                record.level,
+                record.args
+            ));
+        }));
+
+        configure("info,ppu=trace,apu=off").unwrap();
+        assert!(enabled(Target::Timer, Level::Info));
+        assert!(!enabled(Target::Timer, Level::Debug));
+        assert!(enabled(Target::Ppu, Level::Trace));
+        assert!(!enabled(Target::Apu, Level::Error));
+        assert!(configure("ppu=loud").is_err());
+
+        log(Target::Ppu, Level::Trace, format_args!("LY {}", 0x90));
+
+        assert_eq!(*lines.lock().unwrap(), vec!["PPU Trace LY 144"]);
+    }
+}
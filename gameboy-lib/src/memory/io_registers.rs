@@ -0,0 +1,163 @@
+use super::IO_REGISTERS_SIZE;
+
+pub const JOYP: u16 = 0xFF00; // Joypad
+pub const SB: u16 = 0xFF01; // Serial transfer data
+pub const SC: u16 = 0xFF02; // Serial transfer control
+pub const DIV: u16 = 0xFF04; // Divider register
+pub const TIMA: u16 = 0xFF05; // Timer counter
+pub const TMA: u16 = 0xFF06; // Timer modulo
+pub const TAC: u16 = 0xFF07; // Timer control
+pub const IF: u16 = 0xFF0F; // Interrupt flag
+pub const NR52: u16 = 0xFF26; // Sound on/off
+pub const LCDC: u16 = 0xFF40; // LCD control
+pub const STAT: u16 = 0xFF41; // LCD status
+pub const SCY: u16 = 0xFF42; // Background scroll Y
+pub const SCX: u16 = 0xFF43; // Background scroll X
+pub const LY: u16 = 0xFF44; // LCD Y coordinate
+pub const LYC: u16 = 0xFF45; // LY compare
+pub const DMA: u16 = 0xFF46; // OAM DMA source and start
+pub const BGP: u16 = 0xFF47; // Background palette
+pub const OBP0: u16 = 0xFF48; // Object palette 0
+pub const OBP1: u16 = 0xFF49; // Object palette 1
+pub const WY: u16 = 0xFF4A; // Window Y position
+pub const WX: u16 = 0xFF4B; // Window X position plus 7
+pub const BOOT: u16 = 0xFF50; // Boot ROM disable
+
+/// Describes how a single IO register behaves on the bus.
+///
+/// `unused` holds the bits that always read back as 1, `writable` the bits a
+/// CPU write is able to change. Everything else is either read-only or driven
+/// by the hardware behind the register.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IoRegisterMask {
+    pub unused: u8,
+    pub writable: u8,
+}
+
+impl IoRegisterMask {
+    const fn new(unused: u8, writable: u8) -> IoRegisterMask {
+        IoRegisterMask { unused, writable }
+    }
+
+    pub fn read(&self, stored: u8) -> u8 {
+        stored | self.unused
+    }
+
+    pub fn write(&self, stored: u8, value: u8) -> u8 {
+        (stored & !self.writable) | (value & self.writable)
+    }
+}
+
+const READ_WRITE: IoRegisterMask = IoRegisterMask::new(0x00, 0xFF);
+const WRITE_ONLY: IoRegisterMask = IoRegisterMask::new(0xFF, 0xFF);
+const UNMAPPED: IoRegisterMask = IoRegisterMask::new(0xFF, 0x00);
+
+/// Read/write masks for 0xFF00 - 0xFF7F on a DMG.
+pub const IO_REGISTER_MASKS: [IoRegisterMask; IO_REGISTERS_SIZE] = io_register_masks();
+
+const fn io_register_masks() -> [IoRegisterMask; IO_REGISTERS_SIZE] {
+    let mut masks = [UNMAPPED; IO_REGISTERS_SIZE];
+
+    // Joypad: the input lines read 1 (released) until something drives them
+    masks[0x00] = IoRegisterMask::new(0xCF, 0x30);
+
+    // Serial
+    masks[0x01] = READ_WRITE;
+    masks[0x02] = IoRegisterMask::new(0x7E, 0x81);
+
+    // Timer
+    masks[0x04] = READ_WRITE;
+    masks[0x05] = READ_WRITE;
+    masks[0x06] = READ_WRITE;
+    masks[0x07] = IoRegisterMask::new(0xF8, 0x07);
+
+    // Interrupt flag
+    masks[0x0F] = IoRegisterMask::new(0xE0, 0x1F);
+
+    // Sound channel 1
+    masks[0x10] = IoRegisterMask::new(0x80, 0x7F);
+    masks[0x11] = IoRegisterMask::new(0x3F, 0xFF);
+    masks[0x12] = READ_WRITE;
+    masks[0x13] = WRITE_ONLY;
+    masks[0x14] = IoRegisterMask::new(0xBF, 0xC7);
+
+    // Sound channel 2
+    masks[0x16] = IoRegisterMask::new(0x3F, 0xFF);
+    masks[0x17] = READ_WRITE;
+    masks[0x18] = WRITE_ONLY;
+    masks[0x19] = IoRegisterMask::new(0xBF, 0xC7);
+
+    // Sound channel 3
+    masks[0x1A] = IoRegisterMask::new(0x7F, 0x80);
+    masks[0x1B] = WRITE_ONLY;
+    masks[0x1C] = IoRegisterMask::new(0x9F, 0x60);
+    masks[0x1D] = WRITE_ONLY;
+    masks[0x1E] = IoRegisterMask::new(0xBF, 0xC7);
+
+    // Sound channel 4
+    masks[0x20] = IoRegisterMask::new(0xFF, 0x3F);
+    masks[0x21] = READ_WRITE;
+    masks[0x22] = READ_WRITE;
+    masks[0x23] = IoRegisterMask::new(0xBF, 0xC0);
+
+    // Sound control, the channel status bits of NR52 are read-only
+    masks[0x24] = READ_WRITE;
+    masks[0x25] = READ_WRITE;
+    masks[0x26] = IoRegisterMask::new(0x70, 0x80);
+
+    // Wave pattern RAM
+    let mut i = 0x30;
+    while i <= 0x3F {
+        masks[i] = READ_WRITE;
+        i += 1;
+    }
+
+    // LCD, STAT mode bits and LY are driven by the PPU
+    masks[0x40] = READ_WRITE;
+    masks[0x41] = IoRegisterMask::new(0x80, 0x78);
+    masks[0x42] = READ_WRITE;
+    masks[0x43] = READ_WRITE;
+    masks[0x44] = IoRegisterMask::new(0x00, 0x00);
+    masks[0x45] = READ_WRITE;
+    masks[0x46] = READ_WRITE;
+    masks[0x47] = READ_WRITE;
+    masks[0x48] = READ_WRITE;
+    masks[0x49] = READ_WRITE;
+    masks[0x4A] = READ_WRITE;
+    masks[0x4B] = READ_WRITE;
+
+    // Boot ROM disable, reads back as 0xFF once written
+    masks[0x50] = IoRegisterMask::new(0xFF, 0x01);
+
+    masks
+}
+
+pub fn mask(address: u16) -> IoRegisterMask {
+    IO_REGISTER_MASKS[address as usize - super::IO_REGISTERS_BEGIN]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_unused_bits() {
+        assert_eq!(mask(IF).read(0x00), 0xE0);
+        assert_eq!(mask(TAC).read(0x05), 0xFD);
+        assert_eq!(mask(STAT).read(0x00), 0x80);
+    }
+
+    #[test]
+    fn test_write_read_only_bits() {
+        assert_eq!(mask(STAT).write(0x03, 0xFF), 0x7B);
+        assert_eq!(mask(LY).write(0x90, 0x00), 0x90);
+        assert_eq!(mask(NR52).write(0x0F, 0x00), 0x0F);
+    }
+
+    #[test]
+    fn test_unmapped() {
+        assert_eq!(mask(0xFF03).read(0x00), 0xFF);
+        assert_eq!(mask(0xFF03).write(0x00, 0x42), 0x00);
+        assert_eq!(mask(0xFF7F).read(0x00), 0xFF);
+    }
+}
@@ -0,0 +1,570 @@
+use crate::{
+    bus::Bus,
+    debugger::{Access, Watchpoints},
+    joypad::JoypadState,
+    ppu::Ppu,
+    save_state::{SaveStateError, Snapshot, StateReader, StateWriter},
+};
+
+pub mod io_registers;
+
+pub const ROM_BANK_0_BEGIN: usize = 0x0000;
+pub const ROM_BANK_0_END: usize = 0x3FFF;
+pub const ROM_BANK_0_SIZE: usize = ROM_BANK_0_END - ROM_BANK_0_BEGIN + 1;
+
+pub const ROM_BANK_N_BEGIN: usize = 0x4000;
+pub const ROM_BANK_N_END: usize = 0x7FFF;
+pub const ROM_BANK_N_SIZE: usize = ROM_BANK_N_END - ROM_BANK_N_BEGIN + 1;
+
+pub const VRAM_BEGIN: usize = 0x8000;
+pub const VRAM_END: usize = 0x9FFF;
+pub const VRAM_SIZE: usize = VRAM_END - VRAM_BEGIN + 1;
+
+pub const EXTERNAL_RAM_BEGIN: usize = 0xA000;
+pub const EXTERNAL_RAM_END: usize = 0xBFFF;
+pub const EXTERNAL_RAM_SIZE: usize = EXTERNAL_RAM_END - EXTERNAL_RAM_BEGIN + 1;
+
+pub const WORKING_RAM_BEGIN: usize = 0xC000;
+pub const WORKING_RAM_END: usize = 0xDFFF;
+pub const WORKING_RAM_SIZE: usize = WORKING_RAM_END - WORKING_RAM_BEGIN + 1;
+
+pub const ECHO_RAM_BEGIN: usize = 0xE000;
+pub const ECHO_RAM_END: usize = 0xFDFF;
+pub const ECHO_RAM_SIZE: usize = ECHO_RAM_END - ECHO_RAM_BEGIN + 1;
+
+pub const OAM_BEGIN: usize = 0xFE00;
+pub const OAM_END: usize = 0xFE9F;
+pub const OAM_SIZE: usize = OAM_END - OAM_BEGIN + 1;
+
+pub const UNUSED_BEGIN: usize = 0xFEA0;
+pub const UNUSED_END: usize = 0xFEFF;
+pub const UNUSED_SIZE: usize = UNUSED_END - UNUSED_BEGIN + 1;
+
+pub const IO_REGISTERS_BEGIN: usize = 0xFF00;
+pub const IO_REGISTERS_END: usize = 0xFF7F;
+pub const IO_REGISTERS_SIZE: usize = IO_REGISTERS_END - IO_REGISTERS_BEGIN + 1;
+
+pub const HIGH_RAM_BEGIN: usize = 0xFF80;
+pub const HIGH_RAM_END: usize = 0xFFFE;
+pub const HIGH_RAM_SIZE: usize = HIGH_RAM_END - HIGH_RAM_BEGIN + 1;
+
+pub const INTERRUPT_ENABLE_REGISTER: usize = 0xFFFF;
+
+/// Largest ROM that can be mapped. There is no MBC to bank in the rest yet.
+pub const MAX_ROM_SIZE: usize = ROM_BANK_N_END + 1;
+
+/// Rejects ROMs over `MAX_ROM_SIZE`, which would otherwise be written over
+/// VRAM, WRAM and the IO registers.
+pub fn check_rom_size(rom: &[u8]) -> Result<(), String> {
+    if rom.len() > MAX_ROM_SIZE {
+        return Err(format!(
+            "ROM is {} bytes, only ROMs up to {} bytes without an MBC are supported",
+            rom.len(),
+            MAX_ROM_SIZE
+        ));
+    }
+    Ok(())
+}
+
+/// Rejects boot ROMs that would overlay more than the cartridge ROM. This
+/// also keeps their length within the `u16` stored in save states.
+pub fn check_boot_rom_size(boot_rom: &[u8]) -> Result<(), String> {
+    if boot_rom.len() > MAX_ROM_SIZE {
+        return Err(format!(
+            "boot ROM is {} bytes, only boot ROMs up to {} bytes are supported",
+            boot_rom.len(),
+            MAX_ROM_SIZE
+        ));
+    }
+    Ok(())
+}
+
+#[derive(Debug)]
+pub struct Memory {
+    rom_bank_0: [u8; ROM_BANK_0_SIZE],
+    rom_bank_n: [u8; ROM_BANK_N_SIZE],
+    vram: [u8; VRAM_SIZE],
+    external_ram: [u8; EXTERNAL_RAM_SIZE],
+    working_ram: [u8; WORKING_RAM_SIZE],
+    echo_ram: [u8; ECHO_RAM_SIZE],
+    oam: [u8; OAM_SIZE],
+    unused: [u8; UNUSED_SIZE],
+    io_registers: [u8; IO_REGISTERS_SIZE],
+    high_ram: [u8; HIGH_RAM_SIZE],
+    interrupt_enable_register: u8,
+    boot_rom: Vec<u8>, // Overlays the start of ROM until FF50 is written
+    pub watchpoints: Watchpoints,
+    pub joypad: JoypadState,
+    pub serial_output: Vec<u8>, // Every byte sent over the link cable
+    pub ppu: Ppu,
+}
+
+impl Memory {
+    pub fn new() -> Memory {
+        Memory {
+            rom_bank_0: [0; ROM_BANK_0_SIZE],
+            rom_bank_n: [0; ROM_BANK_N_SIZE],
+            vram: [0; VRAM_SIZE],
+            external_ram: [0; EXTERNAL_RAM_SIZE],
+            working_ram: [0; WORKING_RAM_SIZE],
+            echo_ram: [0; ECHO_RAM_SIZE],
+            oam: [0; OAM_SIZE],
+            unused: [0; UNUSED_SIZE],
+            io_registers: [0; IO_REGISTERS_SIZE],
+            high_ram: [0; HIGH_RAM_SIZE],
+            interrupt_enable_register: 0,
+            boot_rom: Vec::new(),
+            watchpoints: Watchpoints::default(),
+            joypad: JoypadState::default(),
+            serial_output: Vec::new(),
+            ppu: Ppu::new(),
+        }
+    }
+
+    pub fn dump(&self) -> Vec<u8> {
+        let mut dump = Vec::new();
+        dump.extend_from_slice(&self.rom_bank_0);
+        dump.extend_from_slice(&self.rom_bank_n);
+        dump.extend_from_slice(&self.vram);
+        dump.extend_from_slice(&self.external_ram);
+        dump.extend_from_slice(&self.working_ram);
+        dump.extend_from_slice(&self.echo_ram);
+        dump.extend_from_slice(&self.oam);
+        dump.extend_from_slice(&self.unused);
+        dump.extend_from_slice(&self.io_registers);
+        dump.extend_from_slice(&self.high_ram);
+        dump.push(self.interrupt_enable_register);
+        dump
+    }
+
+    pub fn read(&self, address: u16) -> u8 {
+        log!(Mem, Trace, "Reading from memory address: 0x{:X}", address);
+        let value = self.peek(address);
+        self.watchpoints.check(address, Access::Read, value);
+        value
+    }
+
+    /// Reads without logging or triggering watchpoints.
+    pub fn peek(&self, address: u16) -> u8 {
+        let address = address as usize;
+        if address < self.boot_rom.len() {
+            return self.boot_rom[address];
+        }
+        match address as usize {
+            ROM_BANK_0_BEGIN..=ROM_BANK_0_END => self.rom_bank_0[address - ROM_BANK_0_BEGIN],
+            ROM_BANK_N_BEGIN..=ROM_BANK_N_END => self.rom_bank_n[address - ROM_BANK_N_BEGIN],
+            VRAM_BEGIN..=VRAM_END => self.vram[address - VRAM_BEGIN],
+            EXTERNAL_RAM_BEGIN..=EXTERNAL_RAM_END => self.external_ram[address - EXTERNAL_RAM_BEGIN],
+            WORKING_RAM_BEGIN..=WORKING_RAM_END => self.working_ram[address - WORKING_RAM_BEGIN],
+            ECHO_RAM_BEGIN..=ECHO_RAM_END => self.echo_ram[address - ECHO_RAM_BEGIN],
+            OAM_BEGIN..=OAM_END => self.oam[address - OAM_BEGIN],
+            UNUSED_BEGIN..=UNUSED_END => self.unused[address - UNUSED_BEGIN],
+            IO_REGISTERS_BEGIN..=IO_REGISTERS_END => self.read_io_register(address as u16),
+            HIGH_RAM_BEGIN..=HIGH_RAM_END => self.high_ram[address - HIGH_RAM_BEGIN],
+            // INTERRUPT_ENABLE_REGISTER, the only address left
+            _ => self.interrupt_enable_register,
+        }
+    }
+
+    /// ROM bank mapped at `address`, 0 outside of ROM. There is no MBC yet,
+    /// so the switchable area always holds bank 1.
+    pub fn rom_bank(&self, address: u16) -> u16 {
+        match address as usize {
+            ROM_BANK_N_BEGIN..=ROM_BANK_N_END => 1,
+            _ => 0,
+        }
+    }
+
+    pub fn read_16(&self, address: u16) -> u16 {
+        let low = self.read(address) as u16;
+        let high = self.read(address + 1) as u16;
+        (high << 8) | low
+    }
+
+    pub fn write(&mut self, address: u16, value: u8) {
+        log!(Mem, Trace, "Writing to memory address: 0x{:X} value: 0x{:X}", address, value);
+        self.watchpoints.check(address, Access::Write, value);
+        self.poke(address, value);
+    }
+
+    /// Writes without logging or triggering watchpoints.
+    pub fn poke(&mut self, address: u16, value: u8) {
+        let address = address as usize;
+        match address {
+            ROM_BANK_0_BEGIN..=ROM_BANK_0_END => self.rom_bank_0[address - ROM_BANK_0_BEGIN] = value,
+            ROM_BANK_N_BEGIN..=ROM_BANK_N_END => self.rom_bank_n[address - ROM_BANK_N_BEGIN] = value,
+            VRAM_BEGIN..=VRAM_END => self.vram[address - VRAM_BEGIN] = value,
+            EXTERNAL_RAM_BEGIN..=EXTERNAL_RAM_END => self.external_ram[address - EXTERNAL_RAM_BEGIN] = value,
+            WORKING_RAM_BEGIN..=WORKING_RAM_END => self.working_ram[address - WORKING_RAM_BEGIN] = value,
+            ECHO_RAM_BEGIN..=ECHO_RAM_END => self.echo_ram[address - ECHO_RAM_BEGIN] = value,
+            OAM_BEGIN..=OAM_END => self.oam[address - OAM_BEGIN] = value,
+            UNUSED_BEGIN..=UNUSED_END => self.unused[address - UNUSED_BEGIN] = value,
+            IO_REGISTERS_BEGIN..=IO_REGISTERS_END => self.write_io_register(address as u16, value),
+            HIGH_RAM_BEGIN..=HIGH_RAM_END => self.high_ram[address - HIGH_RAM_BEGIN] = value,
+            // INTERRUPT_ENABLE_REGISTER, the only address left
+            _ => self.interrupt_enable_register = value,
+        }
+    }
+
+    pub fn write_16(&mut self, address: u16, value: u16) {
+        let low = value as u8;
+        let high = (value >> 8) as u8;
+        self.write(address, low);
+        self.write(address + 1, high);
+    }
+
+    fn read_io_register(&self, address: u16) -> u8 {
+        let stored = self.io_registers[address as usize - IO_REGISTERS_BEGIN];
+        let value = io_registers::mask(address).read(stored);
+        match address {
+            // The input lines are driven by the buttons
+            io_registers::JOYP => (value & 0xF0) | self.joypad.read(stored),
+            _ => value,
+        }
+    }
+
+    fn write_io_register(&mut self, address: u16, value: u8) {
+        let index = address as usize - IO_REGISTERS_BEGIN;
+        self.io_registers[index] = match address {
+            // Any write resets the divider
+            io_registers::DIV => 0,
+            _ => io_registers::mask(address).write(self.io_registers[index], value),
+        };
+
+        if address == io_registers::DMA {
+            self.transfer_oam(value);
+        }
+        // The boot ROM unmaps itself as its last instruction
+        if address == io_registers::BOOT && value != 0 {
+            self.boot_rom = Vec::new();
+        }
+        // Starting a transfer on the internal clock
+        if address == io_registers::SC && value & 0x81 == 0x81 {
+            self.transfer_serial();
+        }
+    }
+
+    // OAM DMA copies 160 bytes from value * 0x100, instantly instead of
+    // over 160 M-cycles
+    fn transfer_oam(&mut self, value: u8) {
+        let source = (value as u16) << 8;
+        for i in 0..OAM_SIZE {
+            self.oam[i] = self.peek(source + i as u16);
+        }
+    }
+
+    /// Advances the peripherals by the given number of T-cycles.
+    pub fn tick(&mut self, cycles: u32) {
+        self.ppu
+            .tick(cycles, &self.vram, &self.oam, &mut self.io_registers);
+    }
+
+    // Serial transfers complete instantly. Without a link partner the received
+    // byte is 0xFF.
+    fn transfer_serial(&mut self) {
+        let sb = io_registers::SB as usize - IO_REGISTERS_BEGIN;
+        let sc = io_registers::SC as usize - IO_REGISTERS_BEGIN;
+        let interrupt_flag = io_registers::IF as usize - IO_REGISTERS_BEGIN;
+        self.serial_output.push(self.io_registers[sb]);
+        self.io_registers[sb] = 0xFF;
+        self.io_registers[sc] &= 0x7F;
+        self.io_registers[interrupt_flag] |= 0x08;
+    }
+
+    /// Maps `boot_rom` over the start of ROM, hiding the cartridge bytes
+    /// below it until a nonzero write to `io_registers::BOOT`. Frontends
+    /// check it with `check_boot_rom_size` first.
+    pub fn map_boot_rom(&mut self, boot_rom: Vec<u8>) {
+        self.boot_rom = boot_rom;
+    }
+
+    pub fn write_vec(&mut self, start_address: u16, data: Vec<u8>) {
+        for (i, byte) in data.iter().enumerate() {
+            self.write(start_address + i as u16, *byte);
+        }
+    }
+}
+
+impl Snapshot for Memory {
+    fn save(&self, writer: &mut StateWriter) {
+        writer.write_bytes(&self.dump());
+        writer.write_u8(self.joypad.bits());
+        writer.write_u16(self.boot_rom.len() as u16);
+        writer.write_bytes(&self.boot_rom);
+    }
+
+    fn load(&mut self, reader: &mut StateReader) -> Result<(), SaveStateError> {
+        reader.read_into(&mut self.rom_bank_0)?;
+        reader.read_into(&mut self.rom_bank_n)?;
+        reader.read_into(&mut self.vram)?;
+        reader.read_into(&mut self.external_ram)?;
+        reader.read_into(&mut self.working_ram)?;
+        reader.read_into(&mut self.echo_ram)?;
+        reader.read_into(&mut self.oam)?;
+        reader.read_into(&mut self.unused)?;
+        reader.read_into(&mut self.io_registers)?;
+        reader.read_into(&mut self.high_ram)?;
+        self.interrupt_enable_register = reader.read_u8()?;
+        self.joypad = JoypadState::from_bits(reader.read_u8()?);
+        let length = reader.read_u16()? as usize;
+        self.boot_rom = reader.read_bytes(length)?.to_vec();
+        Ok(())
+    }
+}
+
+impl Bus for Memory {
+    fn read(&mut self, address: u16) -> u8 {
+        Memory::read(self, address)
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        Memory::write(self, address, value)
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        Memory::tick(self, cycles)
+    }
+
+    fn peek(&self, address: u16) -> u8 {
+        Memory::peek(self, address)
+    }
+
+    fn rom_bank(&self, address: u16) -> u16 {
+        Memory::rom_bank(self, address)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_byte() {
+        let memory = Memory::new();
+        assert_eq!(memory.read(0x0000), 0);
+    }
+
+    #[test]
+    fn test_write_byte() {
+        let mut memory = Memory::new();
+        memory.write(0x0000, 0x01);
+        assert_eq!(memory.read(0x0000), 0x01);
+    }
+
+    #[test]
+    fn test_read_write_boot_rom() {
+        let mut memory = Memory::new();
+        memory.write(0x0000, 0x01);
+        assert_eq!(memory.read(0x0000), 0x01);
+    }
+
+    #[test]
+    fn test_read_write_rom_bank_0() {
+        let mut memory = Memory::new();
+        memory.write(0x0000, 0x01);
+        assert_eq!(memory.read(0x0000), 0x01);
+    }
+
+    #[test]
+    fn test_read_write_rom_bank_n() {
+        let mut memory = Memory::new();
+        memory.write(0x4000, 0x01);
+        assert_eq!(memory.read(0x4000), 0x01);
+    }
+
+    #[test]
+    fn test_read_write_vram() {
+        let mut memory = Memory::new();
+        memory.write(0x8000, 0x01);
+        assert_eq!(memory.read(0x8000), 0x01);
+    }
+
+    #[test]
+    fn test_read_write_external_ram() {
+        let mut memory = Memory::new();
+        memory.write(0xA000, 0x01);
+        assert_eq!(memory.read(0xA000), 0x01);
+    }
+
+    #[test]
+    fn test_read_write_working_ram() {
+        let mut memory = Memory::new();
+        memory.write(0xC000, 0x01);
+        assert_eq!(memory.read(0xC000), 0x01);
+    }
+
+    #[test]
+    fn test_read_write_echo_ram() {
+        let mut memory = Memory::new();
+        memory.write(0xE000, 0x01);
+        assert_eq!(memory.read(0xE000), 0x01);
+    }
+
+    #[test]
+    fn test_read_write_oam() {
+        let mut memory = Memory::new();
+        memory.write(0xFE00, 0x01);
+        assert_eq!(memory.read(0xFE00), 0x01);
+    }
+
+    #[test]
+    fn test_read_write_unused() {
+        let mut memory = Memory::new();
+        memory.write(0xFEA0, 0x01);
+        assert_eq!(memory.read(0xFEA0), 0x01);
+    }
+
+    #[test]
+    fn test_read_write_io_registers() {
+        let mut memory = Memory::new();
+        memory.write(0xFF42, 0x01);
+        assert_eq!(memory.read(0xFF42), 0x01);
+    }
+
+    #[test]
+    fn test_read_io_registers_unused_bits() {
+        let mut memory = Memory::new();
+        memory.write(0xFF0F, 0x01);
+        assert_eq!(memory.read(0xFF0F), 0xE1);
+        memory.write(0xFF00, 0x20);
+        assert_eq!(memory.read(0xFF00), 0xEF);
+    }
+
+    #[test]
+    fn test_serial_transfer() {
+        let mut memory = Memory::new();
+        memory.write(0xFF01, b'O');
+        memory.write(0xFF02, 0x81);
+        memory.write(0xFF01, b'K');
+        memory.write(0xFF02, 0x80);
+        assert_eq!(memory.serial_output, b"O");
+        memory.write(0xFF02, 0x81);
+        assert_eq!(memory.serial_output, b"OK");
+        assert_eq!(memory.read(0xFF01), 0xFF);
+        assert_eq!(memory.read(0xFF02), 0x7F);
+        assert_eq!(memory.read(0xFF0F), 0xE8);
+    }
+
+    #[test]
+    fn test_oam_dma() {
+        let mut memory = Memory::new();
+        memory.write(0xC09F, 0x42);
+        memory.write(0xFF46, 0xC0);
+        assert_eq!(memory.read(0xFE9F), 0x42);
+    }
+
+    #[test]
+    fn test_read_joypad() {
+        let mut memory = Memory::new();
+        memory.joypad.press(crate::joypad::Button::Start);
+        memory.write(0xFF00, 0x10);
+        assert_eq!(memory.read(0xFF00), 0xD7);
+        memory.write(0xFF00, 0x20);
+        assert_eq!(memory.read(0xFF00), 0xEF);
+    }
+
+    #[test]
+    fn test_read_io_registers_write_only() {
+        let mut memory = Memory::new();
+        memory.write(0xFF13, 0x42);
+        assert_eq!(memory.read(0xFF13), 0xFF);
+        memory.write(0xFF11, 0x81);
+        assert_eq!(memory.read(0xFF11), 0xBF);
+    }
+
+    #[test]
+    fn test_read_io_registers_unmapped() {
+        let mut memory = Memory::new();
+        memory.write(0xFF03, 0x01);
+        assert_eq!(memory.read(0xFF03), 0xFF);
+        assert_eq!(memory.read(0xFF4D), 0xFF);
+        assert_eq!(memory.read(0xFF7F), 0xFF);
+    }
+
+    #[test]
+    fn test_write_div_resets() {
+        let mut memory = Memory::new();
+        memory.io_registers[0x04] = 0xAB;
+        memory.write(0xFF04, 0x42);
+        assert_eq!(memory.read(0xFF04), 0x00);
+    }
+
+    #[test]
+    fn test_read_write_high_ram() {
+        let mut memory = Memory::new();
+        memory.write(0xFF80, 0x01);
+        assert_eq!(memory.read(0xFF80), 0x01);
+    }
+
+    #[test]
+    fn test_read_write_interrupt_enable_register() {
+        let mut memory = Memory::new();
+        memory.write(0xFFFF, 0x01);
+        assert_eq!(memory.read(0xFFFF), 0x01);
+    }
+
+    #[test]
+    fn test_read_write_multiple() {
+        let mut memory = Memory::new();
+        memory.write(0x0000, 0x01);
+        memory.write(0x4000, 0x02);
+        memory.write(0x8000, 0x03);
+        memory.write(0xA000, 0x04);
+        memory.write(0xC000, 0x05);
+        memory.write(0xE000, 0x06);
+        memory.write(0xFE00, 0x07);
+        memory.write(0xFEA0, 0x08);
+        memory.write(0xFF42, 0x09);
+        memory.write(0xFF80, 0x0A);
+        memory.write(0xFFFF, 0x0B);
+        assert_eq!(memory.read(0x0000), 0x01);
+        assert_eq!(memory.read(0x4000), 0x02);
+        assert_eq!(memory.read(0x8000), 0x03);
+        assert_eq!(memory.read(0xA000), 0x04);
+        assert_eq!(memory.read(0xC000), 0x05);
+        assert_eq!(memory.read(0xE000), 0x06);
+        assert_eq!(memory.read(0xFE00), 0x07);
+        assert_eq!(memory.read(0xFEA0), 0x08);
+        assert_eq!(memory.read(0xFF42), 0x09);
+        assert_eq!(memory.read(0xFF80), 0x0A);
+        assert_eq!(memory.read(0xFFFF), 0x0B);
+    }
+
+    #[test]
+    fn test_read_write_16() {
+        let mut memory = Memory::new();
+        memory.write_16(0x0000, 0x0102);
+        assert_eq!(memory.read(0x0000), 0x02);
+        assert_eq!(memory.read(0x0001), 0x01);
+    }
+
+    #[test]
+    fn test_read_write_vec() {
+        let mut memory = Memory::new();
+        memory.write_vec(0x0000, vec![0x01, 0x02, 0x03]);
+        assert_eq!(memory.read(0x0000), 0x01);
+        assert_eq!(memory.read(0x0001), 0x02);
+        assert_eq!(memory.read(0x0002), 0x03);
+    }
+
+    #[test]
+    fn test_check_boot_rom_size() {
+        assert!(check_boot_rom_size(&[0x00; 0x100]).is_ok());
+        assert!(check_boot_rom_size(&vec![0x00; MAX_ROM_SIZE]).is_ok());
+        assert!(check_boot_rom_size(&vec![0x00; 0x10000]).is_err());
+    }
+
+    #[test]
+    fn test_boot_rom_unmaps_on_ff50_write() {
+        let mut memory = Memory::new();
+        memory.write(0x0028, 0xEF); // RST $28 vector of the game
+        memory.map_boot_rom(vec![0x31; 0x100]);
+        assert_eq!(memory.read(0x0028), 0x31);
+        assert_eq!(memory.read(0x0100), 0x00);
+
+        memory.write(io_registers::BOOT, 0x00);
+        assert_eq!(memory.read(0x0028), 0x31);
+        memory.write(io_registers::BOOT, 0x01);
+        assert_eq!(memory.read(0x0028), 0xEF);
+    }
+}
@@ -0,0 +1,69 @@
+use std::str::FromStr;
+
+/// Game Boy hardware revision. Only DMG hardware is emulated, the model picks
+/// the register values the boot ROM leaves behind, which games check to find
+/// out what they are running on.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Model {
+    #[default]
+    Dmg,
+    Mgb, // Game Boy Pocket
+    Sgb,
+    Cgb,
+}
+
+pub const MODELS: [Model; 4] = [Model::Dmg, Model::Mgb, Model::Sgb, Model::Cgb];
+
+impl Model {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Model::Dmg => "dmg",
+            Model::Mgb => "mgb",
+            Model::Sgb => "sgb",
+            Model::Cgb => "cgb",
+        }
+    }
+
+    /// AF, BC, DE and HL after the boot ROM has run.
+    pub fn post_boot_registers(&self) -> [u16; 4] {
+        match self {
+            Model::Dmg => [0x01B0, 0x0013, 0x00D8, 0x014D],
+            Model::Mgb => [0xFFB0, 0x0013, 0x00D8, 0x014D],
+            Model::Sgb => [0x0100, 0x0014, 0x0000, 0xC060],
+            Model::Cgb => [0x1180, 0x0000, 0xFF56, 0x000D],
+        }
+    }
+}
+
+impl FromStr for Model {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Model, String> {
+        MODELS
+            .iter()
+            .find(|model| model.name().eq_ignore_ascii_case(s))
+            .copied()
+            .ok_or_else(|| format!("Unknown model: {}", s))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Gameboy;
+
+    #[test]
+    fn test_parse_model() {
+        assert_eq!("CGB".parse(), Ok(Model::Cgb));
+        assert!("gba".parse::<Model>().is_err());
+    }
+
+    #[test]
+    fn test_post_boot_registers() {
+        let mut gameboy = Gameboy::new(vec![], vec![0x00; 0x200]);
+        gameboy.set_model(Model::Mgb);
+        gameboy.reset();
+        assert_eq!(gameboy.registers().a, 0xFF);
+        assert_eq!(gameboy.model(), Model::Mgb);
+    }
+}
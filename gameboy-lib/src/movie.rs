@@ -0,0 +1,498 @@
+use std::fmt;
+
+use crate::{
+    debugger::StopReason,
+    error::GameboyError,
+    joypad::JoypadState,
+    memory::VRAM_BEGIN,
+    save_state::{crc32, SaveStateError, StateReader, StateWriter},
+    Gameboy,
+};
+
+/// Input movies record the joypad state of every frame:
+///
+/// ```text
+/// "GBMV"            magic
+/// u16               format version
+/// u32               CRC-32 of the game ROM
+/// u8                start, 0 = power on, 1 = save state
+/// u32 [u8]          length and save state, only when starting from a state
+/// u32 [u8]          number of frames and the joypad bits of each frame
+/// u32 [u32 u32 u32] number of checkpoints, each a frame number, RAM hash and
+///                   framebuffer hash
+/// ```
+///
+/// All integers are little endian.
+pub const MAGIC: &[u8; 4] = b"GBMV";
+pub const VERSION: u16 = 2;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MovieError {
+    InvalidMagic,
+    UnsupportedVersion(u16),
+    InvalidStart(u8),
+    RomMismatch {
+        expected: u32,
+        found: u32,
+    },
+    SaveState(SaveStateError),
+    Desync {
+        expected: Checkpoint,
+        found: Checkpoint,
+    },
+    Truncated,
+    Emulation(GameboyError),
+    // A frame stopped early at a breakpoint, watchpoint or lockup
+    Interrupted {
+        frame: u32,
+        stop: StopReason,
+    },
+}
+
+impl fmt::Display for MovieError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MovieError::InvalidMagic => write!(f, "Not a movie"),
+            MovieError::UnsupportedVersion(version) => write!(
+                f,
+                "Unsupported movie version {} (expected {})",
+                version, VERSION
+            ),
+            MovieError::InvalidStart(start) => write!(f, "Invalid movie start condition {}", start),
+            MovieError::RomMismatch { expected, found } => write!(
+                f,
+                "Movie was recorded with a different ROM (checksum {:08X}, loaded ROM is {:08X})",
+                found, expected
+            ),
+            MovieError::SaveState(error) => write!(f, "Invalid movie start state: {}", error),
+            MovieError::Desync { expected, found } => {
+                write!(f, "Playback diverged at frame {}:", expected.frame)?;
+                if expected.ram_hash != found.ram_hash {
+                    write!(
+                        f,
+                        " RAM hash {:08X}, expected {:08X}",
+                        found.ram_hash, expected.ram_hash
+                    )?;
+                }
+                if expected.framebuffer_hash != found.framebuffer_hash {
+                    write!(
+                        f,
+                        " framebuffer hash {:08X}, expected {:08X}",
+                        found.framebuffer_hash, expected.framebuffer_hash
+                    )?;
+                }
+                Ok(())
+            }
+            MovieError::Truncated => write!(f, "Movie is truncated"),
+            MovieError::Emulation(error) => write!(f, "Playback stopped: {}", error),
+            MovieError::Interrupted { frame, stop } => {
+                write!(f, "Frame {} stopped early: {:?}", frame, stop)
+            }
+        }
+    }
+}
+
+impl std::error::Error for MovieError {}
+
+impl From<GameboyError> for MovieError {
+    fn from(error: GameboyError) -> MovieError {
+        MovieError::Emulation(error)
+    }
+}
+
+impl From<SaveStateError> for MovieError {
+    fn from(error: SaveStateError) -> MovieError {
+        match error {
+            SaveStateError::Truncated => MovieError::Truncated,
+            error => MovieError::SaveState(error),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MovieStart {
+    PowerOn,
+    SaveState(Vec<u8>),
+}
+
+/// Where a new recording starts from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RecordFrom {
+    PowerOn,      // Reset the machine first
+    CurrentState, // Embed a save state of the running machine
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Checkpoint {
+    pub frame: u32, // Number of movie frames played when the hashes were taken
+    pub ram_hash: u32,
+    pub framebuffer_hash: u32,
+}
+
+impl Checkpoint {
+    fn take(frame: u32, gameboy: &Gameboy) -> Checkpoint {
+        Checkpoint {
+            frame,
+            ram_hash: gameboy.ram_hash(),
+            framebuffer_hash: crc32(gameboy.framebuffer()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Movie {
+    pub rom_checksum: u32,
+    pub start: MovieStart,
+    pub inputs: Vec<JoypadState>,
+    pub checkpoints: Vec<Checkpoint>,
+}
+
+impl Movie {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = StateWriter::default();
+        writer.write_bytes(MAGIC);
+        writer.write_u16(VERSION);
+        writer.write_u32(self.rom_checksum);
+        match &self.start {
+            MovieStart::PowerOn => writer.write_u8(0),
+            MovieStart::SaveState(state) => {
+                writer.write_u8(1);
+                writer.write_u32(state.len() as u32);
+                writer.write_bytes(state);
+            }
+        }
+        writer.write_u32(self.inputs.len() as u32);
+        for input in &self.inputs {
+            writer.write_u8(input.bits());
+        }
+        writer.write_u32(self.checkpoints.len() as u32);
+        for checkpoint in &self.checkpoints {
+            writer.write_u32(checkpoint.frame);
+            writer.write_u32(checkpoint.ram_hash);
+            writer.write_u32(checkpoint.framebuffer_hash);
+        }
+        writer.into_bytes()
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Movie, MovieError> {
+        let mut reader = StateReader::new(data);
+        if reader.read_bytes(MAGIC.len()).ok() != Some(MAGIC.as_slice()) {
+            return Err(MovieError::InvalidMagic);
+        }
+        let version = reader.read_u16()?;
+        if version != VERSION {
+            return Err(MovieError::UnsupportedVersion(version));
+        }
+        let rom_checksum = reader.read_u32()?;
+        let start = match reader.read_u8()? {
+            0 => MovieStart::PowerOn,
+            1 => {
+                let length = reader.read_u32()? as usize;
+                MovieStart::SaveState(reader.read_bytes(length)?.to_vec())
+            }
+            start => return Err(MovieError::InvalidStart(start)),
+        };
+        let frames = reader.read_u32()? as usize;
+        let inputs = reader
+            .read_bytes(frames)?
+            .iter()
+            .map(|bits| JoypadState::from_bits(*bits))
+            .collect();
+        let count = reader.read_u32()?;
+        let mut checkpoints = Vec::new();
+        for _ in 0..count {
+            checkpoints.push(Checkpoint {
+                frame: reader.read_u32()?,
+                ram_hash: reader.read_u32()?,
+                framebuffer_hash: reader.read_u32()?,
+            });
+        }
+        Ok(Movie {
+            rom_checksum,
+            start,
+            inputs,
+            checkpoints,
+        })
+    }
+}
+
+/// Records the joypad state of every frame run through it.
+#[derive(Debug)]
+pub struct MovieRecorder {
+    movie: Movie,
+    checkpoint_interval: u32, // Frames between two checkpoints, 0 for none
+}
+
+impl MovieRecorder {
+    pub fn start(
+        gameboy: &mut Gameboy,
+        from: RecordFrom,
+        checkpoint_interval: u32,
+    ) -> MovieRecorder {
+        let start = match from {
+            RecordFrom::PowerOn => {
+                gameboy.reset();
+                MovieStart::PowerOn
+            }
+            RecordFrom::CurrentState => MovieStart::SaveState(gameboy.save_state()),
+        };
+        MovieRecorder {
+            movie: Movie {
+                rom_checksum: crc32(&gameboy.game_rom),
+                start,
+                inputs: Vec::new(),
+                checkpoints: Vec::new(),
+            },
+            checkpoint_interval,
+        }
+    }
+
+    /// Runs a frame with the current joypad state and records it. A frame
+    /// that fails or stops early is not recorded and the machine is put back
+    /// to where the frame started, playback always runs whole frames.
+    pub fn run_frame(&mut self, gameboy: &mut Gameboy) -> Result<(), MovieError> {
+        let input = gameboy.joypad();
+        let frame = self.movie.inputs.len() as u32 + 1;
+        let state = gameboy.save_state();
+        let error = match gameboy.run_frame() {
+            Ok(result) => result
+                .stop
+                .map(|stop| MovieError::Interrupted { frame, stop }),
+            Err(error) => Some(error.into()),
+        };
+        if let Some(error) = error {
+            gameboy.load_state(&state)?;
+            return Err(error);
+        }
+        self.movie.inputs.push(input);
+
+        if self.checkpoint_interval > 0 && frame % self.checkpoint_interval == 0 {
+            self.movie
+                .checkpoints
+                .push(Checkpoint::take(frame, gameboy));
+        }
+        Ok(())
+    }
+
+    pub fn finish(self) -> Movie {
+        self.movie
+    }
+}
+
+/// Feeds the input of a movie back into the machine, one frame at a time.
+#[derive(Debug)]
+pub struct MoviePlayer {
+    movie: Movie,
+    verify: bool,
+    frame: usize,
+    checkpoint: usize,
+}
+
+impl MoviePlayer {
+    /// Puts the machine into the movie's start condition. With `verify`,
+    /// every checkpoint is compared against the running machine.
+    pub fn start(
+        gameboy: &mut Gameboy,
+        movie: Movie,
+        verify: bool,
+    ) -> Result<MoviePlayer, MovieError> {
+        let expected = crc32(&gameboy.game_rom);
+        if movie.rom_checksum != expected {
+            return Err(MovieError::RomMismatch {
+                expected,
+                found: movie.rom_checksum,
+            });
+        }
+        match &movie.start {
+            MovieStart::PowerOn => gameboy.reset(),
+            MovieStart::SaveState(state) => gameboy.load_state(state)?,
+        }
+        Ok(MoviePlayer {
+            movie,
+            verify,
+            frame: 0,
+            checkpoint: 0,
+        })
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.frame >= self.movie.inputs.len()
+    }
+
+    /// Plays the next frame, returns false once the movie has ended.
+    pub fn run_frame(&mut self, gameboy: &mut Gameboy) -> Result<bool, MovieError> {
+        let Some(input) = self.movie.inputs.get(self.frame) else {
+            return Ok(false);
+        };
+        gameboy.set_joypad(*input);
+        if let Some(stop) = gameboy.run_frame()?.stop {
+            return Err(MovieError::Interrupted {
+                frame: self.frame as u32 + 1,
+                stop,
+            });
+        }
+        self.frame += 1;
+
+        while let Some(checkpoint) = self.movie.checkpoints.get(self.checkpoint) {
+            if checkpoint.frame as usize > self.frame {
+                break;
+            }
+            self.checkpoint += 1;
+            let found = Checkpoint::take(self.frame as u32, gameboy);
+            if self.verify && *checkpoint != found {
+                return Err(MovieError::Desync {
+                    expected: *checkpoint,
+                    found,
+                });
+            }
+        }
+        Ok(true)
+    }
+}
+
+impl Gameboy {
+    /// CRC-32 over everything from VRAM up to the interrupt enable register.
+    pub fn ram_hash(&self) -> u32 {
+        crc32(&self.cpu.bus.dump()[VRAM_BEGIN..])
+    }
+
+    /// Plays a whole movie headless.
+    pub fn play_movie(&mut self, movie: Movie, verify: bool) -> Result<(), MovieError> {
+        let mut player = MoviePlayer::start(self, movie, verify)?;
+        while player.run_frame(self)? {}
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::joypad::Button;
+
+    fn gameboy() -> Gameboy {
+        // Select the action buttons and copy JOYP to 0xC000 forever
+        let mut rom = vec![0x00; 0x110];
+        rom[0x100..0x10E].copy_from_slice(&[
+            0x3E, 0x10, // LD A, 0x10
+            0xEA, 0x00, 0xFF, // LD (0xFF00), A
+            0xFA, 0x00, 0xFF, // LD A, (0xFF00)
+            0xEA, 0x00, 0xC0, // LD (0xC000), A
+            0xC3, 0x05, 0x01, // JP 0x0105
+        ]);
+        Gameboy::new(vec![], rom)
+    }
+
+    fn record(gameboy: &mut Gameboy, from: RecordFrom) -> Movie {
+        let mut recorder = MovieRecorder::start(gameboy, from, 2);
+        for frame in 0..6 {
+            let mut joypad = JoypadState::default();
+            if frame >= 3 {
+                joypad.press(Button::Start);
+            }
+            gameboy.set_joypad(joypad);
+            recorder.run_frame(gameboy).unwrap();
+        }
+        recorder.finish()
+    }
+
+    #[test]
+    fn test_record_and_verify() {
+        let mut gameboy = gameboy();
+        let movie = record(&mut gameboy, RecordFrom::PowerOn);
+        assert_eq!(movie.inputs.len(), 6);
+        assert_eq!(movie.checkpoints.len(), 3);
+        assert_eq!(gameboy.read_memory(0xC000), 0xD7);
+        let expected = gameboy.save_state();
+
+        let mut playback = self::gameboy();
+        assert_eq!(playback.play_movie(movie, true), Ok(()));
+        assert_eq!(playback.save_state(), expected);
+    }
+
+    #[test]
+    fn test_serialize_round_trip() {
+        let mut gameboy = gameboy();
+        gameboy.reset();
+        gameboy.run_frame().unwrap();
+        let movie = record(&mut gameboy, RecordFrom::CurrentState);
+        assert!(matches!(movie.start, MovieStart::SaveState(_)));
+
+        let bytes = movie.to_bytes();
+        assert_eq!(Movie::from_bytes(&bytes), Ok(movie.clone()));
+        assert_eq!(
+            Movie::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(MovieError::Truncated)
+        );
+        assert_eq!(Movie::from_bytes(b"GBSS"), Err(MovieError::InvalidMagic));
+        assert_eq!(self::gameboy().play_movie(movie, true), Ok(()));
+    }
+
+    #[test]
+    fn test_verify_detects_desync() {
+        let mut movie = record(&mut gameboy(), RecordFrom::PowerOn);
+        movie.inputs[5] = JoypadState::default();
+
+        assert_eq!(gameboy().play_movie(movie.clone(), false), Ok(()));
+        assert!(matches!(
+            gameboy().play_movie(movie, true),
+            Err(MovieError::Desync {
+                expected: Checkpoint { frame: 6, .. },
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_rejects_other_rom() {
+        let movie = record(&mut gameboy(), RecordFrom::PowerOn);
+        let mut other = Gameboy::new(vec![], vec![0x00]);
+        assert!(matches!(
+            other.play_movie(movie, true),
+            Err(MovieError::RomMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_interrupted_frame_is_not_recorded() {
+        let mut gameboy = gameboy();
+        let mut recorder = MovieRecorder::start(&mut gameboy, RecordFrom::PowerOn, 0);
+        recorder.run_frame(&mut gameboy).unwrap();
+
+        gameboy.add_breakpoint(0x0105);
+        assert_eq!(
+            recorder.run_frame(&mut gameboy),
+            Err(MovieError::Interrupted {
+                frame: 2,
+                stop: StopReason::Breakpoint(0x0105)
+            })
+        );
+        assert_eq!(recorder.finish().inputs.len(), 1);
+    }
+
+    #[test]
+    fn test_record_after_interruption() {
+        let mut gameboy = gameboy();
+        let mut recorder = MovieRecorder::start(&mut gameboy, RecordFrom::PowerOn, 1);
+        recorder.run_frame(&mut gameboy).unwrap();
+        let before = gameboy.save_state();
+
+        gameboy.add_breakpoint(0x0105);
+        assert!(matches!(
+            recorder.run_frame(&mut gameboy),
+            Err(MovieError::Interrupted { frame: 2, .. })
+        ));
+        assert_eq!(gameboy.save_state(), before);
+
+        gameboy.remove_breakpoint(0x0105);
+        for _ in 0..3 {
+            recorder.run_frame(&mut gameboy).unwrap();
+        }
+        let movie = recorder.finish();
+        assert_eq!(movie.inputs.len(), 4);
+        let expected = gameboy.save_state();
+
+        let mut playback = self::gameboy();
+        assert_eq!(playback.play_movie(movie, true), Ok(()));
+        assert_eq!(playback.save_state(), expected);
+    }
+}
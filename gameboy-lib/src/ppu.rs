@@ -0,0 +1,523 @@
+use std::str::FromStr;
+
+use crate::{
+    memory::{io_registers, IO_REGISTERS_BEGIN, IO_REGISTERS_SIZE, OAM_SIZE, VRAM_SIZE},
+    save_state::{SaveStateError, Snapshot, StateReader, StateWriter},
+};
+
+pub const SCREEN_WIDTH: usize = 160;
+pub const SCREEN_HEIGHT: usize = 144;
+
+const DOTS_PER_LINE: u32 = 456;
+const OAM_SCAN_DOTS: u32 = 80;
+const DRAWING_DOTS: u32 = 172;
+const LINES_PER_FRAME: u8 = 154;
+const SPRITES_PER_LINE: usize = 10;
+
+const VBLANK_INTERRUPT: u8 = 0x01;
+const STAT_INTERRUPT: u8 = 0x02;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mode {
+    HBlank = 0,
+    VBlank = 1,
+    OamScan = 2,
+    Drawing = 3,
+}
+
+/// Maps the four DMG shades to RGB, from lightest to darkest.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Palette {
+    pub colors: [[u8; 3]; 4],
+}
+
+impl Palette {
+    pub const GREYSCALE: Palette = Palette {
+        colors: [
+            [0xFF, 0xFF, 0xFF],
+            [0xAA, 0xAA, 0xAA],
+            [0x55, 0x55, 0x55],
+            [0x00, 0x00, 0x00],
+        ],
+    };
+    pub const CLASSIC_GREEN: Palette = Palette {
+        colors: [
+            [0x9B, 0xBC, 0x0F],
+            [0x8B, 0xAC, 0x0F],
+            [0x30, 0x62, 0x30],
+            [0x0F, 0x38, 0x0F],
+        ],
+    };
+}
+
+impl Default for Palette {
+    fn default() -> Palette {
+        Palette::GREYSCALE
+    }
+}
+
+/// Accepts `grey`, `green` or four custom colors like
+/// `e0f8d0,88c070,346856,081820`.
+impl FromStr for Palette {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Palette, String> {
+        match s {
+            "grey" | "gray" | "greyscale" | "grayscale" => return Ok(Palette::GREYSCALE),
+            "green" | "classic" => return Ok(Palette::CLASSIC_GREEN),
+            _ => {}
+        }
+
+        let colors: Vec<&str> = s.split(',').collect();
+        if colors.len() != 4 {
+            return Err(format!("Invalid palette: {}", s));
+        }
+        let mut palette = Palette::GREYSCALE;
+        for (color, text) in palette.colors.iter_mut().zip(colors) {
+            let text = text.trim().trim_start_matches('#');
+            let value = u32::from_str_radix(text, 16)
+                .ok()
+                .filter(|_| text.len() == 6)
+                .ok_or_else(|| format!("Invalid color: {}", text))?;
+            *color = [(value >> 16) as u8, (value >> 8) as u8, value as u8];
+        }
+        Ok(palette)
+    }
+}
+
+/// Scanline based picture processing unit. Timing follows the fixed mode
+/// lengths of a line without sprites: 80 dots OAM scan, 172 dots drawing
+/// and the rest HBlank. The whole line is rendered when drawing ends.
+#[derive(Debug)]
+pub struct Ppu {
+    dot: u32,        // Position within the current line
+    line: u8,        // Current line, mirrored to LY
+    window_line: u8, // Window lines drawn this frame
+    enabled: bool,
+    stat_line: bool, // STAT interrupts trigger on the rising edge
+    framebuffer: Vec<u8>,
+    frame_ready: bool,
+}
+
+impl Ppu {
+    pub fn new() -> Ppu {
+        Ppu {
+            dot: 0,
+            line: 0,
+            window_line: 0,
+            enabled: false,
+            stat_line: false,
+            framebuffer: vec![0; SCREEN_WIDTH * SCREEN_HEIGHT],
+            frame_ready: false,
+        }
+    }
+
+    /// Shades 0-3 of every pixel after applying the DMG palettes, row by row.
+    pub fn framebuffer(&self) -> &[u8] {
+        &self.framebuffer
+    }
+
+    /// Returns whether VBlank started since the last call.
+    pub fn take_frame_ready(&mut self) -> bool {
+        std::mem::take(&mut self.frame_ready)
+    }
+
+    pub fn mode(&self) -> Mode {
+        if !self.enabled {
+            Mode::HBlank
+        } else if self.line >= SCREEN_HEIGHT as u8 {
+            Mode::VBlank
+        } else if self.dot < OAM_SCAN_DOTS {
+            Mode::OamScan
+        } else if self.dot < OAM_SCAN_DOTS + DRAWING_DOTS {
+            Mode::Drawing
+        } else {
+            Mode::HBlank
+        }
+    }
+
+    pub fn tick(
+        &mut self,
+        cycles: u32,
+        vram: &[u8; VRAM_SIZE],
+        oam: &[u8; OAM_SIZE],
+        io: &mut [u8; IO_REGISTERS_SIZE],
+    ) {
+        if io[index(io_registers::LCDC)] & 0x80 == 0 {
+            if self.enabled {
+                // The screen goes blank and LY stays at 0 while the LCD is off
+                self.enabled = false;
+                self.dot = 0;
+                self.line = 0;
+                self.framebuffer.fill(0);
+                self.update_registers(io);
+            }
+            return;
+        }
+        if !self.enabled {
+            self.enabled = true;
+            self.window_line = 0;
+            self.update_registers(io);
+        }
+
+        for _ in 0..cycles {
+            self.dot += 1;
+            if self.line < SCREEN_HEIGHT as u8 && self.dot == OAM_SCAN_DOTS + DRAWING_DOTS {
+                self.render_line(vram, oam, io);
+            } else if self.dot == DOTS_PER_LINE {
+                self.dot = 0;
+                self.line = (self.line + 1) % LINES_PER_FRAME;
+                if self.line == SCREEN_HEIGHT as u8 {
+                    io[index(io_registers::IF)] |= VBLANK_INTERRUPT;
+                    self.frame_ready = true;
+                    self.window_line = 0;
+                }
+            } else if self.dot != OAM_SCAN_DOTS {
+                continue;
+            }
+            self.update_registers(io);
+        }
+    }
+
+    // Mirrors the mode and LY into STAT and LY and raises the STAT interrupt
+    fn update_registers(&mut self, io: &mut [u8; IO_REGISTERS_SIZE]) {
+        let mode = self.mode();
+        let coincidence = self.enabled && self.line == io[index(io_registers::LYC)];
+        let stat = &mut io[index(io_registers::STAT)];
+        *stat = (*stat & 0x78) | ((coincidence as u8) << 2) | mode as u8;
+
+        let stat_line = self.enabled
+            && ((*stat & 0x40 != 0 && coincidence)
+                || (*stat & 0x20 != 0 && mode == Mode::OamScan)
+                || (*stat & 0x10 != 0 && mode == Mode::VBlank)
+                || (*stat & 0x08 != 0 && mode == Mode::HBlank));
+        if stat_line && !self.stat_line {
+            io[index(io_registers::IF)] |= STAT_INTERRUPT;
+        }
+        self.stat_line = stat_line;
+        io[index(io_registers::LY)] = self.line;
+    }
+
+    fn render_line(
+        &mut self,
+        vram: &[u8; VRAM_SIZE],
+        oam: &[u8; OAM_SIZE],
+        io: &[u8; IO_REGISTERS_SIZE],
+    ) {
+        let register = |address: u16| io[index(address)];
+        let lcdc = register(io_registers::LCDC);
+        let line = self.line;
+        let mut background = [0u8; SCREEN_WIDTH]; // Color indices before the palette
+
+        // Bit 0 turns both the background and the window off on a DMG
+        if lcdc & 0x01 != 0 {
+            let (scx, scy) = (register(io_registers::SCX), register(io_registers::SCY));
+            let (wx, wy) = (register(io_registers::WX), register(io_registers::WY));
+            let window = lcdc & 0x20 != 0 && line >= wy && wx <= 166;
+            let background_map = if lcdc & 0x08 != 0 { 0x1C00 } else { 0x1800 };
+            let window_map = if lcdc & 0x40 != 0 { 0x1C00 } else { 0x1800 };
+
+            for (x, color) in background.iter_mut().enumerate() {
+                let x = x as u8;
+                *color = if window && x + 7 >= wx {
+                    tile_pixel(vram, lcdc, window_map, x + 7 - wx, self.window_line)
+                } else {
+                    tile_pixel(
+                        vram,
+                        lcdc,
+                        background_map,
+                        x.wrapping_add(scx),
+                        line.wrapping_add(scy),
+                    )
+                };
+            }
+            if window {
+                self.window_line += 1;
+            }
+        }
+
+        let bgp = register(io_registers::BGP);
+        let row = &mut self.framebuffer[line as usize * SCREEN_WIDTH..][..SCREEN_WIDTH];
+        for (pixel, color) in row.iter_mut().zip(background) {
+            *pixel = shade(bgp, color);
+        }
+
+        if lcdc & 0x02 != 0 {
+            let palettes = [register(io_registers::OBP0), register(io_registers::OBP1)];
+            render_sprites(row, &background, vram, oam, lcdc, line, palettes);
+        }
+    }
+}
+
+impl Default for Ppu {
+    fn default() -> Ppu {
+        Ppu::new()
+    }
+}
+
+fn render_sprites(
+    row: &mut [u8],
+    background: &[u8; SCREEN_WIDTH],
+    vram: &[u8; VRAM_SIZE],
+    oam: &[u8; OAM_SIZE],
+    lcdc: u8,
+    line: u8,
+    palettes: [u8; 2],
+) {
+    let height = if lcdc & 0x04 != 0 { 16 } else { 8 };
+    let mut sprites: Vec<&[u8]> = oam
+        .chunks(4)
+        .filter(|sprite| {
+            let top = sprite[0] as i16 - 16;
+            (top..top + height).contains(&(line as i16))
+        })
+        .take(SPRITES_PER_LINE)
+        .collect();
+    // The sprite with the smaller X wins, then the one earlier in OAM
+    sprites.sort_by_key(|sprite| sprite[1]);
+
+    let mut occupied = [false; SCREEN_WIDTH];
+    for sprite in sprites {
+        let (y, x, tile, attributes) = (sprite[0], sprite[1], sprite[2], sprite[3]);
+        let mut sprite_line = (line as i16 - (y as i16 - 16)) as u16;
+        if attributes & 0x40 != 0 {
+            sprite_line = height as u16 - 1 - sprite_line;
+        }
+        let tile = if height == 16 { tile & 0xFE } else { tile };
+        let address = tile as usize * 16 + sprite_line as usize * 2;
+        let (low, high) = (vram[address], vram[address + 1]);
+
+        for column in 0..8u8 {
+            let screen_x = x as i16 - 8 + column as i16;
+            if !(0..SCREEN_WIDTH as i16).contains(&screen_x) || occupied[screen_x as usize] {
+                continue;
+            }
+            let bit = if attributes & 0x20 != 0 {
+                column
+            } else {
+                7 - column
+            };
+            let color = ((high >> bit) & 1) << 1 | ((low >> bit) & 1);
+            if color == 0 {
+                continue;
+            }
+            // A hidden sprite pixel still hides lower priority sprites
+            let screen_x = screen_x as usize;
+            occupied[screen_x] = true;
+            if attributes & 0x80 != 0 && background[screen_x] != 0 {
+                continue;
+            }
+            let palette = palettes[(attributes >> 4 & 1) as usize];
+            row[screen_x] = shade(palette, color);
+        }
+    }
+}
+
+// Color index 0-3 of a background or window pixel
+fn tile_pixel(vram: &[u8; VRAM_SIZE], lcdc: u8, map: usize, x: u8, y: u8) -> u8 {
+    let tile = vram[map + (y as usize / 8) * 32 + x as usize / 8];
+    let tile_address = if lcdc & 0x10 != 0 {
+        tile as usize * 16
+    } else {
+        (0x1000 + tile as i8 as i32 * 16) as usize
+    };
+    let address = tile_address + (y as usize % 8) * 2;
+    let bit = 7 - x % 8;
+    ((vram[address + 1] >> bit) & 1) << 1 | ((vram[address] >> bit) & 1)
+}
+
+fn shade(palette: u8, color: u8) -> u8 {
+    (palette >> (color * 2)) & 0x03
+}
+
+fn index(address: u16) -> usize {
+    address as usize - IO_REGISTERS_BEGIN
+}
+
+impl Snapshot for Ppu {
+    fn save(&self, writer: &mut StateWriter) {
+        writer.write_u16(self.dot as u16);
+        writer.write_u8(self.line);
+        writer.write_u8(self.window_line);
+        writer.write_bool(self.enabled);
+        writer.write_bool(self.stat_line);
+        writer.write_bytes(&self.framebuffer);
+    }
+
+    fn load(&mut self, reader: &mut StateReader) -> Result<(), SaveStateError> {
+        self.dot = reader.read_u16()? as u32;
+        self.line = reader.read_u8()?;
+        self.window_line = reader.read_u8()?;
+        self.enabled = reader.read_bool()?;
+        self.stat_line = reader.read_bool()?;
+        reader.read_into(&mut self.framebuffer)?;
+        Ok(())
+    }
+}
+
+/// Converts shades to packed RGB.
+pub fn to_rgb(framebuffer: &[u8], palette: &Palette) -> Vec<u8> {
+    framebuffer
+        .iter()
+        .flat_map(|shade| palette.colors[*shade as usize & 0x03])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Screen {
+        ppu: Ppu,
+        vram: Box<[u8; VRAM_SIZE]>,
+        oam: Box<[u8; OAM_SIZE]>,
+        io: [u8; IO_REGISTERS_SIZE],
+    }
+
+    impl Screen {
+        fn new(lcdc: u8) -> Screen {
+            let mut io = [0; IO_REGISTERS_SIZE];
+            io[index(io_registers::LCDC)] = lcdc;
+            io[index(io_registers::BGP)] = 0b1110_0100;
+            io[index(io_registers::OBP0)] = 0b1110_0100;
+            Screen {
+                ppu: Ppu::new(),
+                vram: Box::new([0; VRAM_SIZE]),
+                oam: Box::new([0; OAM_SIZE]),
+                io,
+            }
+        }
+
+        fn tick(&mut self, cycles: u32) {
+            self.ppu.tick(cycles, &self.vram, &self.oam, &mut self.io);
+        }
+
+        fn pixel(&self, x: usize, y: usize) -> u8 {
+            self.ppu.framebuffer()[y * SCREEN_WIDTH + x]
+        }
+    }
+
+    #[test]
+    fn test_line_timing() {
+        let mut screen = Screen::new(0x80);
+        screen.tick(1);
+        assert_eq!(screen.ppu.mode(), Mode::OamScan);
+        screen.tick(OAM_SCAN_DOTS);
+        assert_eq!(screen.ppu.mode(), Mode::Drawing);
+        assert_eq!(screen.io[index(io_registers::STAT)] & 0x03, 3);
+        screen.tick(DRAWING_DOTS);
+        assert_eq!(screen.ppu.mode(), Mode::HBlank);
+        screen.tick(DOTS_PER_LINE - OAM_SCAN_DOTS - DRAWING_DOTS - 1);
+        assert_eq!(screen.io[index(io_registers::LY)], 1);
+    }
+
+    #[test]
+    fn test_vblank() {
+        let mut screen = Screen::new(0x80);
+        screen.tick(DOTS_PER_LINE * 144);
+        assert_eq!(screen.ppu.mode(), Mode::VBlank);
+        assert_eq!(
+            screen.io[index(io_registers::IF)] & VBLANK_INTERRUPT,
+            VBLANK_INTERRUPT
+        );
+        assert!(screen.ppu.take_frame_ready());
+        assert!(!screen.ppu.take_frame_ready());
+
+        screen.tick(DOTS_PER_LINE * 10);
+        assert_eq!(screen.io[index(io_registers::LY)], 0);
+        assert_eq!(screen.ppu.mode(), Mode::OamScan);
+    }
+
+    #[test]
+    fn test_lyc_interrupt() {
+        let mut screen = Screen::new(0x80);
+        screen.io[index(io_registers::LYC)] = 2;
+        screen.io[index(io_registers::STAT)] = 0x40;
+        screen.tick(DOTS_PER_LINE * 2);
+        assert_eq!(screen.io[index(io_registers::STAT)] & 0x04, 0x04);
+        assert_eq!(
+            screen.io[index(io_registers::IF)] & STAT_INTERRUPT,
+            STAT_INTERRUPT
+        );
+    }
+
+    #[test]
+    fn test_lcd_off() {
+        let mut screen = Screen::new(0x80);
+        screen.tick(DOTS_PER_LINE * 3);
+        screen.io[index(io_registers::LCDC)] = 0x00;
+        screen.tick(DOTS_PER_LINE);
+        assert_eq!(screen.io[index(io_registers::LY)], 0);
+        assert_eq!(screen.ppu.mode(), Mode::HBlank);
+    }
+
+    #[test]
+    fn test_render_background() {
+        // Tile 1 is a solid block of color 3, placed at map position (1, 0)
+        let mut screen = Screen::new(0x91);
+        screen.vram[0x10..0x20].fill(0xFF);
+        screen.vram[0x1801] = 1;
+        screen.tick(DOTS_PER_LINE * 8);
+        assert_eq!(screen.pixel(7, 0), 0);
+        assert_eq!(screen.pixel(8, 0), 3);
+        assert_eq!(screen.pixel(15, 7), 3);
+        assert_eq!(screen.pixel(16, 7), 0);
+
+        // Scrolling moves the block left
+        screen.io[index(io_registers::SCX)] = 4;
+        screen.tick(DOTS_PER_LINE * 154);
+        assert_eq!(screen.pixel(4, 0), 3);
+        assert_eq!(screen.pixel(12, 0), 0);
+    }
+
+    #[test]
+    fn test_render_signed_tile_data() {
+        let mut screen = Screen::new(0x81);
+        screen.vram[0x1000..0x1010].fill(0xFF);
+        screen.tick(DOTS_PER_LINE);
+        assert_eq!(screen.pixel(0, 0), 3);
+    }
+
+    #[test]
+    fn test_render_sprite() {
+        // Sprite with color 1 at the top left corner, X flipped half tile
+        let mut screen = Screen::new(0x93);
+        screen.vram[0x20] = 0xF0;
+        screen.oam[0..4].copy_from_slice(&[16, 8, 2, 0x20]);
+        screen.tick(DOTS_PER_LINE);
+        assert_eq!(screen.pixel(0, 0), 0);
+        assert_eq!(screen.pixel(4, 0), 1);
+        assert_eq!(screen.pixel(7, 0), 1);
+        assert_eq!(screen.pixel(8, 0), 0);
+    }
+
+    #[test]
+    fn test_sprite_behind_background() {
+        let mut screen = Screen::new(0x93);
+        screen.vram[0x10..0x20].fill(0xFF); // Background tile 1
+        screen.vram[0x1800] = 1;
+        screen.vram[0x20] = 0xFF; // Sprite tile 2
+        screen.oam[0..4].copy_from_slice(&[16, 8, 2, 0x80]);
+        screen.oam[4..8].copy_from_slice(&[16, 16, 2, 0x80]);
+        screen.tick(DOTS_PER_LINE);
+        assert_eq!(screen.pixel(0, 0), 3);
+        assert_eq!(screen.pixel(8, 0), 1);
+    }
+
+    #[test]
+    fn test_parse_palette() {
+        assert_eq!("green".parse(), Ok(Palette::CLASSIC_GREEN));
+        let palette: Palette = "ffffff,#aaaaaa,555555,000000".parse().unwrap();
+        assert_eq!(palette, Palette::GREYSCALE);
+        assert!("ffffff,aaaaaa".parse::<Palette>().is_err());
+        assert!("fffff,aaaaaa,555555,000000".parse::<Palette>().is_err());
+    }
+
+    #[test]
+    fn test_to_rgb() {
+        assert_eq!(
+            to_rgb(&[0, 3], &Palette::GREYSCALE),
+            vec![0xFF, 0xFF, 0xFF, 0, 0, 0]
+        );
+    }
+}
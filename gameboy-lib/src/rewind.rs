@@ -0,0 +1,366 @@
+use std::collections::VecDeque;
+
+use crate::{save_state::SaveStateError, Gameboy};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RewindConfig {
+    pub interval: u32,          // Frames between two snapshots
+    pub keyframe_interval: u32, // Snapshots between two full keyframes
+    pub budget: usize,          // Maximum memory used by snapshots in bytes
+}
+
+impl Default for RewindConfig {
+    fn default() -> RewindConfig {
+        RewindConfig {
+            interval: 4,
+            keyframe_interval: 32,
+            budget: 16 * 1024 * 1024,
+        }
+    }
+}
+
+/// A keyframe and the snapshots that were XORed against it.
+#[derive(Debug)]
+struct Group {
+    keyframe: Vec<u8>,    // RLE-compressed save state
+    deltas: Vec<Vec<u8>>, // RLE-compressed XOR against the keyframe
+}
+
+impl Group {
+    fn size(&self) -> usize {
+        self.keyframe.len() + self.deltas.iter().map(Vec::len).sum::<usize>()
+    }
+}
+
+/// Ring buffer of delta-compressed save states. The oldest keyframe and its
+/// deltas are dropped once the memory usage exceeds the budget.
+#[derive(Debug)]
+pub struct Rewind {
+    config: RewindConfig,
+    groups: VecDeque<Group>,
+    keyframe: Vec<u8>, // Uncompressed keyframe of the newest group
+    frames: u32,
+    size: usize, // Compressed size of all groups
+}
+
+impl Rewind {
+    pub fn new(config: RewindConfig) -> Rewind {
+        Rewind {
+            config,
+            groups: VecDeque::new(),
+            keyframe: Vec::new(),
+            frames: 0,
+            size: 0,
+        }
+    }
+
+    pub fn config(&self) -> RewindConfig {
+        self.config
+    }
+
+    /// Number of snapshots that can be stepped back.
+    pub fn len(&self) -> usize {
+        self.groups.iter().map(|group| 1 + group.deltas.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    /// Size of all compressed snapshots plus the uncompressed keyframe the
+    /// newest deltas are decoded against, in bytes.
+    pub fn memory_usage(&self) -> usize {
+        self.size + self.keyframe.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.groups.clear();
+        self.keyframe.clear();
+        self.frames = 0;
+        self.size = 0;
+    }
+
+    /// Counts a frame and returns whether a snapshot is due.
+    fn tick(&mut self) -> bool {
+        self.frames += 1;
+        if self.frames >= self.config.interval.max(1) {
+            self.frames = 0;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn push(&mut self, state: Vec<u8>) {
+        let keyframe_due = match self.groups.back() {
+            Some(group) => {
+                group.deltas.len() + 1 >= self.config.keyframe_interval.max(1) as usize
+                    || state.len() != self.keyframe.len()
+            }
+            None => true,
+        };
+
+        if keyframe_due {
+            let keyframe = rle_encode(&state);
+            self.size += keyframe.len();
+            self.groups.push_back(Group {
+                keyframe,
+                deltas: Vec::new(),
+            });
+            self.keyframe = state;
+        } else {
+            let delta = rle_encode(&xor(&state, &self.keyframe));
+            self.size += delta.len();
+            self.groups.back_mut().unwrap().deltas.push(delta);
+        }
+
+        // Always keep the newest group, even if it alone exceeds the budget
+        while self.memory_usage() > self.config.budget && self.groups.len() > 1 {
+            let group = self.groups.pop_front().unwrap();
+            self.size -= group.size();
+        }
+    }
+
+    /// Removes and returns the newest snapshot.
+    pub fn pop(&mut self) -> Option<Vec<u8>> {
+        self.frames = 0;
+        let group = self.groups.back_mut()?;
+        if let Some(delta) = group.deltas.pop() {
+            self.size -= delta.len();
+            return Some(xor(&rle_decode(&delta), &self.keyframe));
+        }
+
+        let group = self.groups.pop_back().unwrap();
+        self.size -= group.size();
+        let state = std::mem::take(&mut self.keyframe);
+        if let Some(previous) = self.groups.back() {
+            self.keyframe = rle_decode(&previous.keyframe);
+        }
+        Some(state)
+    }
+}
+
+fn xor(a: &[u8], b: &[u8]) -> Vec<u8> {
+    a.iter().zip(b).map(|(a, b)| a ^ b).collect()
+}
+
+// PackBits-style run length encoding. A control byte below 0x80 is followed
+// by that many plus one literal bytes, otherwise the next byte is repeated
+// (control - 0x80 + MIN_RUN) times.
+const MIN_RUN: usize = 3;
+const MAX_RUN: usize = 0x7F + MIN_RUN;
+const MAX_LITERALS: usize = 0x80;
+
+fn rle_encode(data: &[u8]) -> Vec<u8> {
+    let mut encoded = Vec::new();
+    let mut literals_start = 0;
+    let mut i = 0;
+
+    let flush_literals = |encoded: &mut Vec<u8>, literals: &[u8]| {
+        for chunk in literals.chunks(MAX_LITERALS) {
+            encoded.push((chunk.len() - 1) as u8);
+            encoded.extend_from_slice(chunk);
+        }
+    };
+
+    while i < data.len() {
+        let run = data[i..]
+            .iter()
+            .take(MAX_RUN)
+            .take_while(|byte| **byte == data[i])
+            .count();
+        if run >= MIN_RUN {
+            flush_literals(&mut encoded, &data[literals_start..i]);
+            encoded.push((run - MIN_RUN + 0x80) as u8);
+            encoded.push(data[i]);
+            i += run;
+            literals_start = i;
+        } else {
+            i += 1;
+        }
+    }
+    flush_literals(&mut encoded, &data[literals_start..]);
+    encoded
+}
+
+fn rle_decode(data: &[u8]) -> Vec<u8> {
+    let mut decoded = Vec::new();
+    let mut i = 0;
+    while i < data.len() {
+        let control = data[i] as usize;
+        if control < 0x80 {
+            decoded.extend_from_slice(&data[i + 1..i + 2 + control]);
+            i += 2 + control;
+        } else {
+            let run = control - 0x80 + MIN_RUN;
+            decoded.extend(std::iter::repeat_n(data[i + 1], run));
+            i += 2;
+        }
+    }
+    decoded
+}
+
+impl Gameboy {
+    /// Starts recording snapshots, replacing any previous rewind buffer.
+    pub fn enable_rewind(&mut self, config: RewindConfig) {
+        self.rewind = Some(Rewind::new(config));
+    }
+
+    pub fn disable_rewind(&mut self) {
+        self.rewind = None;
+    }
+
+    pub fn rewind_buffer(&self) -> Option<&Rewind> {
+        self.rewind.as_ref()
+    }
+
+    /// Called by `run_frame` at the end of every frame, takes a snapshot
+    /// every `RewindConfig::interval` frames.
+    pub(crate) fn capture_rewind_frame(&mut self) {
+        let due = match self.rewind.as_mut() {
+            Some(rewind) => rewind.tick(),
+            None => false,
+        };
+        if due {
+            let state = self.save_state();
+            self.rewind.as_mut().unwrap().push(state);
+        }
+    }
+
+    /// Restores the newest snapshot and removes it from the buffer, so
+    /// repeated calls step further back. Returns false if there is nothing
+    /// left to rewind to.
+    pub fn rewind(&mut self) -> Result<bool, SaveStateError> {
+        let Some(state) = self.rewind.as_mut().and_then(Rewind::pop) else {
+            return Ok(false);
+        };
+        self.load_state(&state)?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Model;
+
+    #[test]
+    fn test_rle_round_trip() {
+        let mut data = vec![0; 1000];
+        data.extend_from_slice(&[1, 2, 3, 3, 4, 4, 4, 4]);
+        data.extend((0..=255).collect::<Vec<u8>>());
+        data.extend_from_slice(&[7, 7]);
+
+        let encoded = rle_encode(&data);
+        assert!(encoded.len() < data.len());
+        assert_eq!(rle_decode(&encoded), data);
+        assert_eq!(rle_decode(&rle_encode(&[])), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn test_rle_compresses_runs() {
+        assert_eq!(rle_encode(&[0; 5]), vec![0x82, 0x00]);
+        assert_eq!(rle_encode(&[1, 2]), vec![0x01, 1, 2]);
+    }
+
+    #[test]
+    fn test_push_pop_in_reverse_order() {
+        let mut rewind = Rewind::new(RewindConfig {
+            interval: 1,
+            keyframe_interval: 3,
+            budget: usize::MAX,
+        });
+        let states: Vec<Vec<u8>> = (0..7u8).map(|i| vec![i; 64]).collect();
+        for state in &states {
+            rewind.push(state.clone());
+        }
+        assert_eq!(rewind.len(), 7);
+        assert_eq!(rewind.groups.len(), 3);
+
+        for state in states.iter().rev() {
+            assert_eq!(rewind.pop().as_ref(), Some(state));
+        }
+        assert_eq!(rewind.pop(), None);
+        assert_eq!(rewind.memory_usage(), 0);
+    }
+
+    #[test]
+    fn test_budget_drops_oldest_group() {
+        let mut rewind = Rewind::new(RewindConfig {
+            interval: 1,
+            keyframe_interval: 2,
+            budget: 208,
+        });
+        for i in 0..6u8 {
+            rewind.push(vec![i; 200]);
+        }
+        assert_eq!(rewind.groups.len(), 1);
+        assert!(rewind.memory_usage() <= 208);
+        assert_eq!(rewind.pop(), Some(vec![5; 200]));
+        assert_eq!(rewind.pop(), Some(vec![4; 200]));
+        assert_eq!(rewind.pop(), None);
+    }
+
+    #[test]
+    fn test_budget_counts_keyframe() {
+        let mut rewind = Rewind::new(RewindConfig {
+            interval: 1,
+            keyframe_interval: 1,
+            budget: usize::MAX,
+        });
+        rewind.push(vec![0; 200]);
+        assert_eq!(rewind.memory_usage(), 200 + 4);
+        rewind.push(vec![1; 300]);
+        assert_eq!(rewind.memory_usage(), 300 + 4 + 6);
+        rewind.pop();
+        assert_eq!(rewind.memory_usage(), 200 + 4);
+    }
+
+    #[test]
+    fn test_gameboy_rewind() {
+        // INC A; JR -3
+        let mut gameboy = Gameboy::new(vec![], vec![0x3C, 0x18, 0xFD]);
+        gameboy.reset();
+        gameboy.set_pc(0);
+        gameboy.enable_rewind(RewindConfig {
+            interval: 2,
+            ..RewindConfig::default()
+        });
+
+        // Treat every INC A as a frame
+        let mut captured = Vec::new();
+        for frame in 1..=6 {
+            gameboy.step();
+            gameboy.step();
+            gameboy.capture_rewind_frame();
+            if frame % 2 == 0 {
+                captured.push(gameboy.registers().a);
+            }
+        }
+        assert_eq!(gameboy.rewind_buffer().unwrap().len(), 3);
+
+        for a in captured.iter().rev() {
+            assert_eq!(gameboy.rewind(), Ok(true));
+            assert_eq!(gameboy.registers().a, *a);
+        }
+        assert_eq!(gameboy.rewind(), Ok(false));
+    }
+
+    #[test]
+    fn test_gameboy_rewind_reports_load_error() {
+        let mut gameboy = Gameboy::new(vec![], vec![0x00]);
+        gameboy.enable_rewind(RewindConfig {
+            interval: 1,
+            ..RewindConfig::default()
+        });
+        gameboy.capture_rewind_frame();
+        gameboy.set_model(Model::Cgb);
+        assert_eq!(
+            gameboy.rewind(),
+            Err(SaveStateError::ModelMismatch {
+                expected: Model::Cgb,
+                found: Model::Dmg
+            })
+        );
+    }
+}
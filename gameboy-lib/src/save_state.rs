@@ -0,0 +1,466 @@
+use std::fmt;
+
+use crate::{
+    cpu::{registers::Registers, Cpu},
+    model::{Model, MODELS},
+    Gameboy,
+};
+
+/// Save states start with a fixed header followed by tagged sections:
+///
+/// ```text
+/// "GBSS"            magic
+/// u16               format version
+/// u32               CRC-32 of the game ROM the state was taken from
+/// u8                hardware model, an index into `model::MODELS`
+/// [u8; 4] u32 [..]  section tag, payload length and payload, repeated
+/// ```
+///
+/// All integers are little endian. Every component with internal state gets
+/// its own section, adding or changing one requires a version bump. The
+/// memory section holds the flat address space, cartridge RAM banks and MBC
+/// registers get a section of their own once an MBC is emulated.
+pub const MAGIC: &[u8; 4] = b"GBSS";
+pub const VERSION: u16 = 1;
+
+const CPU_SECTION: &[u8; 4] = b"CPU ";
+const MEMORY_SECTION: &[u8; 4] = b"MEM ";
+const PPU_SECTION: &[u8; 4] = b"PPU ";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SaveStateError {
+    InvalidMagic,
+    UnsupportedVersion(u16),
+    RomMismatch { expected: u32, found: u32 },
+    ModelMismatch { expected: Model, found: Model },
+    InvalidModel(u8),
+    MissingSection([u8; 4]),
+    UnknownSection([u8; 4]),
+    InvalidSectionLength([u8; 4]),
+    Truncated,
+}
+
+impl fmt::Display for SaveStateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveStateError::InvalidMagic => write!(f, "Not a save state"),
+            SaveStateError::UnsupportedVersion(version) => write!(
+                f,
+                "Unsupported save state version {} (expected {})",
+                version, VERSION
+            ),
+            SaveStateError::RomMismatch { expected, found } => write!(
+                f,
+                "Save state belongs to a different ROM (checksum {:08X}, loaded ROM is {:08X})",
+                found, expected
+            ),
+            SaveStateError::ModelMismatch { expected, found } => write!(
+                f,
+                "Save state was taken on a different model ({}, running {})",
+                found.name(),
+                expected.name()
+            ),
+            SaveStateError::InvalidModel(model) => {
+                write!(f, "Save state has an invalid model {}", model)
+            }
+            SaveStateError::MissingSection(tag) => {
+                write!(f, "Save state is missing the {} section", tag_name(tag))
+            }
+            SaveStateError::UnknownSection(tag) => {
+                write!(f, "Save state contains unknown section {}", tag_name(tag))
+            }
+            SaveStateError::InvalidSectionLength(tag) => {
+                write!(
+                    f,
+                    "Save state section {} has an invalid length",
+                    tag_name(tag)
+                )
+            }
+            SaveStateError::Truncated => write!(f, "Save state is truncated"),
+        }
+    }
+}
+
+impl std::error::Error for SaveStateError {}
+
+fn tag_name(tag: &[u8; 4]) -> String {
+    String::from_utf8_lossy(tag).trim_end().to_string()
+}
+
+/// Implemented by every component that is part of a save state.
+pub(crate) trait Snapshot {
+    fn save(&self, writer: &mut StateWriter);
+    fn load(&mut self, reader: &mut StateReader) -> Result<(), SaveStateError>;
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct StateWriter {
+    buffer: Vec<u8>,
+}
+
+impl StateWriter {
+    pub fn write_u8(&mut self, value: u8) {
+        self.buffer.push(value);
+    }
+
+    pub fn write_bool(&mut self, value: bool) {
+        self.write_u8(value as u8);
+    }
+
+    pub fn write_u16(&mut self, value: u16) {
+        self.buffer.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_u32(&mut self, value: u32) {
+        self.buffer.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_u64(&mut self, value: u64) {
+        self.buffer.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.buffer
+    }
+
+    fn write_section(&mut self, tag: &[u8; 4], component: &impl Snapshot) {
+        let mut section = StateWriter::default();
+        component.save(&mut section);
+        self.write_bytes(tag);
+        self.write_u32(section.buffer.len() as u32);
+        self.write_bytes(&section.buffer);
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct StateReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> StateReader<'a> {
+    pub fn new(data: &'a [u8]) -> StateReader<'a> {
+        StateReader { data }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn read_bytes(&mut self, length: usize) -> Result<&'a [u8], SaveStateError> {
+        if self.data.len() < length {
+            return Err(SaveStateError::Truncated);
+        }
+        let (bytes, rest) = self.data.split_at(length);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    pub fn read_into(&mut self, destination: &mut [u8]) -> Result<(), SaveStateError> {
+        destination.copy_from_slice(self.read_bytes(destination.len())?);
+        Ok(())
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, SaveStateError> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    pub fn read_bool(&mut self) -> Result<bool, SaveStateError> {
+        Ok(self.read_u8()? != 0)
+    }
+
+    pub fn read_u16(&mut self) -> Result<u16, SaveStateError> {
+        let mut bytes = [0; 2];
+        self.read_into(&mut bytes)?;
+        Ok(u16::from_le_bytes(bytes))
+    }
+
+    pub fn read_u32(&mut self) -> Result<u32, SaveStateError> {
+        let mut bytes = [0; 4];
+        self.read_into(&mut bytes)?;
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    pub fn read_u64(&mut self) -> Result<u64, SaveStateError> {
+        let mut bytes = [0; 8];
+        self.read_into(&mut bytes)?;
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    fn read_section(&mut self) -> Result<([u8; 4], StateReader<'a>), SaveStateError> {
+        let mut tag = [0; 4];
+        self.read_into(&mut tag)?;
+        let length = self.read_u32()? as usize;
+        Ok((tag, StateReader::new(self.read_bytes(length)?)))
+    }
+}
+
+impl Snapshot for Registers {
+    fn save(&self, writer: &mut StateWriter) {
+        for value in [
+            self.a,
+            self.f.get(),
+            self.b,
+            self.c,
+            self.d,
+            self.e,
+            self.h,
+            self.l,
+        ] {
+            writer.write_u8(value);
+        }
+        writer.write_u16(self.sp.get());
+    }
+
+    fn load(&mut self, reader: &mut StateReader) -> Result<(), SaveStateError> {
+        self.a = reader.read_u8()?;
+        self.f.set(reader.read_u8()?);
+        self.b = reader.read_u8()?;
+        self.c = reader.read_u8()?;
+        self.d = reader.read_u8()?;
+        self.e = reader.read_u8()?;
+        self.h = reader.read_u8()?;
+        self.l = reader.read_u8()?;
+        self.sp.set(reader.read_u16()?);
+        Ok(())
+    }
+}
+
+// The memory is stored in its own section, see `Gameboy::save_state`
+impl Snapshot for Cpu {
+    fn save(&self, writer: &mut StateWriter) {
+        self.registers.save(writer);
+        writer.write_u16(self.pc);
+        writer.write_bool(self.interrupts_enabled);
+        writer.write_u64(self.cycles);
+        writer.write_bool(self.locked_up);
+    }
+
+    fn load(&mut self, reader: &mut StateReader) -> Result<(), SaveStateError> {
+        self.registers.load(reader)?;
+        self.pc = reader.read_u16()?;
+        self.interrupts_enabled = reader.read_bool()?;
+        self.cycles = reader.read_u64()?;
+        self.locked_up = reader.read_bool()?;
+        Ok(())
+    }
+}
+
+/// CRC-32 (IEEE) used to tie a save state to its ROM.
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+impl Gameboy {
+    /// Serializes the complete machine state.
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut writer = StateWriter::default();
+        writer.write_bytes(MAGIC);
+        writer.write_u16(VERSION);
+        writer.write_u32(crc32(&self.game_rom));
+        writer.write_u8(self.model as u8);
+        writer.write_section(CPU_SECTION, &self.cpu);
+        writer.write_section(MEMORY_SECTION, &self.cpu.bus);
+        writer.write_section(PPU_SECTION, &self.cpu.bus.ppu);
+        writer.buffer
+    }
+
+    /// Restores a state produced by `save_state`. The machine is left
+    /// untouched if the state is invalid or was taken with a different ROM.
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), SaveStateError> {
+        let mut reader = StateReader::new(data);
+        if reader.read_bytes(MAGIC.len()).ok() != Some(MAGIC.as_slice()) {
+            return Err(SaveStateError::InvalidMagic);
+        }
+        let version = reader.read_u16()?;
+        if version != VERSION {
+            return Err(SaveStateError::UnsupportedVersion(version));
+        }
+        let found = reader.read_u32()?;
+        let expected = crc32(&self.game_rom);
+        if found != expected {
+            return Err(SaveStateError::RomMismatch { expected, found });
+        }
+        let model = reader.read_u8()?;
+        let found = *MODELS
+            .get(model as usize)
+            .ok_or(SaveStateError::InvalidModel(model))?;
+        if found != self.model {
+            return Err(SaveStateError::ModelMismatch {
+                expected: self.model,
+                found,
+            });
+        }
+
+        let mut cpu = Cpu::new();
+        let mut missing = vec![CPU_SECTION, MEMORY_SECTION, PPU_SECTION];
+        while !reader.is_empty() {
+            let (tag, mut section) = reader.read_section()?;
+            match &tag {
+                CPU_SECTION => cpu.load(&mut section)?,
+                MEMORY_SECTION => cpu.bus.load(&mut section)?,
+                PPU_SECTION => cpu.bus.ppu.load(&mut section)?,
+                _ => return Err(SaveStateError::UnknownSection(tag)),
+            }
+            if !section.is_empty() {
+                return Err(SaveStateError::InvalidSectionLength(tag));
+            }
+            missing.retain(|section| **section != tag);
+        }
+        if let Some(tag) = missing.first() {
+            return Err(SaveStateError::MissingSection(**tag));
+        }
+
+        // Serial output is a host side log, keep it across loads
+        cpu.bus.serial_output = std::mem::take(&mut self.cpu.bus.serial_output);
+        cpu.tracer = self.cpu.tracer.take();
+        cpu.bus.watchpoints = std::mem::take(&mut self.cpu.bus.watchpoints);
+        cpu.illegal_opcode_policy = self.cpu.illegal_opcode_policy;
+        self.cpu = cpu;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gameboy() -> Gameboy {
+        // LD A, 0x42; LD (0xC000), A; INC A; JR -3
+        let rom = vec![0x3E, 0x42, 0xEA, 0x00, 0xC0, 0x3C, 0x18, 0xFD];
+        let mut gameboy = Gameboy::new(vec![], rom);
+        gameboy.reset();
+        gameboy.set_pc(0);
+        gameboy
+    }
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn test_round_trip_resumes_identically() {
+        let mut gameboy = gameboy();
+        for _ in 0..5 {
+            gameboy.step();
+        }
+        let state = gameboy.save_state();
+        for _ in 0..10 {
+            gameboy.step();
+        }
+        let expected = gameboy.save_state();
+
+        let mut restored = self::gameboy();
+        restored.load_state(&state).unwrap();
+        assert_eq!(restored.save_state(), state);
+        for _ in 0..10 {
+            restored.step();
+        }
+        assert_eq!(restored.save_state(), expected);
+        assert_eq!(restored.registers().a, gameboy.registers().a);
+        assert_eq!(restored.read_memory(0xC000), gameboy.read_memory(0xC000));
+    }
+
+    #[test]
+    fn test_header() {
+        let state = gameboy().save_state();
+        assert_eq!(&state[0..4], MAGIC);
+        assert_eq!(u16::from_le_bytes([state[4], state[5]]), VERSION);
+    }
+
+    #[test]
+    fn test_rejects_other_rom() {
+        let state = gameboy().save_state();
+        let mut other = Gameboy::new(vec![], vec![0x00]);
+        assert!(matches!(
+            other.load_state(&state),
+            Err(SaveStateError::RomMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_rejects_other_model() {
+        let mut gameboy = gameboy();
+        let mut state = gameboy.save_state();
+        gameboy.set_model(Model::Cgb);
+        assert_eq!(
+            gameboy.load_state(&state),
+            Err(SaveStateError::ModelMismatch {
+                expected: Model::Cgb,
+                found: Model::Dmg
+            })
+        );
+
+        state[10] = 0x04;
+        assert_eq!(
+            gameboy.load_state(&state),
+            Err(SaveStateError::InvalidModel(0x04))
+        );
+    }
+
+    #[test]
+    fn test_rejects_invalid_data() {
+        let mut gameboy = gameboy();
+        let mut state = gameboy.save_state();
+
+        assert_eq!(
+            gameboy.load_state(b"nope"),
+            Err(SaveStateError::InvalidMagic)
+        );
+        assert_eq!(
+            gameboy.load_state(&state[..state.len() - 1]),
+            Err(SaveStateError::Truncated)
+        );
+
+        state[4] = 0xFF;
+        assert_eq!(
+            gameboy.load_state(&state),
+            Err(SaveStateError::UnsupportedVersion(0x00FF))
+        );
+    }
+
+    #[test]
+    fn test_lockup_is_restored() {
+        // NOP; illegal opcode
+        let mut gameboy = Gameboy::new(vec![], vec![0x00, 0xED]);
+        gameboy.reset();
+        gameboy.set_pc(0);
+        let running = gameboy.save_state();
+        gameboy.step();
+        gameboy.step();
+        assert!(gameboy.is_locked_up());
+        let locked_up = gameboy.save_state();
+
+        gameboy.load_state(&running).unwrap();
+        assert!(!gameboy.is_locked_up());
+        gameboy.load_state(&locked_up).unwrap();
+        assert!(gameboy.is_locked_up());
+    }
+
+    #[test]
+    fn test_failed_load_leaves_machine_untouched() {
+        let mut gameboy = gameboy();
+        gameboy.step();
+        let before = gameboy.save_state();
+        let mut state = before.clone();
+        state.truncate(20);
+        assert!(gameboy.load_state(&state).is_err());
+        assert_eq!(gameboy.save_state(), before);
+    }
+}
@@ -0,0 +1,382 @@
+use std::{
+    fmt::Write,
+    panic::{self, AssertUnwindSafe},
+    time::{Duration, Instant},
+};
+
+use crate::{error::IllegalOpcodePolicy, memory, Gameboy, CYCLES_PER_FRAME};
+
+/// Registers Mooneye tests load before executing `LD B, B` on success.
+const MOONEYE_PASS: [u8; 6] = [3, 5, 8, 13, 21, 34];
+const MOONEYE_FAIL: [u8; 6] = [0x42; 6];
+const MOONEYE_DEBUG_OPCODE: u8 = 0x40; // LD B, B
+
+/// Blargg tests put a signature after the status byte at 0xA000 and the
+/// result text at 0xA004. The status is 0x80 while the test is running.
+const BLARGG_STATUS: u16 = 0xA000;
+const BLARGG_SIGNATURE: [u8; 3] = [0xDE, 0xB0, 0x61];
+const BLARGG_TEXT: u16 = 0xA004;
+const BLARGG_RUNNING: u8 = 0x80;
+
+pub const DEFAULT_CYCLE_BUDGET: u64 = 120 * 60 * CYCLES_PER_FRAME; // Two minutes
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TestOutcome {
+    Passed,
+    Failed(String),
+    TimedOut,
+    Error(String), // The emulator stopped with an error or panicked
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestRomResult {
+    pub name: String,
+    pub outcome: TestOutcome,
+    pub cycles: u64,
+    pub serial: String,
+    pub duration: Duration,
+}
+
+impl TestRomResult {
+    pub fn passed(&self) -> bool {
+        self.outcome == TestOutcome::Passed
+    }
+}
+
+/// Runs a Blargg or Mooneye test ROM headless, starting in the post-boot
+/// state, until it reports a result or `cycle_budget` T-cycles have passed.
+pub fn run_test_rom(name: &str, rom: Vec<u8>, cycle_budget: u64) -> TestRomResult {
+    let started = Instant::now();
+    if let Err(error) = memory::check_rom_size(&rom) {
+        return TestRomResult {
+            name: name.to_string(),
+            outcome: TestOutcome::Error(error),
+            cycles: 0,
+            serial: String::new(),
+            duration: started.elapsed(),
+        };
+    }
+    let mut gameboy = Gameboy::new(vec![], rom);
+    // Report illegal opcodes instead of hanging until the budget runs out
+    gameboy.set_illegal_opcode_policy(IllegalOpcodePolicy::Error);
+    let outcome = panic::catch_unwind(AssertUnwindSafe(|| run(&mut gameboy, cycle_budget)))
+        .unwrap_or_else(|payload| {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|message| message.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "Unknown panic".to_string());
+            TestOutcome::Error(message)
+        });
+
+    TestRomResult {
+        name: name.to_string(),
+        outcome,
+        cycles: gameboy.cycles(),
+        serial: String::from_utf8_lossy(gameboy.serial_output()).into_owned(),
+        duration: started.elapsed(),
+    }
+}
+
+fn run(gameboy: &mut Gameboy, cycle_budget: u64) -> TestOutcome {
+    gameboy.reset();
+    let mut serial_length = 0;
+    let mut next_check = CYCLES_PER_FRAME;
+
+    while gameboy.cycles() < cycle_budget {
+        // Stop in front of every `LD B, B`, Mooneye's debug breakpoint
+        let end = next_check.min(cycle_budget);
+        let result = gameboy.run_until(|gameboy| {
+            gameboy.cycles() >= end || gameboy.read_memory(gameboy.pc()) == MOONEYE_DEBUG_OPCODE
+        });
+        if let Err(error) = result {
+            return TestOutcome::Error(error.to_string());
+        }
+
+        if gameboy.read_memory(gameboy.pc()) == MOONEYE_DEBUG_OPCODE {
+            if let Some(outcome) = mooneye_outcome(gameboy) {
+                return outcome;
+            }
+        }
+        // Blargg results are checked once per frame, which also gives the
+        // rest of a failure message time to arrive over serial
+        if gameboy.cycles() >= next_check {
+            next_check += CYCLES_PER_FRAME;
+            if gameboy.serial_output().len() != serial_length {
+                serial_length = gameboy.serial_output().len();
+                if let Some(outcome) = blargg_serial_outcome(gameboy.serial_output()) {
+                    return outcome;
+                }
+            }
+            if let Some(outcome) = blargg_memory_outcome(gameboy) {
+                return outcome;
+            }
+        }
+    }
+    TestOutcome::TimedOut
+}
+
+fn mooneye_outcome(gameboy: &Gameboy) -> Option<TestOutcome> {
+    let registers = gameboy.registers();
+    let values = [
+        registers.b,
+        registers.c,
+        registers.d,
+        registers.e,
+        registers.h,
+        registers.l,
+    ];
+    if values == MOONEYE_PASS {
+        Some(TestOutcome::Passed)
+    } else if values == MOONEYE_FAIL {
+        Some(TestOutcome::Failed("Mooneye failure signature".to_string()))
+    } else {
+        None
+    }
+}
+
+fn blargg_serial_outcome(serial: &[u8]) -> Option<TestOutcome> {
+    let text = String::from_utf8_lossy(serial);
+    if text.contains("Passed") {
+        Some(TestOutcome::Passed)
+    } else if text.contains("Failed") {
+        Some(TestOutcome::Failed(text.trim().to_string()))
+    } else {
+        None
+    }
+}
+
+fn blargg_memory_outcome(gameboy: &Gameboy) -> Option<TestOutcome> {
+    let signature = [1, 2, 3].map(|offset| gameboy.read_memory(BLARGG_STATUS + offset));
+    let status = gameboy.read_memory(BLARGG_STATUS);
+    if signature != BLARGG_SIGNATURE || status == BLARGG_RUNNING {
+        return None;
+    }
+    if status == 0 {
+        return Some(TestOutcome::Passed);
+    }
+
+    let text: Vec<u8> = (BLARGG_TEXT..0xC000)
+        .map(|address| gameboy.read_memory(address))
+        .take_while(|byte| *byte != 0)
+        .collect();
+    Some(TestOutcome::Failed(format!(
+        "Status {:02X}: {}",
+        status,
+        String::from_utf8_lossy(&text).trim()
+    )))
+}
+
+/// Formats the results as a JUnit XML report.
+pub fn junit_xml(suite: &str, results: &[TestRomResult]) -> String {
+    let failures = results
+        .iter()
+        .filter(|result| {
+            matches!(
+                result.outcome,
+                TestOutcome::Failed(_) | TestOutcome::TimedOut
+            )
+        })
+        .count();
+    let errors = results
+        .iter()
+        .filter(|result| matches!(result.outcome, TestOutcome::Error(_)))
+        .count();
+    let time: f64 = results
+        .iter()
+        .map(|result| result.duration.as_secs_f64())
+        .sum();
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        xml,
+        "<testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\" time=\"{:.3}\">",
+        escape(suite),
+        results.len(),
+        failures,
+        errors,
+        time
+    );
+    for result in results {
+        let _ = write!(
+            xml,
+            "  <testcase name=\"{}\" classname=\"{}\" time=\"{:.3}\"",
+            escape(&result.name),
+            escape(suite),
+            result.duration.as_secs_f64()
+        );
+        let (element, message) = match &result.outcome {
+            TestOutcome::Passed => {
+                xml.push_str("/>\n");
+                continue;
+            }
+            TestOutcome::Failed(message) => ("failure", message.clone()),
+            TestOutcome::TimedOut => (
+                "failure",
+                format!("Timed out after {} cycles", result.cycles),
+            ),
+            TestOutcome::Error(message) => ("error", message.clone()),
+        };
+        xml.push_str(">\n");
+        let _ = writeln!(
+            xml,
+            "    <{} message=\"{}\">{}</{}>",
+            element,
+            escape(&message),
+            escape(&result.serial),
+            element
+        );
+        xml.push_str("  </testcase>\n");
+    }
+    xml.push_str("</testsuite>\n");
+    xml
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\n' | '\t' => escaped.push(c),
+            c if c.is_control() => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+impl Gameboy {
+    pub fn serial_output(&self) -> &[u8] {
+        &self.cpu.bus.serial_output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rom(program: &[u8]) -> Vec<u8> {
+        let mut rom = vec![0x00; 0x100 + program.len()];
+        rom[0x100..].copy_from_slice(program);
+        rom
+    }
+
+    // Sends each byte over serial, then loops forever
+    fn serial_rom(text: &str) -> Vec<u8> {
+        let mut program = Vec::new();
+        for byte in text.bytes() {
+            program.extend_from_slice(&[0x3E, byte, 0xEA, 0x01, 0xFF]); // LD A, byte; LD (SB), A
+            program.extend_from_slice(&[0x3E, 0x81, 0xEA, 0x02, 0xFF]); // LD A, 0x81; LD (SC), A
+        }
+        let end = 0x100 + program.len() as u16;
+        program.extend_from_slice(&[0x00, 0xC3, end as u8, (end >> 8) as u8]); // NOP; JP end
+        rom(&program)
+    }
+
+    #[test]
+    fn test_mooneye_pass() {
+        let program = [
+            0x06, 3, 0x0E, 5, 0x16, 8, 0x1E, 13, 0x26, 21, 0x2E, 34,   // LD B..L
+            0x40, // LD B, B
+            0x00, 0x18, 0xFD, // NOP; JR -3
+        ];
+        let result = run_test_rom("mooneye", rom(&program), DEFAULT_CYCLE_BUDGET);
+        assert_eq!(result.outcome, TestOutcome::Passed);
+    }
+
+    #[test]
+    fn test_mooneye_fail() {
+        let program = [
+            0x06, 0x42, 0x0E, 0x42, 0x16, 0x42, 0x1E, 0x42, 0x26, 0x42, 0x2E, 0x42, 0x40, 0x00,
+            0x18, 0xFD,
+        ];
+        let result = run_test_rom("mooneye", rom(&program), DEFAULT_CYCLE_BUDGET);
+        assert!(matches!(result.outcome, TestOutcome::Failed(_)));
+    }
+
+    #[test]
+    fn test_blargg_serial() {
+        let result = run_test_rom("blargg", serial_rom("Passed"), DEFAULT_CYCLE_BUDGET);
+        assert_eq!(result.outcome, TestOutcome::Passed);
+        assert_eq!(result.serial, "Passed");
+
+        let result = run_test_rom("blargg", serial_rom("Failed #2"), DEFAULT_CYCLE_BUDGET);
+        assert_eq!(result.outcome, TestOutcome::Failed("Failed #2".to_string()));
+    }
+
+    #[test]
+    fn test_blargg_memory() {
+        let mut program = Vec::new();
+        for (address, value) in [
+            (0xA001u16, 0xDE),
+            (0xA002, 0xB0),
+            (0xA003, 0x61),
+            (0xA004, b'E'),
+            (0xA000, 0x01),
+        ] {
+            program.extend_from_slice(&[0x3E, value, 0xEA, address as u8, (address >> 8) as u8]);
+        }
+        program.extend_from_slice(&[0x00, 0x18, 0xFD]);
+        let result = run_test_rom("blargg", rom(&program), DEFAULT_CYCLE_BUDGET);
+        assert_eq!(
+            result.outcome,
+            TestOutcome::Failed("Status 01: E".to_string())
+        );
+    }
+
+    #[test]
+    fn test_timeout_and_error() {
+        let result = run_test_rom("loop", rom(&[0x00, 0x18, 0xFD]), CYCLES_PER_FRAME);
+        assert_eq!(result.outcome, TestOutcome::TimedOut);
+        assert!(result.cycles >= CYCLES_PER_FRAME);
+
+        // STOP is not implemented yet
+        let result = run_test_rom("stop", rom(&[0x10, 0x00]), CYCLES_PER_FRAME);
+        assert_eq!(
+            result.outcome,
+            TestOutcome::Error("Unimplemented instruction `stop` at 00:0100".to_string())
+        );
+    }
+
+    #[test]
+    fn test_rom_too_large() {
+        let result = run_test_rom("cpu_instrs", vec![0x00; 0x10000], CYCLES_PER_FRAME);
+        assert_eq!(
+            result.outcome,
+            TestOutcome::Error(
+                "ROM is 65536 bytes, only ROMs up to 32768 bytes without an MBC are supported"
+                    .to_string()
+            )
+        );
+        assert_eq!(result.cycles, 0);
+    }
+
+    #[test]
+    fn test_junit_xml() {
+        let result = |name: &str, outcome| TestRomResult {
+            name: name.to_string(),
+            outcome,
+            cycles: 100,
+            serial: "<out>".to_string(),
+            duration: Duration::from_millis(5),
+        };
+        let xml = junit_xml(
+            "roms",
+            &[
+                result("a.gb", TestOutcome::Passed),
+                result("b.gb", TestOutcome::TimedOut),
+                result("c.gb", TestOutcome::Error("boom".to_string())),
+            ],
+        );
+        assert!(xml.contains("tests=\"3\" failures=\"1\" errors=\"1\""));
+        assert!(xml.contains("<testcase name=\"a.gb\" classname=\"roms\" time=\"0.005\"/>"));
+        assert!(
+            xml.contains("<failure message=\"Timed out after 100 cycles\">&lt;out&gt;</failure>")
+        );
+        assert!(xml.contains("<error message=\"boom\">"));
+    }
+}
@@ -0,0 +1,152 @@
+//! Golden-image regression tests: run a ROM for a number of frames with
+//! scripted input and compare the final frame against a reference PNG.
+//!
+//! Set `GOLDEN_BLESS=1` to (re)write the references from the current output.
+//! ROMs that are not part of the repo are looked up in `GOLDEN_ROM_DIR`
+//! (default `tests/roms`) and the case is skipped when they are missing.
+
+use std::{env, fs, path::PathBuf};
+
+use gameboy_lib::{
+    image::encode_png,
+    joypad::{Button, JoypadState},
+    ppu::{Palette, SCREEN_HEIGHT, SCREEN_WIDTH},
+    Gameboy,
+};
+
+use super::png;
+
+pub enum RomSource {
+    Bytes(Vec<u8>),
+    File(&'static str),
+}
+
+pub struct GoldenCase {
+    pub name: &'static str,
+    pub rom: RomSource,
+    pub frames: u64,
+    /// Buttons held from the given frame on, until the next entry.
+    pub input: Vec<(u64, Vec<Button>)>,
+    pub palette: Palette,
+}
+
+impl GoldenCase {
+    pub fn new(name: &'static str, rom: RomSource, frames: u64) -> GoldenCase {
+        GoldenCase {
+            name,
+            rom,
+            frames,
+            input: Vec::new(),
+            palette: Palette::GREYSCALE,
+        }
+    }
+
+    pub fn hold(mut self, frame: u64, buttons: &[Button]) -> GoldenCase {
+        self.input.push((frame, buttons.to_vec()));
+        self
+    }
+}
+
+fn tests_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests")
+}
+
+fn load_rom(source: &RomSource) -> Option<Vec<u8>> {
+    match source {
+        RomSource::Bytes(bytes) => Some(bytes.clone()),
+        RomSource::File(name) => {
+            let dir = env::var_os("GOLDEN_ROM_DIR")
+                .map(PathBuf::from)
+                .unwrap_or_else(|| tests_dir().join("roms"));
+            fs::read(dir.join(name)).ok()
+        }
+    }
+}
+
+fn render(case: &GoldenCase, rom: Vec<u8>) -> Vec<u8> {
+    let mut gameboy = Gameboy::new(vec![], rom);
+    gameboy.reset();
+    for frame in 0..case.frames {
+        if let Some((_, buttons)) = case.input.iter().find(|(start, _)| *start == frame) {
+            let mut joypad = JoypadState::default();
+            buttons.iter().for_each(|button| joypad.press(*button));
+            gameboy.set_joypad(joypad);
+        }
+        if let Err(error) = gameboy.run_frame() {
+            panic!("{}: {} in frame {}", case.name, error, frame);
+        }
+    }
+    gameboy.framebuffer_rgb(&case.palette)
+}
+
+/// Runs the case and panics on a mismatch, after writing the expected image,
+/// the actual image and a map of the differing pixels side by side to
+/// `<target tmpdir>/golden/<name>-diff.png`.
+pub fn check(case: GoldenCase) {
+    let Some(rom) = load_rom(&case.rom) else {
+        eprintln!("skipping {}: ROM not found", case.name);
+        return;
+    };
+    let actual = render(&case, rom);
+    let reference = tests_dir()
+        .join("golden")
+        .join(format!("{}.png", case.name));
+
+    if env::var_os("GOLDEN_BLESS").is_some() {
+        fs::write(&reference, encode_png(SCREEN_WIDTH, SCREEN_HEIGHT, &actual))
+            .expect("Failed to write reference image");
+        return;
+    }
+
+    let data = fs::read(&reference).unwrap_or_else(|_| {
+        panic!(
+            "Missing reference {}, run with GOLDEN_BLESS=1 to create it",
+            reference.display()
+        )
+    });
+    let expected = png::decode(&data).expect("Invalid reference image");
+    assert_eq!(
+        (expected.width, expected.height),
+        (SCREEN_WIDTH, SCREEN_HEIGHT),
+        "Reference image has the wrong size"
+    );
+
+    let mismatches = expected
+        .rgb
+        .chunks(3)
+        .zip(actual.chunks(3))
+        .filter(|(expected, actual)| expected != actual)
+        .count();
+    if mismatches > 0 {
+        let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("golden");
+        fs::create_dir_all(&dir).expect("Failed to create diff directory");
+        let path = dir.join(format!("{}-diff.png", case.name));
+        let diff = diff_image(&expected.rgb, &actual);
+        fs::write(&path, encode_png(SCREEN_WIDTH * 3, SCREEN_HEIGHT, &diff))
+            .expect("Failed to write diff image");
+        panic!(
+            "{}: {} pixels differ from the reference, see {}",
+            case.name,
+            mismatches,
+            path.display()
+        );
+    }
+}
+
+// Expected | actual | faded expected with differing pixels in red
+fn diff_image(expected: &[u8], actual: &[u8]) -> Vec<u8> {
+    let row = SCREEN_WIDTH * 3;
+    let mut diff = Vec::with_capacity(row * 3 * SCREEN_HEIGHT);
+    for (expected, actual) in expected.chunks(row).zip(actual.chunks(row)) {
+        diff.extend_from_slice(expected);
+        diff.extend_from_slice(actual);
+        for (expected, actual) in expected.chunks(3).zip(actual.chunks(3)) {
+            if expected == actual {
+                diff.extend(expected.iter().map(|value| 192 + value / 4));
+            } else {
+                diff.extend_from_slice(&[0xFF, 0x00, 0x00]);
+            }
+        }
+    }
+    diff
+}
@@ -0,0 +1,306 @@
+//! Minimal PNG decoder for reference images, including a small inflate
+//! implementation. Supports 8 bit and lower depths without interlacing.
+
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub rgb: Vec<u8>,
+}
+
+pub fn decode(data: &[u8]) -> Result<Image, String> {
+    if data.get(..8) != Some(&[0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n']) {
+        return Err("Not a PNG file".to_string());
+    }
+
+    let mut header = None;
+    let mut palette = Vec::new();
+    let mut compressed = Vec::new();
+    let mut offset = 8;
+    while offset + 12 <= data.len() {
+        let length = u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap()) as usize;
+        let kind = &data[offset + 4..offset + 8];
+        let body = data
+            .get(offset + 8..offset + 8 + length)
+            .ok_or("Truncated chunk")?;
+        match kind {
+            b"IHDR" => header = Some(body.to_vec()),
+            b"PLTE" => palette = body.chunks(3).map(|c| [c[0], c[1], c[2]]).collect(),
+            b"IDAT" => compressed.extend_from_slice(body),
+            b"IEND" => break,
+            _ => {}
+        }
+        offset += 12 + length;
+    }
+
+    let header = header.ok_or("Missing IHDR")?;
+    let width = u32::from_be_bytes(header[0..4].try_into().unwrap()) as usize;
+    let height = u32::from_be_bytes(header[4..8].try_into().unwrap()) as usize;
+    let (depth, color_type, interlace) = (header[8] as usize, header[9], header[12]);
+    if depth > 8 || interlace != 0 {
+        return Err(format!(
+            "Unsupported PNG: depth {} interlace {}",
+            depth, interlace
+        ));
+    }
+    let channels = match color_type {
+        0 | 3 => 1,
+        2 => 3,
+        4 => 2,
+        6 => 4,
+        _ => return Err(format!("Unsupported color type {}", color_type)),
+    };
+
+    let raw = inflate(compressed.get(2..).ok_or("Missing zlib header")?)?;
+    let stride = (width * channels * depth).div_ceil(8);
+    let bytes_per_pixel = (channels * depth / 8).max(1);
+    let pixels = unfilter(&raw, stride, height, bytes_per_pixel)?;
+
+    let mut rgb = Vec::with_capacity(width * height * 3);
+    for row in pixels.chunks(stride) {
+        for x in 0..width {
+            let sample = |channel: usize| -> u8 {
+                if depth == 8 {
+                    row[x * channels + channel]
+                } else {
+                    let bit = x * depth;
+                    (row[bit / 8] >> (8 - depth - bit % 8)) & ((1 << depth) - 1) as u8
+                }
+            };
+            let color = match color_type {
+                0 | 4 => {
+                    let value = sample(0) as usize * 255 / ((1 << depth) - 1);
+                    [value as u8; 3]
+                }
+                3 => *palette
+                    .get(sample(0) as usize)
+                    .ok_or("Palette index out of range")?,
+                _ => [sample(0), sample(1), sample(2)],
+            };
+            rgb.extend_from_slice(&color);
+        }
+    }
+    Ok(Image { width, height, rgb })
+}
+
+fn unfilter(raw: &[u8], stride: usize, height: usize, bpp: usize) -> Result<Vec<u8>, String> {
+    if raw.len() < (stride + 1) * height {
+        return Err("Image data is truncated".to_string());
+    }
+    let mut pixels = vec![0u8; stride * height];
+    for y in 0..height {
+        let filter = raw[y * (stride + 1)];
+        let line = &raw[y * (stride + 1) + 1..][..stride];
+        for x in 0..stride {
+            let a = if x >= bpp {
+                pixels[y * stride + x - bpp]
+            } else {
+                0
+            };
+            let b = if y > 0 {
+                pixels[(y - 1) * stride + x]
+            } else {
+                0
+            };
+            let c = if x >= bpp && y > 0 {
+                pixels[(y - 1) * stride + x - bpp]
+            } else {
+                0
+            };
+            let predictor = match filter {
+                0 => 0,
+                1 => a,
+                2 => b,
+                3 => ((a as u16 + b as u16) / 2) as u8,
+                4 => paeth(a, b, c),
+                _ => return Err(format!("Invalid filter {}", filter)),
+            };
+            pixels[y * stride + x] = line[x].wrapping_add(predictor);
+        }
+    }
+    Ok(pixels)
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = (
+        (p - a as i16).abs(),
+        (p - b as i16).abs(),
+        (p - c as i16).abs(),
+    );
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize, // In bits
+}
+
+impl BitReader<'_> {
+    fn bits(&mut self, count: usize) -> Result<u32, String> {
+        let mut value = 0;
+        for i in 0..count {
+            let byte = self
+                .data
+                .get(self.position / 8)
+                .ok_or("Deflate data is truncated")?;
+            value |= ((byte >> (self.position % 8)) as u32 & 1) << i;
+            self.position += 1;
+        }
+        Ok(value)
+    }
+}
+
+// Canonical Huffman code, decoded one bit at a time
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Huffman {
+        let mut counts = [0u16; 16];
+        for length in lengths {
+            counts[*length as usize] += 1;
+        }
+        counts[0] = 0;
+        let mut symbols: Vec<(u8, u16)> = lengths
+            .iter()
+            .enumerate()
+            .filter(|(_, length)| **length != 0)
+            .map(|(symbol, length)| (*length, symbol as u16))
+            .collect();
+        symbols.sort();
+        Huffman {
+            counts,
+            symbols: symbols.into_iter().map(|(_, symbol)| symbol).collect(),
+        }
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<u16, String> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for length in 1..16 {
+            code |= reader.bits(1)? as i32;
+            let count = self.counts[length] as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err("Invalid Huffman code".to_string())
+    }
+}
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+pub fn inflate(data: &[u8]) -> Result<Vec<u8>, String> {
+    let mut reader = BitReader { data, position: 0 };
+    let mut output = Vec::new();
+    loop {
+        let last = reader.bits(1)? == 1;
+        match reader.bits(2)? {
+            0 => {
+                reader.position = reader.position.div_ceil(8) * 8;
+                let start = reader.position / 8;
+                let length = u16::from_le_bytes([data[start], data[start + 1]]) as usize;
+                let block = data
+                    .get(start + 4..start + 4 + length)
+                    .ok_or("Stored block is truncated")?;
+                output.extend_from_slice(block);
+                reader.position = (start + 4 + length) * 8;
+            }
+            1 => {
+                let mut lengths = [8u8; 288];
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                let literals = Huffman::new(&lengths);
+                let distances = Huffman::new(&[5; 30]);
+                inflate_block(&mut reader, &mut output, &literals, &distances)?;
+            }
+            2 => {
+                let literal_count = reader.bits(5)? as usize + 257;
+                let distance_count = reader.bits(5)? as usize + 1;
+                let code_count = reader.bits(4)? as usize + 4;
+                let mut code_lengths = [0u8; 19];
+                for index in CODE_LENGTH_ORDER.iter().take(code_count) {
+                    code_lengths[*index] = reader.bits(3)? as u8;
+                }
+                let code = Huffman::new(&code_lengths);
+
+                let mut lengths = Vec::with_capacity(literal_count + distance_count);
+                while lengths.len() < literal_count + distance_count {
+                    let (value, repeat) = match code.decode(&mut reader)? {
+                        symbol @ 0..=15 => (symbol as u8, 1),
+                        16 => (
+                            *lengths.last().ok_or("Repeat without length")?,
+                            3 + reader.bits(2)?,
+                        ),
+                        17 => (0, 3 + reader.bits(3)?),
+                        _ => (0, 11 + reader.bits(7)?),
+                    };
+                    lengths.extend(std::iter::repeat_n(value, repeat as usize));
+                }
+                let literals = Huffman::new(&lengths[..literal_count]);
+                let distances = Huffman::new(&lengths[literal_count..]);
+                inflate_block(&mut reader, &mut output, &literals, &distances)?;
+            }
+            _ => return Err("Invalid block type".to_string()),
+        }
+        if last {
+            return Ok(output);
+        }
+    }
+}
+
+fn inflate_block(
+    reader: &mut BitReader,
+    output: &mut Vec<u8>,
+    literals: &Huffman,
+    distances: &Huffman,
+) -> Result<(), String> {
+    loop {
+        let symbol = literals.decode(reader)? as usize;
+        match symbol {
+            0..=255 => output.push(symbol as u8),
+            256 => return Ok(()),
+            _ => {
+                let index = symbol - 257;
+                let length = *LENGTH_BASE.get(index).ok_or("Invalid length")? as usize
+                    + reader.bits(LENGTH_EXTRA[index] as usize)? as usize;
+                let index = distances.decode(reader)? as usize;
+                let distance = *DISTANCE_BASE.get(index).ok_or("Invalid distance")? as usize
+                    + reader.bits(DISTANCE_EXTRA[index] as usize)? as usize;
+                if distance > output.len() {
+                    return Err("Distance too far back".to_string());
+                }
+                for _ in 0..length {
+                    output.push(output[output.len() - distance]);
+                }
+            }
+        }
+    }
+}
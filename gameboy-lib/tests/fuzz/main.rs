@@ -0,0 +1,478 @@
+//! Differential fuzzing of the CPU core: random register states and
+//! instruction streams run side by side on `Cpu` over a flat 64 KiB RAM and
+//! on the independent interpreter in `reference.rs`. Registers, IME, cycles
+//! and all of memory are compared after every instruction.
+//!
+//! A divergence is minimized into a one-instruction `Case` and printed as a
+//! line to paste into `regressions`. `FUZZ_CASES` sets the number of random
+//! cases (1000 by default) and `FUZZ_SEED` the seed, e.g.
+//! `FUZZ_CASES=1000000 FUZZ_SEED=7 cargo test --release --test fuzz`.
+//!
+//! HALT, STOP and the illegal opcodes end a case, as the reference does not
+//! model them. The opcodes in `UNIMPLEMENTED` are not generated, and a case
+//! that jumps onto one ends there and is counted as skipped.
+
+mod reference;
+
+use std::{
+    env,
+    panic::{self, AssertUnwindSafe},
+};
+
+use gameboy_lib::{
+    bus::{Bus, FlatRam},
+    cpu::{instructions::instruction_length, Cpu},
+    disassembler,
+    error::GameboyError,
+};
+use reference::Machine;
+
+// Instructions generated per case
+const PROGRAM_LENGTH: usize = 24;
+
+// DAA, CPL and RETI, which `Cpu` does not implement yet. Remove an opcode
+// once it is, `still_unimplemented` fails until then.
+const UNIMPLEMENTED: [u8; 3] = [0x27, 0x2F, 0xD9];
+
+/// xorshift64*, good enough to drive the generator and reproducible
+/// everywhere.
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Rng {
+        // A zero state would only ever produce zeros
+        Rng(seed ^ 0x9E37_79B9_7F4A_7C15)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    fn byte(&mut self) -> u8 {
+        (self.next() >> 56) as u8
+    }
+
+    fn word(&mut self) -> u16 {
+        (self.next() >> 48) as u16
+    }
+}
+
+/// Initial registers, the non-zero bytes of memory and how many
+/// instructions to run.
+#[derive(Debug, Clone, PartialEq)]
+struct Case {
+    registers: [u8; 8], // A, F, B, C, D, E, H, L
+    sp: u16,
+    pc: u16,
+    memory: Vec<(u16, u8)>,
+    steps: usize,
+}
+
+impl Case {
+    fn new(registers: [u8; 8], sp: u16, pc: u16, memory: &[(u16, u8)], steps: usize) -> Case {
+        Case {
+            registers,
+            sp,
+            pc,
+            memory: memory.to_vec(),
+            steps,
+        }
+    }
+
+    /// Random registers, a little data where BC, DE, HL and SP point and
+    /// `PROGRAM_LENGTH` instructions at PC. Opcodes the reference does not
+    /// model and `UNIMPLEMENTED` are not generated, but jumps can still land
+    /// on them.
+    fn random(rng: &mut Rng) -> Case {
+        let mut registers = [0; 8];
+        registers
+            .iter_mut()
+            .for_each(|register| *register = rng.byte());
+        registers[1] &= 0xF0;
+        let (sp, pc) = (rng.word(), rng.word());
+
+        let mut memory = Vec::new();
+        for pair in [0, 2, 4, 6] {
+            let address = (registers[pair] as u16) << 8 | registers[pair + 1] as u16;
+            let address = if pair == 0 { sp } else { address };
+            memory.push((address, rng.byte()));
+            memory.push((address.wrapping_add(1), rng.byte()));
+        }
+
+        let mut address = pc;
+        let mut emit = |byte: u8| {
+            memory.push((address, byte));
+            address = address.wrapping_add(1);
+        };
+        for _ in 0..PROGRAM_LENGTH {
+            let opcode = loop {
+                let opcode = rng.byte();
+                if Machine::supports(opcode) && !UNIMPLEMENTED.contains(&opcode) {
+                    break opcode;
+                }
+            };
+            emit(opcode);
+            for _ in 1..instruction_length(opcode) {
+                emit(rng.byte());
+            }
+        }
+        Case {
+            registers,
+            sp,
+            pc,
+            memory,
+            steps: PROGRAM_LENGTH,
+        }
+    }
+
+    fn reference(&self) -> Machine {
+        let mut machine = Machine::new();
+        let [a, f, b, c, d, e, h, l] = self.registers;
+        (machine.a, machine.f, machine.b, machine.c) = (a, f, b, c);
+        (machine.d, machine.e, machine.h, machine.l) = (d, e, h, l);
+        machine.sp = self.sp;
+        machine.pc = self.pc;
+        for (address, value) in &self.memory {
+            machine.memory[*address as usize] = *value;
+        }
+        machine
+    }
+
+    fn cpu(&self) -> Cpu<FlatRam> {
+        let mut ram = FlatRam::new();
+        for (address, value) in &self.memory {
+            ram.write(*address, *value);
+        }
+        let mut cpu = Cpu::with_bus(ram);
+        let [a, f, b, c, d, e, h, l] = self.registers;
+        let registers = &mut cpu.registers;
+        (registers.a, registers.b, registers.c) = (a, b, c);
+        (registers.d, registers.e, registers.h, registers.l) = (d, e, h, l);
+        registers.f.set(f);
+        registers.sp.set(self.sp);
+        cpu.pc = self.pc;
+        cpu
+    }
+
+    /// The case as Rust source, for `regressions`.
+    fn source(&self) -> String {
+        let hex = |values: &[u8]| {
+            values
+                .iter()
+                .map(|value| format!("0x{:02X}", value))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let memory = self
+            .memory
+            .iter()
+            .map(|(address, value)| format!("(0x{:04X}, 0x{:02X})", address, value))
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!(
+            "Case::new([{}], 0x{:04X}, 0x{:04X}, &[{}], {}),",
+            hex(&self.registers),
+            self.sp,
+            self.pc,
+            memory,
+            self.steps
+        )
+    }
+}
+
+/// Where the two interpreters first disagreed.
+#[derive(Debug)]
+struct Divergence {
+    step: usize,
+    instruction: String,
+    differences: String,
+    // Reference state right before the diverging instruction
+    before: Machine,
+}
+
+fn disassemble(machine: &Machine, pc: u16) -> String {
+    let bytes: Vec<u8> = (0..3)
+        .map(|offset| machine.memory[pc.wrapping_add(offset) as usize])
+        .collect();
+    format!(
+        "{} at {:04X}",
+        disassembler::disassemble_one(&bytes, pc).text,
+        pc
+    )
+}
+
+// Everything that differs between `cpu` and `machine`, empty if nothing does
+fn compare(cpu: &Cpu<FlatRam>, machine: &Machine) -> String {
+    let registers = &cpu.registers;
+    let fields: [(&str, u64, u64); 13] = [
+        ("a", registers.a as u64, machine.a as u64),
+        ("f", registers.f.get() as u64, machine.f as u64),
+        ("b", registers.b as u64, machine.b as u64),
+        ("c", registers.c as u64, machine.c as u64),
+        ("d", registers.d as u64, machine.d as u64),
+        ("e", registers.e as u64, machine.e as u64),
+        ("h", registers.h as u64, machine.h as u64),
+        ("l", registers.l as u64, machine.l as u64),
+        ("sp", registers.sp.get() as u64, machine.sp as u64),
+        ("pc", cpu.pc as u64, machine.pc as u64),
+        ("ime", cpu.interrupts_enabled as u64, machine.ime as u64),
+        ("cycles", cpu.cycles, machine.cycles),
+        ("bus cycles", cpu.bus.cycles, machine.cycles),
+    ];
+    let mut differences: Vec<String> = fields
+        .iter()
+        .filter(|(_, actual, expected)| actual != expected)
+        .map(|(name, actual, expected)| {
+            format!("{}: expected 0x{:X}, actual 0x{:X}", name, expected, actual)
+        })
+        .collect();
+    if cpu.bus.bytes != machine.memory {
+        differences.extend(
+            (0..=0xFFFF)
+                .filter(|&address| cpu.bus.bytes[address] != machine.memory[address])
+                .map(|address| {
+                    format!(
+                        "({:04X}): expected 0x{:02X}, actual 0x{:02X}",
+                        address, machine.memory[address], cpu.bus.bytes[address]
+                    )
+                }),
+        );
+    }
+    differences.join(", ")
+}
+
+/// How a case that did not diverge ended.
+#[derive(Debug, PartialEq)]
+enum Outcome {
+    Passed,
+    // Stopped at one of `UNIMPLEMENTED`
+    Skipped(u8),
+}
+
+/// Runs `case` on both interpreters until it runs out of steps or reaches an
+/// instruction one of them does not support.
+fn run(case: &Case) -> Result<Outcome, Divergence> {
+    let mut cpu = case.cpu();
+    let mut machine = case.reference();
+    for step in 0..case.steps {
+        let before = machine.clone();
+        if machine.step().is_none() {
+            return Ok(Outcome::Passed);
+        }
+        let divergence = |differences| Divergence {
+            step,
+            instruction: disassemble(&before, before.pc),
+            differences,
+            before: before.clone(),
+        };
+        // A panic, like an arithmetic overflow, is a divergence like any other
+        match panic::catch_unwind(AssertUnwindSafe(|| cpu.step())) {
+            Ok(Ok(())) => {}
+            Ok(Err(GameboyError::Unimplemented { .. }))
+                if UNIMPLEMENTED.contains(&before.memory[before.pc as usize]) =>
+            {
+                return Ok(Outcome::Skipped(before.memory[before.pc as usize]))
+            }
+            Ok(Err(error)) => return Err(divergence(error.to_string())),
+            Err(_) => return Err(divergence("panicked".to_string())),
+        }
+        let differences = compare(&cpu, &machine);
+        if !differences.is_empty() {
+            return Err(divergence(differences));
+        }
+    }
+    Ok(Outcome::Passed)
+}
+
+/// Greedily shrinks `case` while `fails` holds: first to the single
+/// instruction that fails, starting from the state right before it, then by
+/// dropping memory bytes and clearing registers one at a time.
+fn minimize(case: &Case, fails: impl Fn(&Case) -> Option<Machine>) -> Case {
+    let Some(before) = fails(case) else {
+        return case.clone();
+    };
+    let mut smallest = Case {
+        registers: [
+            before.a, before.f, before.b, before.c, before.d, before.e, before.h, before.l,
+        ],
+        sp: before.sp,
+        pc: before.pc,
+        memory: (0..=0xFFFF)
+            .filter(|&address| before.memory[address] != 0)
+            .map(|address| (address as u16, before.memory[address]))
+            .collect(),
+        steps: 1,
+    };
+    if fails(&smallest).is_none() {
+        smallest = case.clone();
+    }
+
+    loop {
+        let mut shrunk = false;
+        let mut index = 0;
+        while index < smallest.memory.len() {
+            let mut candidate = smallest.clone();
+            candidate.memory.remove(index);
+            if fails(&candidate).is_some() {
+                smallest = candidate;
+                shrunk = true;
+            } else {
+                index += 1;
+            }
+        }
+        for register in 0..smallest.registers.len() {
+            let mut candidate = smallest.clone();
+            candidate.registers[register] = 0;
+            if candidate != smallest && fails(&candidate).is_some() {
+                smallest = candidate;
+                shrunk = true;
+            }
+        }
+        for candidate in [
+            Case {
+                sp: 0,
+                ..smallest.clone()
+            },
+            Case {
+                pc: 0,
+                ..smallest.clone()
+            },
+        ] {
+            if candidate != smallest && fails(&candidate).is_some() {
+                smallest = candidate;
+                shrunk = true;
+            }
+        }
+        if !shrunk {
+            return smallest;
+        }
+    }
+}
+
+fn diverges(case: &Case) -> Option<Machine> {
+    run(case).err().map(|divergence| divergence.before)
+}
+
+fn env_u64(name: &str, default: u64) -> u64 {
+    env::var(name)
+        .map(|value| {
+            value
+                .parse()
+                .unwrap_or_else(|_| panic!("{} must be a number", name))
+        })
+        .unwrap_or(default)
+}
+
+#[test]
+fn fuzz() {
+    let seed = env_u64("FUZZ_SEED", 0);
+    let cases = env_u64("FUZZ_CASES", 1000);
+    let mut rng = Rng::new(seed);
+    let mut skipped = 0;
+    for index in 0..cases {
+        let case = Case::random(&mut rng);
+        let outcome = run(&case);
+        if let Ok(Outcome::Skipped(_)) = outcome {
+            skipped += 1;
+        }
+        if outcome.is_err() {
+            let minimized = minimize(&case, diverges);
+            let divergence = run(&minimized).unwrap_err();
+            panic!(
+                "case {} of seed {} diverged at step {} on `{}`: {}\nAdd it to `regressions`:\n    {}",
+                index,
+                seed,
+                run(&case).unwrap_err().step,
+                divergence.instruction,
+                divergence.differences,
+                minimized.source()
+            );
+        }
+    }
+    println!(
+        "{} of {} cases stopped early at an unimplemented opcode",
+        skipped, cases
+    );
+}
+
+// Minimized divergences found by `fuzz`
+#[test]
+fn regressions() {
+    #[rustfmt::skip]
+    let cases = [
+        Case::new([0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x29, 0x00], 0xAC93, 0xF0E9, &[(0xF0E9, 0xE8)], 1), // ADD SP, e8 flags
+        Case::new([0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], 0x0000, 0x2BD7, &[(0x2BD7, 0x17)], 1), // RLA clears Z
+        Case::new([0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], 0x0000, 0x7408, &[(0x7408, 0xC4)], 1), // CALL cc jumps
+        Case::new([0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], 0x3711, 0x54BD, &[(0x3711, 0x14), (0x54BD, 0xF1)], 1), // POP AF masks F
+        Case::new([0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], 0x0000, 0x328E, &[(0x328E, 0xCB), (0x328F, 0xF1)], 1), // SET length
+        Case::new([0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], 0x0000, 0xFFFF, &[(0xFFFF, 0x3E), (0x0000, 0x42)], 1), // Operand past 0xFFFF
+        Case::new([0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], 0x0000, 0xC000, &[(0xC000, 0xCB), (0xC001, 0x47)], 1), // BIT 0, A tests one bit
+        Case::new([0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xC1, 0x00], 0x0000, 0xC000, &[(0xC000, 0xCB), (0xC001, 0xC6)], 1), // SET 0, (HL)
+        Case::new([0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xC1, 0x00], 0x0000, 0xC000, &[(0xC000, 0x2A), (0xC100, 0x42)], 1), // LD A, (HL+) loads
+        Case::new([0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], 0x1234, 0xC000, &[(0xC000, 0x08), (0xC001, 0x00), (0xC002, 0xC1)], 1), // LD (a16), SP stores
+        Case::new([0x00, 0x10, 0xFF, 0x00, 0x00, 0x00, 0x00, 0x00], 0x0000, 0xC000, &[(0xC000, 0x88)], 1), // ADC carry in
+        Case::new([0x00, 0x80, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00], 0x0000, 0xC000, &[(0xC000, 0x09)], 1), // ADD HL keeps Z
+        Case::new([0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], 0xFF69, 0xC000, &[(0xC000, 0xF8), (0xC001, 0xFF)], 1), // LD HL, SP+e8
+    ];
+    for case in cases {
+        if let Err(divergence) = run(&case) {
+            panic!(
+                "{} diverged on `{}`: {}",
+                case.source(),
+                divergence.instruction,
+                divergence.differences
+            );
+        }
+    }
+}
+
+// The minimizer on a made-up failure: executing CPL
+#[test]
+fn minimize_shrinks_to_one_instruction() {
+    #[rustfmt::skip]
+    let program = [
+        (0xC000, 0x3C),       // INC A
+        (0xC001, 0x04),       // INC B
+        (0xC002, 0x2F),       // CPL
+        (0xC003, 0x00),       // NOP
+        (0xD000, 0x55),
+    ];
+    let case = Case::new(
+        [0x12, 0x30, 0x56, 0x78, 0x9A, 0xBC, 0xDE, 0xF0],
+        0xD000,
+        0xC000,
+        &program,
+        4,
+    );
+    let executes_cpl = |case: &Case| {
+        let mut machine = case.reference();
+        for _ in 0..case.steps {
+            let before = machine.clone();
+            machine.step()?;
+            if before.memory[before.pc as usize] == 0x2F {
+                return Some(before);
+            }
+        }
+        None
+    };
+    assert_eq!(
+        minimize(&case, executes_cpl),
+        Case::new([0; 8], 0x0000, 0xC002, &[(0xC002, 0x2F)], 1)
+    );
+}
+
+#[test]
+fn still_unimplemented() {
+    for opcode in UNIMPLEMENTED {
+        let case = Case::new([0; 8], 0x0000, 0xC000, &[(0xC000, opcode)], 1);
+        assert_eq!(
+            run(&case).ok(),
+            Some(Outcome::Skipped(opcode)),
+            "0x{:02X} is implemented now, remove it from `UNIMPLEMENTED`",
+            opcode
+        );
+    }
+}
@@ -0,0 +1,521 @@
+//! A deliberately small SM83 interpreter written straight from the opcode
+//! matrix, sharing no code with `gameboy_lib::cpu`. Opcodes are decoded from
+//! their octal fields (`xx yyy zzz`) instead of tables, flags are computed
+//! from their documented definitions and every bus access costs one M-cycle.
+//!
+//! No interrupts are serviced, so the one instruction delay of EI is not
+//! observable and not modelled.
+
+pub const Z: u8 = 0x80;
+pub const N: u8 = 0x40;
+pub const H: u8 = 0x20;
+pub const C: u8 = 0x10;
+
+#[derive(Debug, Clone)]
+pub struct Machine {
+    pub a: u8,
+    pub f: u8,
+    pub b: u8,
+    pub c: u8,
+    pub d: u8,
+    pub e: u8,
+    pub h: u8,
+    pub l: u8,
+    pub sp: u16,
+    pub pc: u16,
+    pub ime: bool,
+    pub memory: Vec<u8>,
+    pub cycles: u64, // T-cycles
+}
+
+impl Machine {
+    pub fn new() -> Machine {
+        Machine {
+            a: 0,
+            f: 0,
+            b: 0,
+            c: 0,
+            d: 0,
+            e: 0,
+            h: 0,
+            l: 0,
+            sp: 0,
+            pc: 0,
+            ime: false,
+            memory: vec![0; 0x10000],
+            cycles: 0,
+        }
+    }
+
+    fn read(&mut self, address: u16) -> u8 {
+        self.cycles += 4;
+        self.memory[address as usize]
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        self.cycles += 4;
+        self.memory[address as usize] = value;
+    }
+
+    fn internal(&mut self) {
+        self.cycles += 4;
+    }
+
+    fn immediate(&mut self) -> u8 {
+        let value = self.read(self.pc);
+        self.pc = self.pc.wrapping_add(1);
+        value
+    }
+
+    fn immediate_16(&mut self) -> u16 {
+        let low = self.immediate() as u16;
+        let high = self.immediate() as u16;
+        high << 8 | low
+    }
+
+    fn flag(&self, flag: u8) -> bool {
+        self.f & flag != 0
+    }
+
+    fn set_flags(&mut self, z: bool, n: bool, h: bool, c: bool) {
+        self.f = if z { Z } else { 0 }
+            | if n { N } else { 0 }
+            | if h { H } else { 0 }
+            | if c { C } else { 0 };
+    }
+
+    fn hl(&self) -> u16 {
+        (self.h as u16) << 8 | self.l as u16
+    }
+
+    fn set_hl(&mut self, value: u16) {
+        self.h = (value >> 8) as u8;
+        self.l = value as u8;
+    }
+
+    // BC, DE, HL, SP
+    fn rp(&self, p: u8) -> u16 {
+        match p {
+            0 => (self.b as u16) << 8 | self.c as u16,
+            1 => (self.d as u16) << 8 | self.e as u16,
+            2 => self.hl(),
+            _ => self.sp,
+        }
+    }
+
+    fn set_rp(&mut self, p: u8, value: u16) {
+        let (high, low) = ((value >> 8) as u8, value as u8);
+        match p {
+            0 => (self.b, self.c) = (high, low),
+            1 => (self.d, self.e) = (high, low),
+            2 => (self.h, self.l) = (high, low),
+            _ => self.sp = value,
+        }
+    }
+
+    // BC, DE, HL, AF
+    fn rp2(&self, p: u8) -> u16 {
+        match p {
+            3 => (self.a as u16) << 8 | self.f as u16,
+            _ => self.rp(p),
+        }
+    }
+
+    fn set_rp2(&mut self, p: u8, value: u16) {
+        match p {
+            3 => {
+                self.a = (value >> 8) as u8;
+                self.f = value as u8 & 0xF0;
+            }
+            _ => self.set_rp(p, value),
+        }
+    }
+
+    // B, C, D, E, H, L, (HL), A
+    fn r(&mut self, index: u8) -> u8 {
+        match index {
+            0 => self.b,
+            1 => self.c,
+            2 => self.d,
+            3 => self.e,
+            4 => self.h,
+            5 => self.l,
+            6 => self.read(self.hl()),
+            _ => self.a,
+        }
+    }
+
+    fn set_r(&mut self, index: u8, value: u8) {
+        match index {
+            0 => self.b = value,
+            1 => self.c = value,
+            2 => self.d = value,
+            3 => self.e = value,
+            4 => self.h = value,
+            5 => self.l = value,
+            6 => self.write(self.hl(), value),
+            _ => self.a = value,
+        }
+    }
+
+    // NZ, Z, NC, C
+    fn condition(&self, cc: u8) -> bool {
+        match cc {
+            0 => !self.flag(Z),
+            1 => self.flag(Z),
+            2 => !self.flag(C),
+            _ => self.flag(C),
+        }
+    }
+
+    fn push(&mut self, value: u16) {
+        self.sp = self.sp.wrapping_sub(1);
+        self.write(self.sp, (value >> 8) as u8);
+        self.sp = self.sp.wrapping_sub(1);
+        self.write(self.sp, value as u8);
+    }
+
+    fn pop(&mut self) -> u16 {
+        let low = self.read(self.sp) as u16;
+        self.sp = self.sp.wrapping_add(1);
+        let high = self.read(self.sp) as u16;
+        self.sp = self.sp.wrapping_add(1);
+        high << 8 | low
+    }
+
+    // ADD, ADC, SUB, SBC, AND, XOR, OR, CP
+    fn alu(&mut self, operation: u8, value: u8) {
+        let a = self.a;
+        let carry = (operation == 1 || operation == 3) && self.flag(C);
+        let carry_in = carry as u8;
+        let result = match operation {
+            0 | 1 => {
+                let sum = a as u16 + value as u16 + carry_in as u16;
+                let half = (a & 0xF) + (value & 0xF) + carry_in > 0xF;
+                self.set_flags(sum as u8 == 0, false, half, sum > 0xFF);
+                sum as u8
+            }
+            2 | 3 | 7 => {
+                let result = a.wrapping_sub(value).wrapping_sub(carry_in);
+                let half = (a & 0xF) < (value & 0xF) + carry_in;
+                let borrow = (a as u16) < value as u16 + carry_in as u16;
+                self.set_flags(result == 0, true, half, borrow);
+                if operation == 7 {
+                    a
+                } else {
+                    result
+                }
+            }
+            4 => {
+                let result = a & value;
+                self.set_flags(result == 0, false, true, false);
+                result
+            }
+            5 => {
+                let result = a ^ value;
+                self.set_flags(result == 0, false, false, false);
+                result
+            }
+            _ => {
+                let result = a | value;
+                self.set_flags(result == 0, false, false, false);
+                result
+            }
+        };
+        self.a = result;
+    }
+
+    // RLC, RRC, RL, RR, SLA, SRA, SWAP, SRL. Returns the result and carry.
+    fn rotate(&self, operation: u8, value: u8) -> (u8, bool) {
+        let carry = self.flag(C) as u8;
+        match operation {
+            0 => (value.rotate_left(1), value & 0x80 != 0),
+            1 => (value.rotate_right(1), value & 1 != 0),
+            2 => (value << 1 | carry, value & 0x80 != 0),
+            3 => (value >> 1 | carry << 7, value & 1 != 0),
+            4 => (value << 1, value & 0x80 != 0),
+            5 => (value >> 1 | value & 0x80, value & 1 != 0),
+            6 => (value.rotate_left(4), false),
+            _ => (value >> 1, value & 1 != 0),
+        }
+    }
+
+    // SP plus a signed byte, flags from the unsigned low byte addition
+    fn sp_plus_offset(&mut self) -> u16 {
+        let offset = self.immediate();
+        let sp = self.sp;
+        let half = (sp & 0xF) + (offset as u16 & 0xF) > 0xF;
+        let carry = (sp & 0xFF) + offset as u16 > 0xFF;
+        self.set_flags(false, false, half, carry);
+        sp.wrapping_add(offset as i8 as u16)
+    }
+
+    fn daa(&mut self) {
+        let mut adjust = 0;
+        let mut carry = self.flag(C);
+        if self.flag(N) {
+            if self.flag(H) {
+                adjust |= 0x06;
+            }
+            if carry {
+                adjust |= 0x60;
+            }
+            self.a = self.a.wrapping_sub(adjust);
+        } else {
+            if self.flag(H) || self.a & 0xF > 9 {
+                adjust |= 0x06;
+            }
+            if carry || self.a > 0x99 {
+                adjust |= 0x60;
+                carry = true;
+            }
+            self.a = self.a.wrapping_add(adjust);
+        }
+        let n = self.flag(N);
+        self.set_flags(self.a == 0, n, false, carry);
+    }
+
+    fn prefixed(&mut self) {
+        let opcode = self.immediate();
+        let (x, y, z) = (opcode >> 6, opcode >> 3 & 7, opcode & 7);
+        let value = self.r(z);
+        match x {
+            0 => {
+                let (result, carry) = self.rotate(y, value);
+                self.set_flags(result == 0, false, false, carry);
+                self.set_r(z, result);
+            }
+            1 => {
+                let carry = self.flag(C);
+                self.set_flags(value & 1 << y == 0, false, true, carry);
+            }
+            2 => self.set_r(z, value & !(1 << y)),
+            _ => self.set_r(z, value | 1 << y),
+        }
+    }
+
+    /// False for HALT, STOP and the illegal opcodes.
+    pub fn supports(opcode: u8) -> bool {
+        !matches!(
+            opcode,
+            0x10 | 0x76
+                | 0xD3
+                | 0xDB
+                | 0xDD
+                | 0xE3
+                | 0xE4
+                | 0xEB
+                | 0xEC
+                | 0xED
+                | 0xF4
+                | 0xFC
+                | 0xFD
+        )
+    }
+
+    /// Executes one instruction, or returns `None` without touching anything
+    /// for HALT, STOP and the illegal opcodes, which are out of scope.
+    pub fn step(&mut self) -> Option<()> {
+        let opcode = self.memory[self.pc as usize];
+        let (x, y, z) = (opcode >> 6, opcode >> 3 & 7, opcode & 7);
+        let (p, q) = (y >> 1, y & 1);
+        if !Machine::supports(opcode) {
+            return None;
+        }
+        self.immediate();
+
+        match (x, z) {
+            (0, 0) => match y {
+                0 => {}
+                1 => {
+                    let address = self.immediate_16();
+                    self.write(address, self.sp as u8);
+                    self.write(address.wrapping_add(1), (self.sp >> 8) as u8);
+                }
+                _ => {
+                    let offset = self.immediate() as i8;
+                    if y == 3 || self.condition(y - 4) {
+                        self.internal();
+                        self.pc = self.pc.wrapping_add(offset as u16);
+                    }
+                }
+            },
+            (0, 1) if q == 0 => {
+                let value = self.immediate_16();
+                self.set_rp(p, value);
+            }
+            (0, 1) => {
+                self.internal();
+                let (hl, value) = (self.hl(), self.rp(p));
+                let half = (hl & 0xFFF) + (value & 0xFFF) > 0xFFF;
+                let (result, carry) = hl.overflowing_add(value);
+                let zero = self.flag(Z);
+                self.set_flags(zero, false, half, carry);
+                self.set_hl(result);
+            }
+            (0, 2) => {
+                let address = match p {
+                    0 | 1 => self.rp(p),
+                    _ => self.hl(),
+                };
+                if q == 0 {
+                    self.write(address, self.a);
+                } else {
+                    self.a = self.read(address);
+                }
+                match p {
+                    2 => self.set_hl(address.wrapping_add(1)),
+                    3 => self.set_hl(address.wrapping_sub(1)),
+                    _ => {}
+                }
+            }
+            (0, 3) => {
+                self.internal();
+                let value = self.rp(p);
+                let value = if q == 0 {
+                    value.wrapping_add(1)
+                } else {
+                    value.wrapping_sub(1)
+                };
+                self.set_rp(p, value);
+            }
+            (0, 4) | (0, 5) => {
+                let value = self.r(y);
+                let carry = self.flag(C);
+                let result = if z == 4 {
+                    let result = value.wrapping_add(1);
+                    self.set_flags(result == 0, false, value & 0xF == 0xF, carry);
+                    result
+                } else {
+                    let result = value.wrapping_sub(1);
+                    self.set_flags(result == 0, true, value & 0xF == 0, carry);
+                    result
+                };
+                self.set_r(y, result);
+            }
+            (0, 6) => {
+                let value = self.immediate();
+                self.set_r(y, value);
+            }
+            (0, 7) => match y {
+                0..=3 => {
+                    let (result, carry) = self.rotate(y, self.a);
+                    self.set_flags(false, false, false, carry);
+                    self.a = result;
+                }
+                4 => self.daa(),
+                5 => {
+                    self.a = !self.a;
+                    self.f |= N | H;
+                }
+                6 => self.f = self.f & Z | C,
+                _ => self.f = (self.f & (Z | C)) ^ C,
+            },
+            (1, _) => {
+                let value = self.r(z);
+                self.set_r(y, value);
+            }
+            (2, _) => {
+                let value = self.r(z);
+                self.alu(y, value);
+            }
+            (3, 0) => match y {
+                0..=3 => {
+                    self.internal();
+                    if self.condition(y) {
+                        self.pc = self.pop();
+                        self.internal();
+                    }
+                }
+                4 => {
+                    let address = 0xFF00 | self.immediate() as u16;
+                    self.write(address, self.a);
+                }
+                5 => {
+                    self.sp = self.sp_plus_offset();
+                    self.internal();
+                    self.internal();
+                }
+                6 => {
+                    let address = 0xFF00 | self.immediate() as u16;
+                    self.a = self.read(address);
+                }
+                _ => {
+                    let value = self.sp_plus_offset();
+                    self.set_hl(value);
+                    self.internal();
+                }
+            },
+            (3, 1) if q == 0 => {
+                let value = self.pop();
+                self.set_rp2(p, value);
+            }
+            (3, 1) => match p {
+                0 | 1 => {
+                    self.pc = self.pop();
+                    self.internal();
+                    if p == 1 {
+                        self.ime = true;
+                    }
+                }
+                2 => self.pc = self.hl(),
+                _ => {
+                    self.internal();
+                    self.sp = self.hl();
+                }
+            },
+            (3, 2) => match y {
+                0..=3 => {
+                    let address = self.immediate_16();
+                    if self.condition(y) {
+                        self.internal();
+                        self.pc = address;
+                    }
+                }
+                4 => self.write(0xFF00 | self.c as u16, self.a),
+                5 => {
+                    let address = self.immediate_16();
+                    self.write(address, self.a);
+                }
+                6 => self.a = self.read(0xFF00 | self.c as u16),
+                _ => {
+                    let address = self.immediate_16();
+                    self.a = self.read(address);
+                }
+            },
+            (3, 3) => match y {
+                0 => {
+                    let address = self.immediate_16();
+                    self.internal();
+                    self.pc = address;
+                }
+                1 => self.prefixed(),
+                6 => self.ime = false,
+                _ => self.ime = true,
+            },
+            (3, 4) | (3, 5) if z == 5 && q == 0 => {
+                self.internal();
+                let value = self.rp2(p);
+                self.push(value);
+            }
+            (3, 4) | (3, 5) => {
+                let address = self.immediate_16();
+                if z == 5 || self.condition(y) {
+                    self.internal();
+                    self.push(self.pc);
+                    self.pc = address;
+                }
+            }
+            (3, 6) => {
+                let value = self.immediate();
+                self.alu(y, value);
+            }
+            _ => {
+                self.internal();
+                self.push(self.pc);
+                self.pc = y as u16 * 8;
+            }
+        }
+        Some(())
+    }
+}
@@ -0,0 +1,70 @@
+mod common;
+
+use common::golden::{check, GoldenCase, RomSource};
+use gameboy_lib::joypad::Button;
+
+// LD A, value; LD (address), A
+fn store(program: &mut Vec<u8>, address: u16, value: u8) {
+    program.extend_from_slice(&[0x3E, value, 0xEA, address as u8, (address >> 8) as u8]);
+}
+
+// Draws a patterned background and one sprite, then keeps copying the action
+// buttons from JOYP into SCX so held buttons scroll the screen
+fn pattern_rom() -> Vec<u8> {
+    let tile = [
+        0xFF, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0xF0, 0x0F, 0x0F, 0xF0, 0xAA, 0x55, 0x3C, 0x3C, 0x81,
+        0x81,
+    ];
+    let mut program = Vec::new();
+    for (i, byte) in tile.iter().enumerate() {
+        store(&mut program, 0x8010 + i as u16, *byte);
+        store(&mut program, 0x8020 + i as u16, 0xFF);
+    }
+    for i in 0..20 {
+        store(&mut program, 0x9800 + i, 1);
+        store(&mut program, 0x9800 + i * 33, 1);
+    }
+    for (offset, value) in [56, 48, 2, 0].iter().enumerate() {
+        store(&mut program, 0xFE00 + offset as u16, *value);
+    }
+    store(&mut program, 0xFF47, 0xE4); // BGP
+    store(&mut program, 0xFF48, 0xE4); // OBP0
+    store(&mut program, 0xFF40, 0x93); // LCDC: LCD, tiles at 0x8000, sprites, background
+
+    let start = 0x100 + program.len() as u16;
+    store(&mut program, 0xFF00, 0x10); // Select the action buttons
+    program.extend_from_slice(&[0xFA, 0x00, 0xFF, 0xEA, 0x43, 0xFF]); // LD A, (JOYP); LD (SCX), A
+    program.extend_from_slice(&[0xC3, start as u8, (start >> 8) as u8]); // JP start
+
+    let mut rom = vec![0x00; 0x100 + program.len()];
+    rom[0x100..].copy_from_slice(&program);
+    rom
+}
+
+#[test]
+fn pattern() {
+    check(GoldenCase::new(
+        "pattern",
+        RomSource::Bytes(pattern_rom()),
+        4,
+    ));
+}
+
+#[test]
+fn pattern_with_input() {
+    let case = GoldenCase::new("pattern_start", RomSource::Bytes(pattern_rom()), 8)
+        .hold(2, &[Button::Start, Button::A])
+        .hold(6, &[Button::Start]);
+    check(case);
+}
+
+// Place the ROM in tests/roms and the official reference in tests/golden
+#[test]
+#[ignore = "dmg-acid2 needs STAT interrupts"]
+fn dmg_acid2() {
+    check(GoldenCase::new(
+        "dmg-acid2",
+        RomSource::File("dmg-acid2.gb"),
+        60,
+    ));
+}
@@ -0,0 +1,404 @@
+//! SingleStepTests conformance: runs the published per-opcode SM83 vectors
+//! through `Cpu::step` on a flat 64 KiB RAM that records every M-cycle, and
+//! compares registers, flags, IME, memory and the bus activity of each cycle.
+//!
+//! `vendored` runs the few vectors per instruction group in
+//! `tests/sm83/vectors` on every `cargo test`. They are written by hand in
+//! the same format, not copied from the published suite. The full suite is
+//! not part of the repo: put its JSON files (`00.json` to `ff.json` and
+//! `cb 00.json` to `cb ff.json`) in `tests/vectors/sm83`, or point
+//! `SM83_TESTS_DIR` at them, and run
+//! `cargo test --test sm83 -- --ignored`. `SM83_TESTS=cb 37,80` restricts
+//! either run to some files.
+//!
+//! Each vector looks like
+//! `{"name": "..", "initial": {"pc": .., "sp": .., "a": .., "f": .., ..,
+//! "ime": 0, "ie": 0, "ram": [[address, value], ..]}, "final": {..},
+//! "cycles": [[address, value, "r-m"], [address, value, "-wm"], null, ..]}`.
+
+mod json;
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use gameboy_lib::{
+    bus::{Bus, FlatRam},
+    cpu::Cpu,
+};
+use json::Value;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Cycle {
+    Idle,
+    Read(u16, u8),
+    Write(u16, u8),
+}
+
+/// Flat RAM that logs what happens on the bus in every M-cycle.
+struct RecordingBus {
+    ram: FlatRam,
+    cycles: Vec<Cycle>,
+}
+
+impl RecordingBus {
+    // The CPU ticks a cycle before each access, so the access belongs to it
+    fn record(&mut self, cycle: Cycle) {
+        match self.cycles.last_mut() {
+            Some(last @ Cycle::Idle) => *last = cycle,
+            _ => self.cycles.push(cycle),
+        }
+    }
+}
+
+impl Bus for RecordingBus {
+    fn read(&mut self, address: u16) -> u8 {
+        let value = self.ram.read(address);
+        self.record(Cycle::Read(address, value));
+        value
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        self.ram.write(address, value);
+        self.record(Cycle::Write(address, value));
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        self.ram.tick(cycles);
+        for _ in 0..cycles / 4 {
+            self.cycles.push(Cycle::Idle);
+        }
+    }
+
+    fn peek(&self, address: u16) -> u8 {
+        self.ram.peek(address)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct State {
+    a: u8,
+    f: u8,
+    b: u8,
+    c: u8,
+    d: u8,
+    e: u8,
+    h: u8,
+    l: u8,
+    sp: u16,
+    pc: u16,
+    ime: bool,
+    ram: Vec<(u16, u8)>,
+}
+
+fn field(value: &Value, name: &str) -> Result<u16, String> {
+    value
+        .get(name)
+        .and_then(Value::as_u16)
+        .ok_or_else(|| format!("missing or invalid `{}`", name))
+}
+
+fn byte(value: &Value, name: &str) -> Result<u8, String> {
+    u8::try_from(field(value, name)?).map_err(|_| format!("`{}` is not a byte", name))
+}
+
+impl State {
+    fn parse(value: &Value) -> Result<State, String> {
+        let ram = value
+            .get("ram")
+            .and_then(Value::as_array)
+            .ok_or("missing `ram`")?
+            .iter()
+            .map(|entry| match entry.as_array() {
+                Some([address, value]) => address.as_u16().zip(value.as_u8()),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()
+            .ok_or("invalid `ram` entry")?;
+        Ok(State {
+            a: byte(value, "a")?,
+            f: byte(value, "f")?,
+            b: byte(value, "b")?,
+            c: byte(value, "c")?,
+            d: byte(value, "d")?,
+            e: byte(value, "e")?,
+            h: byte(value, "h")?,
+            l: byte(value, "l")?,
+            sp: field(value, "sp")?,
+            pc: field(value, "pc")?,
+            ime: field(value, "ime")? != 0,
+            ram,
+        })
+    }
+
+    // Registers and IME of `cpu`, with the addresses of `self.ram` read back
+    fn of(cpu: &Cpu<RecordingBus>, addresses: &[(u16, u8)]) -> State {
+        let registers = &cpu.registers;
+        State {
+            a: registers.a,
+            f: registers.f.get(),
+            b: registers.b,
+            c: registers.c,
+            d: registers.d,
+            e: registers.e,
+            h: registers.h,
+            l: registers.l,
+            sp: registers.sp.get(),
+            pc: cpu.pc,
+            ime: cpu.interrupts_enabled,
+            ram: addresses
+                .iter()
+                .map(|(address, _)| (*address, cpu.bus.peek(*address)))
+                .collect(),
+        }
+    }
+}
+
+// Pins are read, write and memory request, e.g. "r-m". Internal cycles
+// are null or have neither pin set.
+fn parse_cycle(value: &Value) -> Result<Cycle, String> {
+    let Some([address, data, pins]) = value.as_array() else {
+        return Ok(Cycle::Idle);
+    };
+    let (Some(address), Some(pins)) = (address.as_u16(), pins.as_str()) else {
+        return Err("invalid cycle".to_string());
+    };
+    Ok(
+        match (
+            pins.as_bytes().first(),
+            pins.as_bytes().get(1),
+            data.as_u8(),
+        ) {
+            (Some(b'r'), _, Some(data)) => Cycle::Read(address, data),
+            (_, Some(b'w'), Some(data)) => Cycle::Write(address, data),
+            _ => Cycle::Idle,
+        },
+    )
+}
+
+struct Vector {
+    name: String,
+    initial: State,
+    expected: State,
+    cycles: Vec<Cycle>,
+}
+
+impl Vector {
+    fn parse(value: &Value) -> Result<Vector, String> {
+        let name = value
+            .get("name")
+            .and_then(Value::as_str)
+            .ok_or("missing `name`")?;
+        let parse = || -> Result<Vector, String> {
+            Ok(Vector {
+                name: name.to_string(),
+                initial: State::parse(value.get("initial").ok_or("missing `initial`")?)?,
+                expected: State::parse(value.get("final").ok_or("missing `final`")?)?,
+                cycles: value
+                    .get("cycles")
+                    .and_then(Value::as_array)
+                    .ok_or("missing `cycles`")?
+                    .iter()
+                    .map(parse_cycle)
+                    .collect::<Result<_, _>>()?,
+            })
+        };
+        parse().map_err(|error| format!("{}: {}", name, error))
+    }
+
+    /// Some generators start with the opcode already fetched and end with
+    /// the fetch of the next one, like the hardware's overlapped fetch. Shift
+    /// those by one cycle so they line up with `Cpu::step`, which fetches the
+    /// opcode at PC first and nothing after the instruction.
+    fn align_prefetch(&mut self, opcode: u8) {
+        let at = |state: &State, address: u16| {
+            state
+                .ram
+                .iter()
+                .find(|(entry, _)| *entry == address)
+                .map(|(_, value)| *value)
+        };
+        let start = self.initial.pc.wrapping_sub(1);
+        if at(&self.initial, self.initial.pc) == Some(opcode)
+            || at(&self.initial, start) != Some(opcode)
+        {
+            return;
+        }
+        self.initial.pc = start;
+        self.expected.pc = self.expected.pc.wrapping_sub(1);
+        self.cycles.pop();
+        self.cycles.insert(0, Cycle::Read(start, opcode));
+    }
+
+    /// Runs the vector and describes the first difference.
+    fn run(&self) -> Result<(), String> {
+        let mut ram = FlatRam::new();
+        for (address, value) in &self.initial.ram {
+            ram.write(*address, *value);
+        }
+        let mut cpu = Cpu::with_bus(RecordingBus {
+            ram,
+            cycles: Vec::new(),
+        });
+        let registers = &mut cpu.registers;
+        registers.a = self.initial.a;
+        registers.f.set(self.initial.f);
+        registers.b = self.initial.b;
+        registers.c = self.initial.c;
+        registers.d = self.initial.d;
+        registers.e = self.initial.e;
+        registers.h = self.initial.h;
+        registers.l = self.initial.l;
+        registers.sp.set(self.initial.sp);
+        cpu.pc = self.initial.pc;
+        cpu.interrupts_enabled = self.initial.ime;
+
+        cpu.step().map_err(|error| error.to_string())?;
+
+        let actual = State::of(&cpu, &self.expected.ram);
+        if actual != self.expected {
+            return Err(format!(
+                "expected {:?}\n  actual {:?}",
+                self.expected, actual
+            ));
+        }
+        if cpu.bus.cycles != self.cycles {
+            return Err(format!(
+                "expected cycles {:?}\n  actual cycles {:?}",
+                self.cycles, cpu.bus.cycles
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn vectors_dir() -> PathBuf {
+    env::var_os("SM83_TESTS_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/vectors/sm83"))
+}
+
+// `cb 37` for the file `cb 37.json`, followed by its first opcode byte
+fn parse_file_name(stem: &str) -> Option<u8> {
+    let first = stem.split(' ').next()?;
+    u8::from_str_radix(first, 16).ok()
+}
+
+// Runs every vector file in `dir` and fails with a summary of the files
+// that had failures
+fn run_dir(dir: &Path) {
+    let entries = fs::read_dir(dir)
+        .unwrap_or_else(|error| panic!("no vectors in {}: {}", dir.display(), error));
+    let filter: Option<Vec<String>> = env::var("SM83_TESTS").ok().map(|names| {
+        names
+            .split(',')
+            .map(|name| name.trim().to_lowercase())
+            .collect()
+    });
+
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "json")
+        })
+        .collect();
+    files.sort();
+
+    let (mut passed, mut failed) = (0, 0);
+    let mut failures = Vec::new();
+    for path in files {
+        let stem = path.file_stem().unwrap().to_string_lossy().to_lowercase();
+        if filter.as_ref().is_some_and(|names| !names.contains(&stem)) {
+            continue;
+        }
+        let opcode = parse_file_name(&stem)
+            .unwrap_or_else(|| panic!("{}: not named after an opcode", path.display()));
+        let text = fs::read_to_string(&path).expect("Failed to read vectors");
+        let vectors =
+            json::parse(&text).unwrap_or_else(|error| panic!("{}: {}", path.display(), error));
+
+        let mut first_failure = None;
+        let mut file_failed = 0;
+        for value in vectors.as_array().expect("Expected an array of vectors") {
+            let mut vector = Vector::parse(value)
+                .unwrap_or_else(|error| panic!("{}: {}", path.display(), error));
+            vector.align_prefetch(opcode);
+            match vector.run() {
+                Ok(()) => passed += 1,
+                Err(error) => {
+                    file_failed += 1;
+                    first_failure.get_or_insert_with(|| format!("{}: {}", vector.name, error));
+                }
+            }
+        }
+        if let Some(failure) = first_failure {
+            failed += file_failed;
+            failures.push(format!(
+                "{} ({} failed), first: {}",
+                stem, file_failed, failure
+            ));
+        }
+    }
+
+    for failure in &failures {
+        eprintln!("{}", failure);
+    }
+    assert!(passed + failed > 0, "no vectors run from {}", dir.display());
+    assert!(
+        failures.is_empty(),
+        "{} of {} vectors failed",
+        failed,
+        passed + failed
+    );
+}
+
+#[test]
+fn vendored() {
+    run_dir(&PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/sm83/vectors"));
+}
+
+#[test]
+#[ignore = "needs the full suite in tests/vectors/sm83 or SM83_TESTS_DIR"]
+fn sm83() {
+    run_dir(&vectors_dir());
+}
+
+// Checks the runner itself on hand-written vectors in both layouts
+#[test]
+fn runner() {
+    let text = r#"[
+        {
+            "name": "41 fetch first",
+            "initial": {"pc": 49152, "sp": 65534, "a": 1, "b": 2, "c": 3, "d": 4, "e": 5,
+                        "f": 176, "h": 6, "l": 7, "ime": 0, "ie": 0, "ram": [[49152, 65]]},
+            "final": {"pc": 49153, "sp": 65534, "a": 1, "b": 3, "c": 3, "d": 4, "e": 5,
+                      "f": 176, "h": 6, "l": 7, "ime": 0, "ie": 0, "ram": [[49152, 65]]},
+            "cycles": [[49152, 65, "r-m"]]
+        },
+        {
+            "name": "c5 prefetched",
+            "initial": {"pc": 49153, "sp": 53248, "a": 0, "b": 18, "c": 52, "d": 0, "e": 0,
+                        "f": 0, "h": 0, "l": 0, "ime": 0, "ie": 0,
+                        "ram": [[49152, 197], [49153, 0]]},
+            "final": {"pc": 49154, "sp": 53246, "a": 0, "b": 18, "c": 52, "d": 0, "e": 0,
+                      "f": 0, "h": 0, "l": 0, "ime": 0, "ie": 0,
+                      "ram": [[53246, 52], [53247, 18]]},
+            "cycles": [null, [53247, 18, "-wm"], [53246, 52, "-wm"], [49153, 0, "r-m"]]
+        }
+    ]"#;
+    let vectors = json::parse(text).unwrap();
+    let vectors = vectors.as_array().unwrap();
+    let mut load = Vector::parse(&vectors[0]).unwrap();
+    load.align_prefetch(0x41);
+    assert_eq!(load.run(), Ok(()));
+
+    let mut push = Vector::parse(&vectors[1]).unwrap();
+    push.align_prefetch(0xC5);
+    assert_eq!(push.initial.pc, 0xC000);
+    assert_eq!(push.run(), Ok(()));
+
+    push.expected.ram[0].1 = 0x35;
+    assert!(push.run().unwrap_err().starts_with("expected State"));
+}